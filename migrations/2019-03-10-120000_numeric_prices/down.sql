@@ -0,0 +1,43 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE aggregate_trades
+    ALTER COLUMN price TYPE REAL USING price::REAL,
+    ALTER COLUMN quantity TYPE REAL USING quantity::REAL;
+
+ALTER TABLE trades
+    ALTER COLUMN price TYPE REAL USING price::REAL,
+    ALTER COLUMN quantity TYPE REAL USING quantity::REAL;
+
+ALTER TABLE klines
+    ALTER COLUMN open TYPE REAL USING open::REAL,
+    ALTER COLUMN close TYPE REAL USING close::REAL,
+    ALTER COLUMN high TYPE REAL USING high::REAL,
+    ALTER COLUMN low TYPE REAL USING low::REAL,
+    ALTER COLUMN volume TYPE REAL USING volume::REAL,
+    ALTER COLUMN quote_asset_vol TYPE REAL USING quote_asset_vol::REAL,
+    ALTER COLUMN taker_buy_base_vol TYPE REAL USING taker_buy_base_vol::REAL,
+    ALTER COLUMN taker_buy_quote_vol TYPE REAL USING taker_buy_quote_vol::REAL;
+
+ALTER TABLE mini_tickers
+    ALTER COLUMN open TYPE REAL USING open::REAL,
+    ALTER COLUMN close TYPE REAL USING close::REAL,
+    ALTER COLUMN high TYPE REAL USING high::REAL,
+    ALTER COLUMN low TYPE REAL USING low::REAL,
+    ALTER COLUMN base_asset_vol TYPE REAL USING base_asset_vol::REAL,
+    ALTER COLUMN quote_asset_vol TYPE REAL USING quote_asset_vol::REAL;
+
+ALTER TABLE tickers
+    ALTER COLUMN price_change TYPE REAL USING price_change::REAL,
+    ALTER COLUMN price_change_pct TYPE REAL USING price_change_pct::REAL,
+    ALTER COLUMN weight_avg_price TYPE REAL USING weight_avg_price::REAL,
+    ALTER COLUMN first_trade_before_24hr_window TYPE REAL USING first_trade_before_24hr_window::REAL,
+    ALTER COLUMN last_price TYPE REAL USING last_price::REAL,
+    ALTER COLUMN last_quantity TYPE REAL USING last_quantity::REAL,
+    ALTER COLUMN best_bid_price TYPE REAL USING best_bid_price::REAL,
+    ALTER COLUMN best_bid_quantity TYPE REAL USING best_bid_quantity::REAL,
+    ALTER COLUMN best_ask_price TYPE REAL USING best_ask_price::REAL,
+    ALTER COLUMN best_ask_quantity TYPE REAL USING best_ask_quantity::REAL,
+    ALTER COLUMN open_price TYPE REAL USING open_price::REAL,
+    ALTER COLUMN high_price TYPE REAL USING high_price::REAL,
+    ALTER COLUMN low_price TYPE REAL USING low_price::REAL,
+    ALTER COLUMN base_asset_vol TYPE REAL USING base_asset_vol::REAL,
+    ALTER COLUMN quote_asset_vol TYPE REAL USING quote_asset_vol::REAL;
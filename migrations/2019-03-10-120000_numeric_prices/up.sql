@@ -0,0 +1,46 @@
+-- Store prices and quantities as exact decimals instead of REAL (f32).
+-- Existing REAL values are converted as-is, precision already lost is not recovered.
+ALTER TABLE aggregate_trades
+    ALTER COLUMN price TYPE NUMERIC USING price::NUMERIC,
+    ALTER COLUMN quantity TYPE NUMERIC USING quantity::NUMERIC;
+
+ALTER TABLE trades
+    ALTER COLUMN price TYPE NUMERIC USING price::NUMERIC,
+    ALTER COLUMN quantity TYPE NUMERIC USING quantity::NUMERIC;
+
+ALTER TABLE klines
+    ALTER COLUMN open TYPE NUMERIC USING open::NUMERIC,
+    ALTER COLUMN close TYPE NUMERIC USING close::NUMERIC,
+    ALTER COLUMN high TYPE NUMERIC USING high::NUMERIC,
+    ALTER COLUMN low TYPE NUMERIC USING low::NUMERIC,
+    ALTER COLUMN volume TYPE NUMERIC USING volume::NUMERIC,
+    ALTER COLUMN quote_asset_vol TYPE NUMERIC USING quote_asset_vol::NUMERIC,
+    ALTER COLUMN taker_buy_base_vol TYPE NUMERIC USING taker_buy_base_vol::NUMERIC,
+    ALTER COLUMN taker_buy_quote_vol TYPE NUMERIC USING taker_buy_quote_vol::NUMERIC;
+
+ALTER TABLE mini_tickers
+    ALTER COLUMN open TYPE NUMERIC USING open::NUMERIC,
+    ALTER COLUMN close TYPE NUMERIC USING close::NUMERIC,
+    ALTER COLUMN high TYPE NUMERIC USING high::NUMERIC,
+    ALTER COLUMN low TYPE NUMERIC USING low::NUMERIC,
+    ALTER COLUMN base_asset_vol TYPE NUMERIC USING base_asset_vol::NUMERIC,
+    ALTER COLUMN quote_asset_vol TYPE NUMERIC USING quote_asset_vol::NUMERIC;
+
+ALTER TABLE tickers
+    ALTER COLUMN price_change TYPE NUMERIC USING price_change::NUMERIC,
+    ALTER COLUMN price_change_pct TYPE NUMERIC USING price_change_pct::NUMERIC,
+    ALTER COLUMN weight_avg_price TYPE NUMERIC USING weight_avg_price::NUMERIC,
+    ALTER COLUMN first_trade_before_24hr_window TYPE NUMERIC USING first_trade_before_24hr_window::NUMERIC,
+    ALTER COLUMN last_price TYPE NUMERIC USING last_price::NUMERIC,
+    ALTER COLUMN last_quantity TYPE NUMERIC USING last_quantity::NUMERIC,
+    ALTER COLUMN best_bid_price TYPE NUMERIC USING best_bid_price::NUMERIC,
+    ALTER COLUMN best_bid_quantity TYPE NUMERIC USING best_bid_quantity::NUMERIC,
+    ALTER COLUMN best_ask_price TYPE NUMERIC USING best_ask_price::NUMERIC,
+    ALTER COLUMN best_ask_quantity TYPE NUMERIC USING best_ask_quantity::NUMERIC,
+    ALTER COLUMN open_price TYPE NUMERIC USING open_price::NUMERIC,
+    ALTER COLUMN high_price TYPE NUMERIC USING high_price::NUMERIC,
+    ALTER COLUMN low_price TYPE NUMERIC USING low_price::NUMERIC,
+    ALTER COLUMN base_asset_vol TYPE NUMERIC USING base_asset_vol::NUMERIC,
+    ALTER COLUMN quote_asset_vol TYPE NUMERIC USING quote_asset_vol::NUMERIC;
+
+-- book_depth quotes are JSONB, new rows store price/quantity as decimal strings.
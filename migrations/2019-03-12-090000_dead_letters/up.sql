@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE dead_letters (
+    id SERIAL PRIMARY KEY,
+    received_at TIMESTAMP NOT NULL,
+    stream TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    error TEXT NOT NULL
+)
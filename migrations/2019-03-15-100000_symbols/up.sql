@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE symbols (
+    symbol TEXT PRIMARY KEY,
+    status TEXT NOT NULL,
+    base_asset TEXT NOT NULL,
+    quote_asset TEXT NOT NULL,
+    tick_size NUMERIC NOT NULL,
+    step_size NUMERIC NOT NULL,
+    min_qty NUMERIC NOT NULL,
+    max_qty NUMERIC NOT NULL,
+    min_notional NUMERIC NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+)
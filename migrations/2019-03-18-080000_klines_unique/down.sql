@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE klines DROP CONSTRAINT klines_symbol_interval_start_time_key
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- keep the latest copy of each kline before adding the constraint
+DELETE FROM klines a USING klines b
+WHERE a.symbol = b.symbol
+  AND a.interval = b.interval
+  AND a.start_time = b.start_time
+  AND a.id < b.id;
+
+ALTER TABLE klines ADD CONSTRAINT klines_symbol_interval_start_time_key UNIQUE (symbol, interval, start_time)
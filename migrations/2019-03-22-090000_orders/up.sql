@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE orders (
+    id SERIAL PRIMARY KEY,
+    client_order_id TEXT NOT NULL,
+    order_id BIGINT,
+    symbol TEXT NOT NULL,
+    side TEXT NOT NULL,
+    order_type TEXT NOT NULL,
+    time_in_force TEXT,
+    price NUMERIC,
+    stop_price NUMERIC,
+    orig_qty NUMERIC NOT NULL,
+    executed_qty NUMERIC NOT NULL,
+    cumulative_quote_qty NUMERIC NOT NULL,
+    status TEXT NOT NULL,
+    reason TEXT,
+    event_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX orders_client_order_id_idx ON orders (client_order_id, id)
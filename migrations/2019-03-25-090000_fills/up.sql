@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE fills (
+    id SERIAL PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    trade_id BIGINT NOT NULL,
+    order_id BIGINT NOT NULL,
+    client_order_id TEXT NOT NULL,
+    side TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    quantity NUMERIC NOT NULL,
+    quote_qty NUMERIC NOT NULL,
+    commission NUMERIC NOT NULL,
+    commission_asset TEXT,
+    is_maker BOOLEAN NOT NULL,
+    trade_time TIMESTAMP NOT NULL,
+    UNIQUE (symbol, trade_id)
+)
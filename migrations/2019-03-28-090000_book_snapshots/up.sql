@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE book_snapshots (
+    symbol TEXT NOT NULL,
+    last_update_id BIGINT NOT NULL,
+    captured_at TIMESTAMP NOT NULL,
+    levels INTEGER NOT NULL,
+    bids JSONB[] NOT NULL,
+    asks JSONB[] NOT NULL,
+    PRIMARY KEY (symbol, last_update_id, captured_at)
+);
+
+CREATE INDEX book_snapshots_symbol_captured_at ON book_snapshots (symbol, captured_at);
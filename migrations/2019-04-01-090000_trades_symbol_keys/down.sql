@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE klines
+  ALTER COLUMN first_trade_id TYPE INT,
+  ALTER COLUMN last_trade_id TYPE INT;
+
+ALTER TABLE aggregate_trades DROP CONSTRAINT aggregate_trades_pkey;
+ALTER TABLE aggregate_trades
+  ALTER COLUMN trade_id TYPE INT,
+  ALTER COLUMN first_trade_id TYPE INT,
+  ALTER COLUMN last_trade_id TYPE INT;
+ALTER TABLE aggregate_trades ADD PRIMARY KEY (trade_id);
+
+ALTER TABLE trades DROP CONSTRAINT trades_pkey;
+ALTER TABLE trades
+  ALTER COLUMN trade_id TYPE INT,
+  ALTER COLUMN buyer_order_id TYPE INT,
+  ALTER COLUMN seller_order_id TYPE INT;
+ALTER TABLE trades ADD PRIMARY KEY (trade_id);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- trade ids are only unique per symbol, and the busiest symbols are past 2^31
+ALTER TABLE trades DROP CONSTRAINT trades_pkey;
+ALTER TABLE trades
+  ALTER COLUMN trade_id TYPE BIGINT,
+  ALTER COLUMN buyer_order_id TYPE BIGINT,
+  ALTER COLUMN seller_order_id TYPE BIGINT;
+ALTER TABLE trades ADD PRIMARY KEY (symbol, trade_id);
+
+ALTER TABLE aggregate_trades DROP CONSTRAINT aggregate_trades_pkey;
+ALTER TABLE aggregate_trades
+  ALTER COLUMN trade_id TYPE BIGINT,
+  ALTER COLUMN first_trade_id TYPE BIGINT,
+  ALTER COLUMN last_trade_id TYPE BIGINT;
+ALTER TABLE aggregate_trades ADD PRIMARY KEY (symbol, trade_id);
+
+ALTER TABLE klines
+  ALTER COLUMN first_trade_id TYPE BIGINT,
+  ALTER COLUMN last_trade_id TYPE BIGINT;
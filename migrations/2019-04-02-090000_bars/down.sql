@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+INSERT INTO klines (event, event_time, start_time, close_time, symbol, interval, first_trade_id, last_trade_id,
+                    open, close, high, low, volume, num_of_trades, is_kline_closed, quote_asset_vol,
+                    taker_buy_base_vol, taker_buy_quote_vol)
+SELECT 'candle', close_time, start_time, close_time, symbol, spec, first_trade_id, last_trade_id,
+       open, close, high, low, volume, num_of_trades, true, quote_asset_vol,
+       taker_buy_base_vol, taker_buy_quote_vol
+FROM bars
+ON CONFLICT DO NOTHING;
+
+DROP TABLE bars
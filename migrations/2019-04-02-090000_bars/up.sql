@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- tick, volume and dollar bars, several can start in the same millisecond
+CREATE TABLE bars (
+    symbol TEXT NOT NULL,
+    spec TEXT NOT NULL,
+    first_trade_id BIGINT NOT NULL,
+    last_trade_id BIGINT NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    close_time TIMESTAMP NOT NULL,
+    open NUMERIC NOT NULL,
+    close NUMERIC NOT NULL,
+    high NUMERIC NOT NULL,
+    low NUMERIC NOT NULL,
+    volume NUMERIC NOT NULL,
+    num_of_trades INTEGER NOT NULL,
+    quote_asset_vol NUMERIC NOT NULL,
+    taker_buy_base_vol NUMERIC NOT NULL,
+    taker_buy_quote_vol NUMERIC NOT NULL,
+    PRIMARY KEY (symbol, spec, first_trade_id)
+);
+
+CREATE INDEX bars_symbol_spec_start_time ON bars (symbol, spec, start_time);
+
+-- move the threshold bars out of klines, time bars stay there
+INSERT INTO bars
+SELECT symbol, interval, first_trade_id, last_trade_id, start_time, close_time, open, close, high, low,
+       volume, num_of_trades, quote_asset_vol, taker_buy_base_vol, taker_buy_quote_vol
+FROM klines
+WHERE event = 'candle' AND interval LIKE '%:%'
+ON CONFLICT DO NOTHING;
+
+DELETE FROM klines WHERE event = 'candle' AND interval LIKE '%:%';
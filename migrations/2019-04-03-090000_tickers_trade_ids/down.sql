@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tickers
+  ALTER COLUMN first_trade_id TYPE INTEGER,
+  ALTER COLUMN last_trade_id TYPE INTEGER;
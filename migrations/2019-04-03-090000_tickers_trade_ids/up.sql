@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- same width as the trade ids in trades and aggregate_trades
+ALTER TABLE tickers
+  ALTER COLUMN first_trade_id TYPE BIGINT,
+  ALTER COLUMN last_trade_id TYPE BIGINT;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE book_depth
+  ALTER COLUMN update_first TYPE INTEGER,
+  ALTER COLUMN update_final TYPE INTEGER;
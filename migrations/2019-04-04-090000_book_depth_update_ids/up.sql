@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Binance update ids outgrow INT, same width as book_snapshots.last_update_id
+ALTER TABLE book_depth
+  ALTER COLUMN update_first TYPE BIGINT,
+  ALTER COLUMN update_final TYPE BIGINT;
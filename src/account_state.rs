@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use crate::models::account::{AccountInfo, Balance, OrderInfo};
+use crate::models::orders::Order;
+use crate::models::user_data::{ExecutionReport, FillInsert, StreamBalance, UserDataEvent};
+
+/// Balances and open orders, seeded from a REST snapshot and then kept current
+/// by the user data stream instead of polling
+#[derive(Debug, Clone, Default)]
+pub struct AccountState {
+    pub balances: HashMap<String, Balance>, // by asset, e.g: "BTC"
+    pub open_orders: HashMap<String, Order>, // by client order id
+    pub updated_at: Option<NaiveDateTime>,  // event time of the last applied event
+}
+
+impl AccountState {
+    pub fn new() -> Self {
+        AccountState::default()
+    }
+
+    /// GET `/api/v3/account` and `/api/v3/openOrders`, taken right after the stream connects
+    pub fn from_snapshot(account: &AccountInfo, open_orders: &[OrderInfo]) -> Self {
+        AccountState {
+            balances: account.balances.iter().map(|b| (b.asset.clone(), b.clone())).collect(),
+            open_orders: open_orders.iter().map(|o| (o.client_order_id.clone(), Order::from(o))).collect(),
+            updated_at: Some(account.update_time),
+        }
+    }
+
+    pub fn balance(&self, asset: &str) -> Option<&Balance> {
+        self.balances.get(asset)
+    }
+
+    pub fn open_order(&self, client_order_id: &str) -> Option<&Order> {
+        self.open_orders.get(client_order_id)
+    }
+
+    /// Apply one stream event, returns the fill to persist for trade executions
+    pub fn apply(&mut self, event: &UserDataEvent) -> Option<FillInsert> {
+        match event {
+            UserDataEvent::ExecutionReport(report) => {
+                self.updated_at = Some(report.event_time);
+                self.apply_execution(report);
+                return report.to_fill();
+            }
+            UserDataEvent::AccountInfo(info) => {
+                self.updated_at = Some(info.event_time);
+                self.balances.clear();
+                self.set_balances(&info.balances);
+            }
+            UserDataEvent::AccountPosition(position) => {
+                self.updated_at = Some(position.event_time);
+                self.set_balances(&position.balances);
+            }
+            UserDataEvent::BalanceUpdate(update) => {
+                self.updated_at = Some(update.event_time);
+                let balance = self.balances.entry(update.asset.clone()).or_insert_with(|| Balance {
+                    asset: update.asset.clone(),
+                    free: BigDecimal::from(0),
+                    locked: BigDecimal::from(0),
+                });
+                balance.free = &balance.free + &update.delta;
+            }
+        }
+        None
+    }
+
+    /// Apply events received while the snapshot was loading, skipping those sent
+    /// before it was taken, which it already reflects. Returns how many were applied.
+    pub fn replay(&mut self, events: &[UserDataEvent]) -> usize {
+        let taken_at = self.updated_at;
+        let mut applied = 0;
+        for event in events.iter().filter(|e| taken_at.map_or(true, |t| e.event_time() > t)) {
+            self.apply(event);
+            applied += 1;
+        }
+        applied
+    }
+
+    fn apply_execution(&mut self, report: &ExecutionReport) {
+        let client_order_id = report.order_client_id();
+        if !report.status.is_open() {
+            self.open_orders.remove(client_order_id);
+            return;
+        }
+        match self.open_orders.get_mut(client_order_id) {
+            Some(order) => {
+                order.apply(&report.order_update());
+            }
+            None => {
+                self.open_orders.insert(client_order_id.to_owned(), report.to_order());
+            }
+        }
+    }
+
+    fn set_balances(&mut self, balances: &[StreamBalance]) {
+        for b in balances {
+            self.balances.insert(
+                b.asset.clone(),
+                Balance {
+                    asset: b.asset.clone(),
+                    free: b.free.clone(),
+                    locked: b.locked.clone(),
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::account::{TEST_ACCOUNT_DATA, TEST_ORDER_DATA};
+    use crate::models::orders::OrderStatus;
+    use crate::models::user_data::{
+        parse_user_data_event, TEST_ACCOUNT_POSITION_DATA, TEST_BALANCE_UPDATE_DATA, TEST_EXECUTION_REPORT_DATA,
+    };
+    use crate::serde_parsers::create_decimal_benchmark;
+
+    #[test]
+    fn account_state_follows_user_data_events() {
+        let account = serde_json::from_str::<AccountInfo>(TEST_ACCOUNT_DATA).unwrap();
+        let order = serde_json::from_str::<OrderInfo>(TEST_ORDER_DATA).unwrap();
+        let mut state = AccountState::from_snapshot(&account, &[order]);
+        assert_eq!(state.open_order("myOrder1").unwrap().status, OrderStatus::New);
+
+        // an order placed elsewhere shows up partially filled
+        let report = parse_user_data_event(TEST_EXECUTION_REPORT_DATA).unwrap();
+        let fill = state.apply(&report).unwrap();
+        assert_eq!(fill.client_order_id, "mUvoqJxFIILMdfAW5iGSOW");
+        let order = state.open_order("mUvoqJxFIILMdfAW5iGSOW").unwrap();
+        assert_eq!(order.executed_qty, create_decimal_benchmark("0.25"));
+
+        // then canceled: a new id in "c", the order's own in "C"
+        let canceled = TEST_EXECUTION_REPORT_DATA
+            .replace(r#""c": "mUvoqJxFIILMdfAW5iGSOW""#, r#""c": "cancel1""#)
+            .replace(r#""C": """#, r#""C": "mUvoqJxFIILMdfAW5iGSOW""#)
+            .replace(r#""x": "TRADE""#, r#""x": "CANCELED""#)
+            .replace(r#""X": "PARTIALLY_FILLED""#, r#""X": "CANCELED""#);
+        assert_eq!(state.apply(&parse_user_data_event(&canceled).unwrap()), None);
+        assert!(state.open_order("mUvoqJxFIILMdfAW5iGSOW").is_none());
+        assert_eq!(state.open_orders.len(), 1);
+
+        state.apply(&parse_user_data_event(TEST_ACCOUNT_POSITION_DATA).unwrap());
+        state.apply(&parse_user_data_event(TEST_BALANCE_UPDATE_DATA).unwrap());
+        assert_eq!(state.balance("ETH").unwrap().free, BigDecimal::from(10000));
+        assert_eq!(state.balance("BTC").unwrap().free, create_decimal_benchmark("4723946.89208129"));
+    }
+
+    #[test]
+    fn replay_skips_events_older_than_the_snapshot() {
+        let account = serde_json::from_str::<AccountInfo>(TEST_ACCOUNT_DATA).unwrap();
+        let mut state = AccountState::from_snapshot(&account, &[]);
+
+        // the execution report was sent before the snapshot, the balance update after
+        let events = vec![
+            parse_user_data_event(TEST_EXECUTION_REPORT_DATA).unwrap(),
+            parse_user_data_event(TEST_BALANCE_UPDATE_DATA).unwrap(),
+        ];
+        assert_eq!(state.replay(&events), 1);
+        assert!(state.open_order("mUvoqJxFIILMdfAW5iGSOW").is_none());
+        assert_eq!(state.balance("BTC").unwrap().free, create_decimal_benchmark("4723946.89208129"));
+
+        // without a snapshot every event applies
+        assert_eq!(AccountState::new().replay(&events), 2);
+    }
+}
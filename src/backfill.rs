@@ -0,0 +1,293 @@
+use std::thread;
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Timelike};
+use diesel::pg::PgConnection;
+use reqwest::StatusCode;
+
+use crate::error::Result;
+use crate::models::klines::{closed_start_times, upsert_klines, KlineDataInsert, KlineInterval, RestKline};
+use crate::symbols::Symbol;
+
+pub const BINANCE_KLINES_URL: &str = "https://api.binance.com/api/v3/klines";
+pub const KLINES_PAGE_LIMIT: u32 = 1000; // most rows `/api/v3/klines` returns per request
+
+/// Pages through `/api/v3/klines` and upserts into the `klines` table,
+/// fetching only the klines of the range which are not stored and closed yet.
+#[derive(Debug, Clone)]
+pub struct KlineBackfill {
+    pub url: String,            // klines endpoint, see `BINANCE_KLINES_URL`
+    pub symbol: Symbol,         // Symbol
+    pub interval: KlineInterval, // Kline interval
+    pub start: NaiveDateTime,   // first kline start time wanted
+    pub end: NaiveDateTime,     // last kline start time wanted
+    pub limit: u32,             // rows per page, at most `KLINES_PAGE_LIMIT`
+    pub request_weight: u32,    // weight of one klines request
+    pub max_weight: u32,        // per minute budget, Binance allows 1200
+}
+
+impl KlineBackfill {
+    pub fn new(symbol: Symbol, interval: KlineInterval, start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        KlineBackfill {
+            url: BINANCE_KLINES_URL.to_string(),
+            symbol,
+            interval,
+            start,
+            end,
+            limit: KLINES_PAGE_LIMIT,
+            request_weight: 2,
+            max_weight: 1200,
+        }
+    }
+
+    /// Backfill the whole range, returns the number of rows written
+    pub fn run(&self, conn: &PgConnection) -> Result<usize> {
+        let client = reqwest::Client::new();
+
+        let stored = closed_start_times(conn, &self.symbol, &self.interval, self.start, self.end)?;
+        let gaps = missing_ranges(&self.interval, self.start, self.end, &stored);
+        if !stored.is_empty() {
+            info!(symbol = self.symbol.as_str(); "{} {} klines already stored, fetching {} gaps", stored.len(), self.interval, gaps.len());
+        }
+
+        let mut written = 0;
+        for (from, to) in gaps {
+            written += self.fetch_range(conn, &client, from.timestamp_millis(), to.timestamp_millis())?;
+        }
+        Ok(written)
+    }
+
+    /// Page through the klines starting from `start_ms` to `end_ms`
+    fn fetch_range(&self, conn: &PgConnection, client: &reqwest::Client, mut start_ms: i64, end_ms: i64) -> Result<usize> {
+        let mut written = 0;
+        while start_ms <= end_ms {
+            let page = self.fetch_page(client, start_ms, end_ms)?;
+            let page_len = page.len();
+            let last_close = match page.last() {
+                Some(kline) => kline.close_time,
+                None => break,
+            };
+
+            let rows: Vec<KlineDataInsert> = page
+                .into_iter()
+                .map(|kline| kline.into_insert(&self.symbol, &self.interval))
+                .collect();
+            written += upsert_klines(conn, &rows)?;
+            info!(symbol = self.symbol.as_str(); "Backfilled {} klines up to {}", self.interval, last_close);
+
+            if page_len < self.limit as usize {
+                break;
+            }
+            start_ms = last_close.timestamp_millis() + 1;
+        }
+        Ok(written)
+    }
+
+    /// GET one page of klines starting at `start_ms`.
+    /// Waits for the next minute when the used weight reaches `max_weight`,
+    /// and for `Retry-After` when Binance answers 429/418.
+    pub fn fetch_page(&self, client: &reqwest::Client, start_ms: i64, end_ms: i64) -> Result<Vec<RestKline>> {
+        loop {
+            let res = client
+                .get(&self.url)
+                .query(&[
+                    ("symbol", self.symbol.to_uppercase()),
+                    ("interval", self.interval.to_string()),
+                    ("startTime", start_ms.to_string()),
+                    ("endTime", end_ms.to_string()),
+                    ("limit", self.limit.to_string()),
+                ])
+                .send()?;
+
+            if res.status() == StatusCode::TOO_MANY_REQUESTS || res.status() == StatusCode::IM_A_TEAPOT {
+                let retry_after = header_u64(&res, "retry-after").unwrap_or(60);
+                warn!("Klines rate limited ({}), retrying in {}s", res.status(), retry_after);
+                thread::sleep(Duration::from_secs(retry_after));
+                continue;
+            }
+
+            let used_weight = header_u64(&res, "x-mbx-used-weight-1m").or_else(|| header_u64(&res, "x-mbx-used-weight"));
+            let page = res.error_for_status()?.json::<Vec<RestKline>>()?;
+
+            if let Some(used) = used_weight {
+                if used + u64::from(self.request_weight) > u64::from(self.max_weight) {
+                    let wait = 60 - u64::from(chrono::Utc::now().second());
+                    info!("Used weight {}/{}, waiting {}s", used, self.max_weight, wait);
+                    thread::sleep(Duration::from_secs(wait));
+                }
+            }
+            return Ok(page);
+        }
+    }
+}
+
+/// (first, last) kline start times from `start` to `end` missing from `stored`, which is sorted
+fn missing_ranges(
+    interval: &KlineInterval,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    stored: &[NaiveDateTime],
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut t = interval.start_of(start);
+    if t < start {
+        t = interval.next_start(t);
+    }
+    let mut stored = stored.iter().peekable();
+    let mut gaps = Vec::new();
+    let mut gap: Option<(NaiveDateTime, NaiveDateTime)> = None;
+    while t <= end {
+        while stored.peek().map_or(false, |s| **s < t) {
+            stored.next();
+        }
+        if stored.peek() == Some(&&t) {
+            gaps.extend(gap.take());
+        } else {
+            gap = Some((gap.map_or(t, |(first, _)| first), t));
+        }
+        t = interval.next_start(t);
+    }
+    gaps.extend(gap);
+    gaps
+}
+
+fn header_u64(res: &reqwest::Response, name: &str) -> Option<u64> {
+    res.headers().get(name)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::serde_parsers::create_timestamp_benchmark;
+    use crate::mock_server::{serve, MockResponse};
+    use diesel::Connection;
+    use std::sync::{Arc, Mutex};
+
+    const T0: i64 = 1_546_300_800_000; // 2019-01-01
+    const MINUTE: i64 = 60_000;
+
+    /// Stand-in for `/api/v3/klines` serving `total` one minute klines from T0,
+    /// records the startTime of every request
+    fn serve_klines(total: i64) -> (String, Arc<Mutex<Vec<i64>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+
+        let url = serve(move |req| {
+            let param = |name: &str| -> i64 { req.param(name).unwrap().parse().unwrap() };
+            let (start_time, end_time, limit) = (param("startTime"), param("endTime"), param("limit"));
+            seen.lock().unwrap().push(start_time);
+
+            let first = ((start_time - T0 + MINUTE - 1) / MINUTE).max(0);
+            let klines: Vec<String> = (first..total)
+                .map(|i| T0 + i * MINUTE)
+                .filter(|t| *t <= end_time)
+                .take(limit as usize)
+                .map(|t| {
+                    format!(
+                        r#"[{},"0.0010","0.0025","0.0015","0.0020","1000",{},"1.0000",100,"500","0.500","0"]"#,
+                        t,
+                        t + MINUTE - 1
+                    )
+                })
+                .collect();
+            MockResponse::json(200, format!("[{}]", klines.join(","))).header("X-MBX-USED-WEIGHT-1M", "2")
+        });
+        (format!("{}/api/v3/klines", url), requests)
+    }
+
+    #[test]
+    fn db_backfill_pages_and_resumes() {
+        use crate::schema::klines::dsl::*;
+        use diesel::prelude::*;
+
+        let (url, requests) = serve_klines(3000);
+        let mut backfill = KlineBackfill::new(
+            Symbol::new("TESTBTC"),
+            KlineInterval::_1m,
+            create_timestamp_benchmark(T0),
+            create_timestamp_benchmark(T0 + 2499 * MINUTE),
+        );
+        backfill.url = url;
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, crate::error::Error, _>(|| {
+            // 2500 klines in pages of 1000
+            assert_eq!(backfill.run(&conn)?, 2500);
+            assert_eq!(*requests.lock().unwrap(), vec![T0, T0 + 1000 * MINUTE, T0 + 2000 * MINUTE]);
+
+            // nothing left to fetch in the same range
+            assert_eq!(backfill.run(&conn)?, 0);
+            assert_eq!(requests.lock().unwrap().len(), 3);
+
+            // a wider range only fetches the klines not stored yet
+            backfill.end = create_timestamp_benchmark(T0 + 2999 * MINUTE);
+            assert_eq!(backfill.run(&conn)?, 500);
+            assert_eq!(requests.lock().unwrap()[3], T0 + 2500 * MINUTE);
+
+            // re-running a page replaces rows instead of duplicating them
+            let rows: Vec<KlineDataInsert> = klines
+                .filter(symbol.eq(Symbol::new("TESTBTC")))
+                .select((
+                    event, event_time, start_time, close_time, symbol, interval, first_trade_id, last_trade_id,
+                    open, close, high, low, volume, num_of_trades, is_kline_closed, quote_asset_vol,
+                    taker_buy_base_vol, taker_buy_quote_vol,
+                ))
+                .limit(1000)
+                .load(&conn)?;
+            upsert_klines(&conn, &rows)?;
+            let count: i64 = klines.filter(symbol.eq(Symbol::new("TESTBTC"))).count().get_result(&conn)?;
+            assert_eq!(count, 3000);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn db_backfill_fills_gaps_below_newer_klines() {
+        use crate::schema::klines::dsl::*;
+        use diesel::prelude::*;
+
+        let (url, requests) = serve_klines(6000);
+        let mut backfill = KlineBackfill::new(
+            Symbol::new("GAPSBTC"),
+            KlineInterval::_1m,
+            create_timestamp_benchmark(T0),
+            create_timestamp_benchmark(T0 + 99 * MINUTE),
+        );
+        backfill.url = url.clone();
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, crate::error::Error, _>(|| {
+            // a kline stored by the live stream, well after the requested range
+            let mut live = KlineBackfill::new(
+                Symbol::new("GAPSBTC"),
+                KlineInterval::_1m,
+                create_timestamp_benchmark(T0 + 5000 * MINUTE),
+                create_timestamp_benchmark(T0 + 5000 * MINUTE),
+            );
+            live.url = url;
+            assert_eq!(live.run(&conn)?, 1);
+
+            assert_eq!(backfill.run(&conn)?, 100);
+            assert_eq!(requests.lock().unwrap()[1], T0);
+
+            // only the missing klines are fetched again
+            diesel::delete(klines.filter(symbol.eq(Symbol::new("GAPSBTC"))).filter(
+                start_time.between(create_timestamp_benchmark(T0 + 40 * MINUTE), create_timestamp_benchmark(T0 + 49 * MINUTE)),
+            ))
+            .execute(&conn)?;
+            assert_eq!(backfill.run(&conn)?, 10);
+            assert_eq!(requests.lock().unwrap()[2..], [T0 + 40 * MINUTE]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn missing_ranges_between_stored_klines() {
+        let t = |m: i64| create_timestamp_benchmark(T0 + m * MINUTE);
+        let stored = vec![t(2), t(3), t(6), t(20)];
+        // the range starts mid-interval, so the first kline wanted is at 1
+        let gaps = missing_ranges(&KlineInterval::_1m, t(0) + chrono::Duration::seconds(1), t(9), &stored);
+        assert_eq!(gaps, vec![(t(1), t(1)), (t(4), t(5)), (t(7), t(9))]);
+        assert!(missing_ranges(&KlineInterval::_1m, t(2), t(3), &stored).is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Exponential backoff with jitter for reconnecting websocket streams.
+/// Each call to `next_delay()` doubles the delay ceiling, up to `max_delay`,
+/// and returns `None` once `max_retries` consecutive attempts have been made.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub max_retries: Option<u32>, // None retries forever
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(base_delay: Duration, max_delay: Duration, max_retries: Option<u32>) -> Self {
+        Backoff {
+            base_delay,
+            max_delay,
+            max_retries,
+            attempt: 0,
+        }
+    }
+
+    /// Number of consecutive attempts since the last `reset()`
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Call when a connection is successfully established
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Delay before the next reconnect attempt, or `None` if the retry ceiling is hit.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if let Some(max_retries) = self.max_retries {
+            if self.attempt >= max_retries {
+                return None;
+            }
+        }
+        let ceiling = self.delay_ceiling(self.attempt);
+        self.attempt += 1;
+        // "Equal jitter": wait at least half the ceiling, plus a random amount up to the other half.
+        // Stops every stream from hammering Binance at the same instant after a mass disconnect.
+        let half_ms = duration_as_ms(ceiling) / 2;
+        let jitter_ms = if half_ms > 0 { random_u64() % (half_ms + 1) } else { 0 };
+        Some(Duration::from_millis(half_ms + jitter_ms))
+    }
+
+    /// Upper bound of the delay for a given attempt: base_delay * 2^attempt, capped at max_delay
+    pub fn delay_ceiling(&self, attempt: u32) -> Duration {
+        let base_ms = duration_as_ms(self.base_delay);
+        let max_ms = duration_as_ms(self.max_delay);
+        let exp_ms = base_ms.saturating_mul(1u64.checked_shl(attempt).unwrap_or(std::u64::MAX));
+        Duration::from_millis(std::cmp::min(exp_ms, max_ms))
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_millis(500), Duration::from_secs(60), Some(20))
+    }
+}
+
+fn duration_as_ms(d: Duration) -> u64 {
+    d.as_secs() * 1_000 + u64::from(d.subsec_millis())
+}
+
+fn random_u64() -> u64 {
+    let mut buf = [0u8; 8];
+    match SystemRandom::new().fill(&mut buf) {
+        Ok(_) => u64::from_le_bytes(buf),
+        Err(_) => 0, // no jitter rather than no reconnect
+    }
+}
+
+/// Per-stream counters (reconnects, malformed frames, ...), shared between
+/// the stream supervisor and anything that wants to report on them.
+#[derive(Debug, Clone, Default)]
+pub struct StreamCounters {
+    counts: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl StreamCounters {
+    pub fn new() -> Self {
+        StreamCounters::default()
+    }
+
+    pub fn increment(&self, stream: &str) -> u64 {
+        let mut counts = self.counts.lock().expect("StreamCounters lock poisoned");
+        let count = counts.entry(stream.to_string()).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    pub fn get(&self, stream: &str) -> u64 {
+        let counts = self.counts.lock().expect("StreamCounters lock poisoned");
+        *counts.get(stream).unwrap_or(&0)
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, u64> {
+        self.counts.lock().expect("StreamCounters lock poisoned").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_and_caps() {
+        let backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(1_000), None);
+        assert_eq!(backoff.delay_ceiling(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay_ceiling(3), Duration::from_millis(800));
+        assert_eq!(backoff.delay_ceiling(4), Duration::from_millis(1_000));
+        assert_eq!(backoff.delay_ceiling(200), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn backoff_respects_retry_ceiling() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(10), Some(3));
+        for attempt in 0..3 {
+            let delay = backoff.next_delay().expect("should still retry");
+            let ceiling = backoff.delay_ceiling(attempt);
+            assert!(delay >= ceiling / 2 && delay <= ceiling);
+        }
+        assert_eq!(backoff.next_delay(), None);
+        backoff.reset();
+        assert!(backoff.next_delay().is_some());
+    }
+
+    #[test]
+    fn stream_counters_per_stream() {
+        let counters = StreamCounters::new();
+        counters.increment("ethbtc@trade");
+        counters.increment("ethbtc@trade");
+        counters.clone().increment("ethbtc@kline_1m");
+        assert_eq!(counters.get("ethbtc@trade"), 2);
+        assert_eq!(counters.get("ethbtc@kline_1m"), 1);
+        assert_eq!(counters.get("bnbeth@trade"), 0);
+    }
+}
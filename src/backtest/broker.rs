@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime};
+use num::Zero;
+
+use crate::backtest::MarketEvent;
+use crate::models::book_depth::Quote;
+use crate::models::orders::OrderSide;
+use crate::symbols::Symbol;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimOrderKind {
+    Market,
+    Limit(BigDecimal), // limit price
+}
+
+/// Order waiting to be matched by the `SimBroker`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimOrder {
+    pub id: u64,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub kind: SimOrderKind,
+    pub quantity: BigDecimal,
+    pub submitted_at: NaiveDateTime,
+    pub active_at: NaiveDateTime, // submitted_at + latency, not matched before this
+}
+
+/// Executed order, all fills are for the full order quantity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub order_id: u64,
+    pub time: NaiveDateTime,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub fee: BigDecimal, // in the quote asset
+}
+
+impl Fill {
+    pub fn notional(&self) -> BigDecimal {
+        &self.price * &self.quantity
+    }
+}
+
+/// Fees, latency and slippage applied to simulated orders
+#[derive(Debug, Clone)]
+pub struct FillConfig {
+    pub fee_rate: BigDecimal, // fraction of notional, Binance's default is 0.001
+    pub latency: Duration,    // time from submit until the order can match
+    pub slippage_bps: u32,    // market orders fill this much worse than the reference price
+}
+
+impl Default for FillConfig {
+    fn default() -> Self {
+        FillConfig {
+            fee_rate: "0.001".parse().unwrap(),
+            latency: Duration::milliseconds(100),
+            slippage_bps: 5,
+        }
+    }
+}
+
+/// Price levels rebuilt from stored depth diffs. Starts empty, so the top of
+/// book is only meaningful once both sides have been updated.
+#[derive(Debug, Clone, Default)]
+struct ReplayBook {
+    bids: BTreeMap<BigDecimal, BigDecimal>,
+    asks: BTreeMap<BigDecimal, BigDecimal>,
+}
+
+impl ReplayBook {
+    fn apply(side: &mut BTreeMap<BigDecimal, BigDecimal>, quotes: &[Quote]) {
+        for quote in quotes {
+            if quote.quantity.is_zero() {
+                side.remove(&quote.price);
+            } else {
+                side.insert(quote.price.clone(), quote.quantity.clone());
+            }
+        }
+    }
+
+    fn best_bid(&self) -> Option<BigDecimal> {
+        self.bids.keys().next_back().cloned()
+    }
+
+    fn best_ask(&self) -> Option<BigDecimal> {
+        self.asks.keys().next().cloned()
+    }
+}
+
+/// Simulated account: matches orders against replayed events and keeps cash and positions
+/// in the quote asset. Every symbol traded is assumed to share the same quote asset.
+#[derive(Debug, Clone)]
+pub struct SimBroker {
+    pub config: FillConfig,
+    now: NaiveDateTime,
+    next_order_id: u64,
+    open_orders: Vec<SimOrder>,
+    cash: BigDecimal,
+    positions: HashMap<Symbol, BigDecimal>,
+    marks: HashMap<Symbol, BigDecimal>, // latest price per symbol
+    books: HashMap<Symbol, ReplayBook>,
+    fills: Vec<Fill>,
+    traded_notional: BigDecimal,
+    fees: BigDecimal,
+}
+
+impl SimBroker {
+    pub fn new(config: FillConfig, initial_cash: BigDecimal) -> Self {
+        SimBroker {
+            config,
+            now: NaiveDateTime::from_timestamp(0, 0),
+            next_order_id: 1,
+            open_orders: Vec::new(),
+            cash: initial_cash,
+            positions: HashMap::new(),
+            marks: HashMap::new(),
+            books: HashMap::new(),
+            fills: Vec::new(),
+            traded_notional: BigDecimal::zero(),
+            fees: BigDecimal::zero(),
+        }
+    }
+
+    /// Time of the event being replayed
+    pub fn now(&self) -> NaiveDateTime {
+        self.now
+    }
+
+    pub fn cash(&self) -> &BigDecimal {
+        &self.cash
+    }
+
+    pub fn position(&self, symbol: &Symbol) -> BigDecimal {
+        self.positions.get(symbol).cloned().unwrap_or_else(BigDecimal::zero)
+    }
+
+    pub fn mark(&self, symbol: &Symbol) -> Option<&BigDecimal> {
+        self.marks.get(symbol)
+    }
+
+    pub fn open_orders(&self) -> &[SimOrder] {
+        &self.open_orders
+    }
+
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    pub fn traded_notional(&self) -> &BigDecimal {
+        &self.traded_notional
+    }
+
+    pub fn fees(&self) -> &BigDecimal {
+        &self.fees
+    }
+
+    /// Cash plus every position valued at its latest price
+    pub fn equity(&self) -> BigDecimal {
+        let mut equity = self.cash.clone();
+        for (symbol, quantity) in self.positions.iter() {
+            if let Some(mark) = self.marks.get(symbol) {
+                equity += quantity * mark;
+            }
+        }
+        equity
+    }
+
+    /// Queue an order, returns its id. It can match once `latency` has passed.
+    pub fn submit(&mut self, symbol: Symbol, side: OrderSide, kind: SimOrderKind, quantity: BigDecimal) -> u64 {
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        self.open_orders.push(SimOrder {
+            id,
+            symbol,
+            side,
+            kind,
+            quantity,
+            submitted_at: self.now,
+            active_at: self.now + self.config.latency,
+        });
+        id
+    }
+
+    pub fn buy_market(&mut self, symbol: Symbol, quantity: BigDecimal) -> u64 {
+        self.submit(symbol, OrderSide::Buy, SimOrderKind::Market, quantity)
+    }
+
+    pub fn sell_market(&mut self, symbol: Symbol, quantity: BigDecimal) -> u64 {
+        self.submit(symbol, OrderSide::Sell, SimOrderKind::Market, quantity)
+    }
+
+    /// Returns false if the order already filled or was never submitted
+    pub fn cancel(&mut self, order_id: u64) -> bool {
+        let before = self.open_orders.len();
+        self.open_orders.retain(|o| o.id != order_id);
+        self.open_orders.len() != before
+    }
+
+    /// Move the clock to the event, update prices and match open orders. Returns the new fills.
+    pub fn advance(&mut self, event: &MarketEvent) -> Vec<Fill> {
+        self.now = event.time();
+        let symbol = event.symbol().clone();
+
+        // (market buy, market sell, limit buy touched if <=, limit sell touched if >=)
+        let prices = match event {
+            MarketEvent::Trade(t) => Some((t.price.clone(), t.price.clone(), t.price.clone(), t.price.clone())),
+            MarketEvent::Kline(k) => Some((k.close.clone(), k.close.clone(), k.low.clone(), k.high.clone())),
+            MarketEvent::Ticker(t) => Some((
+                t.best_ask_price.clone(),
+                t.best_bid_price.clone(),
+                t.best_ask_price.clone(),
+                t.best_bid_price.clone(),
+            )),
+            MarketEvent::BookUpdate(b) => {
+                let book = self.books.entry(symbol.clone()).or_insert_with(ReplayBook::default);
+                ReplayBook::apply(&mut book.bids, &b.bids);
+                ReplayBook::apply(&mut book.asks, &b.asks);
+                match (book.best_bid(), book.best_ask()) {
+                    (Some(bid), Some(ask)) if bid < ask => Some((ask.clone(), bid.clone(), ask, bid)),
+                    _ => None,
+                }
+            }
+        };
+        let (buy_at, sell_at, buy_touch, sell_touch) = match prices {
+            Some(prices) => prices,
+            None => return vec![],
+        };
+
+        let mark = match event {
+            MarketEvent::Trade(t) => t.price.clone(),
+            MarketEvent::Kline(k) => k.close.clone(),
+            MarketEvent::Ticker(t) => t.last_price.clone(),
+            MarketEvent::BookUpdate(_) => (&buy_at + &sell_at) / BigDecimal::from(2),
+        };
+        self.marks.insert(symbol.clone(), mark);
+
+        let slippage = BigDecimal::from(self.config.slippage_bps) / BigDecimal::from(10_000);
+        let now = self.now;
+        let (matched, open): (Vec<SimOrder>, Vec<SimOrder>) = self.open_orders.drain(..).partition(|o| {
+            o.symbol == symbol
+                && o.active_at <= now
+                && match (&o.kind, o.side) {
+                    (SimOrderKind::Market, _) => true,
+                    (SimOrderKind::Limit(limit), OrderSide::Buy) => buy_touch <= *limit,
+                    (SimOrderKind::Limit(limit), OrderSide::Sell) => sell_touch >= *limit,
+                }
+        });
+        self.open_orders = open;
+
+        let one = BigDecimal::from(1);
+        let mut fills = Vec::with_capacity(matched.len());
+        for order in matched {
+            let price = match (&order.kind, order.side) {
+                (SimOrderKind::Market, OrderSide::Buy) => &buy_at * (&one + &slippage),
+                (SimOrderKind::Market, OrderSide::Sell) => &sell_at * (&one - &slippage),
+                (SimOrderKind::Limit(limit), _) => limit.clone(),
+            };
+            let notional = &price * &order.quantity;
+            let fee = &notional * &self.config.fee_rate;
+            let position = self.positions.entry(order.symbol.clone()).or_insert_with(BigDecimal::zero);
+            match order.side {
+                OrderSide::Buy => {
+                    self.cash -= &notional + &fee;
+                    *position += &order.quantity;
+                }
+                OrderSide::Sell => {
+                    self.cash += &notional - &fee;
+                    *position -= &order.quantity;
+                }
+            }
+            self.traded_notional += notional;
+            self.fees += &fee;
+
+            let fill = Fill {
+                order_id: order.id,
+                time: now,
+                symbol: order.symbol,
+                side: order.side,
+                price,
+                quantity: order.quantity,
+                fee,
+            };
+            self.fills.push(fill.clone());
+            fills.push(fill);
+        }
+        fills
+    }
+}
@@ -0,0 +1,288 @@
+/////////////////////////////////////////////////////////////////
+/// Backtesting: replay stored market data through a `Strategy`,
+/// filling its orders on a simulated account.
+/////////////////////////////////////////////////////////////////
+
+pub mod broker;
+pub mod replay;
+pub mod stats;
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime};
+use num::ToPrimitive;
+
+use crate::models::book_depth::BookDepthData;
+use crate::models::klines::KlineData;
+use crate::models::tickers::TickerData;
+use crate::models::trades::TradeData;
+use crate::symbols::Symbol;
+
+pub use self::broker::{Fill, FillConfig, SimBroker, SimOrder, SimOrderKind};
+pub use crate::models::orders::OrderSide;
+pub use self::replay::{MarketReplay, ReplayConfig};
+pub use self::stats::{EquityPoint, Summary};
+
+/// A stored row, replayed in `event_time` order
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    Trade(TradeData),
+    Kline(KlineData),
+    Ticker(TickerData),
+    BookUpdate(BookDepthData),
+}
+
+impl MarketEvent {
+    pub fn time(&self) -> NaiveDateTime {
+        match self {
+            MarketEvent::Trade(e) => e.event_time,
+            MarketEvent::Kline(e) => e.event_time,
+            MarketEvent::Ticker(e) => e.event_time,
+            MarketEvent::BookUpdate(e) => e.event_time,
+        }
+    }
+
+    pub fn symbol(&self) -> &Symbol {
+        match self {
+            MarketEvent::Trade(e) => &e.symbol,
+            MarketEvent::Kline(e) => &e.symbol,
+            MarketEvent::Ticker(e) => &e.symbol,
+            MarketEvent::BookUpdate(e) => &e.symbol,
+        }
+    }
+
+    /// (event_time, primary key), the order rows are read from each table
+    pub fn key(&self) -> (NaiveDateTime, i64) {
+        match self {
+            MarketEvent::Trade(e) => (e.event_time, e.trade_id),
+            MarketEvent::Kline(e) => (e.event_time, i64::from(e.id)),
+            MarketEvent::Ticker(e) => (e.event_time, i64::from(e.id)),
+            MarketEvent::BookUpdate(e) => (e.event_time, i64::from(e.id)),
+        }
+    }
+}
+
+/// Callbacks for each replayed event. Orders are placed on the `SimBroker`,
+/// and only fill on a later event once the configured latency has passed.
+pub trait Strategy {
+    fn on_trade(&mut self, _trade: &TradeData, _broker: &mut SimBroker) {}
+
+    fn on_kline(&mut self, _kline: &KlineData, _broker: &mut SimBroker) {}
+
+    fn on_book_update(&mut self, _update: &BookDepthData, _broker: &mut SimBroker) {}
+
+    fn on_ticker(&mut self, _ticker: &TickerData, _broker: &mut SimBroker) {}
+
+    fn on_fill(&mut self, _fill: &Fill, _broker: &mut SimBroker) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestConfig {
+    pub initial_cash: BigDecimal,  // in the quote asset
+    pub fills: FillConfig,         // fees, latency and slippage
+    pub equity_interval: Duration, // equity curve resolution, also the Sharpe return period
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        BacktestConfig {
+            initial_cash: BigDecimal::from(1),
+            fills: FillConfig::default(),
+            equity_interval: Duration::minutes(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub equity_curve: Vec<EquityPoint>,
+    pub fills: Vec<Fill>, // trade log
+    pub summary: Summary,
+}
+
+pub struct Backtest<S: Strategy> {
+    pub config: BacktestConfig,
+    pub strategy: S,
+    broker: SimBroker,
+    equity_curve: Vec<EquityPoint>,
+    next_sample: Option<NaiveDateTime>,
+}
+
+impl<S: Strategy> Backtest<S> {
+    pub fn new(config: BacktestConfig, strategy: S) -> Self {
+        let broker = SimBroker::new(config.fills.clone(), config.initial_cash.clone());
+        Backtest {
+            config,
+            strategy,
+            broker,
+            equity_curve: Vec::new(),
+            next_sample: None,
+        }
+    }
+
+    pub fn broker(&self) -> &SimBroker {
+        &self.broker
+    }
+
+    /// Replay events (in `event_time` order, e.g: a `MarketReplay`) through the strategy
+    pub fn run<I>(mut self, events: I) -> BacktestReport
+    where
+        I: IntoIterator<Item = MarketEvent>,
+    {
+        for event in events {
+            self.step(&event);
+        }
+        let now = self.broker.now();
+        self.sample(now, true);
+
+        let to_f64 = |d: &BigDecimal| d.to_f64().unwrap_or(0.0);
+        let summary = Summary::new(
+            &self.equity_curve,
+            self.config.equity_interval,
+            to_f64(self.broker.traded_notional()),
+            to_f64(self.broker.fees()),
+            self.broker.fills().len(),
+        );
+        BacktestReport {
+            equity_curve: self.equity_curve,
+            fills: self.broker.fills().to_vec(),
+            summary,
+        }
+    }
+
+    fn step(&mut self, event: &MarketEvent) {
+        self.sample(event.time(), false);
+
+        for fill in self.broker.advance(event) {
+            self.strategy.on_fill(&fill, &mut self.broker);
+        }
+        match event {
+            MarketEvent::Trade(e) => self.strategy.on_trade(e, &mut self.broker),
+            MarketEvent::Kline(e) => self.strategy.on_kline(e, &mut self.broker),
+            MarketEvent::Ticker(e) => self.strategy.on_ticker(e, &mut self.broker),
+            MarketEvent::BookUpdate(e) => self.strategy.on_book_update(e, &mut self.broker),
+        }
+    }
+
+    /// Record equity as of just before `time`, once per interval
+    fn sample(&mut self, time: NaiveDateTime, last: bool) {
+        let due = match self.next_sample {
+            Some(next) => time >= next,
+            None => true,
+        };
+        if due || last {
+            let equity = self.broker.equity().to_f64().unwrap_or(0.0);
+            self.equity_curve.push(EquityPoint { time, equity });
+            self.next_sample = Some(time + self.config.equity_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
+
+    const T0: i64 = 1_546_300_800_000;
+
+    fn trade(id: i64, ms: i64, price: &str) -> MarketEvent {
+        MarketEvent::Trade(TradeData {
+            trade_id: id,
+            event: "trade".to_owned(),
+            event_time: create_timestamp_benchmark(T0 + ms),
+            symbol: Symbol::new("ETHBTC"),
+            price: create_decimal_benchmark(price),
+            quantity: create_decimal_benchmark("1"),
+            trade_time: create_timestamp_benchmark(T0 + ms),
+            buyer_order_id: 1,
+            seller_order_id: 2,
+            buyer_mkt_maker: false,
+        })
+    }
+
+    /// Buys on the first trade, sells once the price rises 10%
+    #[derive(Default)]
+    struct TakeProfit {
+        entry: Option<BigDecimal>,
+        sold: bool,
+    }
+
+    impl Strategy for TakeProfit {
+        fn on_trade(&mut self, trade: &TradeData, broker: &mut SimBroker) {
+            match &self.entry {
+                None if broker.open_orders().is_empty() => {
+                    broker.buy_market(trade.symbol.clone(), BigDecimal::from(10));
+                }
+                Some(entry) if !self.sold && trade.price >= entry * create_decimal_benchmark("1.1") => {
+                    broker.sell_market(trade.symbol.clone(), BigDecimal::from(10));
+                    self.sold = true;
+                }
+                _ => (),
+            }
+        }
+
+        fn on_fill(&mut self, fill: &Fill, _broker: &mut SimBroker) {
+            if fill.side == OrderSide::Buy {
+                self.entry = Some(fill.price.clone());
+            }
+        }
+    }
+
+    #[test]
+    fn backtest_fills_with_latency_fees_and_slippage() {
+        let config = BacktestConfig {
+            initial_cash: BigDecimal::from(1),
+            fills: FillConfig {
+                fee_rate: create_decimal_benchmark("0.001"),
+                latency: Duration::milliseconds(100),
+                slippage_bps: 10,
+            },
+            equity_interval: Duration::seconds(1),
+        };
+        let events = vec![
+            trade(1, 0, "0.030"),     // buy submitted
+            trade(2, 50, "0.031"),    // too soon, still in flight
+            trade(3, 1_000, "0.032"), // bought at 0.032 + 0.1%
+            trade(4, 2_000, "0.028"),
+            trade(5, 3_000, "0.036"), // sell submitted
+            trade(6, 4_000, "0.035"), // sold at 0.035 - 0.1%
+        ];
+        let report = Backtest::new(config, TakeProfit::default()).run(events);
+
+        assert_eq!(report.fills.len(), 2);
+        let (buy, sell) = (&report.fills[0], &report.fills[1]);
+        assert_eq!(buy.time, create_timestamp_benchmark(T0 + 1_000));
+        assert_eq!(buy.price, create_decimal_benchmark("0.032032"));
+        assert_eq!(buy.fee, create_decimal_benchmark("0.00032032"));
+        assert_eq!(sell.price, create_decimal_benchmark("0.034965"));
+
+        // 1 - 0.32032 - 0.00032032 + 0.34965 - 0.00034965
+        let summary = &report.summary;
+        assert!((summary.final_equity - 1.02866003).abs() < 1e-9);
+        assert!((summary.total_return - 0.02866003).abs() < 1e-9);
+        assert!((summary.turnover - 0.66997).abs() < 1e-9);
+        // marked at 0.028 after buying: 1 - 0.32064032 + 0.28
+        assert!((summary.max_drawdown - 0.04064032).abs() < 1e-9);
+        assert_eq!(report.equity_curve.len(), 6);
+    }
+
+    #[test]
+    fn summary_statistics() {
+        let curve: Vec<EquityPoint> = [100.0, 110.0, 99.0, 120.0, 108.0]
+            .iter()
+            .enumerate()
+            .map(|(i, e)| EquityPoint { time: create_timestamp_benchmark(T0 + i as i64 * 86_400_000), equity: *e })
+            .collect();
+
+        assert!((stats::max_drawdown(&curve) - 0.1).abs() < 1e-12);
+
+        // returns: 0.1, -0.1, 0.2121.., -0.1
+        let returns = [0.1, -0.1, 21.0 / 99.0, -0.1];
+        let mean = returns.iter().sum::<f64>() / 4.0;
+        let sd = (returns.iter().map(|r| (r - mean) * (r - mean)).sum::<f64>() / 3.0).sqrt();
+        let expected = mean / sd * 365f64.sqrt();
+        assert!((stats::sharpe_ratio(&curve, Duration::days(1)) - expected).abs() < 1e-9);
+
+        let flat = vec![curve[0].clone(), curve[0].clone(), curve[0].clone()];
+        assert_eq!(stats::sharpe_ratio(&flat, Duration::days(1)), 0.0);
+    }
+}
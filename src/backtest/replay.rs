@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+use crate::backtest::MarketEvent;
+use crate::models::book_depth::BookDepthData;
+use crate::models::klines::KlineData;
+use crate::models::tickers::TickerData;
+use crate::models::trades::TradeData;
+use crate::symbols::Symbol;
+
+/// Which stored tables are replayed, and for which symbols and time range
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    pub symbols: Vec<Symbol>,
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+    pub trades: bool,
+    pub klines: bool,    // closed klines only
+    pub tickers: bool,
+    pub book_depth: bool,
+    pub page_size: i64,  // rows loaded per query, per table
+}
+
+impl ReplayConfig {
+    pub fn new(symbols: Vec<Symbol>, from: NaiveDateTime, to: NaiveDateTime) -> Self {
+        ReplayConfig {
+            symbols,
+            from,
+            to,
+            trades: true,
+            klines: true,
+            tickers: true,
+            book_depth: true,
+            page_size: 10_000,
+        }
+    }
+}
+
+type Key = (NaiveDateTime, i64); // (event_time, id), unique replay order within a table
+
+/// Pages through one table in (event_time, id) order
+struct TableCursor {
+    buffer: VecDeque<MarketEvent>,
+    last_key: Option<Key>,
+    exhausted: bool,
+    fetch: fn(&PgConnection, &ReplayConfig, Option<Key>) -> QueryResult<Vec<MarketEvent>>,
+}
+
+impl TableCursor {
+    fn new(fetch: fn(&PgConnection, &ReplayConfig, Option<Key>) -> QueryResult<Vec<MarketEvent>>) -> Self {
+        TableCursor {
+            buffer: VecDeque::new(),
+            last_key: None,
+            exhausted: false,
+            fetch,
+        }
+    }
+
+    fn peek(&mut self, conn: &PgConnection, config: &ReplayConfig) -> QueryResult<Option<&MarketEvent>> {
+        if self.buffer.is_empty() && !self.exhausted {
+            let page = (self.fetch)(conn, config, self.last_key)?;
+            self.exhausted = (page.len() as i64) < config.page_size;
+            self.last_key = page.last().map(MarketEvent::key);
+            self.buffer.extend(page);
+        }
+        Ok(self.buffer.front())
+    }
+}
+
+/// Streams stored market data from every table in `event_time` order, across symbols,
+/// without loading the whole range into memory
+pub struct MarketReplay<'a> {
+    conn: &'a PgConnection,
+    config: ReplayConfig,
+    cursors: Vec<TableCursor>,
+}
+
+impl<'a> MarketReplay<'a> {
+    pub fn new(conn: &'a PgConnection, config: ReplayConfig) -> Self {
+        let mut cursors = Vec::new();
+        if config.trades {
+            cursors.push(TableCursor::new(fetch_trades));
+        }
+        if config.klines {
+            cursors.push(TableCursor::new(fetch_klines));
+        }
+        if config.tickers {
+            cursors.push(TableCursor::new(fetch_tickers));
+        }
+        if config.book_depth {
+            cursors.push(TableCursor::new(fetch_book_depth));
+        }
+        MarketReplay { conn, config, cursors }
+    }
+
+    /// Next event across all tables, ties keep the table order above
+    pub fn next_event(&mut self) -> QueryResult<Option<MarketEvent>> {
+        let mut earliest: Option<(usize, NaiveDateTime)> = None;
+        for (i, cursor) in self.cursors.iter_mut().enumerate() {
+            if let Some(event) = cursor.peek(self.conn, &self.config)? {
+                let time = event.time();
+                if earliest.map_or(true, |(_, t)| time < t) {
+                    earliest = Some((i, time));
+                }
+            }
+        }
+        Ok(earliest.and_then(|(i, _)| self.cursors[i].buffer.pop_front()))
+    }
+}
+
+/// Stops at the first database error, after printing it
+impl<'a> Iterator for MarketReplay<'a> {
+    type Item = MarketEvent;
+
+    fn next(&mut self) -> Option<MarketEvent> {
+        match self.next_event() {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Replay stopped, database error: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn fetch_trades(conn: &PgConnection, config: &ReplayConfig, after: Option<Key>) -> QueryResult<Vec<MarketEvent>> {
+    use crate::schema::trades::dsl::*;
+
+    let mut query = trades
+        .filter(symbol.eq_any(&config.symbols))
+        .filter(event_time.between(config.from, config.to))
+        .into_boxed();
+    if let Some((t, i)) = after {
+        query = query.filter(event_time.gt(t).or(event_time.eq(t).and(trade_id.gt(i))));
+    }
+    let rows = query
+        .order((event_time.asc(), trade_id.asc()))
+        .limit(config.page_size)
+        .load::<TradeData>(conn)?;
+    Ok(rows.into_iter().map(MarketEvent::Trade).collect())
+}
+
+fn fetch_klines(conn: &PgConnection, config: &ReplayConfig, after: Option<Key>) -> QueryResult<Vec<MarketEvent>> {
+    use crate::schema::klines::dsl::*;
+
+    let mut query = klines
+        .filter(symbol.eq_any(&config.symbols))
+        .filter(event_time.between(config.from, config.to))
+        .filter(is_kline_closed.eq(true))
+        .into_boxed();
+    if let Some((t, i)) = after {
+        query = query.filter(event_time.gt(t).or(event_time.eq(t).and(id.gt(i as i32))));
+    }
+    let rows = query
+        .order((event_time.asc(), id.asc()))
+        .limit(config.page_size)
+        .load::<KlineData>(conn)?;
+    Ok(rows.into_iter().map(MarketEvent::Kline).collect())
+}
+
+fn fetch_tickers(conn: &PgConnection, config: &ReplayConfig, after: Option<Key>) -> QueryResult<Vec<MarketEvent>> {
+    use crate::schema::tickers::dsl::*;
+
+    let mut query = tickers
+        .filter(symbol.eq_any(&config.symbols))
+        .filter(event_time.between(config.from, config.to))
+        .into_boxed();
+    if let Some((t, i)) = after {
+        query = query.filter(event_time.gt(t).or(event_time.eq(t).and(id.gt(i as i32))));
+    }
+    let rows = query
+        .order((event_time.asc(), id.asc()))
+        .limit(config.page_size)
+        .load::<TickerData>(conn)?;
+    Ok(rows.into_iter().map(MarketEvent::Ticker).collect())
+}
+
+fn fetch_book_depth(conn: &PgConnection, config: &ReplayConfig, after: Option<Key>) -> QueryResult<Vec<MarketEvent>> {
+    use crate::schema::book_depth::dsl::*;
+
+    let mut query = book_depth
+        .filter(symbol.eq_any(&config.symbols))
+        .filter(event_time.between(config.from, config.to))
+        .into_boxed();
+    if let Some((t, i)) = after {
+        query = query.filter(event_time.gt(t).or(event_time.eq(t).and(id.gt(i as i32))));
+    }
+    let rows = query
+        .order((event_time.asc(), id.asc()))
+        .limit(config.page_size)
+        .load::<BookDepthData>(conn)?;
+    Ok(rows.into_iter().map(MarketEvent::BookUpdate).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::models::klines::KlineDataInsert;
+    use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
+
+    const T0: i64 = 1_546_300_800_000;
+
+    #[test]
+    fn db_replay_merges_tables_in_event_time_order() {
+        let symbol = Symbol::new("REPLAYBTC");
+        let trade = |id: i64, ms: i64| TradeData {
+            trade_id: id,
+            event: "trade".to_owned(),
+            event_time: create_timestamp_benchmark(T0 + ms),
+            symbol: symbol.clone(),
+            price: create_decimal_benchmark("0.03"),
+            quantity: create_decimal_benchmark("1"),
+            trade_time: create_timestamp_benchmark(T0 + ms),
+            buyer_order_id: 1,
+            seller_order_id: 2,
+            buyer_mkt_maker: false,
+        };
+        let kline = |ms: i64, closed: bool| KlineDataInsert {
+            event: "kline".to_owned(),
+            event_time: create_timestamp_benchmark(T0 + ms),
+            start_time: create_timestamp_benchmark(T0 + ms - 60_000),
+            close_time: create_timestamp_benchmark(T0 + ms - 1),
+            symbol: symbol.clone(),
+            interval: "1m".to_owned(),
+            first_trade_id: 1,
+            last_trade_id: 2,
+            open: create_decimal_benchmark("0.03"),
+            close: create_decimal_benchmark("0.03"),
+            high: create_decimal_benchmark("0.03"),
+            low: create_decimal_benchmark("0.03"),
+            volume: create_decimal_benchmark("1"),
+            num_of_trades: 2,
+            is_kline_closed: closed,
+            quote_asset_vol: create_decimal_benchmark("0.03"),
+            taker_buy_base_vol: create_decimal_benchmark("1"),
+            taker_buy_quote_vol: create_decimal_benchmark("0.03"),
+        };
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, diesel::result::Error, _>(|| {
+            use crate::schema::{klines, trades};
+
+            // two trades share a timestamp, paged two rows at a time
+            let rows = vec![trade(900_001, 10), trade(900_003, 30), trade(900_002, 30), trade(900_004, 90_000)];
+            diesel::insert_into(trades::table).values(&rows).execute(&conn)?;
+            let rows = vec![kline(20, true), kline(60_000, false), kline(60_020, true)];
+            diesel::insert_into(klines::table).values(&rows).execute(&conn)?;
+
+            let mut config = ReplayConfig::new(
+                vec![symbol.clone()],
+                create_timestamp_benchmark(T0),
+                create_timestamp_benchmark(T0 + 80_000),
+            );
+            config.page_size = 2;
+            let replayed: Vec<String> = MarketReplay::new(&conn, config)
+                .map(|e| match e {
+                    MarketEvent::Trade(t) => format!("trade {}", t.trade_id),
+                    MarketEvent::Kline(k) => format!("kline {}", k.event_time.timestamp_millis() - T0),
+                    other => format!("{:?}", other),
+                })
+                .collect();
+            assert_eq!(
+                replayed,
+                vec!["trade 900001", "kline 20", "trade 900002", "trade 900003", "kline 60020"]
+            );
+            Ok(())
+        });
+    }
+}
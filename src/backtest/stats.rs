@@ -0,0 +1,115 @@
+use std::fmt;
+use std::io;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::backtest::broker::Fill;
+
+/// Account equity, sampled once per `BacktestConfig::equity_interval`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquityPoint {
+    pub time: NaiveDateTime,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub initial_equity: f64,
+    pub final_equity: f64,
+    pub total_return: f64, // final / initial - 1
+    pub sharpe: f64,       // annualized, zero risk-free rate
+    pub max_drawdown: f64, // largest fall from a peak, as a fraction of the peak
+    pub turnover: f64,     // traded notional / initial equity
+    pub num_fills: usize,
+    pub fees: f64,
+}
+
+impl Summary {
+    pub fn new(curve: &[EquityPoint], interval: Duration, traded_notional: f64, fees: f64, num_fills: usize) -> Self {
+        let initial_equity = curve.first().map_or(0.0, |p| p.equity);
+        let final_equity = curve.last().map_or(0.0, |p| p.equity);
+        let ratio = |a: f64, b: f64| if b == 0.0 { 0.0 } else { a / b };
+
+        Summary {
+            initial_equity,
+            final_equity,
+            total_return: ratio(final_equity, initial_equity) - if initial_equity == 0.0 { 0.0 } else { 1.0 },
+            sharpe: sharpe_ratio(curve, interval),
+            max_drawdown: max_drawdown(curve),
+            turnover: ratio(traded_notional, initial_equity),
+            num_fills,
+            fees,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "equity:       {:.8} -> {:.8}", self.initial_equity, self.final_equity)?;
+        writeln!(f, "return:       {:.4}%", self.total_return * 100.0)?;
+        writeln!(f, "sharpe:       {:.4}", self.sharpe)?;
+        writeln!(f, "max drawdown: {:.4}%", self.max_drawdown * 100.0)?;
+        writeln!(f, "turnover:     {:.4}", self.turnover)?;
+        write!(f, "fills:        {} (fees {:.8})", self.num_fills, self.fees)
+    }
+}
+
+/// Mean over standard deviation of per-interval returns, scaled to a year
+pub fn sharpe_ratio(curve: &[EquityPoint], interval: Duration) -> f64 {
+    let returns: Vec<f64> = curve
+        .windows(2)
+        .filter(|w| w[0].equity != 0.0)
+        .map(|w| w[1].equity / w[0].equity - 1.0)
+        .collect();
+    if returns.len() < 2 || interval <= Duration::zero() {
+        return 0.0;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if variance == 0.0 {
+        return 0.0;
+    }
+    let periods_per_year = Duration::days(365).num_milliseconds() as f64 / interval.num_milliseconds() as f64;
+    mean / variance.sqrt() * periods_per_year.sqrt()
+}
+
+pub fn max_drawdown(curve: &[EquityPoint]) -> f64 {
+    let mut peak = std::f64::MIN;
+    let mut drawdown: f64 = 0.0;
+    for point in curve {
+        peak = peak.max(point.equity);
+        if peak > 0.0 {
+            drawdown = drawdown.max((peak - point.equity) / peak);
+        }
+    }
+    drawdown
+}
+
+/// time,equity
+pub fn write_equity_csv<W: io::Write>(mut w: W, curve: &[EquityPoint]) -> io::Result<()> {
+    writeln!(w, "time,equity")?;
+    for point in curve {
+        writeln!(w, "{},{}", point.time, point.equity)?;
+    }
+    Ok(())
+}
+
+/// time,order_id,symbol,side,price,quantity,fee
+pub fn write_fills_csv<W: io::Write>(mut w: W, fills: &[Fill]) -> io::Result<()> {
+    writeln!(w, "time,order_id,symbol,side,price,quantity,fee")?;
+    for fill in fills {
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{}",
+            fill.time,
+            fill.order_id,
+            fill.symbol.to_uppercase(),
+            fill.side,
+            fill.price,
+            fill.quantity,
+            fill.fee
+        )?;
+    }
+    Ok(())
+}
@@ -1,77 +1,75 @@
-use chrono::NaiveDateTime;
-use std::fmt;
-use std::time::Duration;
-
-use trading_sys::models::book_depth::{BookDepthDataInsert, DepthLevels, PartialBookDepthData};
-use trading_sys::{create_book_depth, establish_connection_pg};
+use trading_sys::models::book_depth::{parse_depth_stream, BookDepthDataInsert, BookSnapshot, DepthLevels, PartialBookDepthData};
+use trading_sys::models::combined_stream::split_stream_name;
+use trading_sys::db_writer::Row;
+use trading_sys::error::{Error, Result};
+use trading_sys::health::Observation;
+use trading_sys::symbols::Symbol;
 
 use actix::*;
-use actix_web::ws;
+use chrono::NaiveDateTime;
 
-pub struct BookDepthActor {
-    pub client_writer: ws::ClientWriter,
-    pub depth_levels: Option<DepthLevels>,
-}
+use crate::actors::db_writer::insert_row;
+use crate::actors::health::observe;
+use crate::actors::order_books::{ApplyDiff, OrderBooks};
+use crate::actors::stream::{stream_pair, BinanceEvent};
 
-impl Actor for BookDepthActor {
-    type Context = Context<Self>;
+/// `<symbol>@depth`, diffs applied to the symbol's local order book, see `OrderBooks`
+impl BinanceEvent for BookDepthDataInsert {
+    const SUFFIX: &'static str = "depth";
+    const TARGET: &'static str = module_path!();
+    type State = ();
 
-    fn started(&mut self, ctx: &mut Context<Self>) {
-        // Start heartbeats otherwise server disconnects in 10 seconds
-        self.hb(ctx);
+    fn handles(stream_type: &str) -> bool {
+        parse_depth_stream(stream_type).map(|(levels, _)| levels.is_none()).unwrap_or(false)
     }
 
-    fn stopped(&mut self, _: &mut Context<Self>) {
-        println!("Disconnected");
-        // Stop application on disconnect
-        System::current().stop();
+    fn state(stream: &str) -> Result<()> {
+        stream_pair(stream).map(|_| ())
     }
-}
 
-impl BookDepthActor {
-    fn hb(&self, ctx: &mut Context<Self>) {
-        ctx.run_later(std::time::Duration::new(1, 0), |act, ctx| {
-            act.client_writer.pong("Heartbeat");
-            act.hb(ctx);
-            // client should also check for a timeout here, similar to the
-            // server code
-        });
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
+    }
+
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        observe(stream, Observation::from(&self));
+        insert_row(Row::BookDepth(self.clone()), ctx);
+        OrderBooks::from_registry().do_send(ApplyDiff(self));
     }
 }
 
-/// Handle Websocket messages
-impl StreamHandler<ws::Message, ws::ProtocolError> for BookDepthActor {
-    fn handle(&mut self, msg: ws::Message, _ctx: &mut Context<Self>) {
-        match msg {
-            ws::Message::Text(txt) => match &self.depth_levels {
-                None => {
-                    let book_depth_data: BookDepthDataInsert = serde_json::from_str(&txt).unwrap();
+/// `<symbol>@depth<levels>`, the top of the book stored as a snapshot
+impl BinanceEvent for PartialBookDepthData {
+    const SUFFIX: &'static str = "depth";
+    const TARGET: &'static str = module_path!();
+    type State = (Symbol, DepthLevels);
+
+    fn handles(stream_type: &str) -> bool {
+        parse_depth_stream(stream_type).map(|(levels, _)| levels.is_some()).unwrap_or(false)
+    }
 
-                    println!("{:?}", &book_depth_data);
-                    let connection = establish_connection_pg();
-                    create_book_depth(&connection, book_depth_data);
-                }
-                Some(lvl) => {
-                    let partial_book: PartialBookDepthData =
-                        serde_json::from_str::<PartialBookDepthData>(&txt).unwrap();
-                    println!(
-                        "Partial Book Depth Streams.\nDepth Lvl:{}\n{:#}",
-                        lvl, partial_book
-                    );
-                }
-            },
-            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
-            ws::Message::Pong(pong) => self.client_writer.ping(&pong),
-            _ => (),
+    fn state(stream: &str) -> Result<(Symbol, DepthLevels)> {
+        match parse_depth_stream(split_stream_name(stream).1)? {
+            (Some(levels), _) => Ok((stream_pair(stream)?, levels)),
+            (None, _) => Err(Error::InvalidEnum("partial depth stream", stream.to_owned())),
         }
     }
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("<book_depth.rs>: Websocket Connected.");
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
     }
 
-    fn finished(&mut self, ctx: &mut Context<Self>) {
-        println!("<book_depth.rs>: Websocket Disconnected.");
-        ctx.stop()
+    /// Partial books carry no event time, they are stamped with the time received
+    fn sink<A>(self, state: &mut (Symbol, DepthLevels), stream: &str, received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        let (symbol, lvl) = state;
+        let snapshot = BookSnapshot::new(symbol.clone(), lvl, received_at, self);
+        observe(stream, Observation::from(&snapshot));
+        insert_row(Row::BookSnapshot(snapshot), ctx);
     }
 }
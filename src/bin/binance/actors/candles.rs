@@ -0,0 +1,93 @@
+use trading_sys::candles::{candle_row, BarSpec, CandleBuilder, Tick};
+use trading_sys::models::klines::KlineDataInsert;
+use trading_sys::symbols::Symbol;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::*;
+
+use crate::actors::db_writer::{DbBatcher, InsertRow};
+
+/// Which stream candles are built from. Trades and aggregate trades
+/// cover the same fills, so only one of them is counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TickSource {
+    Trades,
+    AggregateTrades,
+}
+
+/// Rolls live trades into bars for every `BarSpec`, closed bars are written to `klines` or `bars`
+pub struct CandleAggregator {
+    pub source: TickSource,
+    pub specs: Vec<BarSpec>,
+    builders: HashMap<Symbol, Vec<CandleBuilder>>,
+}
+
+impl CandleAggregator {
+    pub fn new(source: TickSource, specs: Vec<BarSpec>) -> Self {
+        CandleAggregator {
+            source,
+            specs,
+            builders: HashMap::new(),
+        }
+    }
+
+    fn write(spec: &BarSpec, candle: KlineDataInsert) {
+        debug!(actor = "candles", symbol = candle.symbol.as_str(); "Candle closed: {} {}", candle.interval, candle.start_time);
+        DbBatcher::from_registry().do_send(InsertRow(candle_row(spec, candle)));
+    }
+}
+
+impl Actor for CandleAggregator {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // Time bars close at the end of their interval, even if no trade follows
+        ctx.run_interval(Duration::from_secs(1), |act, _ctx| {
+            let now = chrono::Utc::now().naive_utc();
+            for builder in act.builders.values_mut().flat_map(|b| b.iter_mut()) {
+                if let Some(candle) = builder.close_expired(now) {
+                    CandleAggregator::write(&builder.spec, candle);
+                }
+            }
+        });
+    }
+}
+
+/// No bars until configured, see `main`
+impl Default for CandleAggregator {
+    fn default() -> Self {
+        CandleAggregator::new(TickSource::Trades, vec![])
+    }
+}
+
+impl Supervised for CandleAggregator {}
+
+impl SystemService for CandleAggregator {}
+
+#[derive(Message)]
+pub struct AddTick(pub TickSource, pub Tick);
+
+impl Handler<AddTick> for CandleAggregator {
+    type Result = ();
+
+    fn handle(&mut self, msg: AddTick, _ctx: &mut Context<Self>) {
+        let AddTick(source, tick) = msg;
+        if source != self.source || self.specs.is_empty() {
+            return;
+        }
+        let specs = &self.specs;
+        let builders = self.builders.entry(tick.symbol.clone()).or_insert_with(|| {
+            specs
+                .iter()
+                .map(|spec| CandleBuilder::new(tick.symbol.clone(), spec.clone()))
+                .collect()
+        });
+        for builder in builders.iter_mut() {
+            if let Some(candle) = builder.push(&tick) {
+                CandleAggregator::write(&builder.spec, candle);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use trading_sys::models::combined_stream::{
+    parse_combined_message, CombinedMessage, SubscriptionMethod, SubscriptionRequest,
+};
+use trading_sys::health::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use trading_sys::metrics::frame_received;
+
+use std::time::Instant;
+
+use actix::*;
+use actix_web::ws;
+
+use crate::actors::recorder::record_frame;
+use crate::actors::stream::StreamHandlers;
+use crate::supervisor::{CombinedConnected, Connected, Disconnected, Malformed, StreamSpec, StreamSupervisor};
+
+/// One websocket carrying many streams via `/stream?streams=a/b/c`
+pub struct CombinedStreamActor {
+    pub client_writer: ws::ClientWriter,
+    pub last_seen: Instant, // last frame of any kind, see `hb`
+    pub stream: StreamSpec,
+    pub supervisor: Addr<StreamSupervisor>,
+    pub request_id: u64,
+    pub handlers: StreamHandlers<CombinedStreamActor>, // parse and sink each stream as on its own socket
+}
+
+impl Actor for CombinedStreamActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // Start heartbeats otherwise server disconnects in 10 seconds
+        self.hb(ctx);
+    }
+
+    fn stopped(&mut self, _: &mut Context<Self>) {
+        // Let the supervisor schedule a reconnect
+        self.supervisor.do_send(Disconnected(self.stream.clone()));
+    }
+}
+
+impl CombinedStreamActor {
+    /// Ping the server every `HEARTBEAT_INTERVAL` and reconnect if nothing,
+    /// not even the pong, arrived within `CLIENT_TIMEOUT`
+    fn hb(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(HEARTBEAT_INTERVAL, |act, ctx| {
+            if act.last_seen.elapsed() > CLIENT_TIMEOUT {
+                warn!(actor = "combined_stream", stream:% = act.stream.stream_name(); "No frames for {:?}, reconnecting", CLIENT_TIMEOUT);
+                ctx.stop();
+                return;
+            }
+            act.client_writer.ping("Heartbeat");
+            act.hb(ctx);
+        });
+    }
+}
+
+/// Add or remove streams on the live socket, without reconnecting
+#[derive(Message)]
+pub struct LiveSubscription {
+    pub method: SubscriptionMethod,
+    pub streams: Vec<StreamSpec>,
+}
+
+impl Handler<LiveSubscription> for CombinedStreamActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: LiveSubscription, _ctx: &mut Context<Self>) {
+        self.request_id += 1;
+        let request = SubscriptionRequest {
+            method: msg.method,
+            params: msg.streams.iter().map(|s| s.stream_name()).collect(),
+            id: self.request_id,
+        };
+        info!(actor = "combined_stream", stream:% = self.stream.stream_name(); "{}", request);
+        self.client_writer.text(request.to_string());
+        if request.method == SubscriptionMethod::UNSUBSCRIBE {
+            for stream in msg.streams.iter() {
+                self.handlers.remove(&stream.stream_name());
+            }
+        }
+    }
+}
+
+/// Handle Websocket messages
+impl StreamHandler<ws::Message, ws::ProtocolError> for CombinedStreamActor {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Context<Self>) {
+        self.last_seen = Instant::now();
+        if let ws::Message::Text(txt) = &msg {
+            record_frame(&self.stream, txt);
+        }
+        match msg {
+            ws::Message::Text(txt) => match parse_combined_message(&txt) {
+                Ok(CombinedMessage::Event(envelope)) => {
+                    frame_received(&envelope.stream);
+                    let received_at = chrono::Utc::now().naive_utc();
+                    if let Err(e) = self.handlers.handle(&envelope.stream, envelope.data.get(), received_at, ctx) {
+                        self.supervisor.do_send(Malformed {
+                            stream: envelope.stream,
+                            payload: txt,
+                            error: e,
+                        });
+                    }
+                }
+                Ok(CombinedMessage::Response(resp)) => {
+                    info!(actor = "combined_stream", stream:% = self.stream.stream_name(); "Subscription response: {:?}", resp)
+                }
+                Err(e) => self.supervisor.do_send(Malformed::new(&self.stream, txt, e)),
+            },
+            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
+            ws::Message::Pong(_) => (), // answer to our heartbeat
+            _ => (),
+        }
+    }
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        info!(actor = "combined_stream", stream:% = self.stream.stream_name(); "Websocket connected");
+        self.supervisor.do_send(Connected(self.stream.clone()));
+        self.supervisor.do_send(CombinedConnected(ctx.address(), self.stream.clone()));
+    }
+
+    fn finished(&mut self, ctx: &mut Context<Self>) {
+        info!(actor = "combined_stream", stream:% = self.stream.stream_name(); "Websocket disconnected");
+        ctx.stop()
+    }
+}
@@ -0,0 +1,382 @@
+use trading_sys::db_writer::{Batch, BatchConfig, Row, RowBuffer, Table, TABLES};
+use trading_sys::metrics::{
+    DB_BATCHES_IN_FLIGHT, DB_BUFFERED_ROWS, DB_INSERT_DURATION, DB_INSERT_ERRORS, DB_PAUSED_STREAMS, DB_ROWS_WRITTEN,
+};
+use trading_sys::backoff::Backoff;
+use trading_sys::{establish_pool_pg, PgPool};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+
+use crate::actors::redis::{PublishRow, RedisWriter};
+
+use actix::*;
+use futures::sync::oneshot;
+use futures::{future, Future};
+
+/////////////////////////////////////////////////////////////////
+/// Postgres writers: stream actors queue rows on the `DbBatcher`,
+/// which hands multi-row batches to a pool of `DbWriter` threads.
+/////////////////////////////////////////////////////////////////
+
+/// Blocking diesel writes, run on a `SyncArbiter` with one connection checked out per batch
+pub struct DbWriter {
+    pub pool: PgPool,
+}
+
+impl Actor for DbWriter {
+    type Context = SyncContext<Self>;
+}
+
+pub struct WriteBatch(pub Batch);
+
+/// A batch which could not be written, handed back so the batcher can retry it
+pub struct WriteFailed {
+    pub batch: Batch,
+    pub error: String,
+}
+
+impl Message for WriteBatch {
+    type Result = Result<usize, WriteFailed>;
+}
+
+impl Handler<WriteBatch> for DbWriter {
+    type Result = Result<usize, WriteFailed>;
+
+    fn handle(&mut self, msg: WriteBatch, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let batch = msg.0;
+        let _timer = DB_INSERT_DURATION.with_label_values(&[batch.table().name()]).start_timer();
+        let written = match self.pool.get() {
+            Ok(conn) => batch.write(&conn).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        written.map_err(|error| WriteFailed { batch, error })
+    }
+}
+
+/// Buffers rows per table and flushes them once `batch_size` is reached, or every `flush_interval`.
+/// When Postgres falls behind, `InsertRow` replies are held back so the sending stream stops reading.
+/// A failed batch is put back in its table's buffer and retried with `BatchConfig::retry` backoff,
+/// the table is not flushed in the meantime so rows are still written in order.
+pub struct DbBatcher {
+    pub config: BatchConfig,
+    pub echo: bool, // write every row to stdout, the `stdout` sink
+    pub redis: Option<Addr<RedisWriter>>, // publish every row, the `redis` sink
+    writer: Option<Addr<DbWriter>>, // None without the `postgres` sink, rows are dropped
+    buffer: RowBuffer,
+    in_flight: usize,
+    waiters: VecDeque<oneshot::Sender<()>>, // senders paused until there is room
+    retries: HashMap<Table, Backoff>, // tables whose last write failed
+    held: HashSet<Table>, // tables waiting out a retry delay
+}
+
+impl DbBatcher {
+    pub fn new(writer: Addr<DbWriter>, config: BatchConfig) -> Self {
+        DbBatcher {
+            config,
+            echo: false,
+            redis: None,
+            writer: Some(writer),
+            buffer: RowBuffer::new(),
+            in_flight: 0,
+            waiters: VecDeque::new(),
+            retries: HashMap::new(),
+            held: HashSet::new(),
+        }
+    }
+
+    /// `max_in_flight` writer threads sharing the pool
+    pub fn with_pool(pool: PgPool, config: BatchConfig) -> Self {
+        let writer = SyncArbiter::start(config.max_in_flight, move || DbWriter { pool: pool.clone() });
+        DbBatcher::new(writer, config)
+    }
+
+    /// Nothing written to Postgres, for collecting with only the `stdout` or `redis` sinks
+    pub fn without_postgres(config: BatchConfig) -> Self {
+        DbBatcher {
+            config,
+            echo: true,
+            redis: None,
+            writer: None,
+            buffer: RowBuffer::new(),
+            in_flight: 0,
+            waiters: VecDeque::new(),
+            retries: HashMap::new(),
+            held: HashSet::new(),
+        }
+    }
+
+    fn update_gauges(&self) {
+        DB_BUFFERED_ROWS.set(self.buffer.len() as i64);
+        DB_BATCHES_IN_FLIGHT.set(self.in_flight as i64);
+        DB_PAUSED_STREAMS.set(self.waiters.len() as i64);
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.buffer.len() >= self.config.max_buffered_rows
+    }
+
+    fn flush(&mut self, table: Table, ctx: &mut Context<Self>) {
+        if self.in_flight >= self.config.max_in_flight || self.buffer.table_len(table) == 0 || self.held.contains(&table) {
+            return; // keeps buffering, flushed again once a write completes or the retry delay is up
+        }
+        let writer = match &self.writer {
+            Some(writer) => writer.clone(),
+            None => return,
+        };
+        let batch = self.buffer.take(table);
+        let rows = batch.len();
+        self.in_flight += 1;
+        self.update_gauges();
+
+        writer
+            .send(WriteBatch(batch))
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                act.in_flight -= 1;
+                match res {
+                    Ok(Ok(written)) => {
+                        act.retries.remove(&table);
+                        DB_ROWS_WRITTEN.with_label_values(&[table.name()]).inc_by(written as i64);
+                        debug!(table = table.name(); "Database write result: {}/{} rows", written, rows)
+                    }
+                    Ok(Err(failed)) => {
+                        DB_INSERT_ERRORS.with_label_values(&[table.name()]).inc();
+                        act.retry(failed, ctx);
+                    }
+                    Err(e) => {
+                        act.retries.remove(&table);
+                        DB_INSERT_ERRORS.with_label_values(&[table.name()]).inc();
+                        error!(table = table.name(); "Database writer unavailable, {} rows dropped: {}", rows, e)
+                    }
+                }
+                act.flush_full(ctx);
+                act.release_waiters();
+                act.update_gauges();
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Put a failed batch back and flush its table again after the next backoff delay,
+    /// or drop it once the table is out of retries
+    fn retry(&mut self, failed: WriteFailed, ctx: &mut Context<Self>) {
+        let table = failed.batch.table();
+        let rows = failed.batch.len();
+        let retry = self.config.retry.clone();
+        let delay = self.retries.entry(table).or_insert(retry).next_delay();
+        match delay {
+            Some(delay) => {
+                warn!(table = table.name(); "Database write error, retrying {} rows in {:?}: {}", rows, delay, failed.error);
+                self.buffer.restore(failed.batch);
+                self.held.insert(table);
+                ctx.run_later(delay, move |act, ctx| {
+                    act.held.remove(&table);
+                    act.flush(table, ctx);
+                });
+            }
+            None => {
+                self.retries.remove(&table);
+                error!(table = table.name(); "Database write error, out of retries, {} rows dropped: {}", rows, failed.error)
+            }
+        }
+    }
+
+    /// Flush tables which reached `batch_size` while every writer was busy
+    fn flush_full(&mut self, ctx: &mut Context<Self>) {
+        for table in TABLES.iter() {
+            if self.buffer.table_len(*table) >= self.config.batch_size {
+                self.flush(*table, ctx);
+            }
+        }
+    }
+
+    fn flush_all(&mut self, ctx: &mut Context<Self>) {
+        for table in TABLES.iter() {
+            self.flush(*table, ctx);
+        }
+    }
+
+    fn release_waiters(&mut self) {
+        while !self.is_saturated() {
+            match self.waiters.pop_front() {
+                Some(waiter) => {
+                    let _ = waiter.send(());
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Echo/publish a row and buffer it, returning its table if it will be written to Postgres
+    fn buffer_row(&mut self, row: Row) -> Option<Table> {
+        if self.echo {
+            // not a log record, stdout is the sink's output and stays quiet under any log filter
+            if let Err(e) = writeln!(std::io::stdout().lock(), "{:?}", row) {
+                warn!("Error writing row to stdout: {}", e);
+            }
+        }
+        if let Some(redis) = &self.redis {
+            redis.do_send(PublishRow(row.clone()));
+        }
+        if self.writer.is_none() {
+            return None;
+        }
+        let table = row.table();
+        self.buffer.push(row);
+        Some(table)
+    }
+
+    fn reply_when_room(&mut self) -> ResponseFuture<(), ()> {
+        let reply: ResponseFuture<(), ()> = if self.is_saturated() {
+            let (tx, rx) = oneshot::channel();
+            self.waiters.push_back(tx);
+            Box::new(rx.map_err(|_| ()))
+        } else {
+            Box::new(future::ok(()))
+        };
+        self.update_gauges();
+        reply
+    }
+}
+
+impl Actor for DbBatcher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(self.config.flush_interval, |act, ctx| act.flush_all(ctx));
+    }
+}
+
+impl Default for DbBatcher {
+    fn default() -> Self {
+        let config = BatchConfig::default();
+        let pool = establish_pool_pg(config.max_in_flight as u32);
+        DbBatcher::with_pool(pool, config)
+    }
+}
+
+impl Supervised for DbBatcher {}
+
+/// Shared by every stream actor, see `DbBatcher::from_registry()`.
+/// Registered with `SystemRegistry::set` on start up, otherwise created from `.env` on first use.
+impl SystemService for DbBatcher {}
+
+/// Queue a row to be written, resolves once the batcher has room for more
+pub struct InsertRow(pub Row);
+
+impl Message for InsertRow {
+    type Result = Result<(), ()>;
+}
+
+impl Handler<InsertRow> for DbBatcher {
+    type Result = ResponseFuture<(), ()>;
+
+    fn handle(&mut self, msg: InsertRow, ctx: &mut Context<Self>) -> Self::Result {
+        if let Some(table) = self.buffer_row(msg.0) {
+            if self.buffer.table_len(table) >= self.config.batch_size {
+                self.flush(table, ctx);
+            }
+        }
+        self.reply_when_room()
+    }
+}
+
+/// Queue every row of one websocket frame, e.g: the all-markets ticker arrays.
+/// Their tables are flushed straight away, so each frame becomes one multi-row INSERT.
+pub struct InsertRows(pub Vec<Row>);
+
+impl Message for InsertRows {
+    type Result = Result<(), ()>;
+}
+
+impl Handler<InsertRows> for DbBatcher {
+    type Result = ResponseFuture<(), ()>;
+
+    fn handle(&mut self, msg: InsertRows, ctx: &mut Context<Self>) -> Self::Result {
+        let mut tables: Vec<Table> = Vec::new();
+        for row in msg.0 {
+            if let Some(table) = self.buffer_row(row) {
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+        }
+        for table in tables {
+            self.flush(table, ctx);
+        }
+        self.reply_when_room()
+    }
+}
+
+/// Send a row to the shared `DbBatcher`. The calling actor stops handling
+/// websocket messages until the row is accepted, so a slow database slows reads
+/// instead of growing the mailbox without bound.
+pub fn insert_row<A>(row: Row, ctx: &mut Context<A>)
+where
+    A: Actor<Context = Context<A>>,
+{
+    let accepted = DbBatcher::from_registry()
+        .send(InsertRow(row))
+        .then(|_| Ok::<(), ()>(()));
+    ctx.wait(actix::fut::wrap_future::<_, A>(accepted));
+}
+
+/// Send every row of a frame to the shared `DbBatcher`, see `InsertRows`
+pub fn insert_rows<A>(rows: Vec<Row>, ctx: &mut Context<A>)
+where
+    A: Actor<Context = Context<A>>,
+{
+    let accepted = DbBatcher::from_registry()
+        .send(InsertRows(rows))
+        .then(|_| Ok::<(), ()>(()));
+    ctx.wait(actix::fut::wrap_future::<_, A>(accepted));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use std::time::Duration;
+    use trading_sys::models::tickers::{TickerDataInsert, TEST_TICKER_DATA};
+
+    #[test]
+    fn holds_back_senders_while_saturated() {
+        let sys = System::new("test-db-batcher");
+
+        // never connects, every write fails once a connection is requested
+        let manager = ConnectionManager::new("postgres://127.0.0.1:1/unreachable");
+        let pool = Pool::builder()
+            .connection_timeout(Duration::from_millis(200))
+            .build_unchecked(manager);
+        let config = BatchConfig {
+            batch_size: 2,
+            flush_interval: Duration::from_secs(60),
+            max_in_flight: 1,
+            max_buffered_rows: 3,
+            retry: Backoff::new(Duration::from_millis(10), Duration::from_millis(10), Some(1)),
+        };
+        let batcher = DbBatcher::with_pool(pool, config).start();
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+
+        // rows 1-2 go to the writer, 3-5 are buffered while it is busy. The 5th saturates
+        // the buffer, so its reply waits until the failing rows are retried once, dropped and free room.
+        let started = std::time::Instant::now();
+        let sends: Vec<_> = (0..5)
+            .map(|_| batcher.send(InsertRow(Row::Ticker(ticker.clone()))))
+            .collect();
+        Arbiter::spawn(future::join_all(sends).then(move |res| {
+            assert_eq!(res.unwrap(), vec![Ok(()); 5]);
+            assert!(started.elapsed() >= Duration::from_millis(350));
+            System::current().stop();
+            Ok(())
+        }));
+
+        let system = System::current();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(10));
+            system.stop();
+        });
+        let _ = sys.run();
+    }
+}
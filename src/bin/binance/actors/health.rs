@@ -0,0 +1,109 @@
+use trading_sys::health::{HealthMonitor, Observation};
+use trading_sys::metrics::EVENT_LATENCY;
+
+use std::time::Duration;
+
+use actix::*;
+
+/// Tracks every stream's health and prints alarms and periodic reports, if configured.
+/// Stream actors send to it unconditionally; without a monitor it drops everything.
+#[derive(Default)]
+pub struct HealthMonitorActor {
+    pub monitor: Option<HealthMonitor>,
+    pub report_interval: Option<Duration>,
+}
+
+impl HealthMonitorActor {
+    pub fn new(monitor: HealthMonitor, report_interval: Option<Duration>) -> Self {
+        HealthMonitorActor { monitor: Some(monitor), report_interval }
+    }
+}
+
+impl Actor for HealthMonitorActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(Duration::from_secs(1), |act, _ctx| {
+            if let Some(monitor) = &mut act.monitor {
+                let now = chrono::Utc::now().naive_utc();
+                for (stream, stale) in monitor.check_stale(now) {
+                    if stale {
+                        warn!(actor = "health", stream:% = stream; "Stale, nothing received for {:?}", monitor.stale_after);
+                    } else {
+                        info!(actor = "health", stream:% = stream; "Recovered");
+                    }
+                }
+            }
+        });
+        if let Some(interval) = self.report_interval {
+            ctx.run_interval(interval, |act, _ctx| {
+                if let Some(monitor) = &mut act.monitor {
+                    for report in monitor.report(chrono::Utc::now().naive_utc()) {
+                        info!(actor = "health"; "{}", report);
+                    }
+                }
+            });
+        }
+    }
+}
+
+impl Supervised for HealthMonitorActor {}
+
+impl SystemService for HealthMonitorActor {}
+
+/// A market event received on `stream`, e.g: "ethbtc@trade"
+#[derive(Message)]
+pub struct Observe(pub String, pub Observation);
+
+impl Handler<Observe> for HealthMonitorActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Observe, _ctx: &mut Context<Self>) {
+        let now = chrono::Utc::now().naive_utc();
+        if let Some(event_time) = msg.1.event_time {
+            let latency_ms = (now - event_time).num_milliseconds().max(0);
+            EVENT_LATENCY.with_label_values(&[&msg.0]).observe(latency_ms as f64 / 1000.0);
+        }
+        if let Some(monitor) = &mut self.monitor {
+            monitor.observe(&msg.0, &msg.1, now);
+        }
+    }
+}
+
+/// Expect messages on these streams from now on, sent on subscribe
+#[derive(Message)]
+pub struct Watch(pub Vec<String>);
+
+impl Handler<Watch> for HealthMonitorActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Watch, _ctx: &mut Context<Self>) {
+        if let Some(monitor) = &mut self.monitor {
+            let now = chrono::Utc::now().naive_utc();
+            for stream in msg.0.iter() {
+                monitor.watch(stream, now);
+            }
+        }
+    }
+}
+
+/// Stop tracking unsubscribed streams
+#[derive(Message)]
+pub struct Unwatch(pub Vec<String>);
+
+impl Handler<Unwatch> for HealthMonitorActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unwatch, _ctx: &mut Context<Self>) {
+        if let Some(monitor) = &mut self.monitor {
+            for stream in msg.0.iter() {
+                monitor.unwatch(stream);
+            }
+        }
+    }
+}
+
+/// Record a market event as received, for rates, latency and gap checks
+pub fn observe(stream: &str, observation: Observation) {
+    HealthMonitorActor::from_registry().do_send(Observe(stream.to_owned(), observation));
+}
@@ -1,70 +1,38 @@
-use chrono::NaiveDateTime;
-use std::fmt;
-use std::time::Duration;
-
-use trading_sys::currency_pairs::CurrencyPair;
-use trading_sys::models::klines::{
-    map_klinemeta_to_klineinsertdata, KlineDataInsert, KlineInterval, KlineMetaData,
-};
-use trading_sys::serde_parsers::deserialize_as_f64;
+use trading_sys::db_writer::Row;
+use trading_sys::models::klines::{map_klinemeta_to_klineinsertdata, KlineDataInsert, KlineMetaData};
+use trading_sys::health::Observation;
+use trading_sys::error::Result;
 
 use actix::*;
-use actix_web::ws;
-
-pub struct KlineActor {
-    pub client_writer: ws::ClientWriter,
-}
-
-impl Actor for KlineActor {
-    type Context = Context<Self>;
+use chrono::NaiveDateTime;
 
-    fn started(&mut self, ctx: &mut Context<Self>) {
-        // Start heartbeats otherwise server disconnects in 10 seconds
-        self.hb(ctx);
-    }
+use crate::actors::db_writer::insert_row;
+use crate::actors::health::observe;
+use crate::actors::stream::BinanceEvent;
 
-    fn stopped(&mut self, _: &mut Context<Self>) {
-        // Stop application on disconnect
-        System::current().stop();
-    }
-}
+/// `<symbol>@kline_<interval>`, flattened from the nested kline payload
+impl BinanceEvent for KlineDataInsert {
+    const SUFFIX: &'static str = "kline";
+    const TARGET: &'static str = module_path!();
+    type State = ();
 
-impl KlineActor {
-    fn hb(&self, ctx: &mut Context<Self>) {
-        ctx.run_later(std::time::Duration::new(1, 0), |act, ctx| {
-            act.client_writer.pong("Heartbeat");
-            act.hb(ctx);
-            // client should check for a timeout here, similar to server code
-        });
+    fn handles(stream_type: &str) -> bool {
+        stream_type.starts_with("kline_")
     }
-}
-
-/// Handle Websocket messages
-impl StreamHandler<ws::Message, ws::ProtocolError> for KlineActor {
-    fn handle(&mut self, msg: ws::Message, _ctx: &mut Context<Self>) {
-        match msg {
-            ws::Message::Text(txt) => {
-                let kline_meta_data: KlineMetaData =
-                    serde_json::from_str::<KlineMetaData>(&txt).unwrap();
-
-                let kline_data_insert = map_klinemeta_to_klineinsertdata(kline_meta_data);
-                let connection = trading_sys::establish_connection_pg();
 
-                println!("{:?}\n", &kline_data_insert);
-                trading_sys::create_kline(&connection, kline_data_insert);
-            }
-            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
-            ws::Message::Pong(pong) => self.client_writer.ping(&pong),
-            _ => (),
-        }
+    fn state(_stream: &str) -> Result<()> {
+        Ok(())
     }
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("<kline.rs>: Websocket Connected.");
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str::<KlineMetaData>(txt).map(map_klinemeta_to_klineinsertdata)
     }
 
-    fn finished(&mut self, ctx: &mut Context<Self>) {
-        println!("<kline.rs>: Websocket Disconnected.");
-        ctx.stop()
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        observe(stream, Observation::from(&self));
+        insert_row(Row::Kline(self), ctx);
     }
 }
@@ -1,81 +1,63 @@
 use trading_sys::models::mini_ticker::MiniTickerDataInsert;
-use trading_sys::models::mini_ticker::MiniTickerQueryType;
-use trading_sys::{create_mini_tickers, establish_connection_pg};
-
-use std::time::Duration;
+use trading_sys::db_writer::Row;
+use trading_sys::health::Observation;
+use trading_sys::error::Result;
 
 use actix::*;
-use actix_web::ws;
+use chrono::NaiveDateTime;
 
-pub struct MiniTickerActor {
-    pub client_writer: ws::ClientWriter,
-    pub all_markets: Option<MiniTickerQueryType>,
-}
+use crate::actors::db_writer::{insert_row, insert_rows};
+use crate::actors::health::observe;
+use crate::actors::stream::BinanceEvent;
 
-impl Actor for MiniTickerActor {
-    type Context = Context<Self>;
+/// `<symbol>@miniTicker`
+impl BinanceEvent for MiniTickerDataInsert {
+    const SUFFIX: &'static str = "miniTicker";
+    const TARGET: &'static str = module_path!();
+    type State = ();
 
-    fn started(&mut self, ctx: &mut Context<Self>) {
-        // Start heartbeats otherwise server disconnects in 10 seconds
-        self.hb(ctx);
+    fn state(_stream: &str) -> Result<()> {
+        Ok(())
     }
 
-    fn stopped(&mut self, _: &mut Context<Self>) {
-        // Stop application on disconnect
-        System::current().stop();
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
     }
-}
 
-impl MiniTickerActor {
-    fn hb(&self, ctx: &mut Context<Self>) {
-        ctx.run_later(std::time::Duration::new(1, 0), |act, ctx| {
-            act.client_writer.pong("Heartbeat");
-            act.hb(ctx); // client should check for a timeout here
-        });
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        observe(stream, Observation::from(&self));
+        insert_row(Row::MiniTicker(self), ctx);
     }
 }
 
-impl StreamHandler<ws::Message, ws::ProtocolError> for MiniTickerActor {
-    fn handle(&mut self, msg: ws::Message, _ctx: &mut Context<Self>) {
-        match msg {
-            ws::Message::Text(txt) => {
-                match self.all_markets {
-                    Some(MiniTickerQueryType::AllMarkets) => {
-                        let mini_ticker_data: Vec<MiniTickerDataInsert> =
-                            serde_json::from_str(&txt).unwrap();
+/// `!miniTicker@arr`, every market's mini ticker in one frame, written with one INSERT
+impl BinanceEvent for Vec<MiniTickerDataInsert> {
+    const SUFFIX: &'static str = "miniTicker";
+    const TARGET: &'static str = module_path!();
+    type State = ();
 
-                        for ticker in mini_ticker_data.iter() {
-                            println!("{:?}", ticker);
-                        }
-                    },
-                    Some(MiniTickerQueryType::SingleMarket) => {
-                        let mini_ticker_data: MiniTickerDataInsert =
-                            serde_json::from_str::<MiniTickerDataInsert>(&txt).unwrap();
-                        println!("{:?}", &mini_ticker_data);
-                        let connection = establish_connection_pg();
-                        create_mini_tickers(&connection, mini_ticker_data);
-                    },
-                    _ => panic!("No MiniTickerQueryType:: provided.")
-                };
-            }
-            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
-            ws::Message::Pong(pong) => self.client_writer.ping(&pong),
-            ws::Message::Close(maybe_reason) => match maybe_reason {
-                Some(reason) => println!("{:?}", reason),
-                None => println!("`ws::Message::Close(?)` with no reason provided."),
-            },
-            _ => (),
-        }
+    fn handles(stream_type: &str) -> bool {
+        stream_type == "miniTicker@arr"
     }
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("<mini_ticker.rs>: Websocket Connected.");
+    fn state(_stream: &str) -> Result<()> {
+        Ok(())
     }
 
-    fn finished(&mut self, ctx: &mut Context<Self>) {
-        println!("<mini_ticker.rs>: Websocket Stopped.");
-        ctx.stop()
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
     }
-}
-
 
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        if let Some(first) = self.first() {
+            observe(stream, Observation::from(first));
+        }
+        insert_rows(self.into_iter().map(Row::MiniTicker).collect(), ctx);
+    }
+}
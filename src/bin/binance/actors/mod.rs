@@ -1,6 +1,18 @@
 pub mod aggregate_trade;
 pub mod book_depth;
+pub mod candles;
+pub mod combined_stream;
+pub mod db_writer;
+pub mod health;
 pub mod klines;
 pub mod mini_ticker;
+pub mod order_books;
+pub mod orders;
+pub mod paper;
+pub mod recorder;
+pub mod redis;
+pub mod replay;
+pub mod stream;
 pub mod trades;
 pub mod tickers;
+pub mod user_data;
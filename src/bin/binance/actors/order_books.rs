@@ -0,0 +1,183 @@
+use trading_sys::error::Result;
+use trading_sys::models::book_depth::{BookDepthDataInsert, PartialBookDepthData};
+use trading_sys::order_book::{fetch_depth_snapshot, BookUpdate, LocalOrderBook};
+use trading_sys::symbols::Symbol;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use actix::*;
+
+use crate::actors::paper::{FeedBook, PaperFeed, SeedBook};
+
+/// Least time between snapshot requests for one symbol, a resync loop
+/// would otherwise spend the REST weight limit on a `limit=1000` snapshot per diff
+pub const MIN_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+
+/////////////////////////////////////////////////////////////////
+/// Local order books kept in sync with the `<symbol>@depth` streams.
+/// Snapshots are fetched on a `SnapshotFetcher` thread, diffs arriving
+/// meanwhile are buffered by the `LocalOrderBook`. Synced books are
+/// passed on to the paper exchange, see `PaperFeed`.
+/////////////////////////////////////////////////////////////////
+
+/// Blocking REST depth snapshots, run on a `SyncArbiter`
+pub struct SnapshotFetcher;
+
+impl Actor for SnapshotFetcher {
+    type Context = SyncContext<Self>;
+}
+
+pub struct FetchSnapshot(pub Symbol);
+
+impl Message for FetchSnapshot {
+    type Result = Result<PartialBookDepthData>;
+}
+
+impl Handler<FetchSnapshot> for SnapshotFetcher {
+    type Result = Result<PartialBookDepthData>;
+
+    fn handle(&mut self, msg: FetchSnapshot, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        fetch_depth_snapshot(&msg.0, 1000)
+    }
+}
+
+/// One symbol's book and its snapshot requests
+#[derive(Debug)]
+pub struct BookSync {
+    pub book: LocalOrderBook,
+    fetching: bool,
+    last_request: Option<Instant>,
+}
+
+impl BookSync {
+    pub fn new(symbol: Symbol) -> Self {
+        BookSync { book: LocalOrderBook::new(symbol), fetching: false, last_request: None }
+    }
+
+    /// True if the book needs a snapshot, none is in flight and the last request was long enough ago
+    pub fn wants_snapshot(&self, now: Instant) -> bool {
+        self.book.needs_snapshot()
+            && !self.fetching
+            && self.last_request.map(|at| now.duration_since(at) >= MIN_SNAPSHOT_INTERVAL).unwrap_or(true)
+    }
+
+    pub fn requested(&mut self, now: Instant) {
+        self.fetching = true;
+        self.last_request = Some(now);
+    }
+
+    /// Apply a fetched snapshot, `None` if the request failed
+    pub fn fetched(&mut self, snapshot: Option<PartialBookDepthData>) -> Option<BookUpdate> {
+        self.fetching = false;
+        snapshot.map(|snapshot| self.book.apply_snapshot(snapshot))
+    }
+}
+
+/// Every symbol's `BookSync`, fed by the depth stream handlers
+#[derive(Default)]
+pub struct OrderBooks {
+    books: HashMap<Symbol, BookSync>,
+    fetcher: Option<Addr<SnapshotFetcher>>,
+}
+
+impl Actor for OrderBooks {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _ctx: &mut Context<Self>) {
+        self.fetcher = Some(SyncArbiter::start(1, || SnapshotFetcher));
+    }
+}
+
+impl Supervised for OrderBooks {}
+
+impl SystemService for OrderBooks {}
+
+impl OrderBooks {
+    /// Request a snapshot for `symbol` off this thread if its book wants one, see `BookSync::wants_snapshot`
+    fn request_snapshot(&mut self, symbol: Symbol, ctx: &mut Context<Self>) {
+        let fetcher = match (&self.fetcher, self.books.get_mut(&symbol)) {
+            (Some(fetcher), Some(sync)) if sync.wants_snapshot(Instant::now()) => {
+                sync.requested(Instant::now());
+                fetcher.clone()
+            }
+            _ => return,
+        };
+        fetcher
+            .send(FetchSnapshot(symbol.clone()))
+            .into_actor(self)
+            .then(move |res, act, _ctx| {
+                let snapshot = match res {
+                    Ok(Ok(snapshot)) => Some(snapshot),
+                    Ok(Err(e)) => {
+                        warn!(actor = "depth", symbol = symbol.as_str(); "Error fetching depth snapshot: {}", e);
+                        None
+                    }
+                    Err(e) => {
+                        error!(actor = "depth", symbol = symbol.as_str(); "Snapshot fetcher unavailable: {}", e);
+                        None
+                    }
+                };
+                if let Some(sync) = act.books.get_mut(&symbol) {
+                    if let Some(status) = sync.fetched(snapshot) {
+                        debug!(actor = "depth", symbol = symbol.as_str(); "{:?}: {}", status, sync.book);
+                        if status == BookUpdate::Applied {
+                            PaperFeed::from_registry().do_send(SeedBook {
+                                symbol: symbol.clone(),
+                                bids: sync.book.bids(),
+                                asks: sync.book.asks(),
+                            });
+                        }
+                    }
+                }
+                // a failed or stale snapshot is retried on a later diff
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+/// A diff from a `<symbol>@depth` stream
+#[derive(Message)]
+pub struct ApplyDiff(pub BookDepthDataInsert);
+
+impl Handler<ApplyDiff> for OrderBooks {
+    type Result = ();
+
+    fn handle(&mut self, msg: ApplyDiff, ctx: &mut Context<Self>) {
+        let symbol = msg.0.symbol.clone();
+        let sync = self.books.entry(symbol.clone()).or_insert_with(|| BookSync::new(symbol.clone()));
+        let status = sync.book.apply_update(msg.0.clone());
+        debug!(actor = "depth", symbol = symbol.as_str(); "{:?}: {}", status, sync.book);
+        if status == BookUpdate::Applied {
+            PaperFeed::from_registry().do_send(FeedBook(msg.0));
+        }
+        self.request_snapshot(symbol, ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_requests_are_rate_limited() {
+        let mut sync = BookSync::new(Symbol::new("BNBBTC"));
+        let start = Instant::now();
+        assert!(sync.wants_snapshot(start));
+
+        // one request in flight at a time
+        sync.requested(start);
+        assert!(!sync.wants_snapshot(start + MIN_SNAPSHOT_INTERVAL));
+
+        // a failed request is retried once the interval is up
+        assert_eq!(sync.fetched(None), None);
+        assert!(!sync.wants_snapshot(start + Duration::from_secs(1)));
+        assert!(sync.wants_snapshot(start + MIN_SNAPSHOT_INTERVAL));
+
+        sync.requested(start + MIN_SNAPSHOT_INTERVAL);
+        let snapshot = PartialBookDepthData { last_update_id: 100, bids: vec![], asks: vec![] };
+        assert_eq!(sync.fetched(Some(snapshot)), Some(BookUpdate::Applied));
+        assert!(!sync.wants_snapshot(start + MIN_SNAPSHOT_INTERVAL * 3));
+    }
+}
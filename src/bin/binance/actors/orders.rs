@@ -0,0 +1,96 @@
+use trading_sys::error::Result;
+use trading_sys::models::orders::{Order, OrderRequest};
+use trading_sys::models::tickers::TickerDataInsert;
+use trading_sys::models::user_data::FillInsert;
+use trading_sys::order_manager::OrderManager;
+
+use actix::*;
+
+/// Owns the order manager, on its own arbiter since gateway calls block.
+/// Ticker streams and our fills keep its risk checks current; without
+/// a manager, e.g: trading disabled or `replay`, everything is dropped.
+/// Nothing in the collector sends `SubmitOrder` or `CancelOrder`: the desk
+/// places no orders by itself, they come from a strategy running in the same
+/// actix system, e.g: `OrderDesk::from_registry().send(SubmitOrder(request))`.
+#[derive(Default)]
+pub struct OrderDesk {
+    pub manager: Option<OrderManager>,
+}
+
+impl OrderDesk {
+    pub fn new(manager: OrderManager) -> Self {
+        OrderDesk { manager: Some(manager) }
+    }
+}
+
+impl Actor for OrderDesk {
+    type Context = Context<Self>;
+}
+
+impl Supervised for OrderDesk {}
+
+impl SystemService for OrderDesk {}
+
+/// Last prices for the price band and notional checks
+#[derive(Message)]
+pub struct FeedTicker(pub TickerDataInsert);
+
+impl Handler<FeedTicker> for OrderDesk {
+    type Result = ();
+
+    fn handle(&mut self, msg: FeedTicker, _ctx: &mut Context<Self>) {
+        if let Some(manager) = &mut self.manager {
+            manager.risk_mut().on_ticker(&msg.0);
+        }
+    }
+}
+
+/// Our fills, for positions and the daily loss limit
+#[derive(Message)]
+pub struct FeedFill(pub FillInsert);
+
+impl Handler<FeedFill> for OrderDesk {
+    type Result = ();
+
+    fn handle(&mut self, msg: FeedFill, _ctx: &mut Context<Self>) {
+        if let Some(manager) = &mut self.manager {
+            manager.risk_mut().on_fill(&msg.0);
+        }
+    }
+}
+
+/// Risk check, record and place an order, see `OrderManager::submit`
+pub struct SubmitOrder(pub OrderRequest);
+
+impl Message for SubmitOrder {
+    type Result = Result<Order>;
+}
+
+impl Handler<SubmitOrder> for OrderDesk {
+    type Result = Result<Order>;
+
+    fn handle(&mut self, msg: SubmitOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        match &mut self.manager {
+            Some(manager) => manager.submit(msg.0),
+            None => Err(trading_sys::error::Error::InvalidOrder("trading is disabled".to_owned())),
+        }
+    }
+}
+
+/// Cancel by client order id, see `OrderManager::cancel`
+pub struct CancelOrder(pub String);
+
+impl Message for CancelOrder {
+    type Result = Result<Order>;
+}
+
+impl Handler<CancelOrder> for OrderDesk {
+    type Result = Result<Order>;
+
+    fn handle(&mut self, msg: CancelOrder, _ctx: &mut Context<Self>) -> Self::Result {
+        match &mut self.manager {
+            Some(manager) => manager.cancel(&msg.0),
+            None => Err(trading_sys::error::Error::InvalidOrder("trading is disabled".to_owned())),
+        }
+    }
+}
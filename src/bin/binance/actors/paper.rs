@@ -0,0 +1,72 @@
+use trading_sys::models::book_depth::{BookDepthDataInsert, Quote};
+use trading_sys::models::trades::TradeData;
+use trading_sys::paper::PaperExchange;
+use trading_sys::symbols::Symbol;
+
+use actix::*;
+
+/// Feeds live trades and the synced order books to the paper exchange, if paper trading.
+/// Stream actors and `OrderBooks` send to it unconditionally; without an exchange it drops everything.
+#[derive(Default)]
+pub struct PaperFeed {
+    pub exchange: Option<PaperExchange>,
+}
+
+impl PaperFeed {
+    pub fn new(exchange: PaperExchange) -> Self {
+        PaperFeed { exchange: Some(exchange) }
+    }
+}
+
+impl Actor for PaperFeed {
+    type Context = Context<Self>;
+}
+
+impl Supervised for PaperFeed {}
+
+impl SystemService for PaperFeed {}
+
+#[derive(Message)]
+pub struct FeedTrade(pub TradeData);
+
+impl Handler<FeedTrade> for PaperFeed {
+    type Result = ();
+
+    fn handle(&mut self, msg: FeedTrade, _ctx: &mut Context<Self>) {
+        if let Some(exchange) = &self.exchange {
+            exchange.on_trade(&msg.0);
+        }
+    }
+}
+
+/// A diff applied to a synced order book, see `OrderBooks`
+#[derive(Message)]
+pub struct FeedBook(pub BookDepthDataInsert);
+
+impl Handler<FeedBook> for PaperFeed {
+    type Result = ();
+
+    fn handle(&mut self, msg: FeedBook, _ctx: &mut Context<Self>) {
+        if let Some(exchange) = &self.exchange {
+            exchange.on_book_update(&msg.0);
+        }
+    }
+}
+
+/// Every level of an order book once synced with its snapshot
+#[derive(Message)]
+pub struct SeedBook {
+    pub symbol: Symbol,
+    pub bids: Vec<Quote>,
+    pub asks: Vec<Quote>,
+}
+
+impl Handler<SeedBook> for PaperFeed {
+    type Result = ();
+
+    fn handle(&mut self, msg: SeedBook, _ctx: &mut Context<Self>) {
+        if let Some(exchange) = &self.exchange {
+            exchange.on_book_snapshot(&msg.symbol, &msg.bids, &msg.asks);
+        }
+    }
+}
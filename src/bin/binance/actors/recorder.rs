@@ -0,0 +1,68 @@
+use trading_sys::recorder::{FrameRecorder, RecordedFrame};
+
+use std::time::Duration;
+
+use actix::*;
+
+use crate::supervisor::StreamSpec;
+
+/// Writes raw frames from every stream actor, if recording.
+/// Stream actors send to it unconditionally; without a recorder it drops everything.
+#[derive(Default)]
+pub struct FrameRecorderActor {
+    pub recorder: Option<FrameRecorder>,
+}
+
+impl FrameRecorderActor {
+    pub fn new(recorder: FrameRecorder) -> Self {
+        FrameRecorderActor { recorder: Some(recorder) }
+    }
+}
+
+impl Actor for FrameRecorderActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(Duration::from_secs(1), |act, _ctx| {
+            if let Some(recorder) = &mut act.recorder {
+                if let Err(e) = recorder.flush() {
+                    error!(actor = "recorder"; "Error flushing recorded frames: {}", e);
+                }
+            }
+        });
+    }
+
+    fn stopped(&mut self, _ctx: &mut Context<Self>) {
+        if let Some(recorder) = &mut self.recorder {
+            let _ = recorder.close();
+        }
+    }
+}
+
+impl Supervised for FrameRecorderActor {}
+
+impl SystemService for FrameRecorderActor {}
+
+#[derive(Message)]
+pub struct RecordFrame(pub RecordedFrame);
+
+impl Handler<RecordFrame> for FrameRecorderActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordFrame, _ctx: &mut Context<Self>) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record(&msg.0) {
+                error!(actor = "recorder", stream:% = msg.0.stream; "Error recording frame: {}", e);
+            }
+        }
+    }
+}
+
+/// Record a text frame as received on `stream`, before it is parsed
+pub fn record_frame(stream: &StreamSpec, txt: &str) {
+    FrameRecorderActor::from_registry().do_send(RecordFrame(RecordedFrame {
+        received_at: chrono::Utc::now().naive_utc(),
+        stream: stream.stream_name(),
+        payload: txt.to_owned(),
+    }));
+}
@@ -0,0 +1,45 @@
+use trading_sys::db_writer::Row;
+use trading_sys::pubsub::ReconnectingPublisher;
+
+use actix::*;
+
+/// Blocking Redis publishes, run on a `SyncArbiter` so a slow Redis doesn't stall the streams.
+/// Rows are forwarded by the `DbBatcher` when the `redis` sink is configured.
+/// While Redis is down rows are dropped and the connection retried with backoff.
+pub struct RedisWriter {
+    pub publisher: ReconnectingPublisher,
+}
+
+impl Actor for RedisWriter {
+    type Context = SyncContext<Self>;
+
+    fn started(&mut self, _ctx: &mut SyncContext<Self>) {
+        if let Err(e) = self.publisher.connect() {
+            error!(actor = "redis"; "Error connecting to Redis, will retry: {}", e);
+        }
+    }
+}
+
+#[derive(Message)]
+pub struct PublishRow(pub Row);
+
+impl Handler<PublishRow> for RedisWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: PublishRow, _ctx: &mut SyncContext<Self>) {
+        let was_connected = self.publisher.is_connected();
+        match self.publisher.publish(&msg.0) {
+            Ok(_) if !was_connected && self.publisher.is_connected() => info!(actor = "redis"; "Reconnected to Redis"),
+            Ok(_) => {}
+            Err(e) => error!(actor = "redis"; "Redis publish error, will reconnect: {}", e),
+        }
+    }
+}
+
+/// One connection to `url`, (re)connected lazily so Redis being down never stops ingestion
+pub fn start_redis_writer(url: &str) -> Addr<RedisWriter> {
+    let url = url.to_owned();
+    SyncArbiter::start(1, move || RedisWriter {
+        publisher: ReconnectingPublisher::new(&url),
+    })
+}
@@ -0,0 +1,108 @@
+use trading_sys::recorder::{read_frames, FrameReader, RecordedFrame};
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix::*;
+use chrono::NaiveDateTime;
+
+use crate::actors::stream::StreamHandlers;
+
+/// Feeds recorded frames through the handler of the stream they were received on, in order,
+/// keeping the recorded gaps between frames divided by `speed`.
+pub struct ReplayActor {
+    pub files: VecDeque<PathBuf>,
+    pub frames: Option<FrameReader>, // current file, read a frame at a time
+    pub speed: f64,           // 1 is real time, 0 as fast as the sinks accept
+    pub linger: Duration,     // wait before stopping, so the last batches are flushed
+    pub previous: Option<NaiveDateTime>,
+    pub replayed: usize,
+    pub skipped: usize,
+    pub handlers: StreamHandlers<ReplayActor>, // the live streams' handlers, kept for the whole replay
+}
+
+impl ReplayActor {
+    pub fn new(files: Vec<PathBuf>, speed: f64, linger: Duration) -> Self {
+        ReplayActor {
+            files: files.into_iter().collect(),
+            frames: None,
+            speed: speed,
+            linger: linger,
+            previous: None,
+            replayed: 0,
+            skipped: 0,
+            handlers: StreamHandlers::new(),
+        }
+    }
+
+    /// Next frame, reading the next file once the current one is done
+    fn next_frame(&mut self) -> Option<RecordedFrame> {
+        loop {
+            if let Some(frame) = self.frames.as_mut().and_then(|frames| frames.next()) {
+                return Some(frame);
+            }
+            let path = self.files.pop_front()?;
+            info!(actor = "replay"; "Replaying {}", path.display());
+            self.frames = match read_frames(&path) {
+                Ok(frames) => Some(frames),
+                Err(e) => {
+                    error!(actor = "replay"; "Error reading {}: {}", path.display(), e);
+                    None
+                }
+            };
+        }
+    }
+
+    /// Recorded gap since the previous frame, scaled by `speed`
+    fn delay(&self, frame: &RecordedFrame) -> Duration {
+        match self.previous {
+            Some(previous) if self.speed > 0.0 => {
+                let gap = (frame.received_at - previous).num_milliseconds().max(0);
+                Duration::from_millis((gap as f64 / self.speed) as u64)
+            }
+            _ => Duration::from_millis(0),
+        }
+    }
+
+    fn schedule(&mut self, ctx: &mut Context<Self>) {
+        let frame = match self.next_frame() {
+            Some(frame) => frame,
+            None => {
+                info!(actor = "replay"; "Replay complete: {} frames replayed, {} skipped", self.replayed, self.skipped);
+                ctx.run_later(self.linger, |_act, _ctx| System::current().stop());
+                return;
+            }
+        };
+        let delay = self.delay(&frame);
+        self.previous = Some(frame.received_at);
+        ctx.run_later(delay, move |act, ctx| {
+            act.play(frame, ctx);
+            act.schedule(ctx);
+        });
+    }
+
+    fn play(&mut self, frame: RecordedFrame, ctx: &mut Context<Self>) {
+        let (socket, received_at) = (frame.stream.clone(), frame.received_at);
+        let result = match frame.market_payload() {
+            Ok(Some((stream, payload))) => self.handlers.handle(&stream, &payload, received_at, ctx),
+            Ok(None) => return,
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(()) => self.replayed += 1,
+            Err(e) => {
+                self.skipped += 1;
+                warn!(actor = "replay", stream:% = socket; "Skipping frame: {}", e);
+            }
+        }
+    }
+}
+
+impl Actor for ReplayActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.schedule(ctx);
+    }
+}
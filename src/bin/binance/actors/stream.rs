@@ -0,0 +1,258 @@
+use trading_sys::error::{Error, Result};
+use trading_sys::health::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use trading_sys::metrics::{frame_received, stream_symbol};
+use trading_sys::models::aggregate_trades::AggregateTradeData;
+use trading_sys::models::book_depth::{BookDepthDataInsert, PartialBookDepthData};
+use trading_sys::models::combined_stream::split_stream_name;
+use trading_sys::models::klines::KlineDataInsert;
+use trading_sys::models::mini_ticker::MiniTickerDataInsert;
+use trading_sys::models::tickers::TickerDataInsert;
+use trading_sys::models::trades::TradeData;
+use trading_sys::symbols::Symbol;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
+
+use actix::*;
+use actix_web::ws;
+use chrono::NaiveDateTime;
+
+use crate::actors::recorder::record_frame;
+use crate::supervisor::{Connected, Disconnected, Malformed, StreamSpec, StreamSupervisor};
+
+/////////////////////////////////////////////////////////////////
+/// Market streams: a `BinanceEvent` impl per stream type says how its
+/// frames parse and where they go, `handler` finds it by stream name.
+/// The same handlers serve a stream on its own socket (`StreamActor`),
+/// on the combined socket and in replays.
+/////////////////////////////////////////////////////////////////
+
+/// A market stream's payload
+pub trait BinanceEvent: fmt::Debug + Sized + 'static {
+    /// Stream type after the `<symbol>@`, without parameters, e.g: "kline" in "ethbtc@kline_1m"
+    const SUFFIX: &'static str;
+
+    /// Log target, `module_path!()` of the impl so each stream type can be filtered on its own
+    const TARGET: &'static str;
+
+    /// Kept for the life of one connection, e.g: the symbol and levels of a partial depth stream
+    type State: 'static;
+
+    /// True if this event is sent on `stream_type`, the stream name after `<symbol>@`
+    fn handles(stream_type: &str) -> bool {
+        stream_type == Self::SUFFIX
+    }
+
+    /// Fresh state for a stream, e.g: "ethbtc@depth20"
+    fn state(stream: &str) -> Result<Self::State>;
+
+    fn parse(txt: &str) -> serde_json::Result<Self>;
+
+    /// Send a parsed event on to Postgres, the health monitor, candles, paper trading...
+    fn sink<A>(self, state: &mut Self::State, stream: &str, received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>;
+}
+
+/// One stream's parse and sink, with its state. Type erased so a socket or replay can carry many streams.
+pub trait FrameHandler<A: Actor<Context = Context<A>>> {
+    /// `BinanceEvent::SUFFIX`, the `actor` field of log records
+    fn actor(&self) -> &'static str;
+
+    /// `BinanceEvent::TARGET`
+    fn target(&self) -> &'static str;
+
+    fn handle(&mut self, txt: &str, received_at: NaiveDateTime, ctx: &mut Context<A>) -> serde_json::Result<()>;
+}
+
+struct EventHandler<M: BinanceEvent> {
+    stream: String,
+    state: M::State,
+}
+
+impl<A, M> FrameHandler<A> for EventHandler<M>
+where
+    A: Actor<Context = Context<A>>,
+    M: BinanceEvent,
+{
+    fn actor(&self) -> &'static str {
+        M::SUFFIX
+    }
+
+    fn target(&self) -> &'static str {
+        M::TARGET
+    }
+
+    fn handle(&mut self, txt: &str, received_at: NaiveDateTime, ctx: &mut Context<A>) -> serde_json::Result<()> {
+        let event = M::parse(txt)?;
+        debug!(target: M::TARGET, actor = M::SUFFIX, stream:% = self.stream, symbol:% = stream_symbol(&self.stream); "{:?}", event);
+        event.sink(&mut self.state, &self.stream, received_at, ctx);
+        Ok(())
+    }
+}
+
+fn event_handler<A, M>(stream: &str) -> Result<Box<dyn FrameHandler<A>>>
+where
+    A: Actor<Context = Context<A>>,
+    M: BinanceEvent,
+{
+    let state = M::state(stream)?;
+    Ok(Box::new(EventHandler::<M> { stream: stream.to_owned(), state }))
+}
+
+/// The handler for a market stream by Binance name, e.g: "ethbtc@kline_1m", "!ticker@arr".
+/// Adding a stream type takes a `BinanceEvent` impl and its line here.
+pub fn handler<A: Actor<Context = Context<A>>>(stream: &str) -> Result<Box<dyn FrameHandler<A>>> {
+    let (_, stream_type) = split_stream_name(stream);
+    macro_rules! first_handling {
+        ($($event:ty,)*) => {$(
+            if <$event as BinanceEvent>::handles(stream_type) {
+                return event_handler::<A, $event>(stream);
+            }
+        )*};
+    }
+    first_handling!(
+        TradeData,
+        AggregateTradeData,
+        KlineDataInsert,
+        BookDepthDataInsert,
+        PartialBookDepthData,
+        TickerDataInsert,
+        Vec<TickerDataInsert>,
+        MiniTickerDataInsert,
+        Vec<MiniTickerDataInsert>,
+    );
+    Err(Error::InvalidEnum("stream", stream.to_owned()))
+}
+
+/// Handlers for the streams on one socket or in one replay, each created on its stream's first frame
+pub struct StreamHandlers<A: Actor<Context = Context<A>>> {
+    handlers: HashMap<String, Box<dyn FrameHandler<A>>>, // by stream name
+}
+
+impl<A: Actor<Context = Context<A>>> StreamHandlers<A> {
+    pub fn new() -> Self {
+        StreamHandlers { handlers: HashMap::new() }
+    }
+
+    pub fn handle(&mut self, stream: &str, txt: &str, received_at: NaiveDateTime, ctx: &mut Context<A>) -> Result<()> {
+        if let Some(handler) = self.handlers.get_mut(stream) {
+            return Ok(handler.handle(txt, received_at, ctx)?);
+        }
+        let mut handler = handler(stream)?;
+        handler.handle(txt, received_at, ctx)?;
+        self.handlers.insert(stream.to_owned(), handler);
+        Ok(())
+    }
+
+    /// Drop a stream's state, e.g: once unsubscribed
+    pub fn remove(&mut self, stream: &str) {
+        self.handlers.remove(stream);
+    }
+}
+
+/// Fails for streams without a handler, or whose name doesn't give the handler its state
+pub fn check_stream(stream: &str) -> Result<()> {
+    handler::<StreamActor>(stream).map(|_| ())
+}
+
+/// Symbol of a per-symbol stream, e.g: "ethbtc@trade" -> ETHBTC
+pub fn stream_pair(stream: &str) -> Result<Symbol> {
+    split_stream_name(stream).0.parse()
+}
+
+/// One raw market stream per socket, e.g: `ws/ethbtc@trade`.
+/// Does the heartbeat, ping/pong, recording and reconnects, the handler does the rest.
+pub struct StreamActor {
+    pub client_writer: ws::ClientWriter,
+    pub last_seen: Instant, // last frame of any kind, see `hb`
+    pub handler: Box<dyn FrameHandler<StreamActor>>,
+    pub stream: StreamSpec,
+    pub stream_name: String, // `stream.stream_name()`, used on every frame
+    pub supervisor: Addr<StreamSupervisor>,
+}
+
+impl Actor for StreamActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // Start heartbeats otherwise server disconnects in 10 seconds
+        self.hb(ctx);
+    }
+
+    fn stopped(&mut self, _: &mut Context<Self>) {
+        // Let the supervisor schedule a reconnect
+        self.supervisor.do_send(Disconnected(self.stream.clone()));
+    }
+}
+
+impl StreamActor {
+    /// Ping the server every `HEARTBEAT_INTERVAL` and reconnect if nothing,
+    /// not even the pong, arrived within `CLIENT_TIMEOUT`
+    fn hb(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(HEARTBEAT_INTERVAL, |act, ctx| {
+            if act.last_seen.elapsed() > CLIENT_TIMEOUT {
+                warn!(target: act.handler.target(), actor = act.handler.actor(), stream:% = act.stream_name; "No frames for {:?}, reconnecting", CLIENT_TIMEOUT);
+                ctx.stop();
+                return;
+            }
+            act.client_writer.ping("Heartbeat");
+            act.hb(ctx);
+        });
+    }
+}
+
+/// Handle Websocket messages
+impl StreamHandler<ws::Message, ws::ProtocolError> for StreamActor {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Context<Self>) {
+        self.last_seen = Instant::now();
+        match msg {
+            ws::Message::Text(txt) => {
+                record_frame(&self.stream, &txt);
+                frame_received(&self.stream_name);
+                if let Err(e) = self.handler.handle(&txt, chrono::Utc::now().naive_utc(), ctx) {
+                    self.supervisor.do_send(Malformed::new(&self.stream, txt, e));
+                }
+            }
+            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
+            ws::Message::Pong(_) => (), // answer to our heartbeat
+            ws::Message::Close(reason) => {
+                info!(target: self.handler.target(), actor = self.handler.actor(), stream:% = self.stream_name; "Websocket closed: {:?}", reason)
+            }
+            _ => (),
+        }
+    }
+
+    fn started(&mut self, _ctx: &mut Context<Self>) {
+        info!(target: self.handler.target(), actor = self.handler.actor(), stream:% = self.stream_name; "Websocket connected");
+        self.supervisor.do_send(Connected(self.stream.clone()));
+    }
+
+    fn finished(&mut self, ctx: &mut Context<Self>) {
+        info!(target: self.handler.target(), actor = self.handler.actor(), stream:% = self.stream_name; "Websocket disconnected");
+        ctx.stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handlers_by_stream_name() {
+        let actor = |stream: &str| handler::<StreamActor>(stream).map(|h| (h.actor(), h.target()));
+        assert_eq!(actor("ethbtc@trade").unwrap(), ("trade", "binance::actors::trades"));
+        assert_eq!(actor("ethbtc@kline_1m").unwrap(), ("kline", "binance::actors::klines"));
+        assert_eq!(actor("ethbtc@depth@100ms").unwrap(), ("depth", "binance::actors::book_depth"));
+        assert_eq!(actor("ethbtc@depth20").unwrap(), ("depth", "binance::actors::book_depth"));
+        assert_eq!(actor("!ticker@arr").unwrap(), ("ticker", "binance::actors::tickers"));
+        assert_eq!(actor("!miniTicker@arr").unwrap(), ("miniTicker", "binance::actors::mini_ticker"));
+
+        // no handler, or no state from the name
+        assert!(check_stream("ethbtc@bookTicker").is_err());
+        assert!(check_stream("ethbtc@trades").is_err());
+        assert!(check_stream("ethbtc@depth10@10ms").is_err());
+        assert!(check_stream("!depth20").is_err());
+    }
+}
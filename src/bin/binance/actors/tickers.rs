@@ -1,64 +1,69 @@
 use trading_sys::models::tickers::TickerDataInsert;
-use trading_sys::{create_tickers, establish_connection_pg};
-
-use std::time::Duration;
+use trading_sys::db_writer::Row;
+use trading_sys::health::Observation;
+use trading_sys::error::Result;
 
 use actix::*;
-use actix_web::ws;
+use chrono::NaiveDateTime;
 
-pub struct TickerActor {
-    pub client_writer: ws::ClientWriter,
-}
+use crate::actors::db_writer::{insert_row, insert_rows};
+use crate::actors::health::observe;
+use crate::actors::orders::{FeedTicker, OrderDesk};
+use crate::actors::stream::BinanceEvent;
 
-impl Actor for TickerActor {
-    type Context = Context<Self>;
+/// `<symbol>@ticker`
+impl BinanceEvent for TickerDataInsert {
+    const SUFFIX: &'static str = "ticker";
+    const TARGET: &'static str = module_path!();
+    type State = ();
 
-    fn started(&mut self, ctx: &mut Context<Self>) {
-        // Start heartbeats otherwise server disconnects in 10 seconds
-        self.hb(ctx);
+    fn state(_stream: &str) -> Result<()> {
+        Ok(())
     }
 
-    fn stopped(&mut self, _: &mut Context<Self>) {
-        // Stop application on disconnect
-        System::current().stop();
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
     }
-}
 
-impl TickerActor {
-    fn hb(&self, ctx: &mut Context<Self>) {
-        ctx.run_later(std::time::Duration::new(1, 0), |act, ctx| {
-            act.client_writer.pong("Heartbeat");
-            act.hb(ctx); // client should check for a timeout here
-        });
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        observe(stream, Observation::from(&self));
+        OrderDesk::from_registry().do_send(FeedTicker(self.clone()));
+        insert_row(Row::Ticker(self), ctx);
     }
 }
 
-impl StreamHandler<ws::Message, ws::ProtocolError> for TickerActor {
-    fn handle(&mut self, msg: ws::Message, _ctx: &mut Context<Self>) {
-        match msg {
-            ws::Message::Text(txt) => {
-                let ticker_data: TickerDataInsert =
-                    serde_json::from_str::<TickerDataInsert>(&txt).unwrap();
-                println!("{:?}", &ticker_data);
-                let connection = establish_connection_pg();
-                create_tickers(&connection, ticker_data);
-            }
-            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
-            ws::Message::Pong(pong) => self.client_writer.ping(&pong),
-            ws::Message::Close(maybe_reason) => match maybe_reason {
-                Some(reason) => println!("{:?}", reason),
-                None => println!("`ws::Message::Close(?)` with no reason provided."),
-            },
-            _ => (),
-        }
+/// `!ticker@arr`, every market's ticker in one frame, written with one INSERT
+impl BinanceEvent for Vec<TickerDataInsert> {
+    const SUFFIX: &'static str = "ticker";
+    const TARGET: &'static str = module_path!();
+    type State = ();
+
+    fn handles(stream_type: &str) -> bool {
+        stream_type == "ticker@arr"
     }
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("<ticker.rs>: Websocket Connected.");
+    fn state(_stream: &str) -> Result<()> {
+        Ok(())
     }
 
-    fn finished(&mut self, ctx: &mut Context<Self>) {
-        println!("<ticker.rs>: Websocket Stopped.");
-        ctx.stop()
+    fn parse(txt: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(txt)
+    }
+
+    fn sink<A>(self, _state: &mut (), stream: &str, _received_at: NaiveDateTime, ctx: &mut Context<A>)
+    where
+        A: Actor<Context = Context<A>>,
+    {
+        if let Some(first) = self.first() {
+            observe(stream, Observation::from(first));
+        }
+        let desk = OrderDesk::from_registry();
+        for ticker in self.iter() {
+            desk.do_send(FeedTicker(ticker.clone()));
+        }
+        insert_rows(self.into_iter().map(Row::Ticker).collect(), ctx);
     }
 }
@@ -0,0 +1,242 @@
+use trading_sys::account_state::AccountState;
+use trading_sys::error::Result;
+use trading_sys::models::account::{AccountInfo, OrderInfo};
+use trading_sys::db_writer::Row;
+use trading_sys::models::user_data::{parse_user_data_event, UserDataEvent};
+use trading_sys::health::{CLIENT_TIMEOUT, HEARTBEAT_INTERVAL};
+use trading_sys::rest_client::BinanceRestClient;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::*;
+use actix_web::ws;
+
+use crate::actors::db_writer::insert_row;
+use crate::actors::orders::{FeedFill, OrderDesk};
+use crate::supervisor::{Connected, Disconnected, Malformed, StreamSpec, StreamSupervisor};
+
+/// Listen keys expire after 60 minutes, Binance recommends a keepalive every 30
+pub const LISTEN_KEY_KEEPALIVE: Duration = Duration::from_secs(30 * 60);
+
+/// Blocking REST calls of one user data stream, run on a `SyncArbiter` so they don't stall the stream actors
+pub struct UserDataRest {
+    pub rest: Arc<BinanceRestClient>,
+}
+
+impl Actor for UserDataRest {
+    type Context = SyncContext<Self>;
+}
+
+/// POST a new listen key, see `BinanceRestClient::create_listen_key`
+pub struct CreateListenKey;
+
+impl Message for CreateListenKey {
+    type Result = Result<String>;
+}
+
+impl Handler<CreateListenKey> for UserDataRest {
+    type Result = Result<String>;
+
+    fn handle(&mut self, _msg: CreateListenKey, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        self.rest.create_listen_key()
+    }
+}
+
+pub struct KeepaliveListenKey(pub String);
+
+impl Message for KeepaliveListenKey {
+    type Result = Result<()>;
+}
+
+impl Handler<KeepaliveListenKey> for UserDataRest {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: KeepaliveListenKey, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        self.rest.keepalive_listen_key(&msg.0)
+    }
+}
+
+/// Sent once the stream is done with its key, errors are only logged
+#[derive(Message)]
+pub struct CloseListenKey(pub String);
+
+impl Handler<CloseListenKey> for UserDataRest {
+    type Result = ();
+
+    fn handle(&mut self, msg: CloseListenKey, _ctx: &mut SyncContext<Self>) {
+        if let Err(e) = self.rest.close_listen_key(&msg.0) {
+            warn!(actor = "user_data", stream = "userData"; "Error closing listen key: {}", e);
+        }
+    }
+}
+
+/// Balances and open orders over REST
+pub struct LoadSnapshot;
+
+impl Message for LoadSnapshot {
+    type Result = Result<(AccountInfo, Vec<OrderInfo>)>;
+}
+
+impl Handler<LoadSnapshot> for UserDataRest {
+    type Result = Result<(AccountInfo, Vec<OrderInfo>)>;
+
+    fn handle(&mut self, _msg: LoadSnapshot, _ctx: &mut SyncContext<Self>) -> Self::Result {
+        let account = self.rest.account()?;
+        let orders = self.rest.open_orders(None)?;
+        Ok((account, orders))
+    }
+}
+
+/// Execution reports and balance changes for our account, over `ws/<listenKey>`.
+/// Fills are written to the `fills` table and fed to the risk checks, open orders and balances are kept in `state`.
+pub struct UserDataActor {
+    pub client_writer: ws::ClientWriter,
+    pub last_seen: Instant, // last frame of any kind, see `hb`
+    pub stream: StreamSpec,
+    pub supervisor: Addr<StreamSupervisor>,
+    pub rest: Addr<UserDataRest>, // REST calls, off this arbiter
+    pub listen_key: String,
+    pub state: AccountState,
+    pub pending: Option<Vec<UserDataEvent>>, // events received while the snapshot loads, see `load_snapshot`
+}
+
+impl Actor for UserDataActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        // Start heartbeats otherwise server disconnects in 10 seconds
+        self.hb(ctx);
+
+        ctx.run_interval(LISTEN_KEY_KEEPALIVE, |act, ctx| {
+            act.rest
+                .send(KeepaliveListenKey(act.listen_key.clone()))
+                .into_actor(act)
+                .then(|res, act, ctx| {
+                    let error = match res {
+                        Ok(Ok(())) => return actix::fut::ok(()),
+                        Ok(Err(e)) => e.to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    // reconnecting creates a new key
+                    warn!(actor = "user_data", stream:% = act.stream.stream_name(); "Listen key keepalive failed: {}", error);
+                    ctx.stop();
+                    actix::fut::ok(())
+                })
+                .spawn(ctx);
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Context<Self>) {
+        self.rest.do_send(CloseListenKey(self.listen_key.clone()));
+        // Let the supervisor schedule a reconnect
+        self.supervisor.do_send(Disconnected(self.stream.clone()));
+    }
+}
+
+impl UserDataActor {
+    /// Ping the server every `HEARTBEAT_INTERVAL` and reconnect if nothing,
+    /// not even the pong, arrived within `CLIENT_TIMEOUT`
+    fn hb(&self, ctx: &mut Context<Self>) {
+        ctx.run_later(HEARTBEAT_INTERVAL, |act, ctx| {
+            if act.last_seen.elapsed() > CLIENT_TIMEOUT {
+                warn!(actor = "user_data", stream:% = act.stream.stream_name(); "No frames for {:?}, reconnecting", CLIENT_TIMEOUT);
+                ctx.stop();
+                return;
+            }
+            act.client_writer.ping("Heartbeat");
+            act.hb(ctx);
+        });
+    }
+
+    /// Events sent before we connected are lost, start from the current REST state.
+    /// Events received while it loads are held in `pending`, then those newer than the snapshot are applied.
+    fn load_snapshot(&mut self, ctx: &mut Context<Self>) {
+        self.pending = Some(Vec::new());
+        self.rest
+            .send(LoadSnapshot)
+            .into_actor(self)
+            .then(|res, act, _ctx| {
+                let pending = act.pending.take().unwrap_or_default();
+                match res {
+                    Ok(Ok((account, orders))) => {
+                        act.state = AccountState::from_snapshot(&account, &orders);
+                        info!(actor = "user_data", stream:% = act.stream.stream_name(); "Loaded {} balances, {} open orders", account.balances.len(), orders.len());
+                    }
+                    // keep what the stream tells us
+                    Ok(Err(e)) => error!(actor = "user_data", stream:% = act.stream.stream_name(); "Error loading account snapshot: {}", e),
+                    Err(e) => error!(actor = "user_data", stream:% = act.stream.stream_name(); "Error loading account snapshot: {}", e),
+                }
+                let applied = act.state.replay(&pending);
+                debug!(actor = "user_data", stream:% = act.stream.stream_name(); "Applied {} of {} events received while loading", applied, pending.len());
+                actix::fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+/// Current balances and open orders
+pub struct GetAccountState;
+
+impl Message for GetAccountState {
+    type Result = AccountState;
+}
+
+impl Handler<GetAccountState> for UserDataActor {
+    type Result = MessageResult<GetAccountState>;
+
+    fn handle(&mut self, _msg: GetAccountState, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.state.clone())
+    }
+}
+
+/// Handle Websocket messages
+impl StreamHandler<ws::Message, ws::ProtocolError> for UserDataActor {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Context<Self>) {
+        self.last_seen = Instant::now();
+        match msg {
+            ws::Message::Text(txt) => match parse_user_data_event(&txt) {
+                Ok(event) => {
+                    if let UserDataEvent::ExecutionReport(report) = &event {
+                        info!(
+                            actor = "user_data", stream:% = self.stream.stream_name(), symbol = report.symbol.as_str();
+                            "{} {} {}: {} of {} filled",
+                            report.order_client_id(), report.execution_type,
+                            report.status, report.cumulative_qty, report.quantity
+                        );
+                    }
+                    // fills are persisted as they arrive, only the state waits on the snapshot
+                    let fill = match &mut self.pending {
+                        Some(pending) => {
+                            pending.push(event.clone());
+                            match &event {
+                                UserDataEvent::ExecutionReport(report) => report.to_fill(),
+                                _ => None,
+                            }
+                        }
+                        None => self.state.apply(&event),
+                    };
+                    if let Some(fill) = fill {
+                        OrderDesk::from_registry().do_send(FeedFill(fill.clone()));
+                        insert_row(Row::Fill(fill), ctx);
+                    }
+                }
+                Err(e) => self.supervisor.do_send(Malformed::new(&self.stream, txt, e)),
+            },
+            ws::Message::Ping(ping) => self.client_writer.pong(&ping),
+            ws::Message::Pong(_) => (), // answer to our heartbeat
+            _ => (),
+        }
+    }
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        info!(actor = "user_data", stream:% = self.stream.stream_name(); "Websocket connected");
+        self.supervisor.do_send(Connected(self.stream.clone()));
+        self.load_snapshot(ctx);
+    }
+
+    fn finished(&mut self, ctx: &mut Context<Self>) {
+        info!(actor = "user_data", stream:% = self.stream.stream_name(); "Websocket disconnected");
+        ctx.stop()
+    }
+}
@@ -1,5 +1,7 @@
 extern crate chrono;
 extern crate clap;
+#[macro_use]
+extern crate log;
 
 #[macro_use]
 extern crate serde_derive;
@@ -9,75 +11,406 @@ extern crate serde_json;
 
 extern crate trading_sys;
 
+use actix::{Actor, Arbiter, System};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
 pub mod actors;
 
+pub mod metrics_server;
+use metrics_server::start_metrics_server;
+
 pub mod spawn_clients;
-use spawn_clients::{
-    binance_api_url,
-    spawn_aggregate_trade_client,
-    spawn_book_depth_client,
-    spawn_kline_client,
-    spawn_mini_ticker_client,
-    spawn_trade_client,
-    spawn_ticker_client,
-};
-
-pub mod db_actions;
-use db_actions::{
-    get_aggregate_trades_from_postgres,
-    get_book_depth_from_postgres,
-    get_klines_from_postgres,
-    get_trades_from_postgres,
-    get_tickers_from_postgres,
-};
-
-use trading_sys::currency_pairs::{CurrencyBase, CurrencyPair, CurrencyPrice};
-use trading_sys::models::book_depth::DepthLevels;
+use spawn_clients::BINANCE_WS_API_URL;
+
+pub mod supervisor;
+use supervisor::{StreamSpec, StreamSupervisor, Subscribe, SubscribeCombined};
+
+use actors::candles::{CandleAggregator, TickSource};
+use actors::db_writer::DbBatcher;
+use actors::health::HealthMonitorActor;
+use actors::orders::OrderDesk;
+use actors::paper::PaperFeed;
+use actors::recorder::FrameRecorderActor;
+use actors::redis::start_redis_writer;
+use actors::replay::ReplayActor;
+
+use trading_sys::backfill::KlineBackfill;
+use trading_sys::backoff::{Backoff, StreamCounters};
+use trading_sys::config::{Config, Sink, DEFAULT_CONFIG_PATH};
+use trading_sys::symbols::{Symbol, SymbolPrice, SymbolRegistry, BINANCE_TICKER_PRICE_URL};
+use trading_sys::{establish_connection_pg, establish_pool_pg, run_migrations};
+use trading_sys::health::HealthMonitor;
+use trading_sys::logging::init_logger;
+use trading_sys::order_manager::OrderManager;
+use trading_sys::risk::{KillSwitch, RiskManager};
 use trading_sys::models::klines::KlineInterval;
-use trading_sys::models::mini_ticker::MiniTickerQueryType;
+use trading_sys::query::{Aggregation, OutputFormat, Query, QueryTable};
+use trading_sys::recorder::{recording_files, FrameRecorder};
+use trading_sys::rest_client::{ApiCredentials, BinanceRestClient};
 
 
+/// Client order id prefix, tells the orders placed here apart from manual ones
+const ORDER_PREFIX: &str = "ts-";
 
 pub fn main() {
+    let matches = parse_args();
+    let config_path = matches.value_of("config").unwrap_or(DEFAULT_CONFIG_PATH);
+    let config = match load_config(config_path, matches.occurrences_of("config") > 0) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error loading config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    config.database.apply_env();
+
+    match matches.subcommand() {
+        ("backfill", Some(args)) => run_backfill(args),
+        ("migrate", Some(_)) => run_migrate(),
+        ("query", Some(args)) => run_query(args),
+        ("replay", Some(args)) => run_replay(&config, args),
+        _ => run_collect(&config),
+    }
+}
 
-    let currencies: Vec<CurrencyPair> = vec![
-        CurrencyPair::ETHBTC,
-        CurrencyPair::ETHUSDT,
-        CurrencyPair::BNBETH,
-        CurrencyPair::LINKETH,
-        CurrencyPair::XLMETH,
-        CurrencyPair::XMRETH,
-        CurrencyPair::ZILETH,
-    ];
-
+/// A missing file is only an error when it was asked for explicitly, an invalid one always is.
+/// Installs the logger from its `[log]` section, RUST_LOG overrides the filter.
+pub fn load_config(path: &str, required: bool) -> trading_sys::error::Result<Config> {
+    let config = match Config::load(path) {
+        Ok(config) => config,
+        Err(e) if required || std::path::Path::new(path).exists() => return Err(e),
+        Err(_) => {
+            let config = Config::default();
+            init_logger(&config.log.filter, config.log.format);
+            info!("No config at {}, using defaults", path);
+            return Ok(config);
+        }
+    };
+    init_logger(&config.log.filter, config.log.format);
+    info!("Loaded config from {}", path);
+    Ok(config)
+}
 
+pub fn run_collect(config: &Config) {
     let sys = actix::System::new("ws-binance");
 
-    // spawn_aggregate_trade_client(CurrencyPair::ETHBTC);
-    // spawn_book_depth_client(CurrencyPair::ETHBTC, Some(DepthLevels::_10));
-    // spawn_book_depth_client(CurrencyPair::ETHBTC, None);
+    // Every stream writes through one batcher and a small connection pool
+    let db_config = config.database.batch_config();
+    let pool = establish_pool_pg(db_config.max_in_flight as u32);
+
+    // Symbol metadata from exchangeInfo, falling back to the last saved copy when offline
+    let conn = pool.get().expect("Error getting connection for symbols");
+    let symbols = match SymbolRegistry::fetch() {
+        Ok(symbols) => {
+            match symbols.save(&conn) {
+                Ok(n) => info!("Saved {} symbols from exchangeInfo", n),
+                Err(e) => error!("Error saving symbols: {}", e),
+            }
+            symbols
+        }
+        Err(e) => {
+            warn!("Error fetching exchangeInfo, using saved symbols: {}", e);
+            SymbolRegistry::load(&conn).expect("Error loading symbols")
+        }
+    };
+    drop(conn);
+    let currencies: Vec<Symbol> = config
+        .collect
+        .symbols
+        .iter()
+        .cloned()
+        .filter(|symbol| match symbols.get(symbol) {
+            Some(info) if info.is_trading() => true,
+            _ => {
+                warn!(symbol = symbol.as_str(); "Skipping, not trading on Binance");
+                false
+            }
+        })
+        .collect();
+
+    start_sinks(config, pool);
+
+    // Raw frames as received, for `replay`
+    if config.record.enabled {
+        let recorder = FrameRecorder::new(&config.record.dir);
+        System::current().registry().set(FrameRecorderActor::new(recorder).start());
+    }
+
+    // Halts every order when tripped: by the daily loss limit, `[risk] halted`, or `/kill_switch` beside the metrics
+    let kill_switch = KillSwitch::default();
+    if config.risk.halted {
+        kill_switch.trip("halted in [risk] config");
+    }
 
-    // for currency in currencies.into_iter() {
-    //     spawn_kline_client(&currency, KlineInterval::_1m);
-    //     spawn_trade_client(&currency);
-    //     spawn_ticker_client(&currency);
-    // }
+    // Orders go to the exchange only with `[trading] mode = "live"`, paper fills come from the streams below.
+    // Either way they are placed through the `OrderDesk`, whose risk checks follow the ticker and user data streams.
+    let gateway = config.trading.trading_mode().map(|mode| mode.gateway(&symbols).map(|gateway| (gateway, mode)));
+    match gateway {
+        Some(Ok(((gateway, paper), trading_mode))) => {
+            match paper {
+                Some(paper) => {
+                    info!("Paper trading: {:?}", trading_mode);
+                    System::current().registry().set(PaperFeed::new(paper).start());
+                }
+                None => info!("Live trading"),
+            }
+            if config.risk.max_position.is_empty() {
+                warn!("No symbols in [risk] max_position, every order will be refused");
+            }
+            let risk = RiskManager::new(config.risk.limits(), symbols.clone(), kill_switch.clone());
+            let mut manager = OrderManager::new(gateway, establish_connection_pg(), ORDER_PREFIX, risk);
+            match manager.restore() {
+                Ok(n) => info!("Restored {} open orders", n),
+                Err(e) => error!("Error restoring open orders: {}", e),
+            }
+            System::current().registry().set(Arbiter::start(move |_| OrderDesk::new(manager)));
+        }
+        Some(Err(e)) => error!("Trading disabled: {}", e),
+        None => info!("Trading off"),
+    }
 
-    spawn_trade_client(&CurrencyPair::ETHBTC);
+    if config.metrics.enabled {
+        start_metrics_server(&config.metrics.listen, kill_switch);
+    }
 
-    spawn_kline_client(&CurrencyPair::ETHBTC, KlineInterval::_1m);
+    // Rates, latency, trade ID and kline gaps per stream, with an alarm for streams gone quiet
+    let monitor = HealthMonitor::new(config.health.stale_after());
+    let health = HealthMonitorActor::new(monitor, config.health.report_interval()).start();
+    System::current().registry().set(health);
+
+    // Reconnects dropped streams with exponential backoff, see `Backoff::default()`
+    let reconnects = StreamCounters::new();
+    let supervisor = StreamSupervisor::new(
+        BINANCE_WS_API_URL.to_string(),
+        Backoff::default(),
+        reconnects.clone(),
+    ).start();
+
+    // Per-symbol streams, the all-markets arrays only once
+    let streams = match StreamSpec::market_streams(&config.collect.streams, &currencies) {
+        Ok(streams) => streams,
+        Err(e) => {
+            eprintln!("Error in [collect] streams: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Subscribing to {}", streams.iter().map(|s| s.stream_name()).collect::<Vec<_>>().join(", "));
+
+    // All streams multiplexed over a single combined socket, or one socket per stream.
+    // More can be added/removed later with SubscribeCombined/UnsubscribeCombined without reconnecting.
+    if config.collect.combined {
+        supervisor.do_send(SubscribeCombined(streams));
+    } else {
+        for stream in streams {
+            supervisor.do_send(Subscribe(stream));
+        }
+    }
 
-    spawn_mini_ticker_client(&CurrencyPair::ETHBTC, Some(MiniTickerQueryType::SingleMarket));
+    // Our own orders and balances, needs BINANCE_API_KEY and BINANCE_SECRET_KEY
+    match ApiCredentials::from_env() {
+        Some(credentials) if config.collect.user_data => {
+            let rest = std::sync::Arc::new(BinanceRestClient::new(credentials));
+            supervisor.do_send(Subscribe(StreamSpec::UserData(rest)));
+        }
+        Some(_) => (),
+        None => info!("No API key set, not opening the user data stream"),
+    }
 
-    spawn_ticker_client(&CurrencyPair::ETHBTC);
+    let _ = sys.run();
+}
 
-    // get_book_depth_from_postgres();
-    // get_klines_from_postgres();
-    // get_trades_from_postgres();
-    // get_aggregate_trades_from_postgres();
+/// The batcher every stream writes through, and bars rolled up from the trade stream
+pub fn start_sinks(config: &Config, pool: trading_sys::PgPool) {
+    let db_config = config.database.batch_config();
+    let sinks = &config.collect.sinks;
+    let mut db_batcher = if sinks.contains(&Sink::Postgres) {
+        DbBatcher::with_pool(pool, db_config)
+    } else {
+        DbBatcher::without_postgres(db_config)
+    };
+    db_batcher.echo = sinks.contains(&Sink::Stdout);
+    if sinks.contains(&Sink::Redis) {
+        db_batcher.redis = Some(start_redis_writer(&config.redis.url));
+    }
+    System::current().registry().set(db_batcher.start());
+
+    // Time bars share the (symbol, interval, start_time) key with Binance's own klines,
+    // so only use intervals that aren't subscribed as klines.
+    let tick_source = match config.candles.source.as_str() {
+        "aggTrade" => TickSource::AggregateTrades,
+        _ => TickSource::Trades,
+    };
+    let candles = CandleAggregator::new(tick_source, config.candles.bars.clone()).start();
+    System::current().registry().set(candles);
+}
 
+/// Feed recorded frames to the configured sinks, see `ReplayActor`
+pub fn run_replay(config: &Config, args: &ArgMatches) {
+    let path = args.value_of("path").unwrap();
+    let speed = args.value_of("speed").unwrap().parse::<f64>().expect("Invalid speed");
+    let files = recording_files(path).expect("Error listing recordings");
+    info!("Replaying {} files from {} at {}x", files.len(), path, speed);
+
+    let sys = actix::System::new("replay-binance");
+    let db_config = config.database.batch_config();
+    start_sinks(config, establish_pool_pg(db_config.max_in_flight as u32));
+    ReplayActor::new(files, speed, db_config.flush_interval * 2).start();
     let _ = sys.run();
+}
+
+pub fn run_migrate() {
+    let connection = trading_sys::establish_connection_pg();
+    match run_migrations(&connection) {
+        Ok(()) => info!("Migrations complete"),
+        Err(e) => error!("Migrations failed: {}", e),
+    }
+}
+
+pub fn run_query(args: &ArgMatches) {
+    let mut query = Query::new(args.value_of("table").unwrap().parse::<QueryTable>().unwrap());
+    query.symbol = args.value_of("symbol").map(|s| s.parse::<Symbol>().expect("Invalid symbol"));
+    query.from = args.value_of("from").map(parse_date_arg);
+    query.to = args.value_of("to").map(parse_date_arg);
+    query.interval = args.value_of("interval").map(|s| s.parse::<KlineInterval>().expect("Invalid interval"));
+    query.aggregations = args
+        .values_of("agg")
+        .map(|aggs| aggs.map(|a| a.parse::<Aggregation>().unwrap()).collect())
+        .unwrap_or_default();
+    query.limit = args.value_of("limit").map(|n| n.parse::<i64>().expect("Invalid limit"));
+    let format = args.value_of("format").unwrap().parse::<OutputFormat>().unwrap();
+
+    let connection = trading_sys::establish_connection_pg();
+    let written = query.run(&connection).and_then(|result| match args.value_of("output") {
+        Some(path) => result.write(format, std::fs::File::create(path)?),
+        None if format == OutputFormat::Parquet => {
+            Err(trading_sys::error::Error::InvalidQuery("parquet needs --output".to_owned()))
+        }
+        None => result.write(format, std::io::stdout()),
+    });
+    if let Err(e) = written {
+        error!("Query failed: {}", e);
+    }
+}
+
+pub fn parse_args<'a>() -> ArgMatches<'a> {
+    App::new("Binance")
+        .about("Streams Binance market data into Postgres")
+        .arg(Arg::with_name("config")
+            .long("config")
+            .takes_value(true)
+            .help("TOML config, defaults to config/binance.toml"))
+        .subcommand(
+            SubCommand::with_name("collect")
+                .about("Subscribes to the configured streams, the default without a subcommand"),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("Prints stored market data, optionally aggregated into intervals")
+                .arg(Arg::with_name("table")
+                    .long("table")
+                    .takes_value(true)
+                    .required(true)
+                    .possible_values(&["aggregate_trades", "bars", "book_depth", "book_snapshots", "klines", "tickers", "trades"]))
+                .arg(Arg::with_name("symbol")
+                    .long("symbol")
+                    .takes_value(true)
+                    .help("e.g: ETHBTC, every symbol if unset"))
+                .arg(Arg::with_name("from")
+                    .long("from")
+                    .takes_value(true)
+                    .help("Start date, inclusive, e.g: 2019-01-01 or 2019-01-01T12:00:00"))
+                .arg(Arg::with_name("to")
+                    .long("to")
+                    .takes_value(true)
+                    .help("End date, exclusive"))
+                .arg(Arg::with_name("interval")
+                    .long("interval")
+                    .takes_value(true)
+                    .help("Kline interval for klines, otherwise the aggregation bucket, e.g: 1m, 1h"))
+                .arg(Arg::with_name("agg")
+                    .long("agg")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .possible_values(&["count", "volume", "vwap", "ohlc"])
+                    .help("Aggregations per interval, defaults to ohlc,volume,count with --interval"))
+                .arg(Arg::with_name("format")
+                    .long("format")
+                    .takes_value(true)
+                    .default_value("table")
+                    .possible_values(&["table", "csv", "jsonl", "parquet"]))
+                .arg(Arg::with_name("output")
+                    .long("output")
+                    .takes_value(true)
+                    .help("File to write, stdout if unset"))
+                .arg(Arg::with_name("limit")
+                    .long("limit")
+                    .takes_value(true)
+                    .help("Rows written, buckets when aggregating; everything if unset")),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Feeds recorded websocket frames through the stream handlers to the configured sinks")
+                .arg(Arg::with_name("path")
+                    .long("path")
+                    .takes_value(true)
+                    .default_value("recordings")
+                    .help("Recording directory or a single .frames.gz file"))
+                .arg(Arg::with_name("speed")
+                    .long("speed")
+                    .takes_value(true)
+                    .default_value("1")
+                    .help("1 keeps the recorded timing, 10 is ten times faster, 0 as fast as possible")),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Applies pending database migrations"),
+        )
+        .subcommand(
+            SubCommand::with_name("backfill")
+                .about("Backfills historical klines from the REST API")
+                .arg(Arg::with_name("symbol")
+                    .long("symbol")
+                    .takes_value(true)
+                    .required(true)
+                    .help("e.g: ETHBTC"))
+                .arg(Arg::with_name("interval")
+                    .long("interval")
+                    .takes_value(true)
+                    .default_value("1m")
+                    .help("Kline interval, e.g: 1m, 1h, 1d"))
+                .arg(Arg::with_name("from")
+                    .long("from")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Start date, e.g: 2019-01-01 or 2019-01-01T12:00:00"))
+                .arg(Arg::with_name("to")
+                    .long("to")
+                    .takes_value(true)
+                    .help("End date, defaults to now")),
+        )
+        .get_matches()
+}
 
+pub fn parse_date_arg(arg: &str) -> chrono::NaiveDateTime {
+    chrono::NaiveDateTime::parse_from_str(arg, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| chrono::NaiveDate::parse_from_str(arg, "%Y-%m-%d").map(|d| d.and_hms(0, 0, 0)))
+        .expect(&format!("Invalid date: {}, expected YYYY-MM-DD[THH:MM:SS]", arg))
+}
+
+pub fn run_backfill(args: &ArgMatches) {
+    let symbol = args.value_of("symbol").unwrap().parse::<Symbol>().expect("Invalid symbol");
+    let interval = args.value_of("interval").unwrap().parse::<KlineInterval>().expect("Invalid interval");
+    let start = parse_date_arg(args.value_of("from").unwrap());
+    let end = args
+        .value_of("to")
+        .map(parse_date_arg)
+        .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+    let connection = trading_sys::establish_connection_pg();
+    match KlineBackfill::new(symbol.clone(), interval, start, end).run(&connection) {
+        Ok(n) => info!(symbol = symbol.as_str(); "Backfill complete: {} klines written", n),
+        Err(e) => error!(symbol = symbol.as_str(); "Backfill failed: {}", e),
+    }
 }
 
 
@@ -114,23 +447,19 @@ pub fn raw_sql_query() {
 
 }
 
-pub fn get_all_base_pairs() {
-    let url = "https://api.binance.com/api/v3/ticker/price";
-    let jsond: Vec<CurrencyPrice> = reqwest::get(url)
+pub fn get_all_base_pairs(symbols: &SymbolRegistry) {
+    let jsond: Vec<SymbolPrice> = reqwest::get(BINANCE_TICKER_PRICE_URL)
         .unwrap()
-        .json::<Vec<CurrencyPrice>>()
+        .json::<Vec<SymbolPrice>>()
         .unwrap();
 
-    let filtered: Vec<CurrencyPrice> = jsond
+    let filtered: Vec<SymbolPrice> = jsond
         .into_iter()
-        .filter(|x| x.symbol.filter_base_pair(CurrencyBase::ETH))
+        .filter(|x| symbols.get(&x.symbol).map_or(false, |info| info.quote_asset == "ETH"))
         .collect();
     println!("{:?}\nOnly ETH base pairs", &filtered);
 
-    for p in &filtered {
-        match p.symbol {
-            CurrencyPair::ETHBTC => println!("{:?}", p.symbol),
-            _ => continue,
-        }
+    for info in symbols.with_base("ETH") {
+        println!("{:?}", info.symbol);
     }
 }
@@ -0,0 +1,50 @@
+use actix_web::{server, App, HttpRequest, HttpResponse};
+
+use trading_sys::metrics::render;
+use trading_sys::risk::KillSwitch;
+
+/// Prometheus scrape endpoint, served on the running actix system.
+/// `/kill_switch` lets an operator halt and resume trading:
+/// GET shows the state, POST trips it (with an optional `?reason=`), DELETE resets it.
+pub fn start_metrics_server(listen: &str, kill_switch: KillSwitch) {
+    let started = server::new(move || {
+        App::with_state(kill_switch.clone())
+            .resource("/metrics", |r| r.f(metrics))
+            .resource("/kill_switch", |r| {
+                r.get().f(kill_switch_state);
+                r.post().f(trip_kill_switch);
+                r.delete().f(reset_kill_switch);
+            })
+    })
+    .bind(listen)
+    .map(|srv| srv.start());
+    match started {
+        Ok(_) => info!("Serving metrics on http://{}/metrics", listen),
+        Err(e) => error!("Error binding metrics endpoint to {}: {}", listen, e),
+    }
+}
+
+fn metrics(_req: &HttpRequest<KillSwitch>) -> HttpResponse {
+    match render() {
+        Ok((text, content_type)) => HttpResponse::Ok().content_type(content_type).body(text),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+fn kill_switch_state(req: &HttpRequest<KillSwitch>) -> HttpResponse {
+    match req.state().halted() {
+        Some(reason) => HttpResponse::Ok().body(format!("halted: {}\n", reason)),
+        None => HttpResponse::Ok().body("trading\n"),
+    }
+}
+
+fn trip_kill_switch(req: &HttpRequest<KillSwitch>) -> HttpResponse {
+    let reason = req.query().get("reason").cloned().unwrap_or_else(|| "tripped by operator".to_owned());
+    req.state().trip(&reason);
+    kill_switch_state(req)
+}
+
+fn reset_kill_switch(req: &HttpRequest<KillSwitch>) -> HttpResponse {
+    req.state().reset();
+    kill_switch_state(req)
+}
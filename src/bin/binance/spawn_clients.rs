@@ -1,182 +1,153 @@
+use std::time::Instant;
+
 use actix::*;
 use actix_web::ws;
-use futures::Future;
+use futures::{future, Future};
+
+use trading_sys::account_state::AccountState;
 
-use trading_sys::currency_pairs::{CurrencyBase, CurrencyPair, CurrencyPrice};
-use trading_sys::models::book_depth::DepthLevels;
-use trading_sys::models::klines::KlineInterval;
-use trading_sys::models::mini_ticker::MiniTickerQueryType;
+use crate::actors::combined_stream::CombinedStreamActor;
+use crate::actors::stream::{handler, StreamActor, StreamHandlers};
+use crate::actors::user_data::{CreateListenKey, UserDataActor, UserDataRest};
+use crate::supervisor::{Disconnected, StreamSpec, StreamSupervisor};
 
-use crate::actors::aggregate_trade::AggregateTradeActor;
-use crate::actors::book_depth::BookDepthActor;
-use crate::actors::klines::KlineActor;
-use crate::actors::mini_ticker::MiniTickerActor;
-use crate::actors::trades::TradeActor;
-use crate::actors::tickers::TickerActor;
+pub const BINANCE_WS_API_URL: &str = "wss://stream.binance.com:9443/";
 
 pub fn binance_api_url(query: String) -> String {
-    let api_url = "wss://stream.binance.com:9443/ws/";
-    format!("{api_url}{query}", api_url = api_url, query = query)
+    format!("{api_url}ws/{query}", api_url = BINANCE_WS_API_URL, query = query)
+}
+
+/// Hand failed websocket handshakes back to the supervisor so they are retried
+fn report_connect_error(
+    stream: StreamSpec,
+    supervisor: Addr<StreamSupervisor>,
+) -> impl FnOnce(ws::ClientError) {
+    move |e| {
+        warn!(stream:% = stream.stream_name(); "Error connecting: {}", e);
+        supervisor.do_send(Disconnected(stream));
+    }
 }
 
 /////////////////////////////////////////////////////////////////
 /// Spawn new Actor scraper clients
 /////////////////////////////////////////////////////////////////
 
-pub fn spawn_book_depth_client(currency_pair: &CurrencyPair, depth_levels: Option<DepthLevels>) {
-    let ws_url = match &depth_levels {
-        None => binance_api_url(format!("{pair}@depth", pair = currency_pair)),
-        Some(lvl) => binance_api_url(format!(
-            "{pair}@depth{lvl}",
-            pair = currency_pair,
-            lvl = lvl
-        )),
+/// Open one raw market stream, its frames parsed and sunk by the stream type's handler, see `BinanceEvent`
+pub fn spawn_stream_client(
+    api_url: &str,
+    stream: StreamSpec,
+    supervisor: Addr<StreamSupervisor>,
+) {
+    // checked on Subscribe, see `StreamSpec::market_streams`
+    let handler = match handler::<StreamActor>(&stream.stream_name()) {
+        Ok(handler) => handler,
+        Err(e) => {
+            error!(stream:% = stream.stream_name(); "Not opening stream: {}", e);
+            return;
+        }
     };
-    println!("Endpoint: {}", ws_url);
-
-    actix::Arbiter::spawn(
-        ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
-        .connect() // Do websocket handshake -> ws::ClientHandshake
-        .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
-        .map(|(reader, writer): (ws::ClientReader, ws::ClientWriter)| {
-            // create an actor
-
-            let addr: actix::Addr<BookDepthActor> =
-                BookDepthActor::create(|ctx: &mut Context<BookDepthActor>| {
-                    BookDepthActor::add_stream(reader, ctx);
-                    BookDepthActor {
-                        client_writer: writer,
-                        depth_levels: depth_levels,
-                    }
-                });
-        }),
-    );
-}
-
-pub fn spawn_aggregate_trade_client(currency_pair: &CurrencyPair) {
-    let ws_url = binance_api_url(format!("{}@aggTrade", currency_pair));
-    println!("Endpoint: {}", ws_url);
+    let ws_url = stream.endpoint(api_url);
+    info!(stream:% = stream.stream_name(); "Endpoint: {}", ws_url);
 
     actix::Arbiter::spawn(
         ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
             .connect() // Do websocket handshake -> ws::ClientHandshake
-            .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
+            .map_err(report_connect_error(stream.clone(), supervisor.clone())) // requires use futures::{future, Future};
             .map(|(reader, writer): (ws::ClientReader, ws::ClientWriter)| {
                 // create an actor
-                let addr: actix::Addr<AggregateTradeActor> = AggregateTradeActor::create(|ctx| {
-                    AggregateTradeActor::add_stream(reader, ctx);
-                    AggregateTradeActor {
-                        client_writer: writer,
-                    }
-                });
-            }),
-    );
-}
-
-pub fn spawn_trade_client(currency_pair: &CurrencyPair) {
-    use crate::actors::trades::ClientCommand;
-
-    let ws_url = binance_api_url(format!("{}@trade", currency_pair));
-    println!("Endpoint: {}", ws_url);
-
-    actix::Arbiter::spawn(
-        ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
-            .connect() // Do websocket handshake -> ws::ClientHandshake
-            .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
-            .map(|stream| {
-                let (reader, writer): (ws::ClientReader, ws::ClientWriter) = stream;
-                // create an actor
-                let addr: actix::Addr<TradeActor> =
-                    TradeActor::create(|ctx: &mut Context<TradeActor>| {
-                        TradeActor::add_stream(reader, ctx);
-                        TradeActor {
+                let addr: actix::Addr<StreamActor> =
+                    StreamActor::create(|ctx: &mut Context<StreamActor>| {
+                        StreamActor::add_stream(reader, ctx);
+                        StreamActor {
                             client_writer: writer,
+                            last_seen: Instant::now(),
+                            handler: handler,
+                            stream_name: stream.stream_name(),
+                            stream: stream,
+                            supervisor: supervisor,
                         }
                     });
-
-                // spawn a new thread and console loop for new actor
-                std::thread::spawn(move || loop {
-                    let mut cmd = String::new();
-                    if std::io::stdin().read_line(&mut cmd).is_err() {
-                        println!("error");
-                        return;
-                    }
-                    addr.do_send(ClientCommand(cmd));
-                });
             }),
     );
 }
 
-pub fn spawn_kline_client(currency_pair: &CurrencyPair, interval: KlineInterval) {
-    let ws_url = binance_api_url(format!("{}@kline_{}", currency_pair, interval));
-    println!("Endpoint: {}", ws_url);
+/// Open a single socket for many streams, see `StreamSpec::Combined`
+pub fn spawn_combined_stream_client(
+    api_url: &str,
+    streams: Vec<StreamSpec>,
+    supervisor: Addr<StreamSupervisor>,
+) {
+    let stream = StreamSpec::Combined(streams);
+    let ws_url = stream.endpoint(api_url);
+    info!(stream:% = stream.stream_name(); "Endpoint: {}", ws_url);
 
     actix::Arbiter::spawn(
         ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
             .connect() // Do websocket handshake -> ws::ClientHandshake
-            .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
+            .map_err(report_connect_error(stream.clone(), supervisor.clone())) // requires use futures::{future, Future};
             .map(|(reader, writer): (ws::ClientReader, ws::ClientWriter)| {
                 // create an actor
-                let addr: actix::Addr<KlineActor> = KlineActor::create(|ctx| {
-                    KlineActor::add_stream(reader, ctx);
-                    KlineActor {
+                let addr: actix::Addr<CombinedStreamActor> = CombinedStreamActor::create(|ctx| {
+                    CombinedStreamActor::add_stream(reader, ctx);
+                    CombinedStreamActor {
                         client_writer: writer,
+                        last_seen: Instant::now(),
+                        stream: stream,
+                        supervisor: supervisor,
+                        request_id: 0,
+                        handlers: StreamHandlers::new(),
                     }
                 });
             }),
     );
 }
 
-pub fn spawn_mini_ticker_client(
-    currency_pair: &CurrencyPair,
-    all_markets: Option<MiniTickerQueryType>,
-) {
-    let ws_url = match &all_markets {
-        Some(MiniTickerQueryType::AllMarkets) => binance_api_url("!miniTicker@arr".to_string()),
-        Some(MiniTickerQueryType::SingleMarket) => binance_api_url(format!("{}@miniTicker", currency_pair)),
-        _ => binance_api_url(format!("{}@miniTicker", currency_pair)),
-    };
-    println!("Endpoint: {}", ws_url);
-
-    actix::Arbiter::spawn(
-        ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
-            .connect() // Do websocket handshake -> ws::ClientHandshake
-            .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
-            .map(|stream| {
-                let (reader, writer): (ws::ClientReader, ws::ClientWriter) = stream;
-                // create an actor
-                let addr: actix::Addr<MiniTickerActor> =
-                    MiniTickerActor::create(|ctx: &mut Context<MiniTickerActor>| {
-                        MiniTickerActor::add_stream(reader, ctx);
-                        MiniTickerActor {
-                            client_writer: writer,
-                            all_markets: all_markets,
-                        }
-                    });
-            }),
-    );
-}
-
-pub fn spawn_ticker_client(
-    currency_pair: &CurrencyPair,
+/// Create a listen key over REST, then open `ws/<listenKey>`.
+/// REST calls run on `rest`, started once by the supervisor.
+pub fn spawn_user_data_client(
+    api_url: &str,
+    stream: StreamSpec,
+    rest: Addr<UserDataRest>,
+    supervisor: Addr<StreamSupervisor>,
 ) {
-    let ws_url = binance_api_url(format!("{}@ticker", currency_pair));
-    println!("Endpoint: {}", ws_url);
-
-    actix::Arbiter::spawn(
-        ws::Client::new(ws_url) // Instantiate ws client  -> ws::Client
-            .connect() // Do websocket handshake -> ws::ClientHandshake
-            .map_err(|e| panic!("Error: {}", e)) // requires use futures::Future;
-            .map(|stream| {
-                let (reader, writer): (ws::ClientReader, ws::ClientWriter) = stream;
-                // create an actor
-                let addr: actix::Addr<TickerActor> =
-                    TickerActor::create(|ctx: &mut Context<TickerActor>| {
-                        TickerActor::add_stream(reader, ctx);
-                        TickerActor {
-                            client_writer: writer,
-                        }
-                    });
-            }),
-    );
+    let api_url = api_url.to_owned();
+    info!(stream:% = stream.stream_name(); "Endpoint: {}ws/<listenKey>", api_url);
+
+    actix::Arbiter::spawn(rest.send(CreateListenKey).then(move |res| {
+        let created = match res {
+            Ok(Ok(listen_key)) => Ok(listen_key),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let listen_key = match created {
+            Ok(listen_key) => listen_key,
+            Err(e) => {
+                error!(stream:% = stream.stream_name(); "Error creating listen key: {}", e);
+                supervisor.do_send(Disconnected(stream));
+                return future::Either::A(future::ok(()));
+            }
+        };
+        future::Either::B(
+            ws::Client::new(format!("{}ws/{}", api_url, listen_key)) // Instantiate ws client  -> ws::Client
+                .connect() // Do websocket handshake -> ws::ClientHandshake
+                .map_err(report_connect_error(stream.clone(), supervisor.clone())) // requires use futures::{future, Future};
+                .map(|(reader, writer): (ws::ClientReader, ws::ClientWriter)| {
+                    // create an actor
+                    let addr: actix::Addr<UserDataActor> =
+                        UserDataActor::create(|ctx: &mut Context<UserDataActor>| {
+                            UserDataActor::add_stream(reader, ctx);
+                            UserDataActor {
+                                client_writer: writer,
+                                last_seen: Instant::now(),
+                                stream: stream,
+                                supervisor: supervisor,
+                                rest: rest,
+                                listen_key: listen_key,
+                                state: AccountState::new(),
+                                pending: None,
+                            }
+                        });
+                }),
+        )
+    }));
 }
@@ -0,0 +1,716 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::*;
+
+use trading_sys::backoff::{Backoff, StreamCounters};
+use trading_sys::config::StreamKind;
+use trading_sys::symbols::Symbol;
+use trading_sys::db_writer::Row;
+use trading_sys::error::{Error, Result};
+use trading_sys::metrics::{PARSE_FAILURES, RECONNECTS, STREAM_FAILURES};
+use trading_sys::models::dead_letters::DeadLetterInsert;
+use trading_sys::models::combined_stream::SubscriptionMethod;
+use trading_sys::rest_client::BinanceRestClient;
+
+use crate::actors::combined_stream::{CombinedStreamActor, LiveSubscription};
+use crate::actors::db_writer::{DbBatcher, InsertRow};
+use crate::actors::health::{HealthMonitorActor, Unwatch, Watch};
+use crate::actors::user_data::UserDataRest;
+use crate::actors::stream::check_stream;
+use crate::spawn_clients::{spawn_combined_stream_client, spawn_stream_client, spawn_user_data_client};
+
+/// Everything needed to (re)open a websocket stream
+#[derive(Debug, Clone)]
+pub enum StreamSpec {
+    Market(String), // one market stream by Binance name, e.g: "ethbtc@kline_1m", see `stream::handler`
+    Combined(Vec<StreamSpec>), // many streams over one `/stream?streams=` socket
+    UserData(Arc<BinanceRestClient>), // a listen key is created on every (re)connect
+}
+
+impl StreamSpec {
+    /// Every configured stream type for every symbol, all-markets streams once.
+    /// Fails for stream types without a handler, so a typo is reported before anything connects,
+    /// and when there is nothing to subscribe to.
+    pub fn market_streams(kinds: &[StreamKind], symbols: &[Symbol]) -> Result<Vec<StreamSpec>> {
+        let mut streams = Vec::new();
+        for kind in kinds {
+            let names: Vec<String> = if kind.all_markets() {
+                vec![kind.to_string()]
+            } else {
+                symbols.iter().map(|symbol| kind.stream_name(symbol)).collect()
+            };
+            for name in names {
+                check_stream(&name)?;
+                streams.push(StreamSpec::Market(name));
+            }
+        }
+        if streams.is_empty() {
+            return Err(Error::Config("no market streams, set [collect] streams and symbols".to_owned()));
+        }
+        Ok(streams)
+    }
+
+    /// Binance stream name, e.g: "ethbtc@kline_1m"
+    pub fn stream_name(&self) -> String {
+        match self {
+            StreamSpec::Market(name) => name.clone(),
+            StreamSpec::Combined(_) => "combined".to_string(),
+            StreamSpec::UserData(_) => "userData".to_string(),
+        }
+    }
+
+    /// Raw streams live at `<api_url>ws/<streamName>`,
+    /// combined streams at `<api_url>stream?streams=<streamName1>/<streamName2>`,
+    /// the user data stream at `<api_url>ws/<listenKey>`, see `spawn_user_data_client`
+    pub fn endpoint(&self, api_url: &str) -> String {
+        match self {
+            StreamSpec::Combined(streams) => {
+                let names: Vec<String> = streams.iter().map(|s| s.stream_name()).collect();
+                format!("{}stream?streams={}", api_url, names.join("/"))
+            }
+            _ => format!("{}ws/{}", api_url, self.stream_name()),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////
+/// Supervisor which re-spawns stream clients after a disconnect
+/////////////////////////////////////////////////////////////////
+
+pub struct StreamSupervisor {
+    pub api_url: String,
+    pub backoff: Backoff, // template, cloned for every stream
+    pub stable_after: Duration, // connections lasting longer than this reset the backoff
+    pub reconnects: StreamCounters,
+    pub malformed: StreamCounters, // frames which failed to parse, per stream
+    pub failed: StreamCounters, // streams given up on after the retry ceiling, the rest keep running
+    backoffs: HashMap<String, Backoff>,
+    connected_at: HashMap<String, Instant>,
+    combined_streams: Vec<StreamSpec>, // current subscriptions on the combined socket
+    combined: Option<Addr<CombinedStreamActor>>,
+    combined_pending: bool, // combined socket is connecting or waiting to reconnect
+    user_data_rest: Option<Addr<UserDataRest>>, // started with the first user data stream, kept across reconnects
+}
+
+impl StreamSupervisor {
+    pub fn new(api_url: String, backoff: Backoff, reconnects: StreamCounters) -> Self {
+        StreamSupervisor {
+            api_url,
+            backoff,
+            stable_after: Duration::from_secs(30),
+            reconnects,
+            malformed: StreamCounters::new(),
+            failed: StreamCounters::new(),
+            backoffs: HashMap::new(),
+            connected_at: HashMap::new(),
+            combined_streams: Vec::new(),
+            combined: None,
+            combined_pending: false,
+            user_data_rest: None,
+        }
+    }
+
+    fn spawn_stream(&mut self, stream: StreamSpec, supervisor: Addr<StreamSupervisor>) {
+        let api_url = &self.api_url;
+        match stream {
+            StreamSpec::Market(_) => spawn_stream_client(api_url, stream, supervisor),
+            StreamSpec::Combined(streams) => spawn_combined_stream_client(api_url, streams, supervisor),
+            StreamSpec::UserData(ref client) => {
+                // one account per collector, its REST thread is reused by every reconnect
+                let rest = self.user_data_rest.get_or_insert_with(|| {
+                    let client = client.clone();
+                    SyncArbiter::start(1, move || UserDataRest { rest: client.clone() })
+                });
+                spawn_user_data_client(api_url, stream.clone(), rest.clone(), supervisor)
+            }
+        }
+    }
+
+    fn is_combined(&self, stream: &StreamSpec) -> bool {
+        let name = stream.stream_name();
+        self.combined_streams.iter().any(|s| s.stream_name() == name)
+    }
+
+    fn subscribe_combined(&mut self, streams: Vec<StreamSpec>, ctx: &mut Context<Self>) {
+        let mut added: Vec<StreamSpec> = Vec::new();
+        for stream in streams.into_iter().filter(supported) {
+            if !self.is_combined(&stream) && !added.iter().any(|s| s.stream_name() == stream.stream_name()) {
+                added.push(stream);
+            }
+        }
+        if added.is_empty() {
+            return;
+        }
+        self.combined_streams.extend(added.clone());
+        watch(&added);
+
+        match &self.combined {
+            Some(addr) => addr.do_send(LiveSubscription {
+                method: SubscriptionMethod::SUBSCRIBE,
+                streams: added,
+            }),
+            // picks up the current subscriptions once connected, see `CombinedConnected`
+            None if self.combined_pending => (),
+            None => {
+                self.combined_pending = true;
+                self.spawn_stream(StreamSpec::Combined(self.combined_streams.clone()), ctx.address());
+            }
+        }
+    }
+
+    fn unsubscribe_combined(&mut self, streams: Vec<StreamSpec>) {
+        let names: Vec<String> = streams.iter().map(|s| s.stream_name()).collect();
+        let (removed, kept): (Vec<StreamSpec>, Vec<StreamSpec>) = self
+            .combined_streams
+            .drain(..)
+            .partition(|s| names.contains(&s.stream_name()));
+        self.combined_streams = kept;
+        let removed_names: Vec<String> = removed.iter().map(|s| s.stream_name()).collect();
+        HealthMonitorActor::from_registry().do_send(Unwatch(removed_names));
+
+        // a socket left without streams stays open for later ones, but isn't reopened, see `Disconnected`
+        if let Some(addr) = &self.combined {
+            if !removed.is_empty() {
+                addr.do_send(LiveSubscription {
+                    method: SubscriptionMethod::UNSUBSCRIBE,
+                    streams: removed,
+                });
+            }
+        }
+    }
+}
+
+/// Market streams need a handler for their type, see `stream::handler`
+fn supported(stream: &StreamSpec) -> bool {
+    match stream {
+        StreamSpec::Market(name) => match check_stream(name) {
+            Ok(()) => true,
+            Err(e) => {
+                error!(stream:% = name; "Not subscribing: {}", e);
+                false
+            }
+        },
+        _ => true,
+    }
+}
+
+/// Start the staleness clock for market streams, the user data stream is quiet without orders
+fn watch(streams: &[StreamSpec]) {
+    let names: Vec<String> = streams
+        .iter()
+        .filter(|s| match s {
+            StreamSpec::UserData(_) => false,
+            _ => true,
+        })
+        .map(|s| s.stream_name())
+        .collect();
+    HealthMonitorActor::from_registry().do_send(Watch(names));
+}
+
+impl Actor for StreamSupervisor {
+    type Context = Context<Self>;
+}
+
+/// Open a new supervised stream on its own socket.
+/// `StreamSpec::Combined` streams are added to the shared combined socket instead.
+#[derive(Message)]
+pub struct Subscribe(pub StreamSpec);
+
+/// Add streams to the combined socket, over the live connection if there is one
+#[derive(Message)]
+pub struct SubscribeCombined(pub Vec<StreamSpec>);
+
+/// Remove streams from the combined socket
+#[derive(Message)]
+pub struct UnsubscribeCombined(pub Vec<StreamSpec>);
+
+/// Sent by the combined stream actor once connected, with the streams it was opened with
+#[derive(Message)]
+pub struct CombinedConnected(pub Addr<CombinedStreamActor>, pub StreamSpec);
+
+/// Sent by a stream actor once its websocket is connected
+#[derive(Message)]
+pub struct Connected(pub StreamSpec);
+
+/// Sent by a stream actor when it stops, or when the handshake fails
+#[derive(Message)]
+pub struct Disconnected(pub StreamSpec);
+
+/// Sent by a stream actor for a frame which failed to parse. The frame is
+/// counted and written to the `dead_letters` table, the stream keeps running.
+#[derive(Message)]
+pub struct Malformed {
+    pub stream: String, // stream name, e.g: "ethbtc@trade"
+    pub payload: String,
+    pub error: Error,
+}
+
+impl Malformed {
+    pub fn new<E: Into<Error>>(stream: &StreamSpec, payload: String, error: E) -> Self {
+        Malformed {
+            stream: stream.stream_name(),
+            payload,
+            error: error.into(),
+        }
+    }
+}
+
+impl Handler<Subscribe> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, ctx: &mut Context<Self>) {
+        match msg.0 {
+            StreamSpec::Combined(streams) => self.subscribe_combined(streams, ctx),
+            stream if !supported(&stream) => (),
+            stream => {
+                watch(std::slice::from_ref(&stream));
+                self.spawn_stream(stream, ctx.address())
+            }
+        }
+    }
+}
+
+impl Handler<SubscribeCombined> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeCombined, ctx: &mut Context<Self>) {
+        self.subscribe_combined(msg.0, ctx);
+    }
+}
+
+impl Handler<UnsubscribeCombined> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeCombined, _ctx: &mut Context<Self>) {
+        self.unsubscribe_combined(msg.0);
+    }
+}
+
+impl Handler<CombinedConnected> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: CombinedConnected, _ctx: &mut Context<Self>) {
+        let CombinedConnected(addr, opened_with) = msg;
+        let opened_with = match opened_with {
+            StreamSpec::Combined(streams) => streams,
+            _ => Vec::new(),
+        };
+        // subscriptions may have changed while the socket was connecting
+        let opened_names: Vec<String> = opened_with.iter().map(|s| s.stream_name()).collect();
+        let added: Vec<StreamSpec> = self
+            .combined_streams
+            .iter()
+            .filter(|s| !opened_names.contains(&s.stream_name()))
+            .cloned()
+            .collect();
+        let removed: Vec<StreamSpec> = opened_with
+            .into_iter()
+            .filter(|s| !self.is_combined(s))
+            .collect();
+        if !added.is_empty() {
+            addr.do_send(LiveSubscription { method: SubscriptionMethod::SUBSCRIBE, streams: added });
+        }
+        if !removed.is_empty() {
+            addr.do_send(LiveSubscription { method: SubscriptionMethod::UNSUBSCRIBE, streams: removed });
+        }
+        self.combined = Some(addr);
+        self.combined_pending = false;
+    }
+}
+
+impl Handler<Connected> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connected, _ctx: &mut Context<Self>) {
+        self.connected_at.insert(msg.0.stream_name(), Instant::now());
+    }
+}
+
+impl Handler<Malformed> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Malformed, _ctx: &mut Context<Self>) {
+        let count = self.malformed.increment(&msg.stream);
+        PARSE_FAILURES.with_label_values(&[&msg.stream]).inc();
+        warn!(stream:% = msg.stream; "Malformed frame #{}: {}", count, msg.error);
+        debug!(stream:% = msg.stream; "Malformed payload: {}", msg.payload);
+        let dead_letter = DeadLetterInsert::new(&msg.stream, &msg.payload, &msg.error);
+        DbBatcher::from_registry().do_send(InsertRow(Row::DeadLetter(dead_letter)));
+    }
+}
+
+impl Handler<Disconnected> for StreamSupervisor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnected, ctx: &mut Context<Self>) {
+        let stream = msg.0;
+        let stream_name = stream.stream_name();
+        if let StreamSpec::Combined(_) = stream {
+            self.combined = None;
+            self.combined_pending = !self.combined_streams.is_empty();
+            if !self.combined_pending {
+                // the next SubscribeCombined opens a fresh socket
+                info!(stream:% = stream_name; "Closed, no streams left");
+                self.backoffs.remove(&stream_name);
+                self.connected_at.remove(&stream_name);
+                return;
+            }
+        }
+        let template = self.backoff.clone();
+        let backoff = self.backoffs.entry(stream_name.clone()).or_insert(template);
+
+        // Only a connection which stayed up for a while counts as a recovery,
+        // otherwise a server which accepts then immediately drops us would never hit the ceiling.
+        if let Some(connected_at) = self.connected_at.remove(&stream_name) {
+            if connected_at.elapsed() >= self.stable_after {
+                backoff.reset();
+            }
+        }
+
+        match backoff.next_delay() {
+            Some(delay) => {
+                let count = self.reconnects.increment(&stream_name);
+                RECONNECTS.with_label_values(&[&stream_name]).inc();
+                warn!(stream:% = stream_name; "Disconnected, reconnect #{} in {:?}", count, delay);
+                ctx.run_later(delay, move |act, ctx| {
+                    // reopen the combined socket with its subscriptions as of now, if any are left
+                    let stream = match stream {
+                        StreamSpec::Combined(_) if act.combined_streams.is_empty() => {
+                            act.combined_pending = false;
+                            return;
+                        }
+                        StreamSpec::Combined(_) => StreamSpec::Combined(act.combined_streams.clone()),
+                        stream => stream,
+                    };
+                    act.spawn_stream(stream, ctx.address());
+                });
+            }
+            None => {
+                // Only this stream is dropped, a later Subscribe starts it over with a fresh backoff
+                error!(stream:% = stream_name; "Exceeded {} reconnect attempts, giving up on this stream", backoff.attempts());
+                self.backoffs.remove(&stream_name);
+                self.failed.increment(&stream_name);
+                STREAM_FAILURES.with_label_values(&[&stream_name]).inc();
+                let names = match stream {
+                    StreamSpec::Combined(_) => {
+                        self.combined_pending = false;
+                        self.combined_streams.drain(..).map(|s| s.stream_name()).collect()
+                    }
+                    stream => vec![stream.stream_name()],
+                };
+                HealthMonitorActor::from_registry().do_send(Unwatch(names));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{server, ws, App, HttpRequest};
+    use futures::Future;
+    use std::sync::mpsc;
+
+    fn market(name: &str) -> StreamSpec {
+        StreamSpec::Market(name.to_owned())
+    }
+
+    #[test]
+    fn market_streams_from_config() {
+        let kinds: Vec<StreamKind> = ["trade", "kline_1m", "depth20@100ms", "!ticker@arr"]
+            .iter()
+            .map(|kind| kind.parse().unwrap())
+            .collect();
+        let symbols = vec![Symbol::new("ETHBTC"), Symbol::new("BNBBTC")];
+        let names: Vec<String> = StreamSpec::market_streams(&kinds, &symbols)
+            .unwrap()
+            .iter()
+            .map(|stream| stream.stream_name())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "ethbtc@trade", "bnbbtc@trade", "ethbtc@kline_1m", "bnbbtc@kline_1m",
+                "ethbtc@depth20@100ms", "bnbbtc@depth20@100ms", "!ticker@arr",
+            ]
+        );
+        assert_eq!(market("ethbtc@trade").endpoint("wss://host/"), "wss://host/ws/ethbtc@trade");
+
+        // stream types without a handler
+        for kind in ["trades", "depth10@10ms", "bookTicker"].iter() {
+            assert!(StreamSpec::market_streams(&[kind.parse().unwrap()], &symbols).is_err());
+        }
+
+        // nothing to subscribe to, `stream?streams=` would be refused forever
+        assert!(StreamSpec::market_streams(&kinds[..1], &[]).is_err());
+        assert!(StreamSpec::market_streams(&[], &symbols).is_err());
+    }
+
+    /// Longest a test waits on a stand-in before giving up
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Poll `done` until it holds or `TIMEOUT` passes, for state no stand-in can report
+    fn wait_until<F: Fn() -> bool>(done: F) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+        while !done() {
+            if Instant::now() > deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    /// Whether the supervisor holds a combined socket, open or about to be
+    struct HasCombinedSocket;
+
+    impl Message for HasCombinedSocket {
+        type Result = bool;
+    }
+
+    impl Handler<HasCombinedSocket> for StreamSupervisor {
+        type Result = bool;
+
+        fn handle(&mut self, _msg: HasCombinedSocket, _ctx: &mut Context<Self>) -> bool {
+            self.combined.is_some() || self.combined_pending
+        }
+    }
+
+    /// Local stand-in for the Binance websocket API which drops every connection after `hold`
+    struct DroppingServer {
+        hold: Duration,
+    }
+
+    impl Actor for DroppingServer {
+        type Context = ws::WebsocketContext<Self>;
+
+        fn started(&mut self, ctx: &mut Self::Context) {
+            ctx.run_later(self.hold, |_act, ctx| ctx.stop());
+        }
+    }
+
+    impl StreamHandler<ws::Message, ws::ProtocolError> for DroppingServer {
+        fn handle(&mut self, _msg: ws::Message, _ctx: &mut Self::Context) {}
+    }
+
+    #[test]
+    fn reconnects_until_retry_ceiling() {
+        let sys = System::new("test-reconnect");
+        let (connections, connected) = mpsc::channel::<String>();
+
+        let srv = server::new(move || {
+            let connections = connections.clone();
+            App::new().default_resource(move |r| {
+                r.f(move |req: &HttpRequest| {
+                    let _ = connections.send(req.uri().to_string());
+                    ws::start(req, DroppingServer { hold: Duration::from_millis(20) })
+                })
+            })
+        })
+        .shutdown_timeout(0)
+        .bind("127.0.0.1:0")
+        .expect("Could not bind websocket stand-in");
+        let addr = srv.addrs()[0];
+        srv.start();
+
+        let reconnects = StreamCounters::new();
+        let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(40), Some(3));
+        let supervisor = StreamSupervisor::new(format!("ws://{}/", addr), backoff, reconnects.clone());
+        let failed = supervisor.failed.clone();
+        let supervisor = supervisor.start();
+        supervisor.do_send(Subscribe(market("ethbtc@trade")));
+
+        // The system keeps running after a stream is given up on, stop once it has been
+        let system = System::current();
+        let given_up = failed.clone();
+        let waiter = std::thread::spawn(move || {
+            // the first connection and 3 reconnects
+            let opened = (0..4).take_while(|_| connected.recv_timeout(TIMEOUT).is_ok()).count();
+            wait_until(|| given_up.get("ethbtc@trade") > 0);
+            system.stop();
+            opened + connected.try_iter().count()
+        });
+
+        let _ = sys.run();
+        assert_eq!(waiter.join().unwrap(), 4);
+        assert_eq!(reconnects.get("ethbtc@trade"), 3);
+        assert_eq!(failed.get("ethbtc@trade"), 1);
+    }
+
+    /// Local stand-in which reports every text frame sent by the client,
+    /// dropping the connection after the first one if `drop_on_text`
+    struct RecordingServer {
+        received: mpsc::Sender<String>,
+        drop_on_text: bool,
+    }
+
+    impl Actor for RecordingServer {
+        type Context = ws::WebsocketContext<Self>;
+    }
+
+    impl StreamHandler<ws::Message, ws::ProtocolError> for RecordingServer {
+        fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+            if let ws::Message::Text(txt) = msg {
+                let _ = self.received.send(txt);
+                if self.drop_on_text {
+                    ctx.stop();
+                }
+            }
+        }
+    }
+
+    /// Start a `RecordingServer`, returns its address with the channels of requested paths and received frames
+    fn recording_server(drop_on_text: bool) -> (std::net::SocketAddr, mpsc::Receiver<String>, mpsc::Receiver<String>) {
+        let (paths, requested) = mpsc::channel::<String>();
+        let (frames, received) = mpsc::channel::<String>();
+        let srv = server::new(move || {
+            let (paths, frames) = (paths.clone(), frames.clone());
+            App::new().default_resource(move |r| {
+                r.f(move |req: &HttpRequest| {
+                    let _ = paths.send(req.uri().to_string());
+                    ws::start(req, RecordingServer { received: frames.clone(), drop_on_text })
+                })
+            })
+        })
+        .shutdown_timeout(0)
+        .bind("127.0.0.1:0")
+        .expect("Could not bind websocket stand-in");
+        let addr = srv.addrs()[0];
+        srv.start();
+        (addr, requested, received)
+    }
+
+    #[test]
+    fn live_subscribe_over_combined_socket() {
+        let sys = System::new("test-combined");
+        let (addr, requested, received) = recording_server(false);
+
+        let supervisor =
+            StreamSupervisor::new(format!("ws://{}/", addr), Backoff::default(), StreamCounters::new()).start();
+        supervisor.do_send(SubscribeCombined(vec![market("ethbtc@trade")]));
+        supervisor.do_send(SubscribeCombined(vec![
+            market("ethbtc@trade"), // already subscribed
+            market("ethbtc@ticker"),
+        ]));
+
+        let system = System::current();
+        let waiter = std::thread::spawn(move || {
+            let frames: Vec<String> = received.recv_timeout(TIMEOUT).into_iter().collect();
+            system.stop();
+            (requested.try_iter().collect::<Vec<_>>(), frames)
+        });
+
+        let _ = sys.run();
+        let (paths, frames) = waiter.join().unwrap();
+        assert_eq!(paths, vec!["/stream?streams=ethbtc@trade".to_string()]);
+        assert_eq!(frames, vec![r#"{"method":"SUBSCRIBE","params":["ethbtc@ticker"],"id":1}"#.to_string()]);
+    }
+
+    #[test]
+    fn combined_socket_not_reopened_without_streams() {
+        let sys = System::new("test-combined-empty");
+        let (addr, requested, received) = recording_server(true);
+
+        let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(40), None);
+        let reconnects = StreamCounters::new();
+        let supervisor = StreamSupervisor::new(format!("ws://{}/", addr), backoff, reconnects.clone()).start();
+        supervisor.do_send(SubscribeCombined(vec![market("ethbtc@trade")]));
+
+        let system = System::current();
+        let waiter = std::thread::spawn(move || {
+            let mut paths: Vec<String> = requested.recv_timeout(TIMEOUT).into_iter().collect();
+            // the server drops the socket on the UNSUBSCRIBE of its only stream
+            supervisor.do_send(UnsubscribeCombined(vec![market("ethbtc@trade")]));
+            let frames: Vec<String> = received.recv_timeout(TIMEOUT).into_iter().collect();
+            // the drop shows up on the next heartbeat, the socket isn't reopened
+            let closed = wait_until(|| !supervisor.send(HasCombinedSocket).wait().unwrap_or(true));
+            // a fresh socket for new streams
+            supervisor.do_send(SubscribeCombined(vec![market("ethbtc@ticker")]));
+            paths.extend(requested.recv_timeout(TIMEOUT));
+            system.stop();
+            (closed, paths, frames)
+        });
+
+        let _ = sys.run();
+        let (closed, paths, frames) = waiter.join().unwrap();
+        assert!(closed);
+        assert_eq!(
+            paths,
+            vec!["/stream?streams=ethbtc@trade".to_string(), "/stream?streams=ethbtc@ticker".to_string()]
+        );
+        assert_eq!(frames, vec![r#"{"method":"UNSUBSCRIBE","params":["ethbtc@trade"],"id":1}"#.to_string()]);
+        assert_eq!(reconnects.get("combined"), 0);
+    }
+
+    /// Local stand-in which sends one unparseable trade frame, then a ping whose pong it reports
+    struct GarbageServer {
+        pongs: mpsc::Sender<String>,
+    }
+
+    impl Actor for GarbageServer {
+        type Context = ws::WebsocketContext<Self>;
+
+        fn started(&mut self, ctx: &mut Self::Context) {
+            ctx.text(r#"{"e":"trade","p":"not a price"}"#);
+            ctx.ping("after garbage");
+        }
+    }
+
+    impl StreamHandler<ws::Message, ws::ProtocolError> for GarbageServer {
+        fn handle(&mut self, msg: ws::Message, _ctx: &mut Self::Context) {
+            if let ws::Message::Pong(pong) = msg {
+                let _ = self.pongs.send(pong);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_frames_are_counted_not_fatal() {
+        use diesel::r2d2::{ConnectionManager, Pool};
+
+        let sys = System::new("test-malformed");
+        let (pongs, ponged) = mpsc::channel::<String>();
+
+        let srv = server::new(move || {
+            let pongs = pongs.clone();
+            App::new().default_resource(move |r| {
+                r.f(move |req: &HttpRequest| ws::start(req, GarbageServer { pongs: pongs.clone() }))
+            })
+        })
+        .shutdown_timeout(0)
+        .bind("127.0.0.1:0")
+        .expect("Could not bind websocket stand-in");
+        let addr = srv.addrs()[0];
+        srv.start();
+
+        // dead letters are dropped, there is no database behind this pool
+        let pool = Pool::builder()
+            .connection_timeout(Duration::from_millis(100))
+            .build_unchecked(ConnectionManager::new("postgres://127.0.0.1:1/unreachable"));
+        let batcher = DbBatcher::with_pool(pool, Default::default()).start();
+        System::current().registry().set(batcher);
+
+        let reconnects = StreamCounters::new();
+        let supervisor = StreamSupervisor::new(format!("ws://{}/", addr), Backoff::default(), reconnects.clone());
+        let malformed = supervisor.malformed.clone();
+        let supervisor = supervisor.start();
+        supervisor.do_send(Subscribe(market("ethbtc@trade")));
+
+        let system = System::current();
+        let counted = malformed.clone();
+        let waiter = std::thread::spawn(move || {
+            // frames are handled in order, so a pong means the stream outlived the garbage
+            let pong = ponged.recv_timeout(TIMEOUT).ok();
+            wait_until(|| counted.get("ethbtc@trade") > 0);
+            system.stop();
+            pong
+        });
+
+        let _ = sys.run();
+        assert_eq!(waiter.join().unwrap(), Some("after garbage".to_string()));
+        assert_eq!(malformed.get("ethbtc@trade"), 1);
+        assert_eq!(reconnects.get("ethbtc@trade"), 0);
+    }
+}
@@ -32,7 +32,7 @@ pub fn build_starts_and_limits(n: i32) -> (Vec<i32>, Vec<i32>) {
 pub fn get_coinslist(start: i32, limit: i32) -> Vec<CmcCoinMetadata> {
     let url = format!("https://api.coinmarketcap.com/v1/ticker/?start={}&limit={}", start, limit);
     // Maximum number of entries returned in one request is limit: 100
-    println!("Requesting: {:?}", &url);
+    info!("Requesting: {:?}", &url);
     let parsed_url = reqwest::Url::parse(&url).expect("Bad url format.");
     let mut response = reqwest::get(parsed_url).expect("Failed to get Url");
     let bodyjson = response.json::<Vec<CmcCoinMetadata>>().unwrap();
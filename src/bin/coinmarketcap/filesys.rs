@@ -1,7 +1,7 @@
 
 pub fn create_data_directory() {
     let date = chrono::Utc::now().format("%Y-%m-%d");
-    println!("Creating directory: {}", &date);
+    info!("Creating directory: {}", &date);
     let _ = std::fs::create_dir(std::path::Path::new(&format!("./data/coinmarketcap/{}", &date)));
 }
 
@@ -9,7 +9,7 @@ pub fn create_data_directory() {
 pub fn check_if_filepath_exists(filename: &str) -> bool {
     match std::fs::File::open(&filename) {
         Ok(_) => {
-            println!("File exists, skipping.");
+            debug!("File exists, skipping: {}", filename);
             true
         },
         Err(_) => false,
@@ -1,6 +1,8 @@
 extern crate chrono;
 extern crate clap;
 #[macro_use]
+extern crate log;
+#[macro_use]
 extern crate serde_derive;
 extern crate serde;
 #[macro_use]
@@ -11,6 +13,8 @@ use regex::Regex;
 use clap::{ Arg, App };
 use std::sync::Arc;
 
+use trading_sys::logging::{init_logger, LogFormat, DEFAULT_LOG_FILTER};
+
 pub mod coinlist;
 use coinlist::build_coinlist;
 
@@ -27,6 +31,7 @@ use filesys::{
 
 pub fn main() {
 
+    init_logger(DEFAULT_LOG_FILTER, LogFormat::Text);
     let matches = parse_args();
     let (_currency, _coinlist, _start_date, _end_date) = parse_options(matches);
     create_data_directory();
@@ -60,7 +65,7 @@ pub fn main() {
             true => continue,
             false => {
                 queue.push(std::thread::spawn(move || {
-                    println!("Requesting: {:?} - {:?}", &rank, &id);
+                    info!("Requesting: {:?} - {:?}", &rank, &id);
                     let csv_data = download_data(&id, start_date, end_date);
                     let _ = std::fs::write(&fp, csv_data.join("\n"));
                 }))
@@ -73,7 +78,7 @@ pub fn main() {
         std::thread::sleep(std::time::Duration::from_millis(250));
     };
 
-    println!("Scraping complete.");
+    info!("Scraping complete.");
 }
 
 
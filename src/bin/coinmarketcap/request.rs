@@ -13,7 +13,7 @@ pub fn download_data(coin: &str, start_date: Arc<String>, end_date: Arc<String>)
 fn dispatch_request(url: &str) -> String {
     let parsed_url = reqwest::Url::parse(url).expect("Bad url format.");
     let mut response = reqwest::get(parsed_url).expect("Failed to get Url");
-    println!("Response from url: {}\n\t{}", url, response.status().to_string());
+    debug!("Response from url: {} {}", url, response.status());
     match response.text() {
         Ok(html) => html,
         Err(e) => panic!("Request Error: {:?}", e),
@@ -13,11 +13,8 @@ extern crate reqwest;
 extern crate data_encoding;
 extern crate ring;
 
-extern crate redis;
-
 extern crate trading_sys;
 
-use ring::{digest, hmac, rand};
 use std::fmt;
 
 fn main() -> std::io::Result<()> {
@@ -50,53 +47,14 @@ fn main() -> std::io::Result<()> {
     let cc = chrono::NaiveDateTime::from_timestamp(1_000_000_000, 0 as u32);
     println!("{:?}", cc);
 
-    //
-    // std::thread::spawn(|| {
-    //     std::process::Command::new("redis-server")
-    //         .output()
-    //         .expect("redis-server error!");
-    // });
-    // let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    // let conn = client.get_connection().unwrap();
-    // redis_get(&conn);
-
-    Ok(())
-}
-
-fn redis_get(conn: &redis::Connection) -> redis::RedisResult<()> {
-    let _: () = redis::cmd("SET").arg("my_key").arg(42).query(conn).unwrap();
-    let user2: String = redis::cmd("GET").arg("user2").query(conn).unwrap();
-    let user3: String = redis::cmd("GET").arg("user3").query(conn).unwrap();
-    println!("Redis: user2: {}", user2);
-    println!("Redis: user3: {}", user3);
     Ok(())
 }
 
 pub fn sign_query(url: &str, query_string: &str) -> String {
     let secret_key = std::env::var("BINANCE_SECRET_KEY")
         .expect("No <BINANCE_SECRET_KEY> environment variable set.");
-    let signing_key = hmac::SigningKey::new(&digest::SHA256, secret_key.as_bytes());
-    let signature = hmac::sign(&signing_key, query_string.as_bytes());
-    println!("signature:  {:?}", signature);
-    // println!("Sha256hash: Signature(SHA256:{})", HexDigest(signature));
-    let url_full = format!(
-        "{}?{}&signature={}",
-        url,
-        query_string,
-        HexDigest(signature)
-    );
-    url_full
-}
-
-#[derive(Debug)]
-pub struct HexDigest(ring::hmac::Signature);
-
-impl fmt::Display for HexDigest {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let sig_as_ref: &[u8] = self.0.as_ref();
-        let hex_digest = data_encoding::HEXLOWER.encode(sig_as_ref);
-        write!(f, "{}", hex_digest)
-    }
+    let signature = trading_sys::rest_client::sign_query(&secret_key, query_string);
+    format!("{}?{}&signature={}", url, query_string, signature)
 }
 
 impl fmt::Display for DepositHistoryResponse {
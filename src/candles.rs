@@ -0,0 +1,431 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use num::Zero;
+
+use crate::db_writer::Row;
+use crate::error::Error;
+use crate::models::aggregate_trades::AggregateTradeData;
+use crate::models::klines::{KlineDataInsert, KlineInterval};
+use crate::models::trades::TradeData;
+use crate::symbols::Symbol;
+
+/////////////////////////////////////////////////////////////////
+/// Candles: OHLCV bars rolled up from raw trades, built as `KlineDataInsert`
+/// rows. Time bars share the `klines` table, tick, volume and dollar
+/// bars go to `bars`, see `candle_row`.
+/////////////////////////////////////////////////////////////////
+
+/// How trades are grouped into bars
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarSpec {
+    Time(KlineInterval), // one bar per interval, aligned like Binance's klines
+    Tick(i32),           // closes after this many trades
+    Volume(BigDecimal),  // closes once base volume reaches this
+    Dollar(BigDecimal),  // closes once quote volume (price * quantity) reaches this
+}
+
+/// Stored in the `interval` column, e.g: 1m, tick:100, volume:50, dollar:1000
+impl fmt::Display for BarSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BarSpec::Time(interval) => write!(f, "{}", interval),
+            BarSpec::Tick(n) => write!(f, "tick:{}", n),
+            BarSpec::Volume(v) => write!(f, "volume:{}", v),
+            BarSpec::Dollar(d) => write!(f, "dollar:{}", d),
+        }
+    }
+}
+
+impl FromStr for BarSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<BarSpec, Error> {
+        let invalid = || Error::InvalidEnum("BarSpec", s.to_string());
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("tick"), Some(n)) => n.parse().map(BarSpec::Tick).map_err(|_| invalid()),
+            (Some("volume"), Some(v)) => v.parse().map(BarSpec::Volume).map_err(|_| invalid()),
+            (Some("dollar"), Some(d)) => d.parse().map(BarSpec::Dollar).map_err(|_| invalid()),
+            (Some(interval), None) => interval.parse().map(BarSpec::Time),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// A trade from either the `trades` or `aggregate_trades` stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tick {
+    pub symbol: Symbol,
+    pub first_trade_id: i64,
+    pub last_trade_id: i64,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub trade_time: NaiveDateTime,
+    pub buyer_mkt_maker: bool, // seller was the taker, so not taker buy volume
+}
+
+impl<'a> From<&'a TradeData> for Tick {
+    fn from(trade: &'a TradeData) -> Self {
+        Tick {
+            symbol: trade.symbol.clone(),
+            first_trade_id: trade.trade_id,
+            last_trade_id: trade.trade_id,
+            price: trade.price.clone(),
+            quantity: trade.quantity.clone(),
+            trade_time: trade.trade_time,
+            buyer_mkt_maker: trade.buyer_mkt_maker,
+        }
+    }
+}
+
+impl<'a> From<&'a AggregateTradeData> for Tick {
+    fn from(trade: &'a AggregateTradeData) -> Self {
+        Tick {
+            symbol: trade.symbol.clone(),
+            first_trade_id: trade.first_trade_id,
+            last_trade_id: trade.last_trade_id,
+            price: trade.price.clone(),
+            quantity: trade.quantity.clone(),
+            trade_time: trade.trade_time,
+            buyer_mkt_maker: trade.buyer_mkt_maker,
+        }
+    }
+}
+
+/// Bar being built, turned into a kline row once it closes
+#[derive(Debug, Clone, PartialEq)]
+struct Bar {
+    start_time: NaiveDateTime,
+    close_time: NaiveDateTime,
+    last_tick_time: NaiveDateTime,
+    first_trade_id: i64,
+    last_trade_id: i64,
+    open: BigDecimal,
+    high: BigDecimal,
+    low: BigDecimal,
+    close: BigDecimal,
+    volume: BigDecimal,
+    num_of_trades: i32,
+    quote_asset_vol: BigDecimal,
+    taker_buy_base_vol: BigDecimal,
+    taker_buy_quote_vol: BigDecimal,
+}
+
+impl Bar {
+    fn new(tick: &Tick, start_time: NaiveDateTime, close_time: NaiveDateTime) -> Self {
+        let zero = BigDecimal::zero();
+        Bar {
+            start_time,
+            close_time,
+            last_tick_time: tick.trade_time,
+            first_trade_id: tick.first_trade_id,
+            last_trade_id: tick.last_trade_id,
+            open: tick.price.clone(),
+            high: tick.price.clone(),
+            low: tick.price.clone(),
+            close: tick.price.clone(),
+            volume: zero.clone(),
+            num_of_trades: 0,
+            quote_asset_vol: zero.clone(),
+            taker_buy_base_vol: zero.clone(),
+            taker_buy_quote_vol: zero,
+        }
+    }
+
+    fn add(&mut self, tick: &Tick) {
+        let quote = &tick.price * &tick.quantity;
+        if tick.price > self.high {
+            self.high = tick.price.clone();
+        }
+        if tick.price < self.low {
+            self.low = tick.price.clone();
+        }
+        self.close = tick.price.clone();
+        self.last_tick_time = tick.trade_time;
+        if tick.trade_time > self.close_time {
+            self.close_time = tick.trade_time; // threshold bars end at their last trade
+        }
+        self.last_trade_id = tick.last_trade_id;
+        self.num_of_trades += (tick.last_trade_id - tick.first_trade_id + 1) as i32;
+        self.volume += &tick.quantity;
+        if !tick.buyer_mkt_maker {
+            self.taker_buy_base_vol += &tick.quantity;
+            self.taker_buy_quote_vol += &quote;
+        }
+        self.quote_asset_vol += quote;
+    }
+
+    fn into_kline(self, symbol: &Symbol, spec: &BarSpec, is_kline_closed: bool) -> KlineDataInsert {
+        KlineDataInsert {
+            event: "candle".to_owned(),
+            event_time: self.last_tick_time,
+            start_time: self.start_time,
+            close_time: self.close_time,
+            symbol: symbol.clone(),
+            interval: spec.to_string(),
+            first_trade_id: self.first_trade_id,
+            last_trade_id: self.last_trade_id,
+            open: self.open,
+            close: self.close,
+            high: self.high,
+            low: self.low,
+            volume: self.volume,
+            num_of_trades: self.num_of_trades,
+            is_kline_closed,
+            quote_asset_vol: self.quote_asset_vol,
+            taker_buy_base_vol: self.taker_buy_base_vol,
+            taker_buy_quote_vol: self.taker_buy_quote_vol,
+        }
+    }
+}
+
+/// Builds bars for one symbol from ticks in trade time order.
+/// Live: `push` each trade and `close_expired` on a timer. Batch: see `build_candles`.
+#[derive(Debug, Clone)]
+pub struct CandleBuilder {
+    pub symbol: Symbol,
+    pub spec: BarSpec,
+    bar: Option<Bar>,
+}
+
+impl CandleBuilder {
+    pub fn new(symbol: Symbol, spec: BarSpec) -> Self {
+        CandleBuilder { symbol, spec, bar: None }
+    }
+
+    /// Add a trade, returns the bar it closed, if any.
+    /// Time bars close on the first trade past their interval, empty intervals have no bar.
+    pub fn push(&mut self, tick: &Tick) -> Option<KlineDataInsert> {
+        let mut closed = None;
+
+        if let (BarSpec::Time(_), Some(bar)) = (&self.spec, &self.bar) {
+            if tick.trade_time > bar.close_time {
+                closed = self.take_closed();
+            }
+        }
+
+        let spec = &self.spec;
+        let bar = self.bar.get_or_insert_with(|| match spec {
+            BarSpec::Time(interval) => {
+                let start = interval.start_of(tick.trade_time);
+                let close = interval.next_start(start) - chrono::Duration::milliseconds(1);
+                Bar::new(tick, start, close)
+            }
+            _ => Bar::new(tick, tick.trade_time, tick.trade_time),
+        });
+        bar.add(tick);
+
+        let full = match &self.spec {
+            BarSpec::Time(_) => false,
+            BarSpec::Tick(n) => bar.num_of_trades >= *n,
+            BarSpec::Volume(v) => bar.volume >= *v,
+            BarSpec::Dollar(d) => bar.quote_asset_vol >= *d,
+        };
+        if full {
+            closed = self.take_closed();
+        }
+        closed
+    }
+
+    /// Close a time bar once `now` is past its interval, even without a later trade
+    pub fn close_expired(&mut self, now: NaiveDateTime) -> Option<KlineDataInsert> {
+        match (&self.spec, &self.bar) {
+            (BarSpec::Time(_), Some(bar)) if now > bar.close_time => self.take_closed(),
+            _ => None,
+        }
+    }
+
+    /// The bar still being built, not closed yet
+    pub fn current(&self) -> Option<KlineDataInsert> {
+        self.bar.clone().map(|bar| bar.into_kline(&self.symbol, &self.spec, false))
+    }
+
+    fn take_closed(&mut self) -> Option<KlineDataInsert> {
+        let (symbol, spec) = (&self.symbol, &self.spec);
+        self.bar.take().map(|bar| bar.into_kline(symbol, spec, true))
+    }
+}
+
+/// Row a closed candle is written as. Threshold bars can close several times in one
+/// millisecond, so they are keyed by their first trade rather than the start time.
+pub fn candle_row(spec: &BarSpec, candle: KlineDataInsert) -> Row {
+    match spec {
+        BarSpec::Time(_) => Row::Kline(candle),
+        _ => Row::Bar(candle.into()),
+    }
+}
+
+/// Roll ticks (ordered by trade time) into bars. The last, unfinished bar is included with `is_kline_closed: false`.
+pub fn build_candles<I>(symbol: Symbol, spec: BarSpec, ticks: I) -> Vec<KlineDataInsert>
+where
+    I: IntoIterator<Item = Tick>,
+{
+    let mut builder = CandleBuilder::new(symbol, spec);
+    let mut candles: Vec<KlineDataInsert> = ticks.into_iter().filter_map(|tick| builder.push(&tick)).collect();
+    candles.extend(builder.current());
+    candles
+}
+
+/// Candles from the `trades` table between `from` and `to`
+pub fn candles_from_trades(
+    conn: &PgConnection,
+    trade_symbol: &Symbol,
+    spec: BarSpec,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> QueryResult<Vec<KlineDataInsert>> {
+    use crate::schema::trades::dsl::*;
+
+    let rows = trades
+        .filter(symbol.eq(trade_symbol))
+        .filter(trade_time.between(from, to))
+        .order((trade_time.asc(), trade_id.asc()))
+        .load::<TradeData>(conn)?;
+    Ok(build_candles(trade_symbol.clone(), spec, rows.iter().map(Tick::from)))
+}
+
+/// Candles from the `aggregate_trades` table between `from` and `to`
+pub fn candles_from_aggregate_trades(
+    conn: &PgConnection,
+    trade_symbol: &Symbol,
+    spec: BarSpec,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> QueryResult<Vec<KlineDataInsert>> {
+    use crate::schema::aggregate_trades::dsl::*;
+
+    let rows = aggregate_trades
+        .filter(symbol.eq(trade_symbol))
+        .filter(trade_time.between(from, to))
+        .order((trade_time.asc(), trade_id.asc()))
+        .load::<AggregateTradeData>(conn)?;
+    Ok(build_candles(trade_symbol.clone(), spec, rows.iter().map(Tick::from)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
+
+    const T0: i64 = 1_546_300_800_000; // 2019-01-01, a Tuesday
+
+    fn tick(id: i64, ms: i64, price: &str, quantity: &str, buyer_mkt_maker: bool) -> Tick {
+        Tick {
+            symbol: Symbol::new("ETHBTC"),
+            first_trade_id: id,
+            last_trade_id: id,
+            price: create_decimal_benchmark(price),
+            quantity: create_decimal_benchmark(quantity),
+            trade_time: create_timestamp_benchmark(T0 + ms),
+            buyer_mkt_maker,
+        }
+    }
+
+    #[test]
+    fn time_bars_match_kline_fields() {
+        let ticks = vec![
+            tick(1, 1_000, "0.030", "2", false),
+            tick(2, 20_000, "0.034", "1", true),
+            tick(3, 59_999, "0.029", "1", false),
+            tick(4, 180_500, "0.031", "3", true), // 3rd minute, the 2nd had no trades
+        ];
+        let candles = build_candles(Symbol::new("ETHBTC"), BarSpec::Time(KlineInterval::_1m), ticks);
+        assert_eq!(candles.len(), 2);
+
+        let first = &candles[0];
+        assert_eq!(first.interval, "1m");
+        assert_eq!(first.start_time, create_timestamp_benchmark(T0));
+        assert_eq!(first.close_time, create_timestamp_benchmark(T0 + 59_999));
+        assert_eq!((first.first_trade_id, first.last_trade_id, first.num_of_trades), (1, 3, 3));
+        assert_eq!(first.open, create_decimal_benchmark("0.030"));
+        assert_eq!(first.high, create_decimal_benchmark("0.034"));
+        assert_eq!(first.low, create_decimal_benchmark("0.029"));
+        assert_eq!(first.close, create_decimal_benchmark("0.029"));
+        assert_eq!(first.volume, create_decimal_benchmark("4"));
+        assert_eq!(first.quote_asset_vol, create_decimal_benchmark("0.123"));
+        assert_eq!(first.taker_buy_base_vol, create_decimal_benchmark("3"));
+        assert_eq!(first.taker_buy_quote_vol, create_decimal_benchmark("0.089"));
+        assert!(first.is_kline_closed);
+
+        assert_eq!(candles[1].start_time, create_timestamp_benchmark(T0 + 180_000));
+        assert!(!candles[1].is_kline_closed);
+
+        // weekly bars start on Monday, monthly on the 1st
+        let t = create_timestamp_benchmark(T0);
+        assert_eq!(KlineInterval::_1w.start_of(t), create_timestamp_benchmark(T0 - 24 * 3_600_000));
+        let dec = chrono::NaiveDate::from_ymd(2018, 12, 1).and_hms(0, 0, 0);
+        assert_eq!(KlineInterval::_1M.start_of(create_timestamp_benchmark(T0 - 1)), dec);
+        assert_eq!(KlineInterval::_1M.next_start(dec), t);
+    }
+
+    #[test]
+    fn threshold_bars_close_on_crossing_tick() {
+        let ticks = || {
+            vec![
+                tick(1, 0, "10", "1", false),
+                tick(2, 1, "11", "2", false),
+                tick(3, 2, "12", "1", true),
+                tick(4, 3, "13", "5", true),
+                tick(5, 4, "14", "1", false),
+            ]
+        };
+
+        let tick_bars = build_candles(Symbol::new("ETHBTC"), BarSpec::Tick(2), ticks());
+        let counts: Vec<(i32, bool)> = tick_bars.iter().map(|c| (c.num_of_trades, c.is_kline_closed)).collect();
+        assert_eq!(counts, vec![(2, true), (2, true), (1, false)]);
+        assert_eq!(tick_bars[0].interval, "tick:2");
+        assert_eq!(tick_bars[1].start_time, create_timestamp_benchmark(T0 + 2));
+        assert_eq!(tick_bars[1].close_time, create_timestamp_benchmark(T0 + 3));
+
+        let volume_bars = build_candles(Symbol::new("ETHBTC"), BarSpec::Volume(create_decimal_benchmark("3")), ticks());
+        let volumes: Vec<BigDecimal> = volume_bars.iter().map(|c| c.volume.clone()).collect();
+        assert_eq!(volumes, vec![create_decimal_benchmark("3"), create_decimal_benchmark("6"), create_decimal_benchmark("1")]);
+
+        // 10 + 22 + 12 = 44, then 65, then 14 left open
+        let dollar_bars = build_candles(Symbol::new("ETHBTC"), BarSpec::Dollar(create_decimal_benchmark("40")), ticks());
+        assert_eq!(dollar_bars.len(), 3);
+        assert_eq!(dollar_bars[0].quote_asset_vol, create_decimal_benchmark("44"));
+        assert_eq!(dollar_bars[0].taker_buy_quote_vol, create_decimal_benchmark("32"));
+        assert_eq!(dollar_bars[1].quote_asset_vol, create_decimal_benchmark("65"));
+        assert!(!dollar_bars[2].is_kline_closed);
+
+        // live time bars close on a timer too
+        let mut builder = CandleBuilder::new(Symbol::new("ETHBTC"), BarSpec::Time(KlineInterval::_1m));
+        assert_eq!(builder.push(&tick(1, 0, "10", "1", false)), None);
+        assert_eq!(builder.close_expired(create_timestamp_benchmark(T0 + 59_999)), None);
+        assert!(builder.close_expired(create_timestamp_benchmark(T0 + 60_000)).unwrap().is_kline_closed);
+        assert_eq!(builder.current(), None);
+    }
+
+    #[test]
+    fn db_threshold_bars_share_a_millisecond() {
+        use crate::db_writer::Batch;
+        use crate::establish_connection_pg;
+        use crate::models::bars::BarInsert;
+        use diesel::result::Error;
+
+        // two trades in the same millisecond close two tick:1 bars
+        let spec = BarSpec::Tick(1);
+        let ticks = vec![tick(1, 0, "10", "1", false), tick(2, 0, "11", "1", false)];
+        let bars: Vec<BarInsert> = build_candles(Symbol::new("ETHBTC"), spec.clone(), ticks)
+            .into_iter()
+            .map(|candle| match candle_row(&spec, candle) {
+                Row::Bar(bar) => bar,
+                other => panic!("expected a bar, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(bars[0].start_time, bars[1].start_time);
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, Error, _>(|| {
+            assert_eq!(Batch::Bars(bars.clone()).write(&conn)?, 2);
+            assert_eq!(Batch::Bars(bars).write(&conn)?, 0);
+            Ok(())
+        });
+    }
+}
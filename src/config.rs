@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bigdecimal::BigDecimal;
+use serde::de::{self, Deserialize, Deserializer};
+
+use crate::candles::BarSpec;
+use crate::db_writer::BatchConfig;
+use crate::error::{Error, Result};
+use crate::gateway::TradingMode;
+use crate::logging::{LogFormat, DEFAULT_LOG_FILTER};
+use crate::metrics::DEFAULT_METRICS_LISTEN;
+use crate::paper::PaperConfig;
+use crate::pubsub::DEFAULT_REDIS_URL;
+use crate::recorder::DEFAULT_RECORD_DIR;
+use crate::risk::RiskLimits;
+use crate::symbols::Symbol;
+
+pub const DEFAULT_CONFIG_PATH: &str = "config/binance.toml";
+
+/// Collector settings, see `config/binance.toml`. Every section is optional.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub collect: CollectConfig,
+    pub candles: CandleConfig,
+    pub redis: RedisConfig,
+    pub record: RecordConfig,
+    pub health: HealthConfig,
+    pub metrics: MetricsConfig,
+    pub trading: TradingConfig,
+    pub risk: RiskConfig,
+    pub log: LogConfig,
+}
+
+impl Config {
+    pub fn parse(s: &str) -> Result<Config> {
+        let config: Config = toml::from_str(s).map_err(|e| Error::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &str) -> Result<Config> {
+        let s = std::fs::read_to_string(path).map_err(|e| Error::Config(format!("{}: {}", path, e)))?;
+        Config::parse(&s).map_err(|e| Error::Config(format!("{}: {}", path, e)))
+    }
+
+    /// Settings which parse but don't work together
+    fn validate(&self) -> Result<()> {
+        for bar in self.candles.bars.iter() {
+            if let BarSpec::Time(interval) = bar {
+                if self.collect.streams.iter().any(|kind| kind.as_str() == format!("kline_{}", interval)) {
+                    return Err(Error::Config(format!(
+                        "candle bar {} would overwrite the subscribed kline_{} stream",
+                        bar, interval
+                    )));
+                }
+            }
+        }
+        // paper fills come from the synced order books, kept by the diff depth streams
+        let diff_depth = |kind: &StreamKind| kind.as_str() == "depth" || kind.as_str().starts_with("depth@");
+        if self.trading.mode == Trading::Paper && !self.collect.streams.iter().any(diff_depth) {
+            return Err(Error::Config("paper trading needs a depth or depth@100ms stream in [collect] streams".to_owned()));
+        }
+        match self.candles.source.as_str() {
+            "trade" | "aggTrade" => Ok(()),
+            source => Err(Error::Config(format!("candle source {} is neither trade nor aggTrade", source))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,     // DATABASE_URL from the environment or .env if unset
+    pub batch_size: usize,       // see `BatchConfig`
+    pub flush_interval_ms: u64,
+    pub max_in_flight: usize,
+    pub max_buffered_rows: usize,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        let batch = BatchConfig::default();
+        DatabaseConfig {
+            url: None,
+            batch_size: batch.batch_size,
+            flush_interval_ms: batch.flush_interval.as_millis() as u64,
+            max_in_flight: batch.max_in_flight,
+            max_buffered_rows: batch.max_buffered_rows,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn batch_config(&self) -> BatchConfig {
+        BatchConfig {
+            batch_size: self.batch_size,
+            flush_interval: Duration::from_millis(self.flush_interval_ms),
+            max_in_flight: self.max_in_flight,
+            max_buffered_rows: self.max_buffered_rows,
+            ..BatchConfig::default()
+        }
+    }
+
+    /// Exported as DATABASE_URL, which every connection helper reads
+    pub fn apply_env(&self) {
+        if let Some(url) = &self.url {
+            std::env::set_var("DATABASE_URL", url);
+        }
+    }
+}
+
+/// Which streams to subscribe to and where their events go
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectConfig {
+    pub symbols: Vec<Symbol>,
+    pub streams: Vec<StreamKind>, // subscribed for every symbol
+    pub combined: bool,           // one multiplexed socket instead of one per stream
+    pub sinks: Vec<Sink>,
+    pub user_data: bool,          // our orders and balances, needs BINANCE_API_KEY
+}
+
+impl Default for CollectConfig {
+    fn default() -> Self {
+        CollectConfig {
+            symbols: vec![Symbol::new("ETHBTC")],
+            streams: vec![
+                StreamKind("trade".to_owned()),
+                StreamKind("kline_1m".to_owned()),
+                StreamKind("miniTicker".to_owned()),
+                StreamKind("ticker".to_owned()),
+            ],
+            combined: true,
+            sinks: vec![Sink::Postgres],
+            user_data: true,
+        }
+    }
+}
+
+/// Bars rolled up from live trades, written to `klines`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CandleConfig {
+    pub source: StreamKind, // trade or aggTrade, they cover the same fills
+    pub bars: Vec<BarSpec>,
+}
+
+impl Default for CandleConfig {
+    fn default() -> Self {
+        CandleConfig {
+            source: StreamKind("trade".to_owned()),
+            bars: vec![BarSpec::Tick(100), BarSpec::Volume(100.into())],
+        }
+    }
+}
+
+/// Used by the `redis` sink
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    pub url: String,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        RedisConfig { url: DEFAULT_REDIS_URL.to_owned() }
+    }
+}
+
+/// Stream health checks, see `health::HealthMonitor`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    pub stale_after_secs: u64,      // alarm when a stream sends nothing for this long
+    pub report_interval_secs: u64,  // print rates, latency and gaps this often, 0 never
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig { stale_after_secs: 60, report_interval_secs: 60 }
+    }
+}
+
+impl HealthConfig {
+    pub fn stale_after(&self) -> Duration {
+        Duration::from_secs(self.stale_after_secs)
+    }
+
+    pub fn report_interval(&self) -> Option<Duration> {
+        match self.report_interval_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+}
+
+/// Log levels per module and the line format, see `logging::init_logger`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub filter: String, // e.g: "info,binance::actors::trades=debug", RUST_LOG overrides it
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { filter: DEFAULT_LOG_FILTER.to_owned(), format: LogFormat::Text }
+    }
+}
+
+/// Prometheus `/metrics` endpoint, see `metrics`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub listen: String, // host:port
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig { enabled: false, listen: DEFAULT_METRICS_LISTEN.to_owned() }
+    }
+}
+
+/// Where the `OrderDesk` sends orders
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TradingConfig {
+    pub mode: Trading,
+    pub paper_balances: BTreeMap<String, BigDecimal>, // asset -> free, starting balances of the paper account
+}
+
+impl Default for TradingConfig {
+    fn default() -> Self {
+        TradingConfig { mode: Trading::Off, paper_balances: PaperConfig::default().balances.into_iter().collect() }
+    }
+}
+
+impl TradingConfig {
+    /// None with trading off
+    pub fn trading_mode(&self) -> Option<TradingMode> {
+        match self.mode {
+            Trading::Off => None,
+            Trading::Live => Some(TradingMode::Live),
+            Trading::Paper => Some(TradingMode::Paper(PaperConfig {
+                balances: self.paper_balances.iter().map(|(asset, free)| (asset.to_uppercase(), free.clone())).collect(),
+                ..PaperConfig::default()
+            })),
+        }
+    }
+}
+
+/// Pre-trade limits for the `OrderDesk`, see `risk::RiskLimits`. Decimals are best written as strings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    pub max_position: HashMap<Symbol, BigDecimal>, // base asset per symbol, unlisted symbols can't be traded
+    pub max_order_notional: BigDecimal,
+    pub max_open_orders: usize,
+    pub price_band: BigDecimal,
+    pub daily_loss_limit: BigDecimal,
+    pub halted: bool,                              // start with the kill switch tripped
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        let limits = RiskLimits::default();
+        RiskConfig {
+            max_position: limits.max_position,
+            max_order_notional: limits.max_order_notional,
+            max_open_orders: limits.max_open_orders,
+            price_band: limits.price_band,
+            daily_loss_limit: limits.daily_loss_limit,
+            halted: false,
+        }
+    }
+}
+
+impl RiskConfig {
+    pub fn limits(&self) -> RiskLimits {
+        RiskLimits {
+            max_position: self.max_position.clone(),
+            max_order_notional: self.max_order_notional.clone(),
+            max_open_orders: self.max_open_orders,
+            price_band: self.price_band.clone(),
+            daily_loss_limit: self.daily_loss_limit.clone(),
+        }
+    }
+}
+
+/// Raw frames written by `collect` for `replay`, see `recorder::FrameRecorder`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    pub dir: String,
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        RecordConfig { enabled: false, dir: DEFAULT_RECORD_DIR.to_owned() }
+    }
+}
+
+/// A market data stream type, as named by Binance after the `<symbol>@`, e.g: "trade", "kline_1m",
+/// "depth20@100ms". Names starting with "!" cover every market and are opened once, e.g: "!ticker@arr".
+/// Which types are supported is up to the collector's stream handlers, checked before connecting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamKind(String);
+
+impl StreamKind {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// One stream for every market rather than one per symbol
+    pub fn all_markets(&self) -> bool {
+        self.0.starts_with('!')
+    }
+
+    /// Binance stream name for `symbol`, e.g: "ethbtc@kline_1m", or "!ticker@arr" for every market
+    pub fn stream_name(&self, symbol: &Symbol) -> String {
+        if self.all_markets() {
+            self.0.clone()
+        } else {
+            format!("{}@{}", symbol, self.0)
+        }
+    }
+}
+
+impl fmt::Display for StreamKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for StreamKind {
+    type Err = Error;
+
+    /// Anything that can go in a `/stream?streams=a/b/c` URL
+    fn from_str(s: &str) -> Result<StreamKind> {
+        let name = s.trim_start_matches('!');
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '@' || c == '_') {
+            return Err(Error::InvalidEnum("StreamKind", s.to_string()));
+        }
+        Ok(StreamKind(s.to_string()))
+    }
+}
+
+/// Where collected rows are sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sink {
+    Postgres, // batched inserts, see `DbBatcher`
+    Stdout,   // every row written to stdout
+    Redis,    // published on `<SYMBOL>:<stream>` channels, see `pubsub`
+}
+
+impl FromStr for Sink {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Sink> {
+        match s {
+            "postgres" => Ok(Sink::Postgres),
+            "stdout" => Ok(Sink::Stdout),
+            "redis" => Ok(Sink::Redis),
+            _ => Err(Error::InvalidEnum("Sink", s.to_string())),
+        }
+    }
+}
+
+/// Whether orders are placed, see `gateway::TradingMode`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trading {
+    Off,   // no `OrderDesk` manager, orders are refused
+    Paper, // filled against the live trade and depth streams
+    Live,  // sent to Binance, needs BINANCE_API_KEY and BINANCE_SECRET_KEY
+}
+
+impl FromStr for Trading {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Trading> {
+        match s {
+            "off" => Ok(Trading::Off),
+            "paper" => Ok(Trading::Paper),
+            "live" => Ok(Trading::Live),
+            _ => Err(Error::InvalidEnum("Trading", s.to_string())),
+        }
+    }
+}
+
+/// Settings are plain strings in the file, parsed with the type's `FromStr`
+macro_rules! deserialize_from_str {
+    ($($t:ty),*) => {$(
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<$t, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse::<$t>().map_err(de::Error::custom)
+            }
+        }
+    )*};
+}
+
+deserialize_from_str!(StreamKind, Sink, BarSpec, LogFormat, Trading);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub static TEST_CONFIG: &str = r#"
+[database]
+url = "postgres://postgres@127.0.0.1/trading_sys"
+batch_size = 100
+
+[collect]
+symbols = ["ETHBTC", "bnbeth"]
+streams = ["trade", "aggTrade", "kline_1h", "depth", "depth10", "ticker", "miniTicker", "!ticker@arr", "depth5@100ms"]
+combined = false
+sinks = ["postgres", "stdout", "redis"]
+
+[candles]
+source = "aggTrade"
+bars = ["5m", "tick:50", "dollar:1000.5"]
+
+[redis]
+url = "redis://10.0.0.2:6380/"
+
+[record]
+enabled = true
+
+[health]
+stale_after_secs = 15
+
+[metrics]
+enabled = true
+listen = "0.0.0.0:9185"
+
+[trading]
+mode = "paper"
+paper_balances = { usdt = "500.5" }
+
+[risk]
+max_position = { ETHBTC = "10", BNBETH = 500 }
+price_band = "0.02"
+halted = true
+
+[log]
+filter = "warn,binance::supervisor=info"
+format = "json"
+"#;
+
+    #[test]
+    fn parse_config_file() {
+        let config = Config::parse(TEST_CONFIG).unwrap();
+        assert_eq!(config.database.batch_size, 100);
+        assert_eq!(config.database.batch_config().flush_interval, Duration::from_secs(1));
+        assert_eq!(config.collect.symbols, vec![Symbol::new("ETHBTC"), Symbol::new("BNBETH")]);
+        assert_eq!(config.collect.streams[2].stream_name(&Symbol::new("ETHBTC")), "ethbtc@kline_1h");
+        assert_eq!(config.collect.streams[7].stream_name(&Symbol::new("ETHBTC")), "!ticker@arr");
+        assert!(config.collect.streams[7].all_markets());
+        assert_eq!(config.collect.streams[8].as_str(), "depth5@100ms");
+        assert_eq!(config.collect.sinks, vec![Sink::Postgres, Sink::Stdout, Sink::Redis]);
+        assert_eq!(config.redis.url, "redis://10.0.0.2:6380/");
+        assert_eq!(config.record, RecordConfig { enabled: true, dir: "recordings".to_owned() });
+        assert_eq!(config.health.stale_after(), Duration::from_secs(15));
+        assert_eq!(config.health.report_interval(), Some(Duration::from_secs(60)));
+        assert_eq!(config.metrics, MetricsConfig { enabled: true, listen: "0.0.0.0:9185".to_owned() });
+        assert_eq!(config.log.format, LogFormat::Json);
+        match config.trading.trading_mode() {
+            Some(TradingMode::Paper(paper)) => {
+                assert_eq!(paper.balances, vec![("USDT".to_owned(), "500.5".parse::<BigDecimal>().unwrap())])
+            }
+            other => panic!("expected paper trading, got {:?}", other),
+        }
+        let limits = config.risk.limits();
+        assert_eq!(limits.max_position[&Symbol::new("ETHBTC")], BigDecimal::from(10));
+        assert_eq!(limits.max_position[&Symbol::new("BNBETH")], BigDecimal::from(500));
+        assert_eq!(limits.price_band, "0.02".parse::<BigDecimal>().unwrap());
+        assert_eq!(limits.max_open_orders, RiskLimits::default().max_open_orders);
+        assert!(config.risk.halted);
+        assert!(config.collect.user_data);
+        assert_eq!(config.candles.source.as_str(), "aggTrade");
+        assert_eq!(config.candles.bars[2].to_string(), "dollar:1000.5");
+        for stream in config.collect.streams.iter() {
+            assert_eq!(stream.to_string().parse::<StreamKind>().unwrap(), *stream);
+        }
+
+        // every section defaults, typos are errors
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(Config::load(DEFAULT_CONFIG_PATH).unwrap(), Config::default());
+        assert!(Config::parse("[collect]\nstreams = [\"trade/ticker\"]").is_err());
+        assert!(Config::parse("[collect]\nsymbol = [\"ETHBTC\"]").is_err());
+        assert!(Config::parse("[candles]\nsource = \"ticker\"").is_err());
+        // stream types without a handler, e.g: "trades", are rejected by the collector, see `StreamSpec::market_streams`
+
+        // time bars share the `klines` table with the kline streams
+        let clash = "[collect]\nstreams = [\"kline_5m\"]\n[candles]\nbars = [\"5m\", \"tick:50\"]";
+        assert!(Config::parse(clash).is_err());
+        assert!(Config::load("config/missing.toml").is_err());
+
+        // paper fills need the synced books, from a diff depth stream
+        assert!(Config::parse("[trading]\nmode = \"paper\"").is_err());
+        assert!(Config::parse("[collect]\nstreams = [\"depth@100ms\"]\n[trading]\nmode = \"paper\"").is_ok());
+        assert!(Config::parse("[collect]\nstreams = [\"depth20\"]\n[trading]\nmode = \"paper\"").is_err());
+        assert_eq!(Config::default().trading.trading_mode(), None);
+    }
+}
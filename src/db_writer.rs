@@ -0,0 +1,449 @@
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::QueryResult;
+
+use crate::backoff::Backoff;
+use crate::models::aggregate_trades::AggregateTradeData;
+use crate::models::bars::BarInsert;
+use crate::models::book_depth::{BookDepthDataInsert, BookSnapshot};
+use crate::models::dead_letters::DeadLetterInsert;
+use crate::models::klines::{upsert_klines, KlineDataInsert};
+use crate::models::mini_ticker::MiniTickerDataInsert;
+use crate::models::tickers::TickerDataInsert;
+use crate::models::trades::TradeData;
+use crate::models::user_data::FillInsert;
+
+/// Thresholds for batching rows before they are written to Postgres
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub batch_size: usize,         // flush a table once it has this many rows
+    pub flush_interval: Duration,  // flush every table at least this often
+    pub max_in_flight: usize,      // concurrent INSERTs, usually the pool size
+    pub max_buffered_rows: usize,  // past this, writers are paused until Postgres catches up
+    pub retry: Backoff,            // delay before a failed batch is written again, dropped once out of retries
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            batch_size: 500,
+            flush_interval: Duration::from_secs(1),
+            max_in_flight: 4,
+            max_buffered_rows: 20_000,
+            retry: Backoff::new(Duration::from_millis(500), Duration::from_secs(30), Some(10)),
+        }
+    }
+}
+
+/// A single row destined for one of the market data tables
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row {
+    AggregateTrade(AggregateTradeData),
+    Bar(BarInsert),
+    BookDepth(BookDepthDataInsert),
+    BookSnapshot(BookSnapshot),
+    DeadLetter(DeadLetterInsert),
+    Fill(FillInsert),
+    Kline(KlineDataInsert),
+    MiniTicker(MiniTickerDataInsert),
+    Ticker(TickerDataInsert),
+    Trade(TradeData),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    AggregateTrades,
+    Bars,
+    BookDepth,
+    BookSnapshots,
+    DeadLetters,
+    Fills,
+    Klines,
+    MiniTickers,
+    Tickers,
+    Trades,
+}
+
+pub const TABLES: [Table; 10] = [
+    Table::AggregateTrades,
+    Table::Bars,
+    Table::BookDepth,
+    Table::BookSnapshots,
+    Table::DeadLetters,
+    Table::Fills,
+    Table::Klines,
+    Table::MiniTickers,
+    Table::Tickers,
+    Table::Trades,
+];
+
+impl Table {
+    /// Postgres table name, e.g: "aggregate_trades"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::AggregateTrades => "aggregate_trades",
+            Table::Bars => "bars",
+            Table::BookDepth => "book_depth",
+            Table::BookSnapshots => "book_snapshots",
+            Table::DeadLetters => "dead_letters",
+            Table::Fills => "fills",
+            Table::Klines => "klines",
+            Table::MiniTickers => "mini_tickers",
+            Table::Tickers => "tickers",
+            Table::Trades => "trades",
+        }
+    }
+}
+
+impl Row {
+    pub fn table(&self) -> Table {
+        match self {
+            Row::AggregateTrade(_) => Table::AggregateTrades,
+            Row::Bar(_) => Table::Bars,
+            Row::BookDepth(_) => Table::BookDepth,
+            Row::BookSnapshot(_) => Table::BookSnapshots,
+            Row::DeadLetter(_) => Table::DeadLetters,
+            Row::Fill(_) => Table::Fills,
+            Row::Kline(_) => Table::Klines,
+            Row::MiniTicker(_) => Table::MiniTickers,
+            Row::Ticker(_) => Table::Tickers,
+            Row::Trade(_) => Table::Trades,
+        }
+    }
+}
+
+/// Rows for a single table, written with one multi-row INSERT
+#[derive(Debug, PartialEq)]
+pub enum Batch {
+    AggregateTrades(Vec<AggregateTradeData>),
+    Bars(Vec<BarInsert>),
+    BookDepth(Vec<BookDepthDataInsert>),
+    BookSnapshots(Vec<BookSnapshot>),
+    DeadLetters(Vec<DeadLetterInsert>),
+    Fills(Vec<FillInsert>),
+    Klines(Vec<KlineDataInsert>),
+    MiniTickers(Vec<MiniTickerDataInsert>),
+    Tickers(Vec<TickerDataInsert>),
+    Trades(Vec<TradeData>),
+}
+
+impl Batch {
+    pub fn len(&self) -> usize {
+        match self {
+            Batch::AggregateTrades(rows) => rows.len(),
+            Batch::Bars(rows) => rows.len(),
+            Batch::BookDepth(rows) => rows.len(),
+            Batch::BookSnapshots(rows) => rows.len(),
+            Batch::DeadLetters(rows) => rows.len(),
+            Batch::Fills(rows) => rows.len(),
+            Batch::Klines(rows) => rows.len(),
+            Batch::MiniTickers(rows) => rows.len(),
+            Batch::Tickers(rows) => rows.len(),
+            Batch::Trades(rows) => rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn table(&self) -> Table {
+        match self {
+            Batch::AggregateTrades(_) => Table::AggregateTrades,
+            Batch::Bars(_) => Table::Bars,
+            Batch::BookDepth(_) => Table::BookDepth,
+            Batch::BookSnapshots(_) => Table::BookSnapshots,
+            Batch::DeadLetters(_) => Table::DeadLetters,
+            Batch::Fills(_) => Table::Fills,
+            Batch::Klines(_) => Table::Klines,
+            Batch::MiniTickers(_) => Table::MiniTickers,
+            Batch::Tickers(_) => Table::Tickers,
+            Batch::Trades(_) => Table::Trades,
+        }
+    }
+
+    /// Write every row, in as few statements as fit the bind parameter limit, see `write_chunked`.
+    /// Trades, fills and bars are keyed by symbol and trade id, so rows replayed after a reconnect
+    /// are skipped instead of failing the whole batch.
+    /// Klines are upserted, so an open kline is updated in place until it closes.
+    pub fn write(&self, conn: &PgConnection) -> QueryResult<usize> {
+        use crate::schema::{aggregate_trades, bars, book_depth, book_snapshots, dead_letters, fills, mini_tickers, tickers, trades};
+
+        match self {
+            Batch::AggregateTrades(rows) => write_chunked(conn, rows, AGGREGATE_TRADE_COLUMNS, |chunk| {
+                diesel::insert_into(aggregate_trades::table)
+                    .values(chunk)
+                    .on_conflict((aggregate_trades::symbol, aggregate_trades::trade_id))
+                    .do_nothing()
+                    .execute(conn)
+            }),
+            Batch::Bars(rows) => write_chunked(conn, rows, BAR_COLUMNS, |chunk| {
+                diesel::insert_into(bars::table).values(chunk).on_conflict_do_nothing().execute(conn)
+            }),
+            Batch::BookDepth(rows) => write_chunked(conn, rows, BOOK_DEPTH_COLUMNS, |chunk| {
+                diesel::insert_into(book_depth::table).values(chunk).execute(conn)
+            }),
+            Batch::BookSnapshots(rows) => write_chunked(conn, rows, BOOK_SNAPSHOT_COLUMNS, |chunk| {
+                diesel::insert_into(book_snapshots::table).values(chunk).on_conflict_do_nothing().execute(conn)
+            }),
+            Batch::DeadLetters(rows) => write_chunked(conn, rows, DEAD_LETTER_COLUMNS, |chunk| {
+                diesel::insert_into(dead_letters::table).values(chunk).execute(conn)
+            }),
+            Batch::Fills(rows) => write_chunked(conn, rows, FILL_COLUMNS, |chunk| {
+                diesel::insert_into(fills::table).values(chunk).on_conflict_do_nothing().execute(conn)
+            }),
+            // chunks are written in order, so a later copy of a kline still replaces an earlier one
+            Batch::Klines(rows) => write_chunked(conn, rows, KLINE_COLUMNS, |chunk| upsert_klines(conn, chunk)),
+            Batch::MiniTickers(rows) => write_chunked(conn, rows, MINI_TICKER_COLUMNS, |chunk| {
+                diesel::insert_into(mini_tickers::table).values(chunk).execute(conn)
+            }),
+            Batch::Tickers(rows) => write_chunked(conn, rows, TICKER_COLUMNS, |chunk| {
+                diesel::insert_into(tickers::table).values(chunk).execute(conn)
+            }),
+            Batch::Trades(rows) => write_chunked(conn, rows, TRADE_COLUMNS, |chunk| {
+                diesel::insert_into(trades::table)
+                    .values(chunk)
+                    .on_conflict((trades::symbol, trades::trade_id))
+                    .do_nothing()
+                    .execute(conn)
+            }),
+        }
+    }
+}
+
+/// Postgres allows at most 65535 bind parameters per statement
+const MAX_BIND_PARAMS: usize = 65_535;
+// bind parameters per row, the inserted columns of each table
+const AGGREGATE_TRADE_COLUMNS: usize = 10;
+const BAR_COLUMNS: usize = 15;
+const BOOK_DEPTH_COLUMNS: usize = 7;
+const BOOK_SNAPSHOT_COLUMNS: usize = 6;
+const DEAD_LETTER_COLUMNS: usize = 4;
+const FILL_COLUMNS: usize = 12;
+const KLINE_COLUMNS: usize = 18;
+const MINI_TICKER_COLUMNS: usize = 9;
+const TICKER_COLUMNS: usize = 23;
+const TRADE_COLUMNS: usize = 10;
+
+/// A large batch, e.g: the all-markets ticker arrays at a couple of thousand rows per frame, or a
+/// backlog after Postgres was slow, can exceed the parameter limit. Split into as few INSERTs as fit,
+/// in one transaction so a failed batch is retried without duplicating the chunks already written.
+fn write_chunked<T, F>(conn: &PgConnection, rows: &[T], columns: usize, mut write: F) -> QueryResult<usize>
+where
+    F: FnMut(&[T]) -> QueryResult<usize>,
+{
+    conn.transaction(|| {
+        let mut written = 0;
+        for chunk in rows.chunks(MAX_BIND_PARAMS / columns) {
+            written += write(chunk)?;
+        }
+        Ok(written)
+    })
+}
+
+/// Per-table buffers of rows waiting to be flushed
+#[derive(Debug, Default)]
+pub struct RowBuffer {
+    aggregate_trades: Vec<AggregateTradeData>,
+    bars: Vec<BarInsert>,
+    book_depth: Vec<BookDepthDataInsert>,
+    book_snapshots: Vec<BookSnapshot>,
+    dead_letters: Vec<DeadLetterInsert>,
+    fills: Vec<FillInsert>,
+    klines: Vec<KlineDataInsert>,
+    mini_tickers: Vec<MiniTickerDataInsert>,
+    tickers: Vec<TickerDataInsert>,
+    trades: Vec<TradeData>,
+}
+
+impl RowBuffer {
+    pub fn new() -> Self {
+        RowBuffer::default()
+    }
+
+    /// Buffer a row, returning how many rows are now waiting for its table
+    pub fn push(&mut self, row: Row) -> usize {
+        match row {
+            Row::AggregateTrade(r) => { self.aggregate_trades.push(r); self.aggregate_trades.len() }
+            Row::Bar(r) => { self.bars.push(r); self.bars.len() }
+            Row::BookDepth(r) => { self.book_depth.push(r); self.book_depth.len() }
+            Row::BookSnapshot(r) => { self.book_snapshots.push(r); self.book_snapshots.len() }
+            Row::DeadLetter(r) => { self.dead_letters.push(r); self.dead_letters.len() }
+            Row::Fill(r) => { self.fills.push(r); self.fills.len() }
+            Row::Kline(r) => { self.klines.push(r); self.klines.len() }
+            Row::MiniTicker(r) => { self.mini_tickers.push(r); self.mini_tickers.len() }
+            Row::Ticker(r) => { self.tickers.push(r); self.tickers.len() }
+            Row::Trade(r) => { self.trades.push(r); self.trades.len() }
+        }
+    }
+
+    pub fn table_len(&self, table: Table) -> usize {
+        match table {
+            Table::AggregateTrades => self.aggregate_trades.len(),
+            Table::Bars => self.bars.len(),
+            Table::BookDepth => self.book_depth.len(),
+            Table::BookSnapshots => self.book_snapshots.len(),
+            Table::DeadLetters => self.dead_letters.len(),
+            Table::Fills => self.fills.len(),
+            Table::Klines => self.klines.len(),
+            Table::MiniTickers => self.mini_tickers.len(),
+            Table::Tickers => self.tickers.len(),
+            Table::Trades => self.trades.len(),
+        }
+    }
+
+    /// Total rows buffered across all tables
+    pub fn len(&self) -> usize {
+        TABLES.iter().map(|t| self.table_len(*t)).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Take every buffered row for `table`
+    pub fn take(&mut self, table: Table) -> Batch {
+        match table {
+            Table::AggregateTrades => Batch::AggregateTrades(self.aggregate_trades.drain(..).collect()),
+            Table::Bars => Batch::Bars(self.bars.drain(..).collect()),
+            Table::BookDepth => Batch::BookDepth(self.book_depth.drain(..).collect()),
+            Table::BookSnapshots => Batch::BookSnapshots(self.book_snapshots.drain(..).collect()),
+            Table::DeadLetters => Batch::DeadLetters(self.dead_letters.drain(..).collect()),
+            Table::Fills => Batch::Fills(self.fills.drain(..).collect()),
+            Table::Klines => Batch::Klines(self.klines.drain(..).collect()),
+            Table::MiniTickers => Batch::MiniTickers(self.mini_tickers.drain(..).collect()),
+            Table::Tickers => Batch::Tickers(self.tickers.drain(..).collect()),
+            Table::Trades => Batch::Trades(self.trades.drain(..).collect()),
+        }
+    }
+
+    /// Put a failed batch back in front of the rows buffered since, so it is retried in order
+    pub fn restore(&mut self, batch: Batch) {
+        fn prepend<T>(rows: &mut Vec<T>, mut batch: Vec<T>) {
+            batch.append(rows);
+            *rows = batch;
+        }
+        match batch {
+            Batch::AggregateTrades(b) => prepend(&mut self.aggregate_trades, b),
+            Batch::Bars(b) => prepend(&mut self.bars, b),
+            Batch::BookDepth(b) => prepend(&mut self.book_depth, b),
+            Batch::BookSnapshots(b) => prepend(&mut self.book_snapshots, b),
+            Batch::DeadLetters(b) => prepend(&mut self.dead_letters, b),
+            Batch::Fills(b) => prepend(&mut self.fills, b),
+            Batch::Klines(b) => prepend(&mut self.klines, b),
+            Batch::MiniTickers(b) => prepend(&mut self.mini_tickers, b),
+            Batch::Tickers(b) => prepend(&mut self.tickers, b),
+            Batch::Trades(b) => prepend(&mut self.trades, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::models::book_depth::TEST_BOOKDEPTH_DATA;
+    use crate::models::klines::{map_klinemeta_to_klineinsertdata, KlineMetaData, TEST_KLINE_DATA};
+    use crate::models::trades::TEST_TRADE_DATA;
+    use crate::models::tickers::TEST_TICKER_DATA;
+    use crate::symbols::Symbol;
+    use diesel::result::Error;
+
+    #[test]
+    fn row_buffer_groups_rows_per_table() {
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+
+        let mut buffer = RowBuffer::new();
+        assert_eq!(buffer.push(Row::Trade(trade)), 1);
+        assert_eq!(buffer.push(Row::Ticker(ticker)), 1);
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+        assert_eq!(buffer.push(Row::Ticker(ticker)), 2);
+        assert_eq!(buffer.len(), 3);
+
+        let batch = buffer.take(Table::Tickers);
+        assert_eq!(batch.table(), Table::Tickers);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(buffer.table_len(Table::Tickers), 0);
+        assert_eq!(buffer.len(), 1);
+        assert!(buffer.take(Table::Klines).is_empty());
+
+        // a failed batch goes back ahead of the rows buffered while it was being written
+        let mut newer = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+        newer.symbol = Symbol::new("ETHBTC");
+        buffer.push(Row::Ticker(newer.clone()));
+        buffer.restore(batch);
+        match buffer.take(Table::Tickers) {
+            Batch::Tickers(rows) => assert_eq!(rows.last(), Some(&newer)),
+            other => panic!("expected tickers, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn db_batch_trade_ids_are_per_symbol() {
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let mut other_symbol = trade.clone();
+        other_symbol.symbol = Symbol::new("ETHBTC");
+        // ids past i32::MAX, as on the busiest symbols
+        let mut large_id = trade.clone();
+        large_id.trade_id = 3_000_000_000;
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, Error, _>(|| {
+            let batch = Batch::Trades(vec![trade.clone(), other_symbol, large_id]);
+            assert_eq!(batch.write(&conn)?, 3);
+            // replayed after a reconnect, skipped
+            assert_eq!(Batch::Trades(vec![trade.clone()]).write(&conn)?, 0);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn db_batch_postgres_write() {
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+        let batch = Batch::Tickers(vec![ticker.clone(); 3]);
+        // more bind parameters than one statement allows, e.g: a backlog of `!ticker@arr` frames
+        let backlog = Batch::Tickers(vec![ticker; 3000]);
+        // every table is chunked, not just the ticker arrays
+        let depth = serde_json::from_str::<BookDepthDataInsert>(TEST_BOOKDEPTH_DATA).unwrap();
+        let depth_backlog = Batch::BookDepth(vec![depth; 10_000]);
+        let kline = map_klinemeta_to_klineinsertdata(serde_json::from_str::<KlineMetaData>(TEST_KLINE_DATA).unwrap());
+        let klines = (0..4000)
+            .map(|i| KlineDataInsert { start_time: kline.start_time + chrono::Duration::minutes(i), ..kline.clone() })
+            .collect();
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, Error, _>(|| {
+            assert_eq!(batch.write(&conn)?, 3);
+            assert_eq!(backlog.write(&conn)?, 3000);
+            assert_eq!(depth_backlog.write(&conn)?, 10_000);
+            assert_eq!(Batch::Klines(klines).write(&conn)?, 4000);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn db_chunked_write_is_all_or_nothing() {
+        use crate::schema::tickers;
+
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, Error, _>(|| {
+            let before: i64 = tickers::table.count().get_result(&conn)?;
+            // one row per chunk, the second chunk fails
+            let mut chunks = 0;
+            let result = write_chunked(&conn, &vec![ticker.clone(); 2], MAX_BIND_PARAMS, |chunk| {
+                chunks += 1;
+                match chunks {
+                    1 => diesel::insert_into(tickers::table).values(chunk).execute(&conn),
+                    _ => Err(Error::RollbackTransaction),
+                }
+            });
+            assert!(result.is_err());
+            assert_eq!(tickers::table.count().get_result::<i64>(&conn)?, before);
+            Ok(())
+        });
+    }
+}
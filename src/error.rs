@@ -0,0 +1,111 @@
+use std::fmt;
+
+/// Crate-level error for anything received from the exchange or read back from Postgres
+#[derive(Debug)]
+pub enum Error {
+    Json(serde_json::Error),               // payload doesn't match the model
+    Database(diesel::result::Error),       // insert/query failed
+    Http(reqwest::Error),                  // REST request failed
+    HttpStatus(u16, String),               // REST error without a Binance error body
+    InvalidUrl(String),                    // REST base URL and path don't make a URL
+    Api(crate::rest_client::ApiError),     // Binance rejected the request, e.g: -2010
+    InvalidTimestamp(i64),                 // not milliseconds since Binance launched
+    InvalidDecimal(String),                // not a decimal string, e.g: "0.0024"
+    UnknownSymbol(String),                 // not a `Symbol`
+    InvalidInterval(String),               // not a `KlineInterval`, e.g: "1m"
+    InvalidEnum(&'static str, String),     // (type, value), e.g: ("OrderStatus", "HALF_FILLED")
+    InvalidOrder(String),                  // rejected locally, before it reached the exchange
+    Config(String),                        // missing or invalid setting, e.g: no API key for live trading
+    RiskRejected(crate::risk::RiskRejection), // refused by the pre-trade risk checks
+    InvalidQuery(String),                  // filters that can't be combined, e.g: aggregating klines
+    Io(std::io::Error),                    // writing query output failed
+    Redis(redis::RedisError),              // publishing to Redis failed
+    Metrics(prometheus::Error),            // registering or encoding Prometheus metrics failed
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::Database(e) => write!(f, "database error: {}", e),
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+            Error::HttpStatus(status, body) => write!(f, "HTTP {}: {}", status, body),
+            Error::InvalidUrl(s) => write!(f, "invalid URL: {}", s),
+            Error::Api(e) => write!(f, "Binance API error: {}", e),
+            Error::InvalidTimestamp(t) => write!(
+                f,
+                "timestamp too small: {}, timestamp format may be in seconds instead of milliseconds",
+                t
+            ),
+            Error::InvalidDecimal(s) => write!(f, "invalid decimal: {:?}", s),
+            Error::UnknownSymbol(s) => write!(f, "unknown symbol: {:?}", s),
+            Error::InvalidInterval(s) => write!(f, "invalid kline interval: {:?}", s),
+            Error::InvalidEnum(name, s) => write!(f, "invalid {}: {:?}", name, s),
+            Error::InvalidOrder(s) => write!(f, "invalid order: {}", s),
+            Error::Config(s) => write!(f, "configuration error: {}", s),
+            Error::RiskRejected(r) => write!(f, "rejected by risk checks: {}", r),
+            Error::InvalidQuery(s) => write!(f, "invalid query: {}", s),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Redis(e) => write!(f, "Redis error: {}", e),
+            Error::Metrics(e) => write!(f, "metrics error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json(e) => Some(e),
+            Error::Database(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Redis(e) => Some(e),
+            Error::Metrics(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<crate::rest_client::ApiError> for Error {
+    fn from(e: crate::rest_client::ApiError) -> Self {
+        Error::Api(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<redis::RedisError> for Error {
+    fn from(e: redis::RedisError) -> Self {
+        Error::Redis(e)
+    }
+}
+
+impl From<prometheus::Error> for Error {
+    fn from(e: prometheus::Error) -> Self {
+        Error::Metrics(e)
+    }
+}
@@ -0,0 +1,72 @@
+use crate::error::{Error, Result};
+use crate::models::account::{AccountInfo, CancelOrderResult, NewOrderResult, OrderInfo};
+use crate::models::orders::OrderRequest;
+use crate::paper::{PaperConfig, PaperExchange};
+use crate::rest_client::{ApiCredentials, BinanceRestClient, OrderRef};
+use crate::symbols::{Symbol, SymbolRegistry};
+
+/// Where orders go. `BinanceRestClient` trades for real, `PaperExchange` simulates
+/// fills against the live streams; `OrderManager` works with either.
+pub trait OrderGateway: Send {
+    /// POST `/api/v3/order`
+    fn submit_order(&self, request: &OrderRequest, client_order_id: &str) -> Result<NewOrderResult>;
+
+    /// DELETE `/api/v3/order`
+    fn cancel_order(&self, symbol: &Symbol, order: &OrderRef) -> Result<CancelOrderResult>;
+
+    /// GET `/api/v3/order`
+    fn order_status(&self, symbol: &Symbol, order: &OrderRef) -> Result<OrderInfo>;
+
+    /// GET `/api/v3/openOrders`, for every symbol if None
+    fn open_orders(&self, symbol: Option<&Symbol>) -> Result<Vec<OrderInfo>>;
+
+    /// GET `/api/v3/account`
+    fn account(&self) -> Result<AccountInfo>;
+}
+
+impl OrderGateway for BinanceRestClient {
+    fn submit_order(&self, request: &OrderRequest, client_order_id: &str) -> Result<NewOrderResult> {
+        self.new_order(&request.to_params(client_order_id))
+    }
+
+    fn cancel_order(&self, symbol: &Symbol, order: &OrderRef) -> Result<CancelOrderResult> {
+        BinanceRestClient::cancel_order(self, symbol, order)
+    }
+
+    fn order_status(&self, symbol: &Symbol, order: &OrderRef) -> Result<OrderInfo> {
+        BinanceRestClient::order_status(self, symbol, order)
+    }
+
+    fn open_orders(&self, symbol: Option<&Symbol>) -> Result<Vec<OrderInfo>> {
+        BinanceRestClient::open_orders(self, symbol)
+    }
+
+    fn account(&self) -> Result<AccountInfo> {
+        BinanceRestClient::account(self)
+    }
+}
+
+/// Live or paper trading, chosen by `[trading] mode` in the config
+#[derive(Debug, Clone, PartialEq)]
+pub enum TradingMode {
+    Live,
+    Paper(PaperConfig),
+}
+
+impl TradingMode {
+    /// Live trading needs BINANCE_API_KEY and BINANCE_SECRET_KEY. The paper exchange
+    /// is returned as well so it can be fed market data.
+    pub fn gateway(&self, symbols: &SymbolRegistry) -> Result<(Box<dyn OrderGateway>, Option<PaperExchange>)> {
+        match self {
+            TradingMode::Live => {
+                let credentials = ApiCredentials::from_env()
+                    .ok_or_else(|| Error::Config("live trading needs BINANCE_API_KEY and BINANCE_SECRET_KEY".to_owned()))?;
+                Ok((Box::new(BinanceRestClient::new(credentials)), None))
+            }
+            TradingMode::Paper(config) => {
+                let exchange = PaperExchange::new(config.clone(), symbols);
+                Ok((Box::new(exchange.clone()), Some(exchange)))
+            }
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+
+use crate::models::aggregate_trades::AggregateTradeData;
+use crate::models::book_depth::{BookDepthDataInsert, BookSnapshot};
+use crate::models::klines::KlineDataInsert;
+use crate::models::mini_ticker::MiniTickerDataInsert;
+use crate::models::tickers::TickerDataInsert;
+use crate::models::trades::TradeData;
+
+/////////////////////////////////////////////////////////////////
+/// Stream health: message rates, exchange-to-local latency,
+/// trade ID and kline gaps, and streams which went quiet.
+/////////////////////////////////////////////////////////////////
+
+/// How often stream actors ping the server and check for a dead socket
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// A socket with no frame at all (data, ping or pong) for this long is dropped and reconnected
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the health checks need from one market event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub event_time: Option<NaiveDateTime>, // None for depth snapshots, which carry no event time
+    pub trade_ids: Option<(i64, i64)>, // first and last trade ID in the event
+    pub kline: Option<(NaiveDateTime, NaiveDateTime)>, // start and close time
+}
+
+impl Observation {
+    fn at(event_time: NaiveDateTime) -> Self {
+        Observation { event_time: Some(event_time), trade_ids: None, kline: None }
+    }
+}
+
+impl<'a> From<&'a TradeData> for Observation {
+    fn from(trade: &'a TradeData) -> Self {
+        let id = i64::from(trade.trade_id);
+        Observation { trade_ids: Some((id, id)), ..Observation::at(trade.event_time) }
+    }
+}
+
+impl<'a> From<&'a AggregateTradeData> for Observation {
+    fn from(trade: &'a AggregateTradeData) -> Self {
+        let ids = (trade.first_trade_id, trade.last_trade_id);
+        Observation { trade_ids: Some(ids), ..Observation::at(trade.event_time) }
+    }
+}
+
+impl<'a> From<&'a KlineDataInsert> for Observation {
+    fn from(kline: &'a KlineDataInsert) -> Self {
+        Observation { kline: Some((kline.start_time, kline.close_time)), ..Observation::at(kline.event_time) }
+    }
+}
+
+impl<'a> From<&'a TickerDataInsert> for Observation {
+    fn from(ticker: &'a TickerDataInsert) -> Self {
+        Observation::at(ticker.event_time)
+    }
+}
+
+impl<'a> From<&'a MiniTickerDataInsert> for Observation {
+    fn from(ticker: &'a MiniTickerDataInsert) -> Self {
+        Observation::at(ticker.event_time)
+    }
+}
+
+impl<'a> From<&'a BookDepthDataInsert> for Observation {
+    fn from(book: &'a BookDepthDataInsert) -> Self {
+        Observation::at(book.event_time)
+    }
+}
+
+impl<'a> From<&'a BookSnapshot> for Observation {
+    fn from(_book: &'a BookSnapshot) -> Self {
+        Observation { event_time: None, trade_ids: None, kline: None }
+    }
+}
+
+/// Counters for one stream. Totals since start, rate and latency since the last `report`.
+#[derive(Debug, Clone, Default)]
+pub struct StreamHealth {
+    pub messages: u64,
+    pub last_message_at: Option<NaiveDateTime>,
+    pub watched_since: Option<NaiveDateTime>, // staleness counts from here until the first message
+    pub last_trade_id: Option<i64>,
+    pub trade_id_gaps: u64,
+    pub missing_trades: u64, // trade IDs skipped over all gaps
+    pub last_kline: Option<(NaiveDateTime, NaiveDateTime)>,
+    pub kline_gaps: u64,
+    pub stale: bool,
+    window_messages: u64,
+    window_latency_ms: i64, // sum, for the mean
+    window_latency_samples: u64,
+    window_max_latency_ms: i64,
+}
+
+impl StreamHealth {
+    pub fn observe(&mut self, observation: &Observation, now: NaiveDateTime) {
+        self.messages += 1;
+        self.window_messages += 1;
+        self.last_message_at = Some(now);
+
+        if let Some(event_time) = observation.event_time {
+            let latency_ms = (now - event_time).num_milliseconds();
+            self.window_latency_ms += latency_ms;
+            self.window_latency_samples += 1;
+            self.window_max_latency_ms = self.window_max_latency_ms.max(latency_ms);
+        }
+
+        if let Some((first, last)) = observation.trade_ids {
+            match self.last_trade_id {
+                Some(previous) if first > previous + 1 => {
+                    self.trade_id_gaps += 1;
+                    self.missing_trades += (first - previous - 1) as u64;
+                }
+                _ => (),
+            }
+            // replayed or out of order trades don't move the high water mark back
+            self.last_trade_id = Some(self.last_trade_id.map_or(last, |previous| previous.max(last)));
+        }
+
+        // An open kline is updated in place until it closes, the next one starts 1ms after its close
+        if let Some((start, close)) = observation.kline {
+            match self.last_kline {
+                Some((_, previous_close)) if start > previous_close + chrono::Duration::milliseconds(1) => {
+                    self.kline_gaps += 1;
+                }
+                _ => (),
+            }
+            if self.last_kline.map_or(true, |(previous_start, _)| start >= previous_start) {
+                self.last_kline = Some((start, close));
+            }
+        }
+    }
+
+    /// Time since the last message, or since the stream was watched if none arrived yet
+    pub fn idle(&self, now: NaiveDateTime) -> Option<chrono::Duration> {
+        self.last_message_at.or(self.watched_since).map(|since| now - since)
+    }
+}
+
+/// One stream's health over the last report interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthReport {
+    pub stream: String,
+    pub messages: u64,
+    pub messages_per_sec: f64,
+    pub mean_latency_ms: Option<i64>,
+    pub max_latency_ms: Option<i64>,
+    pub trade_id_gaps: u64,
+    pub missing_trades: u64,
+    pub kline_gaps: u64,
+    pub idle_ms: Option<i64>,
+    pub stale: bool,
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |v: Option<i64>| v.map_or("-".to_owned(), |v| format!("{}ms", v));
+        write!(
+            f,
+            "{}: {:.1} msg/s, latency mean {} max {}, {} trade ID gaps ({} missing), {} kline gaps, idle {}{}",
+            self.stream,
+            self.messages_per_sec,
+            ms(self.mean_latency_ms),
+            ms(self.max_latency_ms),
+            self.trade_id_gaps,
+            self.missing_trades,
+            self.kline_gaps,
+            ms(self.idle_ms),
+            if self.stale { ", STALE" } else { "" }
+        )
+    }
+}
+
+/// Health of every stream, keyed by Binance stream name, e.g: "ethbtc@trade"
+#[derive(Debug, Clone)]
+pub struct HealthMonitor {
+    pub stale_after: Duration,
+    pub streams: HashMap<String, StreamHealth>,
+    window_start: Option<NaiveDateTime>,
+}
+
+impl HealthMonitor {
+    pub fn new(stale_after: Duration) -> Self {
+        HealthMonitor { stale_after, streams: HashMap::new(), window_start: None }
+    }
+
+    /// Start the staleness clock for a stream before its first message
+    pub fn watch(&mut self, stream: &str, now: NaiveDateTime) {
+        let health = self.streams.entry(stream.to_owned()).or_insert_with(StreamHealth::default);
+        health.watched_since = Some(now);
+    }
+
+    pub fn unwatch(&mut self, stream: &str) {
+        self.streams.remove(stream);
+    }
+
+    pub fn observe(&mut self, stream: &str, observation: &Observation, now: NaiveDateTime) {
+        self.window_start.get_or_insert(now);
+        self.streams
+            .entry(stream.to_owned())
+            .or_insert_with(StreamHealth::default)
+            .observe(observation, now);
+    }
+
+    /// Update staleness, returning streams which went stale (true) or recovered (false) since the last check
+    pub fn check_stale(&mut self, now: NaiveDateTime) -> Vec<(String, bool)> {
+        let stale_after = self.stale_after;
+        let mut changed: Vec<(String, bool)> = Vec::new();
+        for (stream, health) in self.streams.iter_mut() {
+            // negative idle times (clock adjustments) are never stale
+            let stale = health.idle(now).and_then(|idle| idle.to_std().ok()).map_or(false, |idle| idle > stale_after);
+            if stale != health.stale {
+                health.stale = stale;
+                changed.push((stream.clone(), stale));
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    /// Every stream's health since the last report, starting a new window
+    pub fn report(&mut self, now: NaiveDateTime) -> Vec<HealthReport> {
+        let window_secs = self
+            .window_start
+            .map(|start| (now - start).num_milliseconds() as f64 / 1000.0)
+            .unwrap_or(0.0);
+        self.window_start = Some(now);
+
+        let mut reports: Vec<HealthReport> = self
+            .streams
+            .iter_mut()
+            .map(|(stream, health)| {
+                let samples = health.window_latency_samples as i64;
+                let report = HealthReport {
+                    stream: stream.clone(),
+                    messages: health.messages,
+                    messages_per_sec: if window_secs > 0.0 { health.window_messages as f64 / window_secs } else { 0.0 },
+                    mean_latency_ms: if samples > 0 { Some(health.window_latency_ms / samples) } else { None },
+                    max_latency_ms: if samples > 0 { Some(health.window_max_latency_ms) } else { None },
+                    trade_id_gaps: health.trade_id_gaps,
+                    missing_trades: health.missing_trades,
+                    kline_gaps: health.kline_gaps,
+                    idle_ms: health.idle(now).map(|idle| idle.num_milliseconds()),
+                    stale: health.stale,
+                };
+                health.window_messages = 0;
+                health.window_latency_ms = 0;
+                health.window_latency_samples = 0;
+                health.window_max_latency_ms = 0;
+                report
+            })
+            .collect();
+        reports.sort_by(|a, b| a.stream.cmp(&b.stream));
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::klines::{map_klinemeta_to_klineinsertdata, KlineMetaData, TEST_KLINE_DATA};
+    use crate::models::trades::TEST_TRADE_DATA;
+    use crate::serde_parsers::create_timestamp_benchmark;
+
+    #[test]
+    fn trade_gaps_latency_and_staleness() {
+        let mut monitor = HealthMonitor::new(Duration::from_secs(5));
+        let start = create_timestamp_benchmark(1_555_444_330_000);
+        let at = |ms: i64| start + chrono::Duration::milliseconds(ms);
+        let mut trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        trade.event_time = start;
+
+        monitor.watch("bnbbtc@trade", start);
+        monitor.watch("bnbbtc@aggTrade", start);
+        for (id, received) in [(100, 50), (101, 150), (105, 1_000), (103, 1_500)].iter() {
+            trade.trade_id = *id;
+            monitor.observe("bnbbtc@trade", &Observation::from(&trade), at(*received));
+        }
+
+        assert_eq!(monitor.check_stale(at(2_000)), vec![]);
+        assert_eq!(monitor.check_stale(at(6_000)), vec![("bnbbtc@aggTrade".to_owned(), true)]);
+        assert_eq!(monitor.check_stale(at(7_000)), vec![("bnbbtc@trade".to_owned(), true)]);
+
+        let reports = monitor.report(at(10_000));
+        let trades = &reports[1];
+        assert_eq!(trades.stream, "bnbbtc@trade");
+        assert_eq!(trades.messages, 4);
+        assert_eq!(trades.messages_per_sec, 4.0 / 9.95);
+        assert_eq!((trades.mean_latency_ms, trades.max_latency_ms), (Some(675), Some(1_500)));
+        // 102, 103 and 104 skipped, 103 arrived late
+        assert_eq!((trades.trade_id_gaps, trades.missing_trades), (1, 3));
+        assert!(trades.stale);
+
+        trade.trade_id = 106;
+        monitor.observe("bnbbtc@trade", &Observation::from(&trade), at(10_500));
+        assert_eq!(monitor.check_stale(at(11_000)), vec![("bnbbtc@trade".to_owned(), false)]);
+        assert_eq!(monitor.report(at(11_000))[1].trade_id_gaps, 1);
+    }
+
+    #[test]
+    fn kline_continuity() {
+        let mut monitor = HealthMonitor::new(Duration::from_secs(60));
+        let mut kline = map_klinemeta_to_klineinsertdata(serde_json::from_str::<KlineMetaData>(TEST_KLINE_DATA).unwrap());
+        let now = kline.event_time;
+        let minute = chrono::Duration::minutes(1);
+        let mut observe = |kline: &KlineDataInsert| monitor.observe("bnbbtc@kline_1m", &Observation::from(kline), now);
+
+        observe(&kline);
+        observe(&kline); // updated in place
+        kline.start_time = kline.close_time + chrono::Duration::milliseconds(1);
+        kline.close_time = kline.close_time + minute;
+        observe(&kline);
+        // one kline missing
+        kline.start_time = kline.start_time + minute * 2;
+        kline.close_time = kline.close_time + minute * 2;
+        observe(&kline);
+
+        assert_eq!(monitor.streams["bnbbtc@kline_1m"].kline_gaps, 1);
+    }
+}
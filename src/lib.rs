@@ -7,6 +7,7 @@
 extern crate chrono;
 extern crate clap;
 extern crate regex;
+extern crate toml;
 extern crate scraper;
 
 extern crate serde;
@@ -27,15 +28,43 @@ extern crate actix_web;
 extern crate futures;
 
 #[macro_use] extern crate diesel;
+#[macro_use] extern crate diesel_migrations;
+extern crate bigdecimal;
 extern crate dotenv;
+extern crate flate2;
+#[macro_use] extern crate lazy_static;
+extern crate prometheus;
 extern crate redis;
 extern crate uuid;
 
 // pub mod coinmarketcap;
-pub mod currency_pairs;
+pub mod account_state;
+pub mod backfill;
+pub mod backtest;
+pub mod backoff;
+pub mod candles;
+pub mod config;
+pub mod db_writer;
+pub mod error;
+pub mod gateway;
+pub mod health;
+pub mod logging;
+pub mod metrics;
 pub mod models;
+pub mod order_book;
+pub mod order_manager;
+pub mod paper;
+pub mod pubsub;
+pub mod query;
+pub mod recorder;
+pub mod rest_client;
+pub mod risk;
 pub mod schema;
 pub mod serde_parsers;
+pub mod symbols;
+
+#[cfg(test)]
+mod mock_server;
 
 use crate::models::aggregate_trades::AggregateTradeData;
 use crate::models::book_depth::{BookDepthData, BookDepthDataInsert};
@@ -46,6 +75,9 @@ use crate::models::tickers::TickerDataInsert;
 
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 
 
 
@@ -57,6 +89,26 @@ pub fn establish_connection_pg() -> PgConnection {
     PgConnection::establish(&database_url).expect(&format!("Error connecting to {}", database_url))
 }
 
+/// Connection pool for the long running writers, see `db_writer`
+pub fn establish_pool_pg(max_size: u32) -> PgPool {
+    dotenv::dotenv().ok();
+
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env");
+    let manager = ConnectionManager::<PgConnection>::new(database_url.clone());
+
+    Pool::builder()
+        .max_size(max_size)
+        .build(manager)
+        .expect(&format!("Error creating connection pool for {}", database_url))
+}
+
+embed_migrations!("migrations");
+
+/// Apply every migration in `migrations/` that Postgres hasn't recorded yet
+pub fn run_migrations(conn: &PgConnection) -> Result<(), diesel_migrations::RunMigrationsError> {
+    embedded_migrations::run_with_output(conn, &mut std::io::stdout())
+}
+
 pub fn create_trade<'a>(conn: &PgConnection, trade_data: &TradeData) {
     use crate::schema::trades;
     use diesel::prelude::*; // .get_result trait
@@ -70,7 +122,7 @@ pub fn create_trade<'a>(conn: &PgConnection, trade_data: &TradeData) {
     //     .get_result(conn)
     //     .expect("Error saving new trade");
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 pub fn create_aggregate_trade<'a>(conn: &PgConnection, aggregate_trade_data: &AggregateTradeData) {
@@ -81,7 +133,7 @@ pub fn create_aggregate_trade<'a>(conn: &PgConnection, aggregate_trade_data: &Ag
         .values(aggregate_trade_data)
         .execute(conn);
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 pub fn create_book_depth<'a>(conn: &PgConnection, book_depth_data: BookDepthDataInsert) {
@@ -92,18 +144,13 @@ pub fn create_book_depth<'a>(conn: &PgConnection, book_depth_data: BookDepthData
         .values(book_depth_data)
         .execute(conn);
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 pub fn create_kline<'a>(conn: &PgConnection, kline_data: KlineDataInsert) {
-    use crate::schema::klines; // DB table name
-    use diesel::prelude::*;
-
-    let res = diesel::insert_into(klines::table)
-        .values(kline_data)
-        .execute(conn);
+    let res = crate::models::klines::upsert_klines(conn, &[kline_data]);
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 pub fn create_mini_tickers<'a>(conn: &PgConnection, mini_ticker_data: MiniTickerDataInsert) {
@@ -114,7 +161,7 @@ pub fn create_mini_tickers<'a>(conn: &PgConnection, mini_ticker_data: MiniTicker
         .values(mini_ticker_data)
         .execute(conn);
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 pub fn create_tickers<'a>(conn: &PgConnection, ticker_data: TickerDataInsert) {
@@ -125,7 +172,7 @@ pub fn create_tickers<'a>(conn: &PgConnection, ticker_data: TickerDataInsert) {
         .values(ticker_data)
         .execute(conn);
 
-    println!("Database write result: {:?}\n", res);
+    debug!("Database write result: {:?}", res);
 }
 
 #[cfg(test)]
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use chrono::SecondsFormat;
+use log::kv::{self, Key, Value, VisitSource};
+use log::Record;
+
+use crate::error::Error;
+
+/////////////////////////////////////////////////////////////////
+/// Leveled log records with `stream`, `symbol` and `actor` fields,
+/// e.g: `debug!(actor = "trades", stream:% = name; "{:?}", trade)`,
+/// printed as text or one JSON object per line.
+/////////////////////////////////////////////////////////////////
+
+/// env_logger filter spec, e.g: "info,binance::actors::trades=debug". RUST_LOG takes precedence.
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(Error::InvalidEnum("LogFormat", s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Install the global logger, ignored if one is already set (e.g: in tests)
+pub fn init_logger(filters: &str, format: LogFormat) {
+    let filters = std::env::var("RUST_LOG").unwrap_or_else(|_| filters.to_owned());
+    let _ = env_logger::Builder::new()
+        .parse(&filters)
+        .format(move |buf, record| writeln!(buf, "{}", format_record(format, record)))
+        .try_init();
+}
+
+/// Key-value fields attached to a record, in the order they were given
+struct Fields(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.as_str().to_owned(), value.to_string()));
+        Ok(())
+    }
+}
+
+/// One log line, without the trailing newline
+pub fn format_record(format: LogFormat, record: &Record) -> String {
+    let ts = chrono::Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let mut fields = Fields(Vec::new());
+    let _ = record.key_values().visit(&mut fields);
+
+    match format {
+        LogFormat::Text => {
+            let mut line = format!("{} {:<5} {}: {}", ts, record.level(), record.target(), record.args());
+            for (key, value) in fields.0.iter() {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+            line
+        }
+        LogFormat::Json => {
+            let json = |s: &str| serde_json::to_string(s).unwrap_or_default();
+            let mut line = format!(
+                "{{\"ts\":{},\"level\":{},\"target\":{},\"msg\":{}",
+                json(&ts),
+                json(record.level().as_str()),
+                json(record.target()),
+                json(&record.args().to_string())
+            );
+            for (key, value) in fields.0.iter() {
+                line.push_str(&format!(",{}:{}", json(key), json(value)));
+            }
+            line.push('}');
+            line
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn text_and_json_lines() {
+        let fields = [("actor", "trades"), ("stream", "ethbtc@trade"), ("symbol", "ETHBTC")];
+        let format = |format: LogFormat| {
+            let record = Record::builder()
+                .args(format_args!("Websocket \"connected\""))
+                .level(Level::Info)
+                .target("binance::actors::trades")
+                .key_values(&fields)
+                .build();
+            format_record(format, &record)
+        };
+
+        let text = format(LogFormat::Text);
+        assert!(text.ends_with(
+            " INFO  binance::actors::trades: Websocket \"connected\" actor=trades stream=ethbtc@trade symbol=ETHBTC"
+        ));
+
+        let json: serde_json::Value = serde_json::from_str(&format(LogFormat::Json)).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["msg"], "Websocket \"connected\"");
+        assert_eq!(json["stream"], "ethbtc@trade");
+        assert_eq!(json["symbol"], "ETHBTC");
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    }
+}
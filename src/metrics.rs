@@ -0,0 +1,136 @@
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+use crate::error::Result;
+use crate::models::combined_stream::split_stream_name;
+
+/////////////////////////////////////////////////////////////////
+/// Prometheus metrics for the collector, served on `/metrics`.
+/// Streams are labelled by Binance stream name, e.g: "ethbtc@trade",
+/// and tables by their Postgres name.
+/////////////////////////////////////////////////////////////////
+
+pub const DEFAULT_METRICS_LISTEN: &str = "127.0.0.1:9185";
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+
+    pub static ref FRAMES_RECEIVED: IntCounterVec = counter_vec(
+        "binance_frames_received_total",
+        "Websocket text frames received",
+        &["stream", "symbol"],
+    );
+    pub static ref PARSE_FAILURES: IntCounterVec = counter_vec(
+        "binance_parse_failures_total",
+        "Frames which failed to parse, written to dead_letters",
+        &["stream"],
+    );
+    pub static ref RECONNECTS: IntCounterVec = counter_vec(
+        "binance_reconnects_total",
+        "Reconnects scheduled after a stream disconnected",
+        &["stream"],
+    );
+    pub static ref STREAM_FAILURES: IntCounterVec = counter_vec(
+        "binance_stream_failures_total",
+        "Streams given up on after exhausting their reconnect attempts",
+        &["stream"],
+    );
+    pub static ref EVENT_LATENCY: HistogramVec = histogram_vec(
+        "binance_event_latency_seconds",
+        "Exchange event time to local receive time",
+        &["stream"],
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0],
+    );
+
+    pub static ref DB_INSERT_DURATION: HistogramVec = histogram_vec(
+        "binance_db_insert_duration_seconds",
+        "Time to write one multi-row INSERT, including the connection checkout",
+        &["table"],
+        vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0],
+    );
+    pub static ref DB_ROWS_WRITTEN: IntCounterVec = counter_vec(
+        "binance_db_rows_written_total",
+        "Rows written to Postgres",
+        &["table"],
+    );
+    pub static ref DB_INSERT_ERRORS: IntCounterVec = counter_vec(
+        "binance_db_insert_errors_total",
+        "Batches which failed to write, their rows are dropped",
+        &["table"],
+    );
+    pub static ref DB_BUFFERED_ROWS: IntGauge = gauge(
+        "binance_db_buffered_rows",
+        "Rows waiting in the batcher to be written",
+    );
+    pub static ref DB_BATCHES_IN_FLIGHT: IntGauge = gauge(
+        "binance_db_batches_in_flight",
+        "INSERTs currently running",
+    );
+    pub static ref DB_PAUSED_STREAMS: IntGauge = gauge(
+        "binance_db_paused_streams",
+        "Stream actors paused until Postgres catches up",
+    );
+}
+
+fn counter_vec(name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("invalid counter");
+    REGISTRY.register(Box::new(counter.clone())).expect("counter registered twice");
+    counter
+}
+
+fn histogram_vec(name: &str, help: &str, labels: &[&str], buckets: Vec<f64>) -> HistogramVec {
+    let histogram = HistogramVec::new(HistogramOpts::new(name, help).buckets(buckets), labels).expect("invalid histogram");
+    REGISTRY.register(Box::new(histogram.clone())).expect("histogram registered twice");
+    histogram
+}
+
+fn gauge(name: &str, help: &str) -> IntGauge {
+    let gauge = IntGauge::new(name, help).expect("invalid gauge");
+    REGISTRY.register(Box::new(gauge.clone())).expect("gauge registered twice");
+    gauge
+}
+
+/// Symbol label for a stream name, "ALL" for the all-markets arrays
+pub fn stream_symbol(stream: &str) -> String {
+    match split_stream_name(stream) {
+        ("", _) => "ALL".to_owned(),
+        (symbol, _) => symbol.to_uppercase(),
+    }
+}
+
+/// Count a text frame received on `stream`, e.g: "ethbtc@trade"
+pub fn frame_received(stream: &str) {
+    FRAMES_RECEIVED.with_label_values(&[stream, &stream_symbol(stream)]).inc();
+}
+
+/// Every metric in the Prometheus text format, and its content type.
+/// Metrics register on first use, gauges are forced so they are scraped from the start.
+pub fn render() -> Result<(String, String)> {
+    lazy_static::initialize(&DB_BUFFERED_ROWS);
+    lazy_static::initialize(&DB_BATCHES_IN_FLIGHT);
+    lazy_static::initialize(&DB_PAUSED_STREAMS);
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok((String::from_utf8_lossy(&buffer).into_owned(), encoder.format_type().to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_text_format() {
+        frame_received("ethbtc@trade");
+        frame_received("ethbtc@trade");
+        frame_received("!miniTicker@arr");
+        DB_INSERT_DURATION.with_label_values(&["trades"]).observe(0.02);
+        DB_BUFFERED_ROWS.set(42);
+
+        let (text, content_type) = render().unwrap();
+        assert!(content_type.starts_with("text/plain"));
+        assert!(text.contains("binance_frames_received_total{stream=\"ethbtc@trade\",symbol=\"ETHBTC\"} 2"));
+        assert!(text.contains("binance_frames_received_total{stream=\"!miniTicker@arr\",symbol=\"ALL\"} 1"));
+        assert!(text.contains("binance_db_insert_duration_seconds_bucket{table=\"trades\",le=\"0.025\"} 1"));
+        assert!(text.contains("binance_db_buffered_rows 42"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Minimal HTTP/1.1 server standing in for Binance's REST API in tests
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,                    // raw query string, as signed
+    pub params: HashMap<String, String>,  // query and form body parameters
+    pub headers: HashMap<String, String>, // lower case names
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|s| s.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json<S: Into<String>>(status: u16, body: S) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: body.into(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+}
+
+fn parse_params(s: &str, params: &mut HashMap<String, String>) {
+    for pair in s.split('&').filter(|p| !p.is_empty()) {
+        let mut kv = pair.splitn(2, '=');
+        let key = kv.next().unwrap_or_default().to_owned();
+        let value = kv.next().unwrap_or_default().replace("%2C", ",").replace("%3A", ":");
+        params.insert(key, value);
+    }
+}
+
+/// Serve every connection with `handler` on a background thread, returns the base url
+pub fn serve<F>(mut handler: F) -> String
+where
+    F: FnMut(&MockRequest) -> MockResponse + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_owned();
+            let target = parts.next().unwrap_or_default().to_owned();
+            let mut target = target.splitn(2, '?');
+            let path = target.next().unwrap_or_default().to_owned();
+            let query = target.next().unwrap_or_default().to_owned();
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).is_err() || header == "\r\n" || header.is_empty() {
+                    break;
+                }
+                let mut kv = header.splitn(2, ':');
+                let name = kv.next().unwrap_or_default().trim().to_lowercase();
+                let value = kv.next().unwrap_or_default().trim().to_owned();
+                headers.insert(name, value);
+            }
+
+            let length = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+            let mut body = vec![0; length];
+            let _ = reader.read_exact(&mut body);
+            let body = String::from_utf8_lossy(&body).into_owned();
+
+            let mut params = HashMap::new();
+            parse_params(&query, &mut params);
+            parse_params(&body, &mut params);
+
+            let request = MockRequest { method, path, query, params, headers, body };
+            let response = handler(&request);
+
+            let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+            for (name, value) in response.headers.iter() {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(response.body.as_bytes());
+        }
+    });
+    url
+}
@@ -0,0 +1,204 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use crate::models::orders::{OrderSide, OrderStatus, OrderType, TimeInForce};
+use crate::symbols::Symbol;
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
+
+///////////////////////////////////////////////////////////////////////////////
+/// Signed REST API responses, see `rest_client::BinanceRestClient`
+///////////////////////////////////////////////////////////////////////////////
+
+/// GET `/api/v3/account`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    pub maker_commission: i32, // bips, e.g: 10 = 0.1%
+    pub taker_commission: i32,
+    pub buyer_commission: i32,
+    pub seller_commission: i32,
+    pub can_trade: bool,
+    pub can_withdraw: bool,
+    pub can_deposit: bool,
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub update_time: NaiveDateTime,
+    pub balances: Vec<Balance>,
+}
+
+impl AccountInfo {
+    /// Balance for `asset`, e.g: "BTC"
+    pub fn balance(&self, asset: &str) -> Option<&Balance> {
+        self.balances.iter().find(|b| b.asset == asset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    pub asset: String, // Asset, e.g: BTC
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub free: BigDecimal, // Available
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub locked: BigDecimal, // Held by open orders
+}
+
+/// GET `/api/v3/order` and `/api/v3/openOrders`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderInfo {
+    pub symbol: Symbol,
+    pub order_id: i64,
+    pub client_order_id: String,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub price: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub orig_qty: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub executed_qty: BigDecimal,
+    #[serde(rename = "cummulativeQuoteQty")] // sic
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub cumulative_quote_qty: BigDecimal,
+    pub status: OrderStatus,
+    pub time_in_force: TimeInForce,
+    #[serde(rename = "type")]
+    pub order_type: OrderType,
+    pub side: OrderSide,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub stop_price: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub time: NaiveDateTime,
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub update_time: NaiveDateTime,
+    pub is_working: bool,
+}
+
+/// POST `/api/v3/order` with `newOrderRespType=RESULT`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewOrderResult {
+    pub symbol: Symbol,
+    pub order_id: i64,
+    pub client_order_id: String,
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub transact_time: NaiveDateTime,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub executed_qty: BigDecimal,
+    #[serde(rename = "cummulativeQuoteQty")]
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub cumulative_quote_qty: BigDecimal,
+    pub status: OrderStatus,
+}
+
+/// DELETE `/api/v3/order`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelOrderResult {
+    pub symbol: Symbol,
+    pub order_id: i64,
+    pub orig_client_order_id: String, // id of the canceled order, `clientOrderId` is the cancel's own
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub executed_qty: BigDecimal,
+    #[serde(rename = "cummulativeQuoteQty")]
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub cumulative_quote_qty: BigDecimal,
+    pub status: OrderStatus,
+}
+
+/// GET `/api/v3/myTrades`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountTrade {
+    pub symbol: Symbol,
+    pub id: i64, // Trade ID
+    pub order_id: i64,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub price: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub qty: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub quote_qty: BigDecimal,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub commission: BigDecimal,
+    pub commission_asset: String,
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub time: NaiveDateTime,
+    pub is_buyer: bool,
+    pub is_maker: bool,
+    pub is_best_match: bool,
+}
+
+pub static TEST_ACCOUNT_DATA: &str = r#"
+{
+  "makerCommission": 15,
+  "takerCommission": 15,
+  "buyerCommission": 0,
+  "sellerCommission": 0,
+  "canTrade": true,
+  "canWithdraw": true,
+  "canDeposit": true,
+  "updateTime": 1555444333222,
+  "balances": [
+    { "asset": "BTC", "free": "4723846.89208129", "locked": "0.00000000" },
+    { "asset": "LTC", "free": "4763368.68006011", "locked": "0.00000000" }
+  ]
+}
+"#;
+
+pub static TEST_ORDER_DATA: &str = r#"
+{
+  "symbol": "LTCBTC",
+  "orderId": 1,
+  "clientOrderId": "myOrder1",
+  "price": "0.1",
+  "origQty": "1.0",
+  "executedQty": "0.0",
+  "cummulativeQuoteQty": "0.0",
+  "status": "NEW",
+  "timeInForce": "GTC",
+  "type": "LIMIT",
+  "side": "BUY",
+  "stopPrice": "0.0",
+  "icebergQty": "0.0",
+  "time": 1555444333222,
+  "updateTime": 1555444333222,
+  "isWorking": true
+}
+"#;
+
+pub static TEST_MY_TRADES_DATA: &str = r#"
+[
+  {
+    "symbol": "BNBBTC",
+    "id": 28457,
+    "orderId": 100234,
+    "price": "4.00000100",
+    "qty": "12.00000000",
+    "quoteQty": "48.000012",
+    "commission": "10.10000000",
+    "commissionAsset": "BNB",
+    "time": 1555444333222,
+    "isBuyer": true,
+    "isMaker": false,
+    "isBestMatch": true
+  }
+]
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde_parsers::create_decimal_benchmark;
+
+    #[test]
+    fn try_deserialize_account_responses() {
+        let account = serde_json::from_str::<AccountInfo>(TEST_ACCOUNT_DATA).unwrap();
+        assert_eq!(account.balance("BTC").unwrap().free, create_decimal_benchmark("4723846.89208129"));
+        assert!(account.balance("ETH").is_none());
+
+        let order = serde_json::from_str::<OrderInfo>(TEST_ORDER_DATA).unwrap();
+        assert_eq!(order.symbol, Symbol::new("LTCBTC"));
+        assert_eq!((order.order_type, order.status), (OrderType::Limit, OrderStatus::New));
+
+        let trades = serde_json::from_str::<Vec<AccountTrade>>(TEST_MY_TRADES_DATA).unwrap();
+        assert_eq!(trades[0].quote_qty, create_decimal_benchmark("48.000012"));
+    }
+}
@@ -1,35 +1,36 @@
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::fmt;
 
-use crate::currency_pairs::CurrencyPair;
+use crate::symbols::Symbol;
 use crate::schema::aggregate_trades;
-use crate::serde_parsers::{deserialize_as_f32, deserialize_as_naive_date_time_ms};
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
 
 ///////////////////////////////////////////////////////////////////////////////
-#[derive(Debug, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "aggregate_trades"]
 pub struct AggregateTradeData {
     #[serde(rename = "a")]
-    pub trade_id: i32, // Trade ID
+    pub trade_id: i64, // Trade ID
     #[serde(rename = "e")]
     pub event: String, // Event type
     #[serde(rename = "E")]
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub event_time: NaiveDateTime, // Event time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
-    #[serde(deserialize_with = "deserialize_as_f32")]
+    pub symbol: Symbol, // Symbol
+    #[serde(deserialize_with = "deserialize_as_decimal")]
     #[serde(rename = "p")]
-    pub price: f32, // Bids to be updated
-    #[serde(deserialize_with = "deserialize_as_f32")]
+    pub price: BigDecimal, // Bids to be updated
+    #[serde(deserialize_with = "deserialize_as_decimal")]
     #[serde(rename = "q")]
-    pub quantity: f32, // Asks to be updated
+    pub quantity: BigDecimal, // Asks to be updated
     #[serde(rename = "f")]
-    pub first_trade_id: i32, // First update ID in event
+    pub first_trade_id: i64, // First update ID in event
     #[serde(rename = "l")]
-    pub last_trade_id: i32, // Final update ID in event
+    pub last_trade_id: i64, // Final update ID in event
     #[serde(rename = "T")]
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub trade_time: NaiveDateTime, // Final update ID in event
@@ -69,7 +70,7 @@ mod tests {
             AggregateTradeData,
             TEST_AGGTRADE_DATA,
         };
-        use crate::serde_parsers::create_timestamp_benchmark;
+        use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
 
         let aggtrade_data = serde_json::from_str::<AggregateTradeData>(&TEST_AGGTRADE_DATA).unwrap();
 
@@ -77,9 +78,9 @@ mod tests {
             trade_id: 12345,
             event: "aggTrade".to_owned(),
             event_time: create_timestamp_benchmark(1_555_444_333_222),
-            symbol: crate::currency_pairs::CurrencyPair::BNBBTC,
-            price: 0.001,
-            quantity: 100.0,
+            symbol: crate::symbols::Symbol::new("BNBBTC"),
+            price: create_decimal_benchmark("0.001"),
+            quantity: create_decimal_benchmark("100.0"),
             first_trade_id: 100,
             last_trade_id: 105,
             trade_time: create_timestamp_benchmark(1_666_555_444_333),
@@ -0,0 +1,51 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+
+use crate::models::klines::KlineDataInsert;
+use crate::schema::bars;
+use crate::symbols::Symbol;
+
+/// A closed tick, volume or dollar bar. Unlike klines these don't line up with the clock,
+/// so they are keyed by their first trade ID instead of the start time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "bars"]
+pub struct BarInsert {
+    pub symbol: Symbol,
+    pub spec: String, // `BarSpec`, e.g: tick:100, volume:50, dollar:1000
+    pub first_trade_id: i64,
+    pub last_trade_id: i64,
+    pub start_time: NaiveDateTime, // first trade time
+    pub close_time: NaiveDateTime, // last trade time
+    pub open: BigDecimal,
+    pub close: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub volume: BigDecimal,
+    pub num_of_trades: i32,
+    pub quote_asset_vol: BigDecimal,
+    pub taker_buy_base_vol: BigDecimal,
+    pub taker_buy_quote_vol: BigDecimal,
+}
+
+/// `CandleBuilder` rolls every kind of bar into a kline row
+impl From<KlineDataInsert> for BarInsert {
+    fn from(kline: KlineDataInsert) -> Self {
+        BarInsert {
+            symbol: kline.symbol,
+            spec: kline.interval,
+            first_trade_id: kline.first_trade_id,
+            last_trade_id: kline.last_trade_id,
+            start_time: kline.start_time,
+            close_time: kline.close_time,
+            open: kline.open,
+            close: kline.close,
+            high: kline.high,
+            low: kline.low,
+            volume: kline.volume,
+            num_of_trades: kline.num_of_trades,
+            quote_asset_vol: kline.quote_asset_vol,
+            taker_buy_base_vol: kline.taker_buy_base_vol,
+            taker_buy_quote_vol: kline.taker_buy_quote_vol,
+        }
+    }
+}
@@ -1,20 +1,21 @@
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::fmt;
 
-use crate::currency_pairs::CurrencyPair;
-use crate::schema::book_depth;
-use crate::serde_parsers::{deserialize_as_f32, deserialize_as_naive_date_time_ms};
+use crate::symbols::Symbol;
+use crate::schema::{book_depth, book_snapshots};
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
 
-#[derive(Queryable)]
+#[derive(Debug, Clone, Queryable)]
 pub struct BookDepthData {
     pub id: i32,
     pub event: String,             // Event type
     pub event_time: NaiveDateTime, // Event time
-    pub symbol: CurrencyPair,      // Symbol
-    pub update_first: i32,         // First update ID in event
-    pub update_final: i32,         // Final update ID in event
+    pub symbol: Symbol,            // Symbol
+    pub update_first: i64,         // First update ID in event
+    pub update_final: i64,         // Final update ID in event
     pub bids: Vec<Quote>,          // Bids to be updated
     pub asks: Vec<Quote>,          // Asks to be updated
 }
@@ -22,7 +23,7 @@ pub struct BookDepthData {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PartialBookDepthData {
     #[serde(rename = "lastUpdateId")]
-    pub last_update_id: i32, // Last update ID
+    pub last_update_id: i64, // Last update ID
     pub bids: Vec<Quote>, // Bids to be updated
     pub asks: Vec<Quote>, // Asks to be updated
 }
@@ -34,8 +35,34 @@ impl fmt::Display for PartialBookDepthData {
     }
 }
 
+/// Top-N levels from a depth5/10/20 stream, stored as received. The payload has
+/// no symbol or event time, so both come from the subscription and the receive time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "book_snapshots"]
+pub struct BookSnapshot {
+    pub symbol: Symbol,             // Symbol
+    pub last_update_id: i64,        // Last update ID
+    pub captured_at: NaiveDateTime, // Receive time
+    pub levels: i32,                // Subscribed depth, 5, 10 or 20
+    pub bids: Vec<Quote>,           // Best bid first
+    pub asks: Vec<Quote>,           // Best ask first
+}
+
+impl BookSnapshot {
+    pub fn new(symbol: Symbol, levels: &DepthLevels, captured_at: NaiveDateTime, book: PartialBookDepthData) -> Self {
+        BookSnapshot {
+            symbol,
+            last_update_id: book.last_update_id,
+            captured_at,
+            levels: levels.count(),
+            bids: book.bids,
+            asks: book.asks,
+        }
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
-#[derive(Debug, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "book_depth"]
 pub struct BookDepthDataInsert {
     #[serde(rename = "e")]
@@ -44,11 +71,11 @@ pub struct BookDepthDataInsert {
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub event_time: NaiveDateTime, // Event time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
+    pub symbol: Symbol, // Symbol
     #[serde(rename = "U")]
-    pub update_first: i32, // First update ID in event
+    pub update_first: i64, // First update ID in event
     #[serde(rename = "u")]
-    pub update_final: i32, // Final update ID in event
+    pub update_final: i64, // Final update ID in event
     #[serde(rename = "b")]
     pub bids: Vec<Quote>, // Bids to be updated
     #[serde(rename = "a")]
@@ -64,8 +91,8 @@ impl fmt::Display for BookDepthDataInsert {
 
 #[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct Quote {
-    pub price: f32,
-    pub quantity: f32,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
 }
 /// This trait deserializes nested vector/strings: ["0.00123", "150.4", []]
 /// and turns it into Quote { price: 0.00123, quantity: 150.4 }
@@ -87,7 +114,7 @@ impl<'de> Deserialize<'de> for Quote {
             where
                 M: de::MapAccess<'de>,
             {
-                let mut h: std::collections::HashMap<String, f32> =
+                let mut h: std::collections::HashMap<String, BigDecimal> =
                     std::collections::HashMap::new();
                 loop {
                     match dict.next_entry()? {
@@ -98,8 +125,8 @@ impl<'de> Deserialize<'de> for Quote {
                     }
                 }
                 Ok(Quote {
-                    price: *h.get("price").unwrap(),
-                    quantity: *h.get("quantity").unwrap(),
+                    price: h.remove("price").ok_or_else(|| de::Error::missing_field("price"))?,
+                    quantity: h.remove("quantity").ok_or_else(|| de::Error::missing_field("quantity"))?,
                 })
             }
 
@@ -111,15 +138,17 @@ impl<'de> Deserialize<'de> for Quote {
                 // visitor iterators nested vector/string: ["0.00123", "150.4", []]
                 loop {
                     match visitor.next_element()? {
-                        Some(StringOrVec::Price(elem)) => vec.push(elem), // convert strings to f64
+                        Some(StringOrVec::Price(elem)) => vec.push(elem), // convert strings to BigDecimal
                         Some(StringOrVec::Vec(elem)) => continue,         // skip vectors []
                         None => break, // break, when next_element() is empty
                     }
                 }
-                Ok(Quote {
-                    price: vec[0],
-                    quantity: vec[1],
-                })
+                if vec.len() < 2 {
+                    return Err(de::Error::invalid_length(vec.len(), &self));
+                }
+                let quantity = vec.remove(1);
+                let price = vec.remove(0);
+                Ok(Quote { price, quantity })
             }
         }
         deserializer.deserialize_any(QuoteVisitor)
@@ -130,8 +159,8 @@ impl<'de> Deserialize<'de> for Quote {
 /// StringOrVec::Price(elem) and StringOrVec::Vec(elem) accordingly
 #[derive(Debug, Serialize, Clone)]
 pub enum StringOrVec {
-    Price(f32),
-    Vec(Option<Vec<f32>>),
+    Price(BigDecimal),
+    Vec(Option<Vec<BigDecimal>>),
 }
 impl<'de> Deserialize<'de> for StringOrVec {
     fn deserialize<D>(deserializer: D) -> Result<StringOrVec, D::Error>
@@ -151,7 +180,10 @@ impl<'de> Deserialize<'de> for StringOrVec {
             where
                 E: de::Error,
             {
-                Ok(StringOrVec::Price(value.to_owned().parse::<f32>().unwrap()))
+                value
+                    .parse::<BigDecimal>()
+                    .map(StringOrVec::Price)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
             }
 
             fn visit_seq<S>(self, visitor: S) -> Result<Self::Value, S::Error>
@@ -186,7 +218,7 @@ impl FromSql<Jsonb, Pg> for Quote {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DepthLevels {
     _5,
     _10,
@@ -202,6 +234,79 @@ impl fmt::Display for DepthLevels {
     }
 }
 
+impl DepthLevels {
+    pub fn count(&self) -> i32 {
+        match self {
+            DepthLevels::_5 => 5,
+            DepthLevels::_10 => 10,
+            DepthLevels::_20 => 20,
+        }
+    }
+}
+
+impl std::str::FromStr for DepthLevels {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<DepthLevels, Self::Err> {
+        match s {
+            "5" => Ok(DepthLevels::_5),
+            "10" => Ok(DepthLevels::_10),
+            "20" => Ok(DepthLevels::_20),
+            _ => Err(crate::error::Error::InvalidEnum("DepthLevels", s.to_string())),
+        }
+    }
+}
+
+/// How often depth streams push, Binance defaults to every 1000ms
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateSpeed {
+    _1000ms,
+    _100ms,
+}
+
+impl Default for UpdateSpeed {
+    fn default() -> Self {
+        UpdateSpeed::_1000ms
+    }
+}
+
+impl UpdateSpeed {
+    /// Appended to the stream name, e.g: "ethbtc@depth10@100ms"
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            UpdateSpeed::_1000ms => "",
+            UpdateSpeed::_100ms => "@100ms",
+        }
+    }
+}
+
+impl std::str::FromStr for UpdateSpeed {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<UpdateSpeed, Self::Err> {
+        match s {
+            "1000ms" => Ok(UpdateSpeed::_1000ms),
+            "100ms" => Ok(UpdateSpeed::_100ms),
+            _ => Err(crate::error::Error::InvalidEnum("UpdateSpeed", s.to_string())),
+        }
+    }
+}
+
+/// Levels and update speed of a depth stream type, e.g: "depth" is the diff stream at 1000ms,
+/// "depth20@100ms" the top 20 levels every 100ms
+pub fn parse_depth_stream(stream_type: &str) -> crate::error::Result<(Option<DepthLevels>, UpdateSpeed)> {
+    if !stream_type.starts_with("depth") {
+        return Err(crate::error::Error::InvalidEnum("depth stream", stream_type.to_string()));
+    }
+    let mut parts = stream_type["depth".len()..].splitn(2, '@');
+    let levels = match parts.next() {
+        Some("") | None => None,
+        Some(levels) => Some(levels.parse()?),
+    };
+    let speed = parts.next().map(str::parse::<UpdateSpeed>).transpose()?.unwrap_or_default();
+    Ok((levels, speed))
+}
+
 
 
 pub static TEST_BOOKDEPTH_DATA: &str = r#"
@@ -238,19 +343,62 @@ mod tests {
             BookDepthDataInsert,
             TEST_BOOKDEPTH_DATA,
         };
-        use crate::serde_parsers::create_timestamp_benchmark;
+        use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
 
         let test_book_depth_data = serde_json::from_str::<BookDepthDataInsert>(&TEST_BOOKDEPTH_DATA).unwrap();
 
         let mock_data = BookDepthDataInsert {
             event: "depthUpdate".to_owned(),
             event_time: create_timestamp_benchmark(1_555_444_333_222),
-            symbol: crate::currency_pairs::CurrencyPair::BNBBTC,
+            symbol: crate::symbols::Symbol::new("BNBBTC"),
             update_first: 157,
             update_final: 160,
-            bids: vec![Quote { price: 0.0024, quantity: 10.0 }],
-            asks: vec![Quote { price: 0.0026, quantity: 100.0 }],
+            bids: vec![Quote { price: create_decimal_benchmark("0.0024"), quantity: create_decimal_benchmark("10") }],
+            asks: vec![Quote { price: create_decimal_benchmark("0.0026"), quantity: create_decimal_benchmark("100") }],
         };
         assert_eq!(test_book_depth_data, mock_data)
     }
+
+    #[test]
+    fn db_book_snapshots_are_keyed_by_update_id_and_time() {
+        use crate::db_writer::Batch;
+        use crate::establish_connection_pg;
+        use crate::serde_parsers::create_timestamp_benchmark;
+        use diesel::connection::Connection;
+        use diesel::prelude::*;
+
+        let book = r#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}"#;
+        let book = serde_json::from_str::<PartialBookDepthData>(book).unwrap();
+        let captured_at = create_timestamp_benchmark(1_555_444_333_222);
+        let snapshot = BookSnapshot::new(crate::symbols::Symbol::new("BNBBTC"), &DepthLevels::_5, captured_at, book);
+        assert_eq!(snapshot.levels, 5);
+
+        let conn = establish_connection_pg();
+        conn.begin_test_transaction().unwrap();
+        let batch = Batch::BookSnapshots(vec![snapshot.clone()]);
+        assert_eq!(batch.write(&conn).unwrap(), 1);
+        assert_eq!(batch.write(&conn).unwrap(), 0); // replayed
+        let stored = book_snapshots::table.load::<BookSnapshot>(&conn).unwrap();
+        assert_eq!(stored, vec![snapshot]);
+    }
+
+    #[test]
+    fn malformed_quotes_are_errors() {
+        assert!(serde_json::from_str::<Quote>(r#"["0.0024"]"#).is_err());
+        assert!(serde_json::from_str::<Quote>(r#"["0.0024", "ten", []]"#).is_err());
+        assert!(serde_json::from_str::<Quote>(r#"{"price": "0.0024"}"#).is_err());
+        let quote = serde_json::from_str::<Quote>(r#"{"price": "0.0024", "quantity": 10}"#).unwrap();
+        assert_eq!(quote.quantity, bigdecimal::BigDecimal::from(10));
+    }
+
+    #[test]
+    fn depth_stream_types() {
+        assert_eq!(parse_depth_stream("depth").unwrap(), (None, UpdateSpeed::_1000ms));
+        assert_eq!(parse_depth_stream("depth@100ms").unwrap(), (None, UpdateSpeed::_100ms));
+        assert_eq!(parse_depth_stream("depth10").unwrap(), (Some(DepthLevels::_10), UpdateSpeed::_1000ms));
+        assert_eq!(parse_depth_stream("depth5@100ms").unwrap(), (Some(DepthLevels::_5), UpdateSpeed::_100ms));
+        assert!(parse_depth_stream("depth10@10ms").is_err());
+        assert!(parse_depth_stream("depth15").is_err());
+        assert!(parse_depth_stream("trade").is_err());
+    }
 }
@@ -0,0 +1,120 @@
+use serde_json::value::RawValue;
+use std::fmt;
+
+/// Anything received on a `/stream?streams=a/b/c` connection:
+/// either a wrapped market event, or the reply to a SUBSCRIBE/UNSUBSCRIBE request.
+#[derive(Debug)]
+pub enum CombinedMessage {
+    Event(CombinedStreamEnvelope),
+    Response(SubscriptionResponse),
+}
+
+/// Combined stream events are wrapped as: {"stream":"<streamName>","data":<rawPayload>}.
+/// `data` is kept as received, and parsed by the stream's handler as on its own socket.
+#[derive(Debug, Deserialize)]
+pub struct CombinedStreamEnvelope {
+    pub stream: String,
+    pub data: Box<RawValue>,
+}
+
+/// "ethbtc@depth@100ms" -> ("ethbtc", "depth@100ms"), "!miniTicker@arr" -> ("", "miniTicker@arr")
+pub fn split_stream_name(stream: &str) -> (&str, &str) {
+    if stream.starts_with('!') {
+        return ("", &stream[1..]);
+    }
+    match stream.find('@') {
+        Some(i) => (&stream[..i], &stream[i + 1..]),
+        None => ("", stream),
+    }
+}
+
+/// Raw payloads can't be buffered by `#[serde(untagged)]`, so try an event first, then a reply
+pub fn parse_combined_message(txt: &str) -> serde_json::Result<CombinedMessage> {
+    match serde_json::from_str::<CombinedStreamEnvelope>(txt) {
+        Ok(envelope) => Ok(CombinedMessage::Event(envelope)),
+        Err(e) => serde_json::from_str::<SubscriptionResponse>(txt)
+            .map(CombinedMessage::Response)
+            .map_err(|_| e),
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Live subscription management over an open connection
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum SubscriptionMethod {
+    SUBSCRIBE,
+    UNSUBSCRIBE,
+    LIST_SUBSCRIPTIONS,
+}
+
+/// e.g: {"method":"SUBSCRIBE","params":["btcusdt@aggTrade","btcusdt@depth"],"id":1}
+#[derive(Debug, Serialize)]
+pub struct SubscriptionRequest {
+    pub method: SubscriptionMethod,
+    pub params: Vec<String>,
+    pub id: u64,
+}
+
+/// e.g: {"result":null,"id":1}
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SubscriptionResponse {
+    pub result: Option<serde_json::Value>,
+    pub id: u64,
+}
+
+impl fmt::Display for SubscriptionRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(&self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tickers::TEST_TICKER_DATA;
+    use crate::models::trades::TEST_TRADE_DATA;
+
+    #[test]
+    fn try_unwrap_combined_stream_events() {
+        let trade_msg = format!(r#"{{"stream":"bnbbtc@trade","data":{}}}"#, TEST_TRADE_DATA);
+        match parse_combined_message(&trade_msg).unwrap() {
+            CombinedMessage::Event(envelope) => {
+                assert_eq!(envelope.stream, "bnbbtc@trade");
+                assert_eq!(envelope.data.get(), TEST_TRADE_DATA.trim());
+            }
+            other => panic!("Expected an event, got {:?}", other),
+        }
+
+        let tickers_msg = format!(r#"{{"stream":"!ticker@arr","data":[{},{}]}}"#, TEST_TICKER_DATA, TEST_TICKER_DATA);
+        match parse_combined_message(&tickers_msg).unwrap() {
+            CombinedMessage::Event(envelope) => assert_eq!(envelope.stream, "!ticker@arr"),
+            other => panic!("Expected every market's ticker, got {:?}", other),
+        }
+        assert!(parse_combined_message(r#"{"stream":"bnbbtc@trade"}"#).is_err());
+    }
+
+    #[test]
+    fn try_subscription_messages() {
+        let request = SubscriptionRequest {
+            method: SubscriptionMethod::SUBSCRIBE,
+            params: vec!["ethbtc@trade".to_string()],
+            id: 1,
+        };
+        assert_eq!(
+            request.to_string(),
+            r#"{"method":"SUBSCRIBE","params":["ethbtc@trade"],"id":1}"#
+        );
+
+        match parse_combined_message(r#"{"result":null,"id":1}"#).unwrap() {
+            CombinedMessage::Response(resp) => assert_eq!(resp, SubscriptionResponse { result: None, id: 1 }),
+            other => panic!("Expected a subscription response, got {:?}", other),
+        }
+
+        assert_eq!(split_stream_name("!miniTicker@arr"), ("", "miniTicker@arr"));
+        assert_eq!(split_stream_name("ethbtc@depth10@100ms"), ("ethbtc", "depth10@100ms"));
+    }
+}
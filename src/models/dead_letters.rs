@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+
+use crate::schema::dead_letters;
+
+/// A websocket frame which could not be parsed, kept for inspection instead of crashing the stream
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Insertable)]
+#[table_name = "dead_letters"]
+pub struct DeadLetterInsert {
+    pub received_at: NaiveDateTime, // Local time the frame was received
+    pub stream: String,             // Stream name, e.g: "ethbtc@trade"
+    pub payload: String,            // Raw frame
+    pub error: String,              // Why it was rejected
+}
+
+impl DeadLetterInsert {
+    pub fn new(stream: &str, payload: &str, error: &crate::error::Error) -> Self {
+        DeadLetterInsert {
+            received_at: chrono::Utc::now().naive_utc(),
+            stream: stream.to_string(),
+            payload: payload.to_string(),
+            error: error.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct DeadLetter {
+    pub id: i32,                    // PostgreSQL ID
+    pub received_at: NaiveDateTime, // Local time the frame was received
+    pub stream: String,             // Stream name
+    pub payload: String,            // Raw frame
+    pub error: String,              // Why it was rejected
+}
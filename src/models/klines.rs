@@ -1,11 +1,18 @@
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
 use serde::de;
-use serde::de::{Deserialize, Deserializer};
+use serde::de::{Deserialize, Deserializer, IgnoredAny};
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 
-use crate::currency_pairs::CurrencyPair;
+use crate::error::Error;
+use crate::symbols::Symbol;
 use crate::schema::klines;
-use crate::serde_parsers::{deserialize_as_f32, deserialize_as_naive_date_time_ms};
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct KlineMetaData {
@@ -15,7 +22,7 @@ pub struct KlineMetaData {
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub event_time: NaiveDateTime, // Event time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
+    pub symbol: Symbol, // Symbol
     #[serde(rename = "k")]
     pub kline_data: NewKlineData, //  without id
 }
@@ -36,41 +43,41 @@ pub struct NewKlineData {
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub close_time: NaiveDateTime, // Kline close time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
+    pub symbol: Symbol, // Symbol
     #[serde(rename = "i")]
     pub interval: String, // Kline Intervel
     #[serde(rename = "f")]
-    pub first_trade_id: i32, // First trade ID
+    pub first_trade_id: i64, // First trade ID
     #[serde(rename = "L")]
-    pub last_trade_id: i32, // Last trade ID
+    pub last_trade_id: i64, // Last trade ID
     #[serde(rename = "o")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub open: f32, // Open price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub open: BigDecimal, // Open price
     #[serde(rename = "c")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub close: f32, // Close price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub close: BigDecimal, // Close price
     #[serde(rename = "h")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub high: f32, // High price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub high: BigDecimal, // High price
     #[serde(rename = "l")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub low: f32, // Low price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub low: BigDecimal, // Low price
     #[serde(rename = "v")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub volume: f32, // Volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub volume: BigDecimal, // Volume
     #[serde(rename = "n")]
     pub num_of_trades: i32, // Number of trades
     #[serde(rename = "x")]
     pub is_kline_closed: bool, // Is this Kline closed?
     #[serde(rename = "q")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub quote_asset_vol: f32, // Quote asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub quote_asset_vol: BigDecimal, // Quote asset volume
     #[serde(rename = "V")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub taker_buy_base_vol: f32, // Taker buy base asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub taker_buy_base_vol: BigDecimal, // Taker buy base asset volume
     #[serde(rename = "Q")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub taker_buy_quote_vol: f32, // Taker buy quote asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub taker_buy_quote_vol: BigDecimal, // Taker buy quote asset volume
 }
 
 impl fmt::Display for NewKlineData {
@@ -80,30 +87,30 @@ impl fmt::Display for NewKlineData {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "klines"]
 pub struct KlineDataInsert {
     pub event: String,
     pub event_time: NaiveDateTime,
     pub start_time: NaiveDateTime,
     pub close_time: NaiveDateTime,
-    pub symbol: CurrencyPair,
+    pub symbol: Symbol,
     pub interval: String,
-    pub first_trade_id: i32,
-    pub last_trade_id: i32,
-    pub open: f32,
-    pub close: f32,
-    pub high: f32,
-    pub low: f32,
-    pub volume: f32,
+    pub first_trade_id: i64,
+    pub last_trade_id: i64,
+    pub open: BigDecimal,
+    pub close: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub volume: BigDecimal,
     pub num_of_trades: i32,
     pub is_kline_closed: bool,
-    pub quote_asset_vol: f32,
-    pub taker_buy_base_vol: f32,
-    pub taker_buy_quote_vol: f32,
+    pub quote_asset_vol: BigDecimal,
+    pub taker_buy_base_vol: BigDecimal,
+    pub taker_buy_quote_vol: BigDecimal,
 }
 
-#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "klines"]
 pub struct KlineData {
     pub id: i32,
@@ -111,23 +118,23 @@ pub struct KlineData {
     pub event_time: NaiveDateTime,
     pub start_time: NaiveDateTime,
     pub close_time: NaiveDateTime,
-    pub symbol: CurrencyPair,
+    pub symbol: Symbol,
     pub interval: String,
-    pub first_trade_id: i32,
-    pub last_trade_id: i32,
-    pub open: f32,
-    pub close: f32,
-    pub high: f32,
-    pub low: f32,
-    pub volume: f32,
+    pub first_trade_id: i64,
+    pub last_trade_id: i64,
+    pub open: BigDecimal,
+    pub close: BigDecimal,
+    pub high: BigDecimal,
+    pub low: BigDecimal,
+    pub volume: BigDecimal,
     pub num_of_trades: i32,
     pub is_kline_closed: bool,
-    pub quote_asset_vol: f32,
-    pub taker_buy_base_vol: f32,
-    pub taker_buy_quote_vol: f32,
+    pub quote_asset_vol: BigDecimal,
+    pub taker_buy_base_vol: BigDecimal,
+    pub taker_buy_quote_vol: BigDecimal,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum KlineInterval {
     _1m,
     _3m,
@@ -167,6 +174,198 @@ impl std::fmt::Display for KlineInterval {
     }
 }
 
+impl KlineInterval {
+    /// Fixed length in milliseconds, None for calendar months
+    pub fn duration_ms(&self) -> Option<i64> {
+        const MINUTE: i64 = 60_000;
+        const HOUR: i64 = 60 * MINUTE;
+        const DAY: i64 = 24 * HOUR;
+        match self {
+            KlineInterval::_1m => Some(MINUTE),
+            KlineInterval::_3m => Some(3 * MINUTE),
+            KlineInterval::_5m => Some(5 * MINUTE),
+            KlineInterval::_15m => Some(15 * MINUTE),
+            KlineInterval::_30m => Some(30 * MINUTE),
+            KlineInterval::_1h => Some(HOUR),
+            KlineInterval::_2h => Some(2 * HOUR),
+            KlineInterval::_4h => Some(4 * HOUR),
+            KlineInterval::_6h => Some(6 * HOUR),
+            KlineInterval::_8h => Some(8 * HOUR),
+            KlineInterval::_12h => Some(12 * HOUR),
+            KlineInterval::_1d => Some(DAY),
+            KlineInterval::_3d => Some(3 * DAY),
+            KlineInterval::_1w => Some(7 * DAY),
+            KlineInterval::_1M => None,
+        }
+    }
+
+    /// Start of the interval containing `t`, aligned like Binance's klines:
+    /// from the unix epoch in UTC, weeks start on Monday, months on the 1st.
+    pub fn start_of(&self, t: NaiveDateTime) -> NaiveDateTime {
+        use chrono::Datelike;
+
+        const MONDAY_OFFSET_MS: i64 = 4 * 24 * 3_600_000; // 1970-01-01 was a Thursday
+        let ms = t.timestamp_millis();
+        let start_ms = match (self, self.duration_ms()) {
+            (KlineInterval::_1w, Some(d)) => (ms - MONDAY_OFFSET_MS).div_euclid(d) * d + MONDAY_OFFSET_MS,
+            (_, Some(d)) => ms.div_euclid(d) * d,
+            (_, None) => return t.date().with_day(1).unwrap().and_hms(0, 0, 0),
+        };
+        NaiveDateTime::from_timestamp(start_ms.div_euclid(1000), (start_ms.rem_euclid(1000) * 1_000_000) as u32)
+    }
+
+    /// Start of the interval after the one starting at `start`
+    pub fn next_start(&self, start: NaiveDateTime) -> NaiveDateTime {
+        use chrono::Datelike;
+
+        match self.duration_ms() {
+            Some(d) => start + chrono::Duration::milliseconds(d),
+            None if start.month() == 12 => chrono::NaiveDate::from_ymd(start.year() + 1, 1, 1).and_hms(0, 0, 0),
+            None => chrono::NaiveDate::from_ymd(start.year(), start.month() + 1, 1).and_hms(0, 0, 0),
+        }
+    }
+}
+
+impl FromStr for KlineInterval {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KlineInterval, Error> {
+        match s {
+            "1m" => Ok(KlineInterval::_1m),
+            "3m" => Ok(KlineInterval::_3m),
+            "5m" => Ok(KlineInterval::_5m),
+            "15m" => Ok(KlineInterval::_15m),
+            "30m" => Ok(KlineInterval::_30m),
+            "1h" => Ok(KlineInterval::_1h),
+            "2h" => Ok(KlineInterval::_2h),
+            "4h" => Ok(KlineInterval::_4h),
+            "6h" => Ok(KlineInterval::_6h),
+            "8h" => Ok(KlineInterval::_8h),
+            "12h" => Ok(KlineInterval::_12h),
+            "1d" => Ok(KlineInterval::_1d),
+            "3d" => Ok(KlineInterval::_3d),
+            "1w" => Ok(KlineInterval::_1w),
+            "1M" => Ok(KlineInterval::_1M),
+            _ => Err(Error::InvalidInterval(s.to_string())),
+        }
+    }
+}
+
+/// Kline from the REST `/api/v3/klines` endpoint, sent as an array without field names
+#[derive(Debug, Deserialize)]
+pub struct RestKline {
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub start_time: NaiveDateTime, // Kline start time
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub open: BigDecimal, // Open price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub high: BigDecimal, // High price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub low: BigDecimal, // Low price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub close: BigDecimal, // Close price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub volume: BigDecimal, // Volume
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub close_time: NaiveDateTime, // Kline close time
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub quote_asset_vol: BigDecimal, // Quote asset volume
+    pub num_of_trades: i32, // Number of trades
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub taker_buy_base_vol: BigDecimal, // Taker buy base asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub taker_buy_quote_vol: BigDecimal, // Taker buy quote asset volume
+    pub ignore: IgnoredAny, // Unused field
+}
+
+impl RestKline {
+    /// The REST endpoint has no trade ids, they are stored as -1
+    pub fn into_insert(self, symbol: &Symbol, interval: &KlineInterval) -> KlineDataInsert {
+        KlineDataInsert {
+            event: "kline".to_owned(),
+            event_time: self.close_time,
+            start_time: self.start_time,
+            close_time: self.close_time,
+            symbol: symbol.clone(),
+            interval: interval.to_string(),
+            first_trade_id: -1,
+            last_trade_id: -1,
+            open: self.open,
+            close: self.close,
+            high: self.high,
+            low: self.low,
+            volume: self.volume,
+            num_of_trades: self.num_of_trades,
+            is_kline_closed: self.close_time < chrono::Utc::now().naive_utc(),
+            quote_asset_vol: self.quote_asset_vol,
+            taker_buy_base_vol: self.taker_buy_base_vol,
+            taker_buy_quote_vol: self.taker_buy_quote_vol,
+        }
+    }
+}
+
+/// Insert klines keyed by (symbol, interval, start_time), replacing rows already stored.
+/// The stream sends the same kline many times until it closes, only the last copy is kept.
+pub fn upsert_klines(conn: &PgConnection, rows: &[KlineDataInsert]) -> QueryResult<usize> {
+    use crate::schema::klines::dsl::*;
+
+    let mut latest: Vec<&KlineDataInsert> = Vec::with_capacity(rows.len());
+    let mut index: HashMap<(&Symbol, &str, NaiveDateTime), usize> = HashMap::new();
+    for row in rows {
+        let key = (&row.symbol, row.interval.as_str(), row.start_time);
+        match index.get(&key) {
+            Some(&i) => latest[i] = row,
+            None => {
+                index.insert(key, latest.len());
+                latest.push(row);
+            }
+        }
+    }
+
+    diesel::insert_into(klines)
+        .values(latest)
+        .on_conflict((symbol, interval, start_time))
+        .do_update()
+        .set((
+            event.eq(excluded(event)),
+            event_time.eq(excluded(event_time)),
+            close_time.eq(excluded(close_time)),
+            first_trade_id.eq(excluded(first_trade_id)),
+            last_trade_id.eq(excluded(last_trade_id)),
+            open.eq(excluded(open)),
+            close.eq(excluded(close)),
+            high.eq(excluded(high)),
+            low.eq(excluded(low)),
+            volume.eq(excluded(volume)),
+            num_of_trades.eq(excluded(num_of_trades)),
+            is_kline_closed.eq(excluded(is_kline_closed)),
+            quote_asset_vol.eq(excluded(quote_asset_vol)),
+            taker_buy_base_vol.eq(excluded(taker_buy_base_vol)),
+            taker_buy_quote_vol.eq(excluded(taker_buy_quote_vol)),
+        ))
+        .execute(conn)
+}
+
+/// Start times of the closed klines stored for this symbol and interval, from `from` to `to` inclusive
+pub fn closed_start_times(
+    conn: &PgConnection,
+    kline_symbol: &Symbol,
+    kline_interval: &KlineInterval,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+) -> QueryResult<Vec<NaiveDateTime>> {
+    use crate::schema::klines::dsl::*;
+
+    klines
+        .filter(symbol.eq(kline_symbol))
+        .filter(interval.eq(kline_interval.to_string()))
+        .filter(is_kline_closed.eq(true))
+        .filter(start_time.between(from, to))
+        .select(start_time)
+        .order(start_time.asc())
+        .load(conn)
+}
+
 pub fn map_klinemeta_to_klineinsertdata(kline_meta_data: KlineMetaData) -> KlineDataInsert {
     let kd = kline_meta_data.kline_data;
     KlineDataInsert {
@@ -226,7 +425,7 @@ mod tests {
             map_klinemeta_to_klineinsertdata, KlineDataInsert, KlineMetaData, NewKlineData,
             TEST_KLINE_DATA,
         };
-        use crate::serde_parsers::create_timestamp_benchmark;
+        use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
 
         let kline_meta_data = serde_json::from_str::<KlineMetaData>(&TEST_KLINE_DATA).unwrap();
         let kline_data_insert = map_klinemeta_to_klineinsertdata(kline_meta_data);
@@ -236,20 +435,20 @@ mod tests {
             event_time: create_timestamp_benchmark(1_555_444_333_222),
             start_time: create_timestamp_benchmark(1_555_444_333_222),
             close_time: create_timestamp_benchmark(1_555_444_333_222),
-            symbol: crate::currency_pairs::CurrencyPair::BNBBTC,
+            symbol: crate::symbols::Symbol::new("BNBBTC"),
             interval: "1m".to_owned(),
             first_trade_id: 100,
             last_trade_id: 200,
-            open: 0.0010,
-            close: 0.0020,
-            high: 0.0025,
-            low: 0.0015,
-            volume: 1000.0,
+            open: create_decimal_benchmark("0.0010"),
+            close: create_decimal_benchmark("0.0020"),
+            high: create_decimal_benchmark("0.0025"),
+            low: create_decimal_benchmark("0.0015"),
+            volume: create_decimal_benchmark("1000.0"),
             num_of_trades: 100,
             is_kline_closed: false,
-            quote_asset_vol: 1.0,
-            taker_buy_base_vol: 500.0,
-            taker_buy_quote_vol: 0.5,
+            quote_asset_vol: create_decimal_benchmark("1.0"),
+            taker_buy_base_vol: create_decimal_benchmark("500.0"),
+            taker_buy_quote_vol: create_decimal_benchmark("0.5"),
         };
         assert_eq!(kline_data_insert, mock_data)
     }
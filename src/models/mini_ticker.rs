@@ -1,13 +1,14 @@
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::fmt;
 
-use crate::currency_pairs::CurrencyPair;
+use crate::symbols::Symbol;
 use crate::schema::mini_tickers;
-use crate::serde_parsers::{deserialize_as_f32, deserialize_as_naive_date_time_ms};
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
 
-#[derive(Debug, Serialize, Deserialize, Insertable, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Insertable, PartialEq)]
 #[table_name = "mini_tickers"]
 pub struct MiniTickerDataInsert {
     #[serde(rename = "e")]
@@ -16,25 +17,25 @@ pub struct MiniTickerDataInsert {
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub event_time: NaiveDateTime, // Event time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
+    pub symbol: Symbol, // Symbol
     #[serde(rename = "o")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub open: f32, // Open price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub open: BigDecimal, // Open price
     #[serde(rename = "c")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub close: f32, // Close price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub close: BigDecimal, // Close price
     #[serde(rename = "h")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub high: f32, // High price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub high: BigDecimal, // High price
     #[serde(rename = "l")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub low: f32, // Low price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub low: BigDecimal, // Low price
     #[serde(rename = "v")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub base_asset_vol: f32, // Total traded base asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub base_asset_vol: BigDecimal, // Total traded base asset volume
     #[serde(rename = "q")]
-    #[serde(deserialize_with = "deserialize_as_f32")]
-    pub quote_asset_vol: f32, // Total traded quote asset volume
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub quote_asset_vol: BigDecimal, // Total traded quote asset volume
 }
 
 impl std::fmt::Display for MiniTickerData {
@@ -49,16 +50,16 @@ pub struct MiniTickerData {
     pub id: i32,                   // PostgreSQL ID
     pub event: String,             // Event type
     pub event_time: NaiveDateTime, // Event time
-    pub symbol: CurrencyPair,      // Symbol
-    pub open: f32,                 // Open price
-    pub close: f32,                // Close price
-    pub high: f32,                 // High price
-    pub low: f32,                  // Low price
-    pub base_asset_vol: f32,       // Total traded base asset volume
-    pub quote_asset_vol: f32,      // Total traded quote asset volume
+    pub symbol: Symbol,            // Symbol
+    pub open: BigDecimal,                 // Open price
+    pub close: BigDecimal,                // Close price
+    pub high: BigDecimal,                 // High price
+    pub low: BigDecimal,                  // Low price
+    pub base_asset_vol: BigDecimal,       // Total traded base asset volume
+    pub quote_asset_vol: BigDecimal,      // Total traded quote asset volume
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MiniTickerQueryType {
     AllMarkets,
     SingleMarket,
@@ -84,20 +85,20 @@ mod tests {
     fn try_deserialize_mini_ticker() {
         use crate::models::mini_ticker::MiniTickerDataInsert;
         use crate::models::mini_ticker::TEST_MINI_TICKER_DATA;
-        use crate::serde_parsers::create_timestamp_benchmark;
+        use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
 
         let jsond_test =
             serde_json::from_str::<MiniTickerDataInsert>(TEST_MINI_TICKER_DATA).unwrap();
         let mock_data = MiniTickerDataInsert {
             event: "24hrMiniTicker".to_owned(),
             event_time: create_timestamp_benchmark(1_222_333_444_555),
-            symbol: crate::currency_pairs::CurrencyPair::BNBBTC,
-            close: 0.0025,
-            open: 0.0010,
-            high: 0.0025,
-            low: 0.0010,
-            base_asset_vol: 10000.0,
-            quote_asset_vol: 18.0,
+            symbol: crate::symbols::Symbol::new("BNBBTC"),
+            close: create_decimal_benchmark("0.0025"),
+            open: create_decimal_benchmark("0.0010"),
+            high: create_decimal_benchmark("0.0025"),
+            low: create_decimal_benchmark("0.0010"),
+            base_asset_vol: create_decimal_benchmark("10000.0"),
+            quote_asset_vol: create_decimal_benchmark("18.0"),
         };
         assert_eq!(jsond_test, mock_data)
     }
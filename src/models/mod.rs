@@ -3,23 +3,30 @@ use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::fmt;
 
-use crate::currency_pairs::CurrencyPair;
-use crate::serde_parsers::deserialize_as_f32;
+use crate::symbols::Symbol;
+use crate::serde_parsers::deserialize_as_decimal;
 
 use crate::schema::posts;
 
+pub mod account;
 #[allow(unused_variables)]
 pub mod aggregate_trades;
+pub mod bars;
 #[allow(unused_variables)]
 pub mod book_depth;
 #[allow(unused_variables)]
+pub mod combined_stream;
+pub mod dead_letters;
+#[allow(unused_variables)]
 pub mod klines;
 #[allow(unused_variables)]
 pub mod mini_ticker;
+pub mod orders;
 #[allow(unused_variables)]
 pub mod trades;
 #[allow(unused_variables)]
 pub mod tickers;
+pub mod user_data;
 
 
 #[derive(Queryable)]
@@ -42,7 +49,7 @@ pub struct NewPost<'a> {
 //     pub trade_id: i32,             // Trade ID
 //     pub event: String,             // Event type
 //     pub event_time: NaiveDateTime, // Event time
-//     pub symbol: CurrencyPair,      // Symbol
+//     pub symbol: Symbol,            // Symbol
 //     pub price: f32,                // Price
 //     pub quantity: f32,             // Quantity
 //     pub buyer_order_id: i32,       // Buyer order ID
@@ -57,7 +64,7 @@ pub struct NewPost<'a> {
 //         i32,
 //         String,
 //         NaiveDateTime,
-//         CurrencyPair,
+//         Symbol,
 //         f32,
 //         f32,
 //         i32,
@@ -0,0 +1,455 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::deserialize::FromSql;
+use diesel::pg::Pg;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::models::account::{CancelOrderResult, NewOrderResult, OrderInfo};
+use crate::schema::orders;
+use crate::symbols::Symbol;
+
+/// Enum sent and stored as Binance's upper case names, e.g: PARTIALLY_FILLED.
+/// Implements Display/FromStr, serde and diesel `Text` conversions.
+macro_rules! binance_enum {
+    ($name:ident { $($variant:ident => $s:expr,)+ }) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $($name::$variant => write!(f, $s),)+
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<$name, Error> {
+                match s {
+                    $($s => Ok($name::$variant),)+
+                    _ => Err(Error::InvalidEnum(stringify!($name), s.to_string())),
+                }
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<$name, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl ToSql<Text, Pg> for $name {
+            fn to_sql<W: std::io::Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
+                ToSql::<Text, Pg>::to_sql(&self.to_string(), out)
+            }
+        }
+
+        impl FromSql<Text, Pg> for $name {
+            fn from_sql(maybe_bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+                let s = <String as FromSql<Text, Pg>>::from_sql(maybe_bytes)?;
+                Ok(s.parse::<$name>()?)
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+binance_enum!(OrderSide {
+    Buy => "BUY",
+    Sell => "SELL",
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum OrderType {
+    Limit,
+    Market,
+    StopLossLimit,   // limit order placed once stop_price is reached
+    TakeProfitLimit, // limit order placed once stop_price is reached
+    LimitMaker,      // rejected if it would match immediately
+}
+
+binance_enum!(OrderType {
+    Limit => "LIMIT",
+    Market => "MARKET",
+    StopLossLimit => "STOP_LOSS_LIMIT",
+    TakeProfitLimit => "TAKE_PROFIT_LIMIT",
+    LimitMaker => "LIMIT_MAKER",
+});
+
+impl OrderType {
+    /// Binance requires timeInForce and price on these
+    pub fn is_limit(&self) -> bool {
+        match self {
+            OrderType::Limit | OrderType::StopLossLimit | OrderType::TakeProfitLimit => true,
+            OrderType::Market | OrderType::LimitMaker => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum TimeInForce {
+    Gtc, // good till canceled
+    Ioc, // immediate or cancel
+    Fok, // fill or kill
+}
+
+binance_enum!(TimeInForce {
+    Gtc => "GTC",
+    Ioc => "IOC",
+    Fok => "FOK",
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum OrderStatus {
+    PendingNew, // local only: created, not acknowledged by the exchange yet
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    PendingCancel,
+    Rejected,
+    Expired,
+}
+
+binance_enum!(OrderStatus {
+    PendingNew => "PENDING_NEW",
+    New => "NEW",
+    PartiallyFilled => "PARTIALLY_FILLED",
+    Filled => "FILLED",
+    Canceled => "CANCELED",
+    PendingCancel => "PENDING_CANCEL",
+    Rejected => "REJECTED",
+    Expired => "EXPIRED",
+});
+
+impl OrderStatus {
+    /// Can still fill or be canceled
+    pub fn is_open(&self) -> bool {
+        match self {
+            OrderStatus::PendingNew | OrderStatus::New | OrderStatus::PartiallyFilled | OrderStatus::PendingCancel => true,
+            OrderStatus::Filled | OrderStatus::Canceled | OrderStatus::Rejected | OrderStatus::Expired => false,
+        }
+    }
+}
+
+/// Why an `executionReport` was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExecutionType {
+    New,
+    Canceled,
+    Replaced, // unused by Binance
+    Rejected,
+    Trade,
+    Expired,
+}
+
+binance_enum!(ExecutionType {
+    New => "NEW",
+    Canceled => "CANCELED",
+    Replaced => "REPLACED",
+    Rejected => "REJECTED",
+    Trade => "TRADE",
+    Expired => "EXPIRED",
+});
+
+/// A new order, before a client order id is assigned
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>, // required by limit orders, see `OrderType::is_limit`
+    pub quantity: BigDecimal,
+    pub price: Option<BigDecimal>,
+    pub stop_price: Option<BigDecimal>, // STOP_LOSS_LIMIT and TAKE_PROFIT_LIMIT trigger
+}
+
+impl OrderRequest {
+    pub fn limit(symbol: Symbol, side: OrderSide, quantity: BigDecimal, price: BigDecimal) -> Self {
+        OrderRequest {
+            symbol,
+            side,
+            order_type: OrderType::Limit,
+            time_in_force: Some(TimeInForce::Gtc),
+            quantity,
+            price: Some(price),
+            stop_price: None,
+        }
+    }
+
+    pub fn market(symbol: Symbol, side: OrderSide, quantity: BigDecimal) -> Self {
+        OrderRequest {
+            symbol,
+            side,
+            order_type: OrderType::Market,
+            time_in_force: None,
+            quantity,
+            price: None,
+            stop_price: None,
+        }
+    }
+
+    /// Catch what Binance would reject for missing parameters
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.quantity <= BigDecimal::from(0) {
+            return Err(Error::InvalidOrder(format!("quantity must be positive: {}", self.quantity)));
+        }
+        if self.order_type.is_limit() && (self.price.is_none() || self.time_in_force.is_none()) {
+            return Err(Error::InvalidOrder(format!("{} needs a price and time in force", self.order_type)));
+        }
+        if self.order_type == OrderType::LimitMaker && self.price.is_none() {
+            return Err(Error::InvalidOrder("LIMIT_MAKER needs a price".to_owned()));
+        }
+        match self.order_type {
+            OrderType::StopLossLimit | OrderType::TakeProfitLimit if self.stop_price.is_none() => {
+                Err(Error::InvalidOrder(format!("{} needs a stop price", self.order_type)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Parameters for POST `/api/v3/order`
+    pub fn to_params(&self, client_order_id: &str) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("symbol", self.symbol.to_uppercase()),
+            ("side", self.side.to_string()),
+            ("type", self.order_type.to_string()),
+            ("quantity", self.quantity.to_string()),
+            ("newClientOrderId", client_order_id.to_owned()),
+            ("newOrderRespType", "RESULT".to_owned()),
+        ];
+        if let Some(time_in_force) = self.time_in_force {
+            params.push(("timeInForce", time_in_force.to_string()));
+        }
+        if let Some(price) = &self.price {
+            params.push(("price", price.to_string()));
+        }
+        if let Some(stop_price) = &self.stop_price {
+            params.push(("stopPrice", stop_price.to_string()));
+        }
+        params
+    }
+}
+
+/// Current state of an order we placed, keyed by our client order id
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    pub client_order_id: String,
+    pub order_id: Option<i64>, // exchange id, None until acknowledged
+    pub request: OrderRequest,
+    pub status: OrderStatus,
+    pub executed_qty: BigDecimal,
+    pub cumulative_quote_qty: BigDecimal,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Order {
+    pub fn remaining_qty(&self) -> BigDecimal {
+        &self.request.quantity - &self.executed_qty
+    }
+
+    /// Apply an exchange update, returns false if it changes nothing or is stale:
+    /// executed quantity never decreases and a closed order never reopens
+    pub fn apply(&mut self, update: &OrderUpdate) -> bool {
+        let stale = update.executed_qty < self.executed_qty || (!self.status.is_open() && update.status.is_open());
+        let unchanged = update.status == self.status && update.executed_qty == self.executed_qty;
+        if stale || unchanged {
+            return false;
+        }
+        self.order_id = Some(update.order_id);
+        self.status = update.status;
+        self.executed_qty = update.executed_qty.clone();
+        self.cumulative_quote_qty = update.cumulative_quote_qty.clone();
+        self.updated_at = update.time;
+        true
+    }
+
+    /// Row for the `orders` table recording this state
+    pub fn to_event(&self, reason: Option<String>) -> OrderEventInsert {
+        OrderEventInsert {
+            client_order_id: self.client_order_id.clone(),
+            order_id: self.order_id,
+            symbol: self.request.symbol.clone(),
+            side: self.request.side,
+            order_type: self.request.order_type,
+            time_in_force: self.request.time_in_force,
+            price: self.request.price.clone(),
+            stop_price: self.request.stop_price.clone(),
+            orig_qty: self.request.quantity.clone(),
+            executed_qty: self.executed_qty.clone(),
+            cumulative_quote_qty: self.cumulative_quote_qty.clone(),
+            status: self.status,
+            reason,
+            event_time: self.updated_at,
+        }
+    }
+}
+
+impl From<&OrderInfo> for Order {
+    fn from(info: &OrderInfo) -> Self {
+        Order {
+            client_order_id: info.client_order_id.clone(),
+            order_id: Some(info.order_id),
+            request: OrderRequest {
+                symbol: info.symbol.clone(),
+                side: info.side,
+                order_type: info.order_type,
+                time_in_force: Some(info.time_in_force),
+                quantity: info.orig_qty.clone(),
+                price: Some(info.price.clone()),
+                stop_price: Some(info.stop_price.clone()),
+            },
+            status: info.status,
+            executed_qty: info.executed_qty.clone(),
+            cumulative_quote_qty: info.cumulative_quote_qty.clone(),
+            updated_at: info.update_time,
+        }
+    }
+}
+
+/// Status and fills reported by the exchange for one of our orders
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderUpdate {
+    pub client_order_id: String,
+    pub order_id: i64,
+    pub status: OrderStatus,
+    pub executed_qty: BigDecimal,
+    pub cumulative_quote_qty: BigDecimal,
+    pub time: NaiveDateTime,
+}
+
+impl From<&OrderInfo> for OrderUpdate {
+    fn from(info: &OrderInfo) -> Self {
+        OrderUpdate {
+            client_order_id: info.client_order_id.clone(),
+            order_id: info.order_id,
+            status: info.status,
+            executed_qty: info.executed_qty.clone(),
+            cumulative_quote_qty: info.cumulative_quote_qty.clone(),
+            time: info.update_time,
+        }
+    }
+}
+
+impl From<&NewOrderResult> for OrderUpdate {
+    fn from(result: &NewOrderResult) -> Self {
+        OrderUpdate {
+            client_order_id: result.client_order_id.clone(),
+            order_id: result.order_id,
+            status: result.status,
+            executed_qty: result.executed_qty.clone(),
+            cumulative_quote_qty: result.cumulative_quote_qty.clone(),
+            time: result.transact_time,
+        }
+    }
+}
+
+/// The cancel response has no timestamp, uses the local time
+impl From<&CancelOrderResult> for OrderUpdate {
+    fn from(result: &CancelOrderResult) -> Self {
+        OrderUpdate {
+            client_order_id: result.orig_client_order_id.clone(),
+            order_id: result.order_id,
+            status: result.status,
+            executed_qty: result.executed_qty.clone(),
+            cumulative_quote_qty: result.cumulative_quote_qty.clone(),
+            time: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// One state transition of an order, the `orders` table is append only
+#[derive(Debug, Clone, PartialEq, Insertable)]
+#[table_name = "orders"]
+pub struct OrderEventInsert {
+    pub client_order_id: String,
+    pub order_id: Option<i64>,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub price: Option<BigDecimal>,
+    pub stop_price: Option<BigDecimal>,
+    pub orig_qty: BigDecimal,
+    pub executed_qty: BigDecimal,
+    pub cumulative_quote_qty: BigDecimal,
+    pub status: OrderStatus,
+    pub reason: Option<String>, // why it was rejected or canceled, if known
+    pub event_time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable)]
+pub struct OrderEvent {
+    pub id: i32,
+    pub client_order_id: String,
+    pub order_id: Option<i64>,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub time_in_force: Option<TimeInForce>,
+    pub price: Option<BigDecimal>,
+    pub stop_price: Option<BigDecimal>,
+    pub orig_qty: BigDecimal,
+    pub executed_qty: BigDecimal,
+    pub cumulative_quote_qty: BigDecimal,
+    pub status: OrderStatus,
+    pub reason: Option<String>,
+    pub event_time: NaiveDateTime,
+}
+
+impl OrderEvent {
+    /// Order state as of this transition
+    pub fn into_order(self) -> Order {
+        Order {
+            client_order_id: self.client_order_id,
+            order_id: self.order_id,
+            request: OrderRequest {
+                symbol: self.symbol,
+                side: self.side,
+                order_type: self.order_type,
+                time_in_force: self.time_in_force,
+                quantity: self.orig_qty,
+                price: self.price,
+                stop_price: self.stop_price,
+            },
+            status: self.status,
+            executed_qty: self.executed_qty,
+            cumulative_quote_qty: self.cumulative_quote_qty,
+            updated_at: self.event_time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_enums_use_binance_names() {
+        assert_eq!(OrderType::TakeProfitLimit.to_string(), "TAKE_PROFIT_LIMIT");
+        assert_eq!("PARTIALLY_FILLED".parse::<OrderStatus>().unwrap(), OrderStatus::PartiallyFilled);
+        assert_eq!(serde_json::to_string(&OrderSide::Sell).unwrap(), r#""SELL""#);
+        assert_eq!(serde_json::from_str::<TimeInForce>(r#""IOC""#).unwrap(), TimeInForce::Ioc);
+        assert!("HALF_FILLED".parse::<OrderStatus>().is_err());
+        assert!(OrderStatus::PartiallyFilled.is_open() && !OrderStatus::Expired.is_open());
+    }
+}
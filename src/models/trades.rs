@@ -1,37 +1,38 @@
+use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
 use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::fmt;
 
-use crate::currency_pairs::CurrencyPair;
+use crate::symbols::Symbol;
 use crate::schema::trades;
-use crate::serde_parsers::{deserialize_as_f32, deserialize_as_naive_date_time_ms};
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Queryable, QueryableByName, Insertable)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable, QueryableByName, Insertable)]
 #[table_name = "trades"]
 pub struct TradeData {
     #[serde(rename = "t")]
-    pub trade_id: i32, // Trade ID
+    pub trade_id: i64, // Trade ID
     #[serde(rename = "e")]
     pub event: String, // Event type
     #[serde(rename = "E")]
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub event_time: NaiveDateTime, // Event time
     #[serde(rename = "s")]
-    pub symbol: CurrencyPair, // Symbol
-    #[serde(deserialize_with = "deserialize_as_f32")]
+    pub symbol: Symbol, // Symbol
+    #[serde(deserialize_with = "deserialize_as_decimal")]
     #[serde(rename = "p")]
-    pub price: f32, // Price
-    #[serde(deserialize_with = "deserialize_as_f32")]
+    pub price: BigDecimal, // Price
+    #[serde(deserialize_with = "deserialize_as_decimal")]
     #[serde(rename = "q")]
-    pub quantity: f32, // Quantity
+    pub quantity: BigDecimal, // Quantity
     #[serde(rename = "T")]
     #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
     pub trade_time: NaiveDateTime, // trade time
     #[serde(rename = "b")]
-    pub buyer_order_id: i32, // Buyer order ID
+    pub buyer_order_id: i64, // Buyer order ID
     #[serde(rename = "a")]
-    pub seller_order_id: i32, // Seller order ID
+    pub seller_order_id: i64, // Seller order ID
     #[serde(rename = "m")]
     pub buyer_mkt_maker: bool, //  is buyer the market maker?
 }
@@ -67,7 +68,7 @@ mod tests {
             TradeData,
             TEST_TRADE_DATA,
         };
-        use crate::serde_parsers::create_timestamp_benchmark;
+        use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
 
         let trade_data = serde_json::from_str::<TradeData>(&TEST_TRADE_DATA).unwrap();
 
@@ -75,9 +76,9 @@ mod tests {
             trade_id: 12345,
             event: "trade".to_owned(),
             event_time: create_timestamp_benchmark(1_555_444_333_222),
-            symbol: crate::currency_pairs::CurrencyPair::BNBBTC,
-            price: 0.001,
-            quantity: 100.0,
+            symbol: crate::symbols::Symbol::new("BNBBTC"),
+            price: create_decimal_benchmark("0.001"),
+            quantity: create_decimal_benchmark("100.0"),
             buyer_order_id: 88,
             seller_order_id: 50,
             trade_time: create_timestamp_benchmark(1_666_555_444_333),
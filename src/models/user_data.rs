@@ -0,0 +1,343 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use serde::de::Error;
+
+use crate::models::orders::{
+    ExecutionType, Order, OrderRequest, OrderSide, OrderStatus, OrderType, OrderUpdate, TimeInForce,
+};
+use crate::schema::fills;
+use crate::serde_parsers::{deserialize_as_decimal, deserialize_as_naive_date_time_ms};
+use crate::symbols::Symbol;
+
+///////////////////////////////////////////////////////////////////////////////
+/// User data stream: `wss://stream.binance.com:9443/ws/<listenKey>`
+///////////////////////////////////////////////////////////////////////////////
+
+/// POST `/api/v3/userDataStream`, valid for 60 minutes unless kept alive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenKey {
+    pub listen_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserDataEvent {
+    ExecutionReport(ExecutionReport),
+    AccountInfo(OutboundAccountInfo),
+    AccountPosition(OutboundAccountPosition),
+    BalanceUpdate(BalanceUpdate),
+}
+
+impl UserDataEvent {
+    /// `"E"`, when the event was sent
+    pub fn event_time(&self) -> NaiveDateTime {
+        match self {
+            UserDataEvent::ExecutionReport(report) => report.event_time,
+            UserDataEvent::AccountInfo(info) => info.event_time,
+            UserDataEvent::AccountPosition(position) => position.event_time,
+            UserDataEvent::BalanceUpdate(update) => update.event_time,
+        }
+    }
+}
+
+/// Dispatch on the event type, `"e"`
+pub fn parse_user_data_event(txt: &str) -> serde_json::Result<UserDataEvent> {
+    let data = serde_json::from_str::<serde_json::Value>(txt)?;
+    let event = data.get("e").and_then(|e| e.as_str()).unwrap_or_default().to_owned();
+    match event.as_str() {
+        "executionReport" => Ok(UserDataEvent::ExecutionReport(serde_json::from_value(data)?)),
+        "outboundAccountInfo" => Ok(UserDataEvent::AccountInfo(serde_json::from_value(data)?)),
+        "outboundAccountPosition" => Ok(UserDataEvent::AccountPosition(serde_json::from_value(data)?)),
+        "balanceUpdate" => Ok(UserDataEvent::BalanceUpdate(serde_json::from_value(data)?)),
+        e => Err(serde_json::Error::custom(format!("unsupported user data event: {:?}", e))),
+    }
+}
+
+/// Sent for every order update: placed, canceled, rejected, (partially) filled or expired
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    #[serde(rename = "E", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub event_time: NaiveDateTime,
+    #[serde(rename = "s")]
+    pub symbol: Symbol,
+    #[serde(rename = "c")]
+    pub client_order_id: String,
+    #[serde(rename = "S")]
+    pub side: OrderSide,
+    #[serde(rename = "o")]
+    pub order_type: OrderType,
+    #[serde(rename = "f")]
+    pub time_in_force: TimeInForce,
+    #[serde(rename = "q", deserialize_with = "deserialize_as_decimal")]
+    pub quantity: BigDecimal,
+    #[serde(rename = "p", deserialize_with = "deserialize_as_decimal")]
+    pub price: BigDecimal,
+    #[serde(rename = "P", deserialize_with = "deserialize_as_decimal")]
+    pub stop_price: BigDecimal,
+    #[serde(rename = "C")]
+    pub orig_client_order_id: Option<String>, // on cancels, the canceled order's client order id
+    #[serde(rename = "x")]
+    pub execution_type: ExecutionType,
+    #[serde(rename = "X")]
+    pub status: OrderStatus,
+    #[serde(rename = "r")]
+    pub reject_reason: String, // "NONE" unless rejected
+    #[serde(rename = "i")]
+    pub order_id: i64,
+    #[serde(rename = "l", deserialize_with = "deserialize_as_decimal")]
+    pub last_executed_qty: BigDecimal,
+    #[serde(rename = "z", deserialize_with = "deserialize_as_decimal")]
+    pub cumulative_qty: BigDecimal,
+    #[serde(rename = "L", deserialize_with = "deserialize_as_decimal")]
+    pub last_executed_price: BigDecimal,
+    #[serde(rename = "n", deserialize_with = "deserialize_as_decimal")]
+    pub commission: BigDecimal,
+    #[serde(rename = "N")]
+    pub commission_asset: Option<String>, // null until the first fill
+    #[serde(rename = "T", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub transaction_time: NaiveDateTime,
+    #[serde(rename = "t")]
+    pub trade_id: i64, // -1 unless execution_type is TRADE
+    #[serde(rename = "w")]
+    pub is_working: bool, // on the book
+    #[serde(rename = "m")]
+    pub is_maker: bool,
+    #[serde(rename = "Z", deserialize_with = "deserialize_as_decimal")]
+    pub cumulative_quote_qty: BigDecimal,
+}
+
+impl ExecutionReport {
+    /// Client order id of the order this report is about. Cancels carry a new id
+    /// in `c` and the canceled order's id in `C`.
+    pub fn order_client_id(&self) -> &str {
+        match &self.orig_client_order_id {
+            Some(id) if !id.is_empty() && id != "null" => id,
+            _ => &self.client_order_id,
+        }
+    }
+
+    pub fn order_update(&self) -> OrderUpdate {
+        OrderUpdate {
+            client_order_id: self.order_client_id().to_owned(),
+            order_id: self.order_id,
+            status: self.status,
+            executed_qty: self.cumulative_qty.clone(),
+            cumulative_quote_qty: self.cumulative_quote_qty.clone(),
+            time: self.transaction_time,
+        }
+    }
+
+    /// The order as of this report, for orders placed outside this process
+    pub fn to_order(&self) -> Order {
+        Order {
+            client_order_id: self.order_client_id().to_owned(),
+            order_id: Some(self.order_id),
+            request: OrderRequest {
+                symbol: self.symbol.clone(),
+                side: self.side,
+                order_type: self.order_type,
+                time_in_force: Some(self.time_in_force),
+                quantity: self.quantity.clone(),
+                price: Some(self.price.clone()),
+                stop_price: Some(self.stop_price.clone()),
+            },
+            status: self.status,
+            executed_qty: self.cumulative_qty.clone(),
+            cumulative_quote_qty: self.cumulative_quote_qty.clone(),
+            updated_at: self.transaction_time,
+        }
+    }
+
+    /// Row for the `fills` table, only for TRADE reports
+    pub fn to_fill(&self) -> Option<FillInsert> {
+        if self.execution_type != ExecutionType::Trade {
+            return None;
+        }
+        Some(FillInsert {
+            symbol: self.symbol.clone(),
+            trade_id: self.trade_id,
+            order_id: self.order_id,
+            client_order_id: self.order_client_id().to_owned(),
+            side: self.side,
+            price: self.last_executed_price.clone(),
+            quantity: self.last_executed_qty.clone(),
+            quote_qty: &self.last_executed_price * &self.last_executed_qty,
+            commission: self.commission.clone(),
+            commission_asset: self.commission_asset.clone(),
+            is_maker: self.is_maker,
+            trade_time: self.transaction_time,
+        })
+    }
+}
+
+/// Free and locked amounts of one asset, as sent in `B`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamBalance {
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "f", deserialize_with = "deserialize_as_decimal")]
+    pub free: BigDecimal,
+    #[serde(rename = "l", deserialize_with = "deserialize_as_decimal")]
+    pub locked: BigDecimal,
+}
+
+/// Every balance, sent after any change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboundAccountInfo {
+    #[serde(rename = "E", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub event_time: NaiveDateTime,
+    #[serde(rename = "T")]
+    pub can_trade: bool,
+    #[serde(rename = "W")]
+    pub can_withdraw: bool,
+    #[serde(rename = "D")]
+    pub can_deposit: bool,
+    #[serde(rename = "u", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub last_update_time: NaiveDateTime,
+    #[serde(rename = "B")]
+    pub balances: Vec<StreamBalance>,
+}
+
+/// Only the balances which changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboundAccountPosition {
+    #[serde(rename = "E", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub event_time: NaiveDateTime,
+    #[serde(rename = "u", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub last_update_time: NaiveDateTime,
+    #[serde(rename = "B")]
+    pub balances: Vec<StreamBalance>,
+}
+
+/// Deposit, withdrawal or transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceUpdate {
+    #[serde(rename = "E", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub event_time: NaiveDateTime,
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "d", deserialize_with = "deserialize_as_decimal")]
+    pub delta: BigDecimal, // change of the free balance
+    #[serde(rename = "T", deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub clear_time: NaiveDateTime,
+}
+
+/// One execution of one of our orders, unique per (symbol, trade_id)
+#[derive(Debug, Clone, PartialEq, Insertable)]
+#[table_name = "fills"]
+pub struct FillInsert {
+    pub symbol: Symbol,
+    pub trade_id: i64,
+    pub order_id: i64,
+    pub client_order_id: String,
+    pub side: OrderSide,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub quote_qty: BigDecimal,
+    pub commission: BigDecimal,
+    pub commission_asset: Option<String>,
+    pub is_maker: bool,
+    pub trade_time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Queryable)]
+pub struct Fill {
+    pub id: i32, // PostgreSQL ID
+    pub symbol: Symbol,
+    pub trade_id: i64,
+    pub order_id: i64,
+    pub client_order_id: String,
+    pub side: OrderSide,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+    pub quote_qty: BigDecimal,
+    pub commission: BigDecimal,
+    pub commission_asset: Option<String>,
+    pub is_maker: bool,
+    pub trade_time: NaiveDateTime,
+}
+
+pub static TEST_EXECUTION_REPORT_DATA: &str = r#"
+{
+  "e": "executionReport",
+  "E": 1499405658658,
+  "s": "ETHBTC",
+  "c": "mUvoqJxFIILMdfAW5iGSOW",
+  "S": "BUY",
+  "o": "LIMIT",
+  "f": "GTC",
+  "q": "1.00000000",
+  "p": "0.10264410",
+  "P": "0.00000000",
+  "F": "0.00000000",
+  "g": -1,
+  "C": "",
+  "x": "TRADE",
+  "X": "PARTIALLY_FILLED",
+  "r": "NONE",
+  "i": 4293153,
+  "l": "0.25000000",
+  "z": "0.25000000",
+  "L": "0.10264410",
+  "n": "0.00025000",
+  "N": "ETH",
+  "T": 1499405658657,
+  "t": 2387,
+  "I": 8641984,
+  "w": true,
+  "m": false,
+  "M": false,
+  "O": 1499405658657,
+  "Z": "0.02566102"
+}
+"#;
+
+pub static TEST_ACCOUNT_POSITION_DATA: &str = r#"
+{
+  "e": "outboundAccountPosition",
+  "E": 1564034571105,
+  "u": 1564034571073,
+  "B": [
+    { "a": "ETH", "f": "10000.000000", "l": "0.000000" }
+  ]
+}
+"#;
+
+pub static TEST_BALANCE_UPDATE_DATA: &str = r#"
+{
+  "e": "balanceUpdate",
+  "E": 1573200697110,
+  "a": "BTC",
+  "d": "100.00000000",
+  "T": 1573200697068
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde_parsers::create_decimal_benchmark;
+
+    #[test]
+    fn try_deserialize_user_data_events() {
+        let report = match parse_user_data_event(TEST_EXECUTION_REPORT_DATA).unwrap() {
+            UserDataEvent::ExecutionReport(report) => report,
+            other => panic!("expected executionReport, got {:?}", other),
+        };
+        assert_eq!(report.order_client_id(), "mUvoqJxFIILMdfAW5iGSOW");
+        assert_eq!(report.order_update().status, OrderStatus::PartiallyFilled);
+        let fill = report.to_fill().unwrap();
+        assert_eq!((fill.trade_id, fill.commission_asset.as_ref().map(|a| a.as_str())), (2387, Some("ETH")));
+        assert_eq!(fill.quote_qty, create_decimal_benchmark("0.0256610250"));
+
+        match parse_user_data_event(TEST_ACCOUNT_POSITION_DATA).unwrap() {
+            UserDataEvent::AccountPosition(position) => assert_eq!(position.balances[0].asset, "ETH"),
+            other => panic!("expected outboundAccountPosition, got {:?}", other),
+        }
+        match parse_user_data_event(TEST_BALANCE_UPDATE_DATA).unwrap() {
+            UserDataEvent::BalanceUpdate(update) => assert_eq!(update.delta, BigDecimal::from(100)),
+            other => panic!("expected balanceUpdate, got {:?}", other),
+        }
+        assert!(parse_user_data_event(r#"{"e": "listStatus"}"#).is_err());
+    }
+}
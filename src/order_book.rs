@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bigdecimal::BigDecimal;
+use num::Zero;
+
+use crate::symbols::Symbol;
+use crate::models::book_depth::{BookDepthDataInsert, PartialBookDepthData, Quote};
+
+pub const BINANCE_DEPTH_SNAPSHOT_URL: &str = "https://api.binance.com/api/v1/depth";
+
+/// Diff events held while waiting on a snapshot. Past this the oldest are dropped,
+/// which will surface as a gap and trigger another resnapshot.
+pub const MAX_BUFFERED_EVENTS: usize = 10_000;
+
+/// Outcome of feeding a diff event or snapshot into the `LocalOrderBook`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookUpdate {
+    Buffered, // no snapshot yet, event held until one arrives
+    Stale,    // event is older than the book and was dropped
+    Applied,  // book is in sync
+    Resync,   // gap in update IDs: book was cleared, fetch a fresh snapshot
+}
+
+/// Order book maintained from a REST depth snapshot plus the `<symbol>@depth` diff stream,
+/// following Binance's "How to manage a local order book correctly" procedure:
+///   1. Buffer diff events from the stream.
+///   2. Get a depth snapshot.
+///   3. Drop any event where `u` (update_final) <= lastUpdateId of the snapshot.
+///   4. The first processed event should have U <= lastUpdateId+1 AND u >= lastUpdateId+1.
+///   5. Each new event's U should be equal to the previous event's u+1.
+///   6. Quantities are absolute, a quantity of 0 removes the price level.
+#[derive(Debug)]
+pub struct LocalOrderBook {
+    pub symbol: Symbol,
+    last_update_id: Option<i64>, // None until synced with a snapshot
+    first_event_pending: bool,   // next event must bridge the snapshot (step 4)
+    bids: BTreeMap<BigDecimal, BigDecimal>, // price -> quantity
+    asks: BTreeMap<BigDecimal, BigDecimal>,
+    buffer: Vec<BookDepthDataInsert>,
+}
+
+impl LocalOrderBook {
+    pub fn new(symbol: Symbol) -> Self {
+        LocalOrderBook {
+            symbol,
+            last_update_id: None,
+            first_event_pending: false,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn last_update_id(&self) -> Option<i64> {
+        self.last_update_id
+    }
+
+    pub fn needs_snapshot(&self) -> bool {
+        self.last_update_id.is_none()
+    }
+
+    /// Reset the book from a REST snapshot, then replay any buffered diff events.
+    pub fn apply_snapshot(&mut self, snapshot: PartialBookDepthData) -> BookUpdate {
+        self.bids.clear();
+        self.asks.clear();
+        update_levels(&mut self.bids, &snapshot.bids);
+        update_levels(&mut self.asks, &snapshot.asks);
+        self.last_update_id = Some(snapshot.last_update_id);
+        self.first_event_pending = true;
+
+        let buffered: Vec<BookDepthDataInsert> = self.buffer.drain(..).collect();
+        let mut status = BookUpdate::Applied;
+        for event in buffered {
+            // A Resync means the snapshot is older than the buffered events and a newer
+            // one is needed. Remaining events are re-buffered by `apply_update`.
+            if self.apply_update(event) == BookUpdate::Resync {
+                status = BookUpdate::Resync;
+            }
+        }
+        status
+    }
+
+    /// Apply a diff event from the `<symbol>@depth` stream
+    pub fn apply_update(&mut self, event: BookDepthDataInsert) -> BookUpdate {
+        let last_update_id = match self.last_update_id {
+            Some(id) => id,
+            None => {
+                if self.buffer.len() >= MAX_BUFFERED_EVENTS {
+                    self.buffer.remove(0);
+                }
+                self.buffer.push(event);
+                return BookUpdate::Buffered;
+            }
+        };
+
+        if event.update_final <= last_update_id {
+            return BookUpdate::Stale;
+        }
+
+        let in_sequence = if self.first_event_pending {
+            event.update_first <= last_update_id + 1
+        } else {
+            event.update_first == last_update_id + 1
+        };
+        if !in_sequence {
+            warn!(
+                "{} order book gap: expected update {}, got {}..{}. Resyncing.",
+                self.symbol, last_update_id + 1, event.update_first, event.update_final
+            );
+            self.clear();
+            self.buffer.push(event);
+            return BookUpdate::Resync;
+        }
+
+        update_levels(&mut self.bids, &event.bids);
+        update_levels(&mut self.asks, &event.asks);
+        self.last_update_id = Some(event.update_final);
+        self.first_event_pending = false;
+        BookUpdate::Applied
+    }
+
+    /// Drop all levels and wait for a new snapshot
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.last_update_id = None;
+        self.first_event_pending = false;
+    }
+
+    pub fn best_bid(&self) -> Option<Quote> {
+        self.bids.iter().next_back().map(to_quote)
+    }
+
+    pub fn best_ask(&self) -> Option<Quote> {
+        self.asks.iter().next().map(to_quote)
+    }
+
+    /// Top `levels` bids (highest first) and asks (lowest first)
+    pub fn depth(&self, levels: usize) -> (Vec<Quote>, Vec<Quote>) {
+        let bids = self.bids.iter().rev().take(levels).map(to_quote).collect();
+        let asks = self.asks.iter().take(levels).map(to_quote).collect();
+        (bids, asks)
+    }
+
+    /// Full bid ladder, highest price first
+    pub fn bids(&self) -> Vec<Quote> {
+        self.bids.iter().rev().map(to_quote).collect()
+    }
+
+    /// Full ask ladder, lowest price first
+    pub fn asks(&self) -> Vec<Quote> {
+        self.asks.iter().map(to_quote).collect()
+    }
+}
+
+impl fmt::Display for LocalOrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => write!(
+                f,
+                "{} bid: {} x {} | ask: {} x {}",
+                self.symbol.to_uppercase(), bid.price, bid.quantity, ask.price, ask.quantity
+            ),
+            _ => write!(f, "{} order book empty", self.symbol.to_uppercase()),
+        }
+    }
+}
+
+fn update_levels(levels: &mut BTreeMap<BigDecimal, BigDecimal>, quotes: &[Quote]) {
+    for quote in quotes {
+        if quote.quantity.is_zero() {
+            levels.remove(&quote.price);
+        } else {
+            levels.insert(quote.price.clone(), quote.quantity.clone());
+        }
+    }
+}
+
+fn to_quote((price, quantity): (&BigDecimal, &BigDecimal)) -> Quote {
+    Quote {
+        price: price.clone(),
+        quantity: quantity.clone(),
+    }
+}
+
+/// GET a depth snapshot from the REST API, `limit` is one of 5, 10, 20, 50, 100, 500, 1000
+pub fn fetch_depth_snapshot(
+    currency_pair: &Symbol,
+    limit: u32,
+) -> crate::error::Result<PartialBookDepthData> {
+    let url = format!(
+        "{}?symbol={}&limit={}",
+        BINANCE_DEPTH_SNAPSHOT_URL,
+        currency_pair.to_uppercase(),
+        limit
+    );
+    Ok(reqwest::get(&url)?.json::<PartialBookDepthData>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
+
+    fn quote(price: &str, quantity: &str) -> Quote {
+        Quote { price: create_decimal_benchmark(price), quantity: create_decimal_benchmark(quantity) }
+    }
+
+    fn diff(update_first: i64, update_final: i64, bids: Vec<Quote>, asks: Vec<Quote>) -> BookDepthDataInsert {
+        BookDepthDataInsert {
+            event: "depthUpdate".to_owned(),
+            event_time: create_timestamp_benchmark(1_555_444_333_222),
+            symbol: Symbol::new("BNBBTC"),
+            update_first,
+            update_final,
+            bids,
+            asks,
+        }
+    }
+
+    fn snapshot() -> PartialBookDepthData {
+        PartialBookDepthData {
+            last_update_id: 100,
+            bids: vec![quote("0.0024", "10.0"), quote("0.0023", "5.0")],
+            asks: vec![quote("0.0026", "100.0"), quote("0.0027", "20.0")],
+        }
+    }
+
+    #[test]
+    fn syncs_buffered_events_with_snapshot() {
+        let mut book = LocalOrderBook::new(Symbol::new("BNBBTC"));
+        assert_eq!(book.apply_update(diff(95, 99, vec![quote("0.0020", "1.0")], vec![])), BookUpdate::Buffered);
+        assert_eq!(book.apply_update(diff(100, 102, vec![quote("0.0025", "3.0")], vec![])), BookUpdate::Buffered);
+        assert!(book.needs_snapshot());
+
+        // first event is stale and dropped, second bridges lastUpdateId + 1
+        assert_eq!(book.apply_snapshot(snapshot()), BookUpdate::Applied);
+        assert_eq!(book.last_update_id(), Some(102));
+        assert_eq!(book.best_bid(), Some(quote("0.0025", "3.0")));
+        assert_eq!(book.best_ask(), Some(quote("0.0026", "100.0")));
+
+        // zero quantity removes a level
+        assert_eq!(book.apply_update(diff(103, 104, vec![], vec![quote("0.0026", "0.0")])), BookUpdate::Applied);
+        assert_eq!(book.best_ask(), Some(quote("0.0027", "20.0")));
+
+        let (bids, asks) = book.depth(2);
+        assert_eq!(bids, vec![quote("0.0025", "3.0"), quote("0.0024", "10.0")]);
+        assert_eq!(asks, vec![quote("0.0027", "20.0")]);
+        assert_eq!(book.bids().len(), 3);
+    }
+
+    #[test]
+    fn gap_triggers_resync() {
+        let mut book = LocalOrderBook::new(Symbol::new("BNBBTC"));
+        assert_eq!(book.apply_snapshot(snapshot()), BookUpdate::Applied);
+        assert_eq!(book.apply_update(diff(101, 105, vec![], vec![])), BookUpdate::Applied);
+        assert_eq!(book.apply_update(diff(104, 105, vec![], vec![])), BookUpdate::Stale);
+
+        // update 106 was missed
+        assert_eq!(book.apply_update(diff(107, 110, vec![], vec![])), BookUpdate::Resync);
+        assert!(book.needs_snapshot());
+        assert_eq!(book.best_bid(), None);
+
+        // snapshot older than the buffered event cannot be bridged either
+        assert_eq!(book.apply_snapshot(snapshot()), BookUpdate::Resync);
+        let newer = PartialBookDepthData { last_update_id: 106, ..snapshot() };
+        assert_eq!(book.apply_snapshot(newer), BookUpdate::Applied);
+        assert_eq!(book.last_update_id(), Some(110));
+    }
+
+    #[test]
+    fn update_ids_past_i32_max() {
+        let last = i64::from(std::i32::MAX);
+        let snapshot: PartialBookDepthData =
+            serde_json::from_str(&format!(r#"{{"lastUpdateId":{},"bids":[],"asks":[]}}"#, last)).unwrap();
+        let event: BookDepthDataInsert = serde_json::from_str(&format!(
+            r#"{{"e":"depthUpdate","E":1555444333222,"s":"BNBBTC","U":{},"u":{},"b":[["0.0024","10.0",[]]],"a":[]}}"#,
+            last - 1,
+            last + 2
+        ))
+        .unwrap();
+
+        let mut book = LocalOrderBook::new(Symbol::new("BNBBTC"));
+        assert_eq!(book.apply_update(event), BookUpdate::Buffered);
+        assert_eq!(book.apply_snapshot(snapshot), BookUpdate::Applied);
+        assert_eq!(book.last_update_id(), Some(last + 2));
+        assert_eq!(book.apply_update(diff(last + 3, last + 5, vec![], vec![])), BookUpdate::Applied);
+        assert_eq!(book.apply_update(diff(last + 7, last + 8, vec![], vec![])), BookUpdate::Resync);
+    }
+}
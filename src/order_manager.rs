@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use bigdecimal::BigDecimal;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::orders::{Order, OrderEvent, OrderRequest, OrderStatus, OrderUpdate};
+use crate::gateway::OrderGateway;
+use crate::rest_client::OrderRef;
+use crate::risk::RiskManager;
+
+/// Binance accepts client order ids matching ^[a-zA-Z0-9-_]{1,36}$
+const MAX_CLIENT_ORDER_ID_LEN: usize = 36;
+
+/// Places orders through a gateway, live or paper, and tracks them by client order id.
+/// Every state transition is appended to the `orders` table.
+pub struct OrderManager {
+    gateway: Box<dyn OrderGateway>,
+    conn: PgConnection,
+    prefix: String, // client order id prefix, tells our orders apart from manual ones
+    orders: HashMap<String, Order>,
+    risk: RiskManager, // pre-trade checks, fed tickers and fills by whoever owns the manager
+}
+
+impl OrderManager {
+    /// Every order is checked against `risk` before it is recorded or sent
+    pub fn new(gateway: Box<dyn OrderGateway>, conn: PgConnection, prefix: &str, risk: RiskManager) -> Self {
+        OrderManager {
+            gateway,
+            conn,
+            prefix: prefix.to_owned(),
+            orders: HashMap::new(),
+            risk,
+        }
+    }
+
+    pub fn risk_mut(&mut self) -> &mut RiskManager {
+        &mut self.risk
+    }
+
+    /// Reload orders left open by a previous run from their latest transition
+    pub fn restore(&mut self) -> Result<usize> {
+        use crate::schema::orders::dsl::*;
+
+        let latest = orders
+            .distinct_on(client_order_id)
+            .order((client_order_id, id.desc()))
+            .load::<OrderEvent>(&self.conn)?;
+        for order in latest.into_iter().map(OrderEvent::into_order) {
+            if order.status.is_open() && order.client_order_id.starts_with(&self.prefix) {
+                self.orders.insert(order.client_order_id.clone(), order);
+            }
+        }
+        Ok(self.orders.len())
+    }
+
+    pub fn gateway(&self) -> &dyn OrderGateway {
+        self.gateway.as_ref()
+    }
+
+    pub fn get(&self, client_order_id: &str) -> Option<&Order> {
+        self.orders.get(client_order_id)
+    }
+
+    pub fn open_orders(&self) -> Vec<&Order> {
+        self.orders.values().filter(|o| o.status.is_open()).collect()
+    }
+
+    /// Every recorded transition of an order, oldest first
+    pub fn history(&self, client_order_id: &str) -> Result<Vec<OrderEvent>> {
+        use crate::schema::orders::dsl;
+
+        Ok(dsl::orders
+            .filter(dsl::client_order_id.eq(client_order_id))
+            .order(dsl::id.asc())
+            .load::<OrderEvent>(&self.conn)?)
+    }
+
+    fn next_client_order_id(&self) -> String {
+        let id = format!("{}{}", self.prefix, Uuid::new_v4().to_simple());
+        id.chars().take(MAX_CLIENT_ORDER_ID_LEN).collect()
+    }
+
+    fn record(&self, order: &Order, reason: Option<String>) -> Result<()> {
+        use crate::schema::orders;
+
+        diesel::insert_into(orders::table)
+            .values(&order.to_event(reason))
+            .execute(&self.conn)?;
+        Ok(())
+    }
+
+    /// Record the order as PENDING_NEW, then place it. A rejection is recorded as
+    /// REJECTED and returned as the error; if the request fails without an answer
+    /// the order stays PENDING_NEW until `refresh` finds out what happened.
+    /// Orders refused by the risk checks are neither recorded nor sent.
+    pub fn submit(&mut self, request: OrderRequest) -> Result<Order> {
+        request.validate()?;
+        let open: Vec<&Order> = self.orders.values().filter(|o| o.status.is_open()).collect();
+        self.risk.check(&request, &open).map_err(Error::RiskRejected)?;
+
+        let mut order = Order {
+            client_order_id: self.next_client_order_id(),
+            order_id: None,
+            request,
+            status: OrderStatus::PendingNew,
+            executed_qty: BigDecimal::from(0),
+            cumulative_quote_qty: BigDecimal::from(0),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        self.record(&order, None)?;
+        self.orders.insert(order.client_order_id.clone(), order.clone());
+
+        match self.gateway.submit_order(&order.request, &order.client_order_id) {
+            Ok(result) => self.apply_update(&OrderUpdate::from(&result)).map(|o| o.unwrap_or(order)),
+            Err(Error::Api(e)) => {
+                order.status = OrderStatus::Rejected;
+                order.updated_at = chrono::Utc::now().naive_utc();
+                self.record(&order, Some(e.to_string()))?;
+                self.orders.insert(order.client_order_id.clone(), order);
+                Err(Error::Api(e))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Cancel the rest of an order, fills so far are kept
+    pub fn cancel(&mut self, client_order_id: &str) -> Result<Order> {
+        let symbol = self.tracked(client_order_id)?.request.symbol.clone();
+        let result = self
+            .gateway
+            .cancel_order(&symbol, &OrderRef::ClientOrderId(client_order_id.to_owned()))?;
+        self.apply_update(&OrderUpdate::from(&result))?;
+        self.tracked(client_order_id).map(Order::clone)
+    }
+
+    /// Cancel an order and place the unfilled remainder again at `price`, or with the
+    /// same price if None. Nothing is placed if the order filled before the cancel.
+    pub fn replace(&mut self, client_order_id: &str, price: Option<BigDecimal>) -> Result<Option<Order>> {
+        let canceled = self.cancel(client_order_id)?;
+        let remaining = canceled.remaining_qty();
+        if remaining <= BigDecimal::from(0) {
+            return Ok(None);
+        }
+        let mut request = canceled.request.clone();
+        request.quantity = remaining;
+        if price.is_some() {
+            request.price = price;
+        }
+        self.submit(request).map(Some)
+    }
+
+    /// GET the order's current state, e.g: to pick up fills or a lost submit
+    pub fn refresh(&mut self, client_order_id: &str) -> Result<Order> {
+        let symbol = self.tracked(client_order_id)?.request.symbol.clone();
+        let info = self
+            .gateway
+            .order_status(&symbol, &OrderRef::ClientOrderId(client_order_id.to_owned()))?;
+        self.apply_update(&OrderUpdate::from(&info))?;
+        self.tracked(client_order_id).map(Order::clone)
+    }
+
+    /// Apply an update from the exchange and persist it if the order changed.
+    /// Returns None for orders this manager didn't place.
+    pub fn apply_update(&mut self, update: &OrderUpdate) -> Result<Option<Order>> {
+        let order = match self.orders.get_mut(&update.client_order_id) {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        if !order.apply(update) {
+            return Ok(Some(order.clone()));
+        }
+        let order = order.clone();
+        self.record(&order, None)?;
+        info!(
+            symbol = order.request.symbol.as_str();
+            "Order {} {}: {} of {} filled",
+            order.client_order_id, order.status, order.executed_qty, order.request.quantity
+        );
+        Ok(Some(order))
+    }
+
+    fn tracked(&self, client_order_id: &str) -> Result<&Order> {
+        self.orders
+            .get(client_order_id)
+            .ok_or_else(|| Error::InvalidOrder(format!("unknown client order id: {}", client_order_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::mock_server::{serve, MockRequest, MockResponse};
+    use crate::models::orders::{OrderSide, OrderType};
+    use crate::rest_client::{ApiCredentials, BinanceRestClient};
+    use crate::serde_parsers::create_decimal_benchmark;
+    use crate::risk::{KillSwitch, RiskLimits};
+    use crate::symbols::{Symbol, SymbolInfo, SymbolRegistry};
+    use std::sync::{Arc, Mutex};
+
+    /// Order book of one symbol: every order rests until the test fills it
+    #[derive(Default)]
+    struct MockExchange {
+        next_id: i64,
+        orders: HashMap<String, serde_json::Value>,
+    }
+
+    impl MockExchange {
+        fn handle(&mut self, req: &MockRequest) -> MockResponse {
+            let client_id = req.param("newClientOrderId").or(req.param("origClientOrderId")).unwrap_or_default();
+            match (req.method.as_str(), req.path.as_str()) {
+                ("POST", "/api/v3/order") if req.param("quantity").map_or(false, |q| q.parse::<f64>().unwrap() > 100.0) => {
+                    MockResponse::json(400, r#"{"code": -2010, "msg": "Account has insufficient balance for requested action."}"#)
+                }
+                ("POST", "/api/v3/order") => {
+                    self.next_id += 1;
+                    let order = json!({
+                        "symbol": req.param("symbol"),
+                        "orderId": self.next_id,
+                        "clientOrderId": client_id,
+                        "transactTime": 1555444333222i64,
+                        "price": req.param("price"),
+                        "origQty": req.param("quantity"),
+                        "executedQty": "0",
+                        "cummulativeQuoteQty": "0",
+                        "status": "NEW",
+                        "timeInForce": req.param("timeInForce"),
+                        "type": req.param("type"),
+                        "side": req.param("side"),
+                        "stopPrice": "0",
+                        "time": 1555444333222i64,
+                        "updateTime": 1555444333222i64,
+                        "isWorking": true,
+                    });
+                    self.orders.insert(client_id.to_owned(), order.clone());
+                    MockResponse::json(200, order.to_string())
+                }
+                (method, "/api/v3/order") => match self.orders.get_mut(client_id) {
+                    Some(order) => {
+                        if method == "DELETE" {
+                            order["status"] = json!("CANCELED");
+                            order["origClientOrderId"] = json!(client_id);
+                        }
+                        MockResponse::json(200, order.to_string())
+                    }
+                    None => MockResponse::json(400, r#"{"code": -2013, "msg": "Order does not exist."}"#),
+                },
+                _ => MockResponse::json(404, "{}"),
+            }
+        }
+
+        fn fill(&mut self, client_id: &str, qty: &str, quote_qty: &str) {
+            let order = self.orders.get_mut(client_id).unwrap();
+            order["executedQty"] = json!(qty);
+            order["cummulativeQuoteQty"] = json!(quote_qty);
+            order["status"] = json!("PARTIALLY_FILLED");
+            order["updateTime"] = json!(1555444334000i64);
+        }
+    }
+
+    /// Loose limits on LTCBTC, so orders reach the mock exchange
+    fn risk_manager() -> RiskManager {
+        let symbol = Symbol::new("LTCBTC");
+        let mut symbols = SymbolRegistry::new();
+        symbols.insert(SymbolInfo {
+            symbol: symbol.clone(),
+            status: "TRADING".to_owned(),
+            base_asset: "LTC".to_owned(),
+            quote_asset: "BTC".to_owned(),
+            tick_size: create_decimal_benchmark("0.0001"),
+            step_size: BigDecimal::from(1),
+            min_qty: BigDecimal::from(1),
+            max_qty: BigDecimal::from(100_000),
+            min_notional: create_decimal_benchmark("0.001"),
+            updated_at: chrono::Utc::now().naive_utc(),
+        });
+        let mut limits = RiskLimits::default();
+        limits.max_position.insert(symbol.clone(), BigDecimal::from(10_000));
+        let mut risk = RiskManager::new(limits, symbols, KillSwitch::default());
+        risk.set_last_price(&symbol, create_decimal_benchmark("0.015"));
+        risk
+    }
+
+    #[test]
+    fn db_order_lifecycle_against_mock_exchange() {
+        let exchange = Arc::new(Mutex::new(MockExchange::default()));
+        let handler = exchange.clone();
+        let url = serve(move |req| handler.lock().unwrap().handle(req));
+
+        let conn = establish_connection_pg();
+        conn.begin_test_transaction().unwrap();
+        let client = BinanceRestClient::with_base_url(ApiCredentials::new("key", "secret"), &url);
+        let mut manager = OrderManager::new(Box::new(client), conn, "test-", risk_manager());
+
+        let symbol = Symbol::new("LTCBTC");
+        let price = create_decimal_benchmark("0.0150");
+        let order = manager
+            .submit(OrderRequest::limit(symbol.clone(), OrderSide::Buy, 10.into(), price.clone()))
+            .unwrap();
+        assert!(order.client_order_id.starts_with("test-") && order.client_order_id.len() == 36);
+        assert_eq!((order.order_id, order.status), (Some(1), OrderStatus::New));
+
+        // partial fill, then replace the remaining 6 at a better price
+        exchange.lock().unwrap().fill(&order.client_order_id, "4", "0.06");
+        let order = manager.refresh(&order.client_order_id).unwrap();
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(manager.refresh(&order.client_order_id).unwrap(), order); // unchanged, not recorded again
+
+        let replacement = manager
+            .replace(&order.client_order_id, Some(create_decimal_benchmark("0.0151")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.request.quantity, BigDecimal::from(6));
+        assert_eq!(replacement.request.order_type, OrderType::Limit);
+        assert_eq!(manager.get(&order.client_order_id).unwrap().status, OrderStatus::Canceled);
+        assert_eq!(manager.open_orders(), vec![&replacement]);
+
+        let statuses: Vec<(OrderStatus, BigDecimal)> = manager
+            .history(&order.client_order_id)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.status, e.executed_qty))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (OrderStatus::PendingNew, 0.into()),
+                (OrderStatus::New, 0.into()),
+                (OrderStatus::PartiallyFilled, 4.into()),
+                (OrderStatus::Canceled, 4.into()),
+            ]
+        );
+
+        // rejected by the exchange, and by validation before sending
+        match manager.submit(OrderRequest::market(symbol.clone(), OrderSide::Sell, 1000.into())) {
+            Err(Error::Api(e)) => assert_eq!(i32::from(e.code), -2010),
+            other => panic!("expected -2010, got {:?}", other),
+        }
+        let mut no_stop = OrderRequest::limit(symbol.clone(), OrderSide::Sell, 1.into(), price);
+        no_stop.order_type = OrderType::StopLossLimit;
+        match manager.submit(no_stop) {
+            Err(Error::InvalidOrder(_)) => (),
+            other => panic!("expected invalid order, got {:?}", other),
+        }
+        let rejected = manager.orders.values().find(|o| o.status == OrderStatus::Rejected).unwrap();
+        let history = manager.history(&rejected.client_order_id).unwrap();
+        assert_eq!(history.iter().map(|e| e.status).collect::<Vec<_>>(), vec![OrderStatus::PendingNew, OrderStatus::Rejected]);
+        assert!(history[1].reason.as_ref().unwrap().contains("insufficient balance"));
+
+        // a new manager picks up the open replacement
+        let mut restored = OrderManager::new(
+            Box::new(BinanceRestClient::with_base_url(ApiCredentials::new("key", "secret"), &url)),
+            manager.conn,
+            "test-",
+            risk_manager(),
+        );
+        assert_eq!(restored.restore().unwrap(), 1);
+        assert_eq!(restored.get(&replacement.client_order_id).unwrap().executed_qty, BigDecimal::from(0));
+    }
+}
@@ -0,0 +1,702 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use num::{ToPrimitive, Zero};
+
+use crate::error::{Error, Result};
+use crate::gateway::OrderGateway;
+use crate::models::account::{AccountInfo, AccountTrade, Balance, CancelOrderResult, NewOrderResult, OrderInfo};
+use crate::models::book_depth::{BookDepthDataInsert, Quote};
+use crate::models::orders::{OrderRequest, OrderSide, OrderStatus, OrderType, TimeInForce};
+use crate::models::trades::TradeData;
+use crate::rest_client::{ApiError, ApiErrorCode, OrderRef};
+use crate::symbols::{Symbol, SymbolRegistry};
+
+/// Fees and starting balances of the paper account
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperConfig {
+    pub maker_fee_rate: BigDecimal, // fraction of the amount received, Binance's default is 0.001
+    pub taker_fee_rate: BigDecimal,
+    pub balances: Vec<(String, BigDecimal)>, // (asset, free)
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        PaperConfig {
+            maker_fee_rate: "0.001".parse().unwrap(),
+            taker_fee_rate: "0.001".parse().unwrap(),
+            balances: vec![("USDT".to_owned(), BigDecimal::from(10_000)), ("BTC".to_owned(), BigDecimal::from(1))],
+        }
+    }
+}
+
+/// Price levels of the synced local order book, seeded from its snapshot then kept up by
+/// the `<symbol>@depth` diffs, plus the last trade price
+#[derive(Debug, Clone, Default)]
+struct PaperBook {
+    bids: BTreeMap<BigDecimal, BigDecimal>,
+    asks: BTreeMap<BigDecimal, BigDecimal>,
+    last_price: Option<BigDecimal>,
+}
+
+impl PaperBook {
+    fn apply(levels: &mut BTreeMap<BigDecimal, BigDecimal>, quotes: &[Quote]) {
+        for quote in quotes {
+            if quote.quantity.is_zero() {
+                levels.remove(&quote.price);
+            } else {
+                levels.insert(quote.price.clone(), quote.quantity.clone());
+            }
+        }
+    }
+
+    /// Quantity resting at `price` on our side of the book
+    fn level(&self, side: OrderSide, price: &BigDecimal) -> BigDecimal {
+        let levels = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        levels.get(price).cloned().unwrap_or_else(BigDecimal::zero)
+    }
+
+    /// Levels a taker order on `side` matches against, best first, within `limit` if given
+    fn takeable(&self, side: OrderSide, limit: Option<&BigDecimal>) -> Vec<(BigDecimal, BigDecimal)> {
+        let levels: Vec<(&BigDecimal, &BigDecimal)> = match side {
+            OrderSide::Buy => self.asks.iter().collect(),
+            OrderSide::Sell => self.bids.iter().rev().collect(),
+        };
+        levels
+            .into_iter()
+            .filter(|(price, _)| match (side, limit) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(limit)) => *price <= limit,
+                (OrderSide::Sell, Some(limit)) => *price >= limit,
+            })
+            .map(|(p, q)| (p.clone(), q.clone()))
+            .collect()
+    }
+
+    fn take(&mut self, side: OrderSide, price: &BigDecimal, quantity: &BigDecimal) {
+        let levels = match side {
+            OrderSide::Buy => &mut self.asks,
+            OrderSide::Sell => &mut self.bids,
+        };
+        let left = levels.get(price).map(|q| q - quantity).unwrap_or_else(BigDecimal::zero);
+        if left > BigDecimal::zero() {
+            levels.insert(price.clone(), left);
+        } else {
+            levels.remove(price);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PaperOrder {
+    info: OrderInfo,
+    queue_ahead: BigDecimal, // resting quantity in front of us at our price
+    locked: BigDecimal,      // held funds: quote asset for buys, base asset for sells
+    triggered: bool,         // stop orders wait for the last price to cross stop_price
+}
+
+impl PaperOrder {
+    fn remaining(&self) -> BigDecimal {
+        &self.info.orig_qty - &self.info.executed_qty
+    }
+
+    /// True if a trade at `price` passed this order's trigger
+    fn trigger_hit(&self, price: &BigDecimal) -> bool {
+        let stop = &self.info.stop_price;
+        match (self.info.order_type, self.info.side) {
+            (OrderType::StopLossLimit, OrderSide::Sell) | (OrderType::TakeProfitLimit, OrderSide::Buy) => price <= stop,
+            (OrderType::StopLossLimit, OrderSide::Buy) | (OrderType::TakeProfitLimit, OrderSide::Sell) => price >= stop,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PaperState {
+    config: PaperConfig,
+    assets: HashMap<Symbol, (String, String)>, // (base, quote)
+    books: HashMap<Symbol, PaperBook>,
+    orders: HashMap<String, PaperOrder>, // by client order id
+    open: Vec<String>,                   // open client order ids, oldest first
+    balances: BTreeMap<String, Balance>,
+    trades: Vec<AccountTrade>,
+    next_order_id: i64,
+    next_trade_id: i64,
+    now: NaiveDateTime, // time of the latest market event
+}
+
+fn reject(code: ApiErrorCode, msg: &str) -> Error {
+    Error::Api(ApiError { code, msg: msg.to_owned() })
+}
+
+impl PaperState {
+    fn balance(&mut self, asset: &str) -> &mut Balance {
+        self.balances.entry(asset.to_owned()).or_insert_with(|| Balance {
+            asset: asset.to_owned(),
+            free: BigDecimal::zero(),
+            locked: BigDecimal::zero(),
+        })
+    }
+
+    fn find(&self, order: &OrderRef) -> Option<String> {
+        match order {
+            OrderRef::ClientOrderId(id) if self.orders.contains_key(id) => Some(id.clone()),
+            OrderRef::ClientOrderId(_) => None,
+            OrderRef::OrderId(order_id) => self
+                .orders
+                .iter()
+                .find(|(_, o)| o.info.order_id == *order_id)
+                .map(|(id, _)| id.clone()),
+        }
+    }
+
+    fn submit(&mut self, request: &OrderRequest, client_order_id: &str) -> Result<NewOrderResult> {
+        request.validate()?;
+        let (base, quote) = match self.assets.get(&request.symbol) {
+            Some(assets) => assets.clone(),
+            None => return Err(reject(ApiErrorCode::InvalidSymbol, "Invalid symbol.")),
+        };
+        if self.orders.contains_key(client_order_id) {
+            return Err(reject(ApiErrorCode::NewOrderRejected, "Duplicate order sent."));
+        }
+
+        let book = self.books.entry(request.symbol.clone()).or_insert_with(PaperBook::default);
+        let limit = match request.order_type {
+            OrderType::Market => None,
+            _ => request.price.clone(),
+        };
+        if request.order_type == OrderType::LimitMaker && !book.takeable(request.side, limit.as_ref()).is_empty() {
+            return Err(reject(ApiErrorCode::NewOrderRejected, "Order would immediately match and take."));
+        }
+
+        // hold the funds the order could spend
+        let (lock_asset, lock) = match (request.side, &limit) {
+            (OrderSide::Sell, _) => (base, request.quantity.clone()),
+            (OrderSide::Buy, Some(price)) => (quote, price * &request.quantity),
+            (OrderSide::Buy, None) => {
+                let mut cost = BigDecimal::zero();
+                let mut left = request.quantity.clone();
+                for (price, qty) in book.takeable(OrderSide::Buy, None) {
+                    let take = if qty < left { qty } else { left.clone() };
+                    cost += &price * &take;
+                    left -= take;
+                }
+                if left > BigDecimal::zero() {
+                    match &book.last_price {
+                        Some(price) => cost += price * &left,
+                        None => return Err(reject(ApiErrorCode::NewOrderRejected, "No market price for a market order.")),
+                    }
+                }
+                (quote, cost)
+            }
+        };
+        {
+            let balance = self.balance(&lock_asset);
+            if balance.free < lock {
+                return Err(reject(
+                    ApiErrorCode::NewOrderRejected,
+                    "Account has insufficient balance for requested action.",
+                ));
+            }
+            balance.free -= &lock;
+            balance.locked += &lock;
+        }
+
+        let now = self.now;
+        let order = PaperOrder {
+            info: OrderInfo {
+                symbol: request.symbol.clone(),
+                order_id: self.next_order_id,
+                client_order_id: client_order_id.to_owned(),
+                price: request.price.clone().unwrap_or_else(BigDecimal::zero),
+                orig_qty: request.quantity.clone(),
+                executed_qty: BigDecimal::zero(),
+                cumulative_quote_qty: BigDecimal::zero(),
+                status: OrderStatus::New,
+                time_in_force: request.time_in_force.unwrap_or(TimeInForce::Gtc),
+                order_type: request.order_type,
+                side: request.side,
+                stop_price: request.stop_price.clone().unwrap_or_else(BigDecimal::zero),
+                time: now,
+                update_time: now,
+                is_working: true,
+            },
+            queue_ahead: BigDecimal::zero(),
+            locked: lock,
+            triggered: request.stop_price.is_none(),
+        };
+        self.next_order_id += 1;
+        self.orders.insert(client_order_id.to_owned(), order);
+        self.open.push(client_order_id.to_owned());
+
+        if self.orders[client_order_id].triggered {
+            self.activate(client_order_id);
+        }
+        let info = &self.orders[client_order_id].info;
+        Ok(NewOrderResult {
+            symbol: info.symbol.clone(),
+            order_id: info.order_id,
+            client_order_id: info.client_order_id.clone(),
+            transact_time: now,
+            executed_qty: info.executed_qty.clone(),
+            cumulative_quote_qty: info.cumulative_quote_qty.clone(),
+            status: info.status,
+        })
+    }
+
+    /// Match a new (or just triggered) order as taker, then rest the remainder
+    /// at the back of its price level, or expire it for market, IOC and FOK orders
+    fn activate(&mut self, id: &str) {
+        let (symbol, side, order_type, time_in_force, remaining, price) = {
+            let o = &self.orders[id];
+            (o.info.symbol.clone(), o.info.side, o.info.order_type, o.info.time_in_force, o.remaining(), o.info.price.clone())
+        };
+        let limit = if order_type == OrderType::Market { None } else { Some(price.clone()) };
+        let levels = self.books[&symbol].takeable(side, limit.as_ref());
+
+        let available = levels.iter().fold(BigDecimal::zero(), |sum, (_, qty)| sum + qty);
+        if time_in_force == TimeInForce::Fok && available < remaining {
+            return self.close(id, OrderStatus::Expired);
+        }
+        let mut left = remaining;
+        for (level_price, qty) in levels {
+            if left.is_zero() {
+                break;
+            }
+            let take = if qty < left { qty } else { left.clone() };
+            self.books.get_mut(&symbol).unwrap().take(side, &level_price, &take);
+            self.fill(id, &take, &level_price, false);
+            left -= take;
+        }
+
+        if left > BigDecimal::zero() {
+            if order_type == OrderType::Market || time_in_force != TimeInForce::Gtc {
+                self.close(id, OrderStatus::Expired);
+            } else {
+                let queue_ahead = self.books[&symbol].level(side, &price);
+                self.orders.get_mut(id).unwrap().queue_ahead = queue_ahead;
+            }
+        }
+    }
+
+    fn fill(&mut self, id: &str, quantity: &BigDecimal, price: &BigDecimal, is_maker: bool) {
+        let fee_rate = if is_maker { self.config.maker_fee_rate.clone() } else { self.config.taker_fee_rate.clone() };
+        let now = self.now;
+        let (symbol, side, order_id, release) = {
+            let o = self.orders.get_mut(id).unwrap();
+            let remaining = o.remaining();
+            let release = match o.info.side {
+                OrderSide::Sell => quantity.clone(),
+                OrderSide::Buy if *quantity == remaining => o.locked.clone(),
+                OrderSide::Buy => &o.locked * quantity / &remaining,
+            };
+            o.locked -= &release;
+            o.info.executed_qty += quantity;
+            o.info.cumulative_quote_qty += quantity * price;
+            o.info.status = if o.remaining().is_zero() { OrderStatus::Filled } else { OrderStatus::PartiallyFilled };
+            o.info.update_time = now;
+            (o.info.symbol.clone(), o.info.side, o.info.order_id, release)
+        };
+        let (base, quote) = self.assets[&symbol].clone();
+        let quote_qty = quantity * price;
+
+        // commission is taken from the asset received
+        let (commission, commission_asset) = match side {
+            OrderSide::Buy => {
+                let commission = quantity * &fee_rate;
+                let q = self.balance(&quote);
+                q.locked -= &release;
+                q.free += &release - &quote_qty;
+                self.balance(&base).free += quantity - &commission;
+                (commission, base)
+            }
+            OrderSide::Sell => {
+                let commission = &quote_qty * &fee_rate;
+                self.balance(&base).locked -= &release;
+                self.balance(&quote).free += &quote_qty - &commission;
+                (commission, quote)
+            }
+        };
+        self.trades.push(AccountTrade {
+            symbol,
+            id: self.next_trade_id,
+            order_id,
+            price: price.clone(),
+            qty: quantity.clone(),
+            quote_qty,
+            commission,
+            commission_asset,
+            time: now,
+            is_buyer: side == OrderSide::Buy,
+            is_maker,
+            is_best_match: true,
+        });
+        self.next_trade_id += 1;
+
+        if self.orders[id].info.status == OrderStatus::Filled {
+            self.close(id, OrderStatus::Filled);
+        }
+    }
+
+    /// Take the order off the book and give back whatever it still holds
+    fn close(&mut self, id: &str, status: OrderStatus) {
+        let (symbol, side, refund) = {
+            let o = self.orders.get_mut(id).unwrap();
+            o.info.status = status;
+            o.info.is_working = false;
+            o.info.update_time = self.now;
+            let refund = std::mem::replace(&mut o.locked, BigDecimal::zero());
+            (o.info.symbol.clone(), o.info.side, refund)
+        };
+        let (base, quote) = self.assets[&symbol].clone();
+        let balance = self.balance(if side == OrderSide::Buy { &quote } else { &base });
+        balance.locked -= &refund;
+        balance.free += refund;
+        self.open.retain(|open| open != id);
+    }
+
+    /// A live trade triggers stop orders, and fills resting orders it traded through.
+    /// At our price level, the traded quantity first works through the queue ahead of us.
+    fn on_trade(&mut self, trade: &TradeData) {
+        self.now = trade.trade_time;
+        self.books.entry(trade.symbol.clone()).or_insert_with(PaperBook::default).last_price = Some(trade.price.clone());
+
+        let ids: Vec<String> = self
+            .open
+            .iter()
+            .filter(|id| self.orders[*id].info.symbol == trade.symbol)
+            .cloned()
+            .collect();
+        let mut left = trade.quantity.clone();
+        for id in ids {
+            if !self.open.contains(&id) {
+                continue;
+            }
+            if !self.orders[&id].triggered {
+                if self.orders[&id].trigger_hit(&trade.price) {
+                    self.orders.get_mut(&id).unwrap().triggered = true;
+                    self.activate(&id);
+                }
+                continue;
+            }
+
+            let o = self.orders.get_mut(&id).unwrap();
+            // the buyer was the maker, so a seller hit the bids
+            let (hits_us, through) = match o.info.side {
+                OrderSide::Buy => (trade.buyer_mkt_maker && trade.price <= o.info.price, trade.price < o.info.price),
+                OrderSide::Sell => (!trade.buyer_mkt_maker && trade.price >= o.info.price, trade.price > o.info.price),
+            };
+            if !hits_us || o.info.order_type == OrderType::Market {
+                continue;
+            }
+            // never more than traded, left over from orders ahead of this one
+            let quantity = if through {
+                let take = if left < o.remaining() { left.clone() } else { o.remaining() };
+                left -= &take;
+                take
+            } else {
+                if o.queue_ahead >= left {
+                    o.queue_ahead -= &left;
+                    left = BigDecimal::zero();
+                    continue;
+                }
+                left -= &o.queue_ahead;
+                o.queue_ahead = BigDecimal::zero();
+                let take = if left < o.remaining() { left.clone() } else { o.remaining() };
+                left -= &take;
+                take
+            };
+            if quantity > BigDecimal::zero() {
+                let price = o.info.price.clone();
+                self.fill(&id, &quantity, &price, true);
+            }
+        }
+    }
+
+    /// Cancels ahead of us shrink the queue, the queue never grows behind us
+    fn on_book_update(&mut self, depth: &BookDepthDataInsert) {
+        self.now = depth.event_time;
+        let book = self.books.entry(depth.symbol.clone()).or_insert_with(PaperBook::default);
+        PaperBook::apply(&mut book.bids, &depth.bids);
+        PaperBook::apply(&mut book.asks, &depth.asks);
+        self.shrink_queues(&depth.symbol);
+    }
+
+    /// Replace every level with those of a freshly synced local order book
+    fn on_book_snapshot(&mut self, symbol: &Symbol, bids: &[Quote], asks: &[Quote]) {
+        let book = self.books.entry(symbol.clone()).or_insert_with(PaperBook::default);
+        book.bids.clear();
+        book.asks.clear();
+        PaperBook::apply(&mut book.bids, bids);
+        PaperBook::apply(&mut book.asks, asks);
+        self.shrink_queues(symbol);
+    }
+
+    fn shrink_queues(&mut self, symbol: &Symbol) {
+        let book = &self.books[symbol];
+        for id in self.open.iter() {
+            let o = self.orders.get_mut(id).unwrap();
+            if o.info.symbol == *symbol && o.triggered {
+                let level = book.level(o.info.side, &o.info.price);
+                if level < o.queue_ahead {
+                    o.queue_ahead = level;
+                }
+            }
+        }
+    }
+}
+
+/// Simulated exchange for paper trading: orders go through the same `OrderGateway`
+/// as live trading and are matched against the live trade and depth streams.
+/// Limit orders join the back of their price level and only fill once the quantity
+/// ahead of them has traded, or the price trades through them.
+#[derive(Debug, Clone)]
+pub struct PaperExchange {
+    state: Arc<Mutex<PaperState>>,
+}
+
+impl PaperExchange {
+    pub fn new(config: PaperConfig, symbols: &SymbolRegistry) -> Self {
+        let mut state = PaperState {
+            config: config.clone(),
+            assets: HashMap::new(),
+            books: HashMap::new(),
+            orders: HashMap::new(),
+            open: Vec::new(),
+            balances: BTreeMap::new(),
+            trades: Vec::new(),
+            next_order_id: 1,
+            next_trade_id: 1,
+            now: chrono::Utc::now().naive_utc(),
+        };
+        for info in symbols.iter() {
+            state.assets.insert(info.symbol.clone(), (info.base_asset.clone(), info.quote_asset.clone()));
+        }
+        for (asset, free) in config.balances.iter() {
+            state.balance(asset).free = free.clone();
+        }
+        PaperExchange { state: Arc::new(Mutex::new(state)) }
+    }
+
+    pub fn on_trade(&self, trade: &TradeData) {
+        self.state.lock().unwrap().on_trade(trade)
+    }
+
+    /// A diff applied to a synced local order book
+    pub fn on_book_update(&self, depth: &BookDepthDataInsert) {
+        self.state.lock().unwrap().on_book_update(depth)
+    }
+
+    /// The full ladder of a local order book once synced, or resynced
+    pub fn on_book_snapshot(&self, symbol: &Symbol, bids: &[Quote], asks: &[Quote]) {
+        self.state.lock().unwrap().on_book_snapshot(symbol, bids, asks)
+    }
+
+    pub fn balance(&self, asset: &str) -> Option<Balance> {
+        self.state.lock().unwrap().balances.get(asset).cloned()
+    }
+
+    /// Every simulated execution, oldest first
+    pub fn trades(&self) -> Vec<AccountTrade> {
+        self.state.lock().unwrap().trades.clone()
+    }
+}
+
+impl OrderGateway for PaperExchange {
+    fn submit_order(&self, request: &OrderRequest, client_order_id: &str) -> Result<NewOrderResult> {
+        self.state.lock().unwrap().submit(request, client_order_id)
+    }
+
+    fn cancel_order(&self, _symbol: &Symbol, order: &OrderRef) -> Result<CancelOrderResult> {
+        let mut state = self.state.lock().unwrap();
+        let id = state
+            .find(order)
+            .ok_or_else(|| reject(ApiErrorCode::NoSuchOrder, "Order does not exist."))?;
+        if !state.open.contains(&id) {
+            return Err(reject(ApiErrorCode::CancelRejected, "Unknown order sent."));
+        }
+        state.close(&id, OrderStatus::Canceled);
+        let info = &state.orders[&id].info;
+        Ok(CancelOrderResult {
+            symbol: info.symbol.clone(),
+            order_id: info.order_id,
+            orig_client_order_id: id.clone(),
+            executed_qty: info.executed_qty.clone(),
+            cumulative_quote_qty: info.cumulative_quote_qty.clone(),
+            status: info.status,
+        })
+    }
+
+    fn order_status(&self, _symbol: &Symbol, order: &OrderRef) -> Result<OrderInfo> {
+        let state = self.state.lock().unwrap();
+        state
+            .find(order)
+            .map(|id| state.orders[&id].info.clone())
+            .ok_or_else(|| reject(ApiErrorCode::NoSuchOrder, "Order does not exist."))
+    }
+
+    fn open_orders(&self, symbol: Option<&Symbol>) -> Result<Vec<OrderInfo>> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .open
+            .iter()
+            .map(|id| state.orders[id].info.clone())
+            .filter(|info| symbol.map_or(true, |s| info.symbol == *s))
+            .collect())
+    }
+
+    fn account(&self) -> Result<AccountInfo> {
+        let state = self.state.lock().unwrap();
+        let bips = |rate: &BigDecimal| (rate * BigDecimal::from(10_000)).to_i32().unwrap_or(0);
+        Ok(AccountInfo {
+            maker_commission: bips(&state.config.maker_fee_rate),
+            taker_commission: bips(&state.config.taker_fee_rate),
+            buyer_commission: 0,
+            seller_commission: 0,
+            can_trade: true,
+            can_withdraw: false,
+            can_deposit: false,
+            update_time: state.now,
+            balances: state.balances.values().cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use diesel::connection::Connection;
+    use crate::order_manager::OrderManager;
+    use crate::risk::{KillSwitch, RiskLimits, RiskManager};
+    use crate::serde_parsers::{create_decimal_benchmark, create_timestamp_benchmark};
+    use crate::symbols::{ExchangeInfo, TEST_EXCHANGE_INFO_DATA};
+
+    const T0: i64 = 1_546_300_800_000;
+
+    fn exchange() -> PaperExchange {
+        let info = serde_json::from_str::<ExchangeInfo>(TEST_EXCHANGE_INFO_DATA).unwrap();
+        let config = PaperConfig {
+            balances: vec![("BTC".to_owned(), BigDecimal::from(1)), ("ETH".to_owned(), BigDecimal::from(10))],
+            ..PaperConfig::default()
+        };
+        PaperExchange::new(config, &SymbolRegistry::from_exchange_info(info))
+    }
+
+    fn depth(ms: i64, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> BookDepthDataInsert {
+        let quotes = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|(p, q)| Quote { price: create_decimal_benchmark(p), quantity: create_decimal_benchmark(q) })
+                .collect()
+        };
+        BookDepthDataInsert {
+            event: "depthUpdate".to_owned(),
+            event_time: create_timestamp_benchmark(T0 + ms),
+            symbol: Symbol::new("ETHBTC"),
+            update_first: 1,
+            update_final: 1,
+            bids: quotes(bids),
+            asks: quotes(asks),
+        }
+    }
+
+    fn trade(ms: i64, price: &str, quantity: &str, buyer_mkt_maker: bool) -> TradeData {
+        TradeData {
+            trade_id: ms,
+            event: "trade".to_owned(),
+            event_time: create_timestamp_benchmark(T0 + ms),
+            symbol: Symbol::new("ETHBTC"),
+            price: create_decimal_benchmark(price),
+            quantity: create_decimal_benchmark(quantity),
+            trade_time: create_timestamp_benchmark(T0 + ms),
+            buyer_order_id: 1,
+            seller_order_id: 2,
+            buyer_mkt_maker,
+        }
+    }
+
+    #[test]
+    fn limit_orders_wait_their_turn_in_the_queue() {
+        let paper = exchange();
+        let ethbtc = Symbol::new("ETHBTC");
+        // levels from before the book synced are replaced by the synced book
+        paper.on_book_update(&depth(0, &[("0.0295", "50")], &[]));
+        let synced = depth(0, &[("0.030", "5")], &[("0.031", "2"), ("0.032", "10")]);
+        paper.on_book_snapshot(&ethbtc, &synced.bids, &synced.asks);
+
+        // bid joins 5 ETH already resting at 0.030, BTC is held
+        let bid = OrderRequest::limit(ethbtc.clone(), OrderSide::Buy, 2.into(), create_decimal_benchmark("0.030"));
+        paper.submit_order(&bid, "bid").unwrap();
+        assert_eq!(paper.balance("BTC").unwrap().locked, create_decimal_benchmark("0.06"));
+
+        // 1 ETH cancelled ahead of us, then 5 sold: 4 work through the queue, 1 fills us
+        paper.on_book_update(&depth(10, &[("0.030", "4")], &[]));
+        paper.on_trade(&trade(20, "0.030", "5", true));
+        let status = paper.order_status(&ethbtc, &OrderRef::ClientOrderId("bid".to_owned())).unwrap();
+        assert_eq!((status.status, status.executed_qty.clone()), (OrderStatus::PartiallyFilled, BigDecimal::from(1)));
+
+        // trades through our price fill at most the quantity traded
+        paper.on_trade(&trade(30, "0.029", "0.1", true));
+        let status = paper.order_status(&ethbtc, &OrderRef::OrderId(status.order_id)).unwrap();
+        assert_eq!(status.executed_qty, create_decimal_benchmark("1.1"));
+        paper.on_trade(&trade(35, "0.029", "5", true));
+        let status = paper.order_status(&ethbtc, &OrderRef::OrderId(status.order_id)).unwrap();
+        assert_eq!(status.status, OrderStatus::Filled);
+        assert!(paper.open_orders(None).unwrap().is_empty());
+
+        // market sell walks the bids as taker, fee is charged in BTC
+        paper.on_book_update(&depth(40, &[("0.030", "0"), ("0.029", "1"), ("0.028", "10")], &[]));
+        let sell = OrderRequest::market(ethbtc.clone(), OrderSide::Sell, 3.into());
+        let result = paper.submit_order(&sell, "sell").unwrap();
+        assert_eq!(result.cumulative_quote_qty, create_decimal_benchmark("0.085"));
+
+        let trades = paper.trades();
+        assert_eq!(trades.len(), 5);
+        assert_eq!(trades[2].qty, create_decimal_benchmark("0.9"));
+        assert!(trades[0].is_maker && !trades[4].is_maker);
+        assert_eq!(trades[0].commission, create_decimal_benchmark("0.001")); // 0.1% of 1 ETH
+        // 1 - 0.06 + 0.085 * 0.999
+        assert_eq!(paper.balance("BTC").unwrap().free, create_decimal_benchmark("1.024915"));
+        assert_eq!(paper.balance("ETH").unwrap().free, create_decimal_benchmark("8.998"));
+
+        match paper.submit_order(&OrderRequest::market(ethbtc, OrderSide::Sell, 100.into()), "too much") {
+            Err(Error::Api(e)) => assert_eq!(e.code, ApiErrorCode::NewOrderRejected),
+            other => panic!("expected insufficient balance, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn db_order_manager_trades_on_paper_exchange() {
+        let paper = exchange();
+        let ethbtc = Symbol::new("ETHBTC");
+        paper.on_book_update(&depth(0, &[("0.030", "5")], &[("0.031", "2")]));
+
+        let conn = establish_connection_pg();
+        conn.begin_test_transaction().unwrap();
+        let info = serde_json::from_str::<ExchangeInfo>(TEST_EXCHANGE_INFO_DATA).unwrap();
+        let mut limits = RiskLimits { price_band: create_decimal_benchmark("0.1"), ..RiskLimits::default() };
+        limits.max_position.insert(ethbtc.clone(), BigDecimal::from(10));
+        let mut risk = RiskManager::new(limits, SymbolRegistry::from_exchange_info(info), KillSwitch::default());
+        risk.set_last_price(&ethbtc, create_decimal_benchmark("0.030"));
+        let mut manager = OrderManager::new(Box::new(paper.clone()), conn, "paper-", risk);
+
+        let order = manager
+            .submit(OrderRequest::limit(ethbtc.clone(), OrderSide::Sell, 1.into(), create_decimal_benchmark("0.032")))
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::New);
+        paper.on_trade(&trade(10, "0.033", "1", false));
+        assert_eq!(manager.refresh(&order.client_order_id).unwrap().status, OrderStatus::Filled);
+
+        let order = manager
+            .submit(OrderRequest::limit(ethbtc, OrderSide::Buy, 1.into(), create_decimal_benchmark("0.029")))
+            .unwrap();
+        assert_eq!(manager.cancel(&order.client_order_id).unwrap().status, OrderStatus::Canceled);
+        assert_eq!(paper.balance("BTC").unwrap().locked, BigDecimal::zero());
+    }
+}
@@ -0,0 +1,260 @@
+use redis::PipelineCommands;
+use std::time::{Duration, Instant};
+
+use crate::backoff::Backoff;
+use crate::db_writer::Row;
+use crate::error::Result;
+use crate::symbols::Symbol;
+
+/////////////////////////////////////////////////////////////////
+/// Redis fan-out: every market event is published on a
+/// `<SYMBOL>:<stream>` channel, e.g: "ETHBTC:trade", "ETHBTC:kline_1m",
+/// and the latest values are kept in a `<SYMBOL>:latest` hash
+/// so other services can read state without querying Postgres.
+/////////////////////////////////////////////////////////////////
+
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1/";
+
+/// Pub/sub channel for a row, None for rows that aren't market data (fills, dead letters)
+pub fn channel(row: &Row) -> Option<String> {
+    let (symbol, stream) = match row {
+        Row::Trade(t) => (&t.symbol, "trade".to_owned()),
+        Row::AggregateTrade(t) => (&t.symbol, "aggTrade".to_owned()),
+        Row::Kline(k) => (&k.symbol, format!("kline_{}", k.interval)),
+        Row::Bar(b) => (&b.symbol, format!("bar_{}", b.spec)),
+        Row::Ticker(t) => (&t.symbol, "ticker".to_owned()),
+        Row::MiniTicker(t) => (&t.symbol, "miniTicker".to_owned()),
+        Row::BookDepth(b) => (&b.symbol, "depth".to_owned()),
+        Row::BookSnapshot(b) => (&b.symbol, format!("depth{}", b.levels)),
+        Row::Fill(_) | Row::DeadLetter(_) => return None,
+    };
+    Some(format!("{}:{}", symbol.as_str(), stream))
+}
+
+/// Hash holding the latest values for a symbol
+pub fn latest_key(symbol: &Symbol) -> String {
+    format!("{}:latest", symbol.as_str())
+}
+
+/// Fields of the `<SYMBOL>:latest` hash updated by a row. Depth diffs only carry
+/// changed levels, so best bid/ask come from the ticker or depth snapshots; klines once closed, bars always are.
+pub fn latest_fields(row: &Row) -> Vec<(String, String)> {
+    let field = |name: &str, value: String| (name.to_owned(), value);
+    match row {
+        Row::Trade(t) => vec![
+            field("last_price", t.price.to_string()),
+            field("last_quantity", t.quantity.to_string()),
+            field("last_trade_time", t.trade_time.timestamp_millis().to_string()),
+        ],
+        Row::AggregateTrade(t) => vec![
+            field("last_price", t.price.to_string()),
+            field("last_quantity", t.quantity.to_string()),
+            field("last_trade_time", t.trade_time.timestamp_millis().to_string()),
+        ],
+        Row::Ticker(t) => vec![
+            field("last_price", t.last_price.to_string()),
+            field("best_bid_price", t.best_bid_price.to_string()),
+            field("best_bid_quantity", t.best_bid_quantity.to_string()),
+            field("best_ask_price", t.best_ask_price.to_string()),
+            field("best_ask_quantity", t.best_ask_quantity.to_string()),
+        ],
+        Row::MiniTicker(t) => vec![field("last_price", t.close.to_string())],
+        Row::BookSnapshot(b) => {
+            let mut fields = Vec::new();
+            if let Some(bid) = b.bids.first() {
+                fields.push(field("best_bid_price", bid.price.to_string()));
+                fields.push(field("best_bid_quantity", bid.quantity.to_string()));
+            }
+            if let Some(ask) = b.asks.first() {
+                fields.push(field("best_ask_price", ask.price.to_string()));
+                fields.push(field("best_ask_quantity", ask.quantity.to_string()));
+            }
+            fields
+        }
+        Row::Kline(k) if k.is_kline_closed => {
+            let kline = serde_json::to_string(k).unwrap_or_default();
+            vec![field(&format!("kline_{}", k.interval), kline)]
+        }
+        Row::Bar(b) => {
+            let bar = serde_json::to_string(b).unwrap_or_default();
+            vec![field(&format!("bar_{}", b.spec), bar)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn row_symbol(row: &Row) -> Option<&Symbol> {
+    match row {
+        Row::Trade(t) => Some(&t.symbol),
+        Row::AggregateTrade(t) => Some(&t.symbol),
+        Row::Kline(k) => Some(&k.symbol),
+        Row::Bar(b) => Some(&b.symbol),
+        Row::Ticker(t) => Some(&t.symbol),
+        Row::MiniTicker(t) => Some(&t.symbol),
+        Row::BookDepth(b) => Some(&b.symbol),
+        Row::BookSnapshot(b) => Some(&b.symbol),
+        Row::Fill(_) | Row::DeadLetter(_) => None,
+    }
+}
+
+/// Blocking connection, one per writer thread
+pub struct RedisPublisher {
+    conn: redis::Connection,
+}
+
+impl RedisPublisher {
+    pub fn connect(url: &str) -> Result<Self> {
+        let conn = redis::Client::open(url)?.get_connection()?;
+        Ok(RedisPublisher { conn })
+    }
+
+    /// PUBLISH the row as JSON and update the latest-value hash in one round trip
+    pub fn publish(&self, row: &Row) -> Result<()> {
+        let (channel, symbol) = match (channel(row), row_symbol(row)) {
+            (Some(channel), Some(symbol)) => (channel, symbol),
+            _ => return Ok(()),
+        };
+        let payload = match row {
+            Row::Trade(t) => serde_json::to_string(t)?,
+            Row::AggregateTrade(t) => serde_json::to_string(t)?,
+            Row::Kline(k) => serde_json::to_string(k)?,
+            Row::Bar(b) => serde_json::to_string(b)?,
+            Row::Ticker(t) => serde_json::to_string(t)?,
+            Row::MiniTicker(t) => serde_json::to_string(t)?,
+            Row::BookDepth(b) => serde_json::to_string(b)?,
+            Row::BookSnapshot(b) => serde_json::to_string(b)?,
+            Row::Fill(_) | Row::DeadLetter(_) => return Ok(()),
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.publish(channel, payload).ignore();
+        let fields = latest_fields(row);
+        if !fields.is_empty() {
+            pipe.hset_multiple(latest_key(symbol), &fields).ignore();
+        }
+        pipe.query::<()>(&self.conn)?;
+        Ok(())
+    }
+}
+
+/// Publisher that survives Redis going away: rows published while disconnected
+/// are dropped, and the connection is retried with backoff on the next publish
+/// once the delay has passed, so a Redis outage never stalls ingestion.
+pub struct ReconnectingPublisher {
+    url: String,
+    conn: Option<RedisPublisher>,
+    backoff: Backoff,
+    retry_at: Option<Instant>, // no reconnect attempts before this
+}
+
+impl ReconnectingPublisher {
+    pub fn new(url: &str) -> Self {
+        ReconnectingPublisher {
+            url: url.to_owned(),
+            conn: None,
+            backoff: Backoff::new(Duration::from_millis(500), Duration::from_secs(30), None),
+            retry_at: None,
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// Connect if not connected and the backoff delay has passed
+    pub fn connect(&mut self) -> Result<()> {
+        if self.conn.is_some() || self.retry_at.map_or(false, |at| Instant::now() < at) {
+            return Ok(());
+        }
+        match RedisPublisher::connect(&self.url) {
+            Ok(conn) => {
+                self.conn = Some(conn);
+                self.backoff.reset();
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.disconnect();
+                Err(e)
+            }
+        }
+    }
+
+    /// Publish on the current connection, dropping it on error. `Ok(false)` if the row was
+    /// dropped because we're waiting to reconnect
+    pub fn publish(&mut self, row: &Row) -> Result<bool> {
+        self.connect()?;
+        let result = match &self.conn {
+            Some(conn) => conn.publish(row),
+            None => return Ok(false),
+        };
+        if result.is_err() {
+            self.disconnect();
+        }
+        result.map(|_| true)
+    }
+
+    fn disconnect(&mut self) {
+        self.conn = None;
+        let delay = self.backoff.next_delay().unwrap_or(self.backoff.max_delay);
+        self.retry_at = Some(Instant::now() + delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::klines::{map_klinemeta_to_klineinsertdata, KlineMetaData, TEST_KLINE_DATA};
+    use crate::models::tickers::{TickerDataInsert, TEST_TICKER_DATA};
+    use crate::models::trades::{TradeData, TEST_TRADE_DATA};
+
+    #[test]
+    fn channels_and_latest_fields() {
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let row = Row::Trade(trade.clone());
+        assert_eq!(channel(&row), Some(format!("{}:trade", trade.symbol.as_str())));
+        assert_eq!(latest_key(&trade.symbol), format!("{}:latest", trade.symbol.as_str()));
+        assert_eq!(latest_fields(&row)[0], ("last_price".to_owned(), trade.price.to_string()));
+
+        let ticker = serde_json::from_str::<TickerDataInsert>(TEST_TICKER_DATA).unwrap();
+        let fields = latest_fields(&Row::Ticker(ticker.clone()));
+        assert_eq!(fields[1], ("best_bid_price".to_owned(), ticker.best_bid_price.to_string()));
+        assert_eq!(fields[3], ("best_ask_price".to_owned(), ticker.best_ask_price.to_string()));
+
+        // open klines are published but don't replace the last closed one
+        let mut kline = map_klinemeta_to_klineinsertdata(serde_json::from_str::<KlineMetaData>(TEST_KLINE_DATA).unwrap());
+        assert_eq!(channel(&Row::Kline(kline.clone())), Some("BNBBTC:kline_1m".to_owned()));
+        assert!(latest_fields(&Row::Kline(kline.clone())).is_empty());
+        kline.is_kline_closed = true;
+        assert_eq!(latest_fields(&Row::Kline(kline))[0].0, "kline_1m");
+    }
+
+    #[test]
+    fn reconnect_waits_for_backoff() {
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let mut publisher = ReconnectingPublisher::new("redis://127.0.0.1:1/");
+        assert!(publisher.publish(&Row::Trade(trade.clone())).is_err());
+        assert!(!publisher.is_connected());
+        // within the backoff delay rows are dropped without trying to connect
+        assert_eq!(publisher.publish(&Row::Trade(trade)).unwrap(), false);
+    }
+
+    #[test]
+    #[ignore] // needs a redis-server on DEFAULT_REDIS_URL
+    fn redis_publish_round_trip() {
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let mut subscriber = redis::Client::open(DEFAULT_REDIS_URL).unwrap().get_connection().unwrap();
+        let mut pubsub = subscriber.as_pubsub();
+        pubsub.subscribe(channel(&Row::Trade(trade.clone())).unwrap()).unwrap();
+        pubsub.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut publisher = ReconnectingPublisher::new(DEFAULT_REDIS_URL);
+        assert!(publisher.publish(&Row::Trade(trade.clone())).unwrap());
+        let payload: String = pubsub.get_message().unwrap().get_payload().unwrap();
+        assert_eq!(serde_json::from_str::<TradeData>(&payload).unwrap(), trade);
+
+        let conn = redis::Client::open(DEFAULT_REDIS_URL).unwrap().get_connection().unwrap();
+        let price: String = redis::cmd("HGET").arg(latest_key(&trade.symbol)).arg("last_price").query(&conn).unwrap();
+        assert_eq!(price, trade.price.to_string());
+    }
+}
@@ -0,0 +1,793 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use num::Zero;
+
+use crate::error::{Error, Result};
+use crate::models::aggregate_trades::AggregateTradeData;
+use crate::models::book_depth::{BookDepthData, BookSnapshot};
+use crate::models::bars::BarInsert;
+use crate::models::klines::{KlineData, KlineInterval};
+use crate::models::tickers::TickerData;
+use crate::models::trades::TradeData;
+use crate::symbols::Symbol;
+
+/////////////////////////////////////////////////////////////////
+/// Query stored market data with filters, optionally bucketed
+/// into intervals, and write it as a table, CSV, JSON lines or Parquet.
+/////////////////////////////////////////////////////////////////
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Tables that can be queried, named like the Postgres tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryTable {
+    Trades,
+    AggregateTrades,
+    Klines,
+    Bars,
+    Tickers,
+    BookDepth,
+    BookSnapshots,
+}
+
+impl FromStr for QueryTable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<QueryTable> {
+        match s {
+            "trades" => Ok(QueryTable::Trades),
+            "aggregate_trades" => Ok(QueryTable::AggregateTrades),
+            "klines" => Ok(QueryTable::Klines),
+            "bars" => Ok(QueryTable::Bars),
+            "tickers" => Ok(QueryTable::Tickers),
+            "book_depth" => Ok(QueryTable::BookDepth),
+            "book_snapshots" => Ok(QueryTable::BookSnapshots),
+            _ => Err(Error::InvalidEnum("QueryTable", s.to_string())),
+        }
+    }
+}
+
+/// Computed per interval bucket from trades, aggregate trades or tickers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Count,  // number of rows
+    Volume, // base asset quantity
+    Vwap,   // volume weighted average price
+    Ohlc,   // open, high, low, close
+}
+
+impl FromStr for Aggregation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Aggregation> {
+        match s {
+            "count" => Ok(Aggregation::Count),
+            "volume" => Ok(Aggregation::Volume),
+            "vwap" => Ok(Aggregation::Vwap),
+            "ohlc" => Ok(Aggregation::Ohlc),
+            _ => Err(Error::InvalidEnum("Aggregation", s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    JsonLines,
+    Parquet, // needs the `parquet` feature
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            "parquet" => Ok(OutputFormat::Parquet),
+            _ => Err(Error::InvalidEnum("OutputFormat", s.to_string())),
+        }
+    }
+}
+
+/// One value of a result row
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Int(i64),
+    Decimal(BigDecimal),
+    Text(String),
+    Time(NaiveDateTime),
+    Bool(bool),
+    Json(serde_json::Value),
+}
+
+impl Cell {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Cell::Int(n) => json!(n),
+            Cell::Bool(b) => json!(b),
+            Cell::Json(v) => v.clone(),
+            other => json!(other.to_string()), // decimals as strings keep every digit
+        }
+    }
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Cell::Int(n) => write!(f, "{}", n),
+            Cell::Decimal(d) => write!(f, "{}", d),
+            Cell::Text(s) => write!(f, "{}", s),
+            Cell::Time(t) => write!(f, "{}", t.format(TIME_FORMAT)),
+            Cell::Bool(b) => write!(f, "{}", b),
+            Cell::Json(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A model that can be printed as a result row
+pub trait ToRow {
+    fn columns() -> Vec<&'static str>;
+    fn to_row(&self) -> Vec<Cell>;
+}
+
+impl ToRow for TradeData {
+    fn columns() -> Vec<&'static str> {
+        vec!["trade_id", "trade_time", "symbol", "price", "quantity", "buyer_mkt_maker"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Int(self.trade_id.into()),
+            Cell::Time(self.trade_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Decimal(self.price.clone()),
+            Cell::Decimal(self.quantity.clone()),
+            Cell::Bool(self.buyer_mkt_maker),
+        ]
+    }
+}
+
+impl ToRow for AggregateTradeData {
+    fn columns() -> Vec<&'static str> {
+        vec!["trade_id", "trade_time", "symbol", "price", "quantity", "first_trade_id", "last_trade_id", "buyer_mkt_maker"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Int(self.trade_id.into()),
+            Cell::Time(self.trade_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Decimal(self.price.clone()),
+            Cell::Decimal(self.quantity.clone()),
+            Cell::Int(self.first_trade_id.into()),
+            Cell::Int(self.last_trade_id.into()),
+            Cell::Bool(self.buyer_mkt_maker),
+        ]
+    }
+}
+
+impl ToRow for KlineData {
+    fn columns() -> Vec<&'static str> {
+        vec!["start_time", "symbol", "interval", "open", "high", "low", "close", "volume", "num_of_trades", "is_kline_closed"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Time(self.start_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Text(self.interval.clone()),
+            Cell::Decimal(self.open.clone()),
+            Cell::Decimal(self.high.clone()),
+            Cell::Decimal(self.low.clone()),
+            Cell::Decimal(self.close.clone()),
+            Cell::Decimal(self.volume.clone()),
+            Cell::Int(self.num_of_trades.into()),
+            Cell::Bool(self.is_kline_closed),
+        ]
+    }
+}
+
+impl ToRow for BarInsert {
+    fn columns() -> Vec<&'static str> {
+        vec!["start_time", "symbol", "spec", "first_trade_id", "open", "high", "low", "close", "volume", "num_of_trades"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Time(self.start_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Text(self.spec.clone()),
+            Cell::Int(self.first_trade_id),
+            Cell::Decimal(self.open.clone()),
+            Cell::Decimal(self.high.clone()),
+            Cell::Decimal(self.low.clone()),
+            Cell::Decimal(self.close.clone()),
+            Cell::Decimal(self.volume.clone()),
+            Cell::Int(self.num_of_trades.into()),
+        ]
+    }
+}
+
+impl ToRow for TickerData {
+    fn columns() -> Vec<&'static str> {
+        vec!["event_time", "symbol", "last_price", "last_quantity", "best_bid_price", "best_ask_price", "base_asset_vol"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Time(self.event_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Decimal(self.last_price.clone()),
+            Cell::Decimal(self.last_quantity.clone()),
+            Cell::Decimal(self.best_bid_price.clone()),
+            Cell::Decimal(self.best_ask_price.clone()),
+            Cell::Decimal(self.base_asset_vol.clone()),
+        ]
+    }
+}
+
+impl ToRow for BookDepthData {
+    fn columns() -> Vec<&'static str> {
+        vec!["event_time", "symbol", "update_first", "update_final", "bids", "asks"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Time(self.event_time),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Int(self.update_first),
+            Cell::Int(self.update_final),
+            Cell::Json(json!(self.bids)),
+            Cell::Json(json!(self.asks)),
+        ]
+    }
+}
+
+impl ToRow for BookSnapshot {
+    fn columns() -> Vec<&'static str> {
+        vec!["captured_at", "symbol", "last_update_id", "levels", "bids", "asks"]
+    }
+
+    fn to_row(&self) -> Vec<Cell> {
+        vec![
+            Cell::Time(self.captured_at),
+            Cell::Text(self.symbol.to_uppercase()),
+            Cell::Int(self.last_update_id),
+            Cell::Int(self.levels.into()),
+            Cell::Json(json!(self.bids)),
+            Cell::Json(json!(self.asks)),
+        ]
+    }
+}
+
+/// A price and quantity at a point in time, what aggregations are computed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub symbol: Symbol,
+    pub time: NaiveDateTime,
+    pub price: BigDecimal,
+    pub quantity: BigDecimal,
+}
+
+/// Rows ready to be written
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl ResultSet {
+    pub fn from_rows<T: ToRow>(rows: &[T]) -> Self {
+        ResultSet {
+            columns: T::columns().into_iter().map(str::to_owned).collect(),
+            rows: rows.iter().map(ToRow::to_row).collect(),
+        }
+    }
+
+    /// One row per (interval start, symbol), oldest first
+    pub fn aggregate(samples: &[Sample], interval: &KlineInterval, aggregations: &[Aggregation]) -> Self {
+        let mut buckets: BTreeMap<(NaiveDateTime, Symbol), Vec<&Sample>> = BTreeMap::new();
+        for sample in samples {
+            let key = (interval.start_of(sample.time), sample.symbol.clone());
+            buckets.entry(key).or_insert_with(Vec::new).push(sample);
+        }
+
+        let mut columns = vec!["start_time".to_owned(), "symbol".to_owned()];
+        for aggregation in aggregations {
+            match aggregation {
+                Aggregation::Count => columns.push("count".to_owned()),
+                Aggregation::Volume => columns.push("volume".to_owned()),
+                Aggregation::Vwap => columns.push("vwap".to_owned()),
+                Aggregation::Ohlc => columns.extend(["open", "high", "low", "close"].iter().map(|c| c.to_string())),
+            }
+        }
+
+        let rows = buckets
+            .into_iter()
+            .map(|((start, symbol), samples)| {
+                let volume = samples.iter().fold(BigDecimal::zero(), |sum, s| sum + &s.quantity);
+                let mut row = vec![Cell::Time(start), Cell::Text(symbol.to_uppercase())];
+                for aggregation in aggregations {
+                    match aggregation {
+                        Aggregation::Count => row.push(Cell::Int(samples.len() as i64)),
+                        Aggregation::Volume => row.push(Cell::Decimal(volume.clone())),
+                        Aggregation::Vwap => {
+                            let last = &samples[samples.len() - 1].price;
+                            let vwap = if volume.is_zero() {
+                                last.clone()
+                            } else {
+                                let notional = samples.iter().fold(BigDecimal::zero(), |sum, s| sum + &s.price * &s.quantity);
+                                (notional / &volume).with_scale(8)
+                            };
+                            row.push(Cell::Decimal(vwap));
+                        }
+                        Aggregation::Ohlc => {
+                            let prices: Vec<&BigDecimal> = samples.iter().map(|s| &s.price).collect();
+                            row.push(Cell::Decimal(prices[0].clone()));
+                            row.push(Cell::Decimal((*prices.iter().max().unwrap()).clone()));
+                            row.push(Cell::Decimal((*prices.iter().min().unwrap()).clone()));
+                            row.push(Cell::Decimal(prices[prices.len() - 1].clone()));
+                        }
+                    }
+                }
+                row
+            })
+            .collect();
+        ResultSet { columns, rows }
+    }
+
+    pub fn write<W: Write + Send>(&self, format: OutputFormat, out: W) -> Result<()> {
+        match format {
+            OutputFormat::Table => self.write_table(out),
+            OutputFormat::Csv => self.write_csv(out),
+            OutputFormat::JsonLines => self.write_json_lines(out),
+            OutputFormat::Parquet => self.write_parquet(out),
+        }
+    }
+
+    fn write_table<W: Write>(&self, mut out: W) -> Result<()> {
+        let cells: Vec<Vec<String>> = self.rows.iter().map(|r| r.iter().map(Cell::to_string).collect()).collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| cells.iter().map(|r| r[i].len()).fold(c.len(), usize::max))
+            .collect();
+        let line = |values: Vec<&str>| {
+            let padded: Vec<String> = values.iter().zip(widths.iter()).map(|(v, w)| format!("{:<1$}", v, w)).collect();
+            padded.join(" | ").trim_end().to_owned()
+        };
+        writeln!(out, "{}", line(self.columns.iter().map(String::as_str).collect()))?;
+        writeln!(out, "{}", widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("-+-"))?;
+        for row in cells.iter() {
+            writeln!(out, "{}", line(row.iter().map(String::as_str).collect()))?;
+        }
+        writeln!(out, "({} rows)", self.rows.len())?;
+        Ok(())
+    }
+
+    fn write_csv<W: Write>(&self, mut out: W) -> Result<()> {
+        let escape = |s: String| {
+            if s.contains(|c| c == ',' || c == '"' || c == '\n') {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s
+            }
+        };
+        writeln!(out, "{}", self.columns.join(","))?;
+        for row in self.rows.iter() {
+            let values: Vec<String> = row.iter().map(|c| escape(c.to_string())).collect();
+            writeln!(out, "{}", values.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn write_json_lines<W: Write>(&self, mut out: W) -> Result<()> {
+        for row in self.rows.iter() {
+            let object: serde_json::Map<String, serde_json::Value> =
+                self.columns.iter().cloned().zip(row.iter().map(Cell::to_json)).collect();
+            writeln!(out, "{}", serde_json::Value::Object(object))?;
+        }
+        Ok(())
+    }
+
+    /// Integers and times as INT64, booleans as BOOLEAN, everything else as UTF8 strings,
+    /// so decimals keep their precision. Column types come from the first row.
+    #[cfg(feature = "parquet")]
+    fn write_parquet<W: Write + Send>(&self, out: W) -> Result<()> {
+        use parquet::data_type::{BoolType, ByteArray, ByteArrayType, Int64Type};
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+        use std::sync::Arc;
+
+        let to_io = |e: parquet::errors::ParquetError| Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e));
+        let first = self.rows.first();
+        let fields: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match first.map(|r| &r[i]) {
+                Some(Cell::Int(_)) => format!("REQUIRED INT64 {};", name),
+                Some(Cell::Time(_)) => format!("REQUIRED INT64 {} (TIMESTAMP_MILLIS);", name),
+                Some(Cell::Bool(_)) => format!("REQUIRED BOOLEAN {};", name),
+                _ => format!("REQUIRED BYTE_ARRAY {} (UTF8);", name),
+            })
+            .collect();
+        let schema = parse_message_type(&format!("message query {{ {} }}", fields.join(" "))).map_err(to_io)?;
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(out, Arc::new(schema), properties).map_err(to_io)?;
+
+        let mut row_group = writer.next_row_group().map_err(to_io)?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column().map_err(to_io)? {
+            let cells = self.rows.iter().map(|r| &r[index]);
+            match first.map(|r| &r[index]) {
+                Some(Cell::Int(_)) | Some(Cell::Time(_)) => {
+                    let values: Vec<i64> = cells
+                        .map(|c| match c {
+                            Cell::Int(n) => *n,
+                            Cell::Time(t) => t.timestamp_millis(),
+                            _ => 0,
+                        })
+                        .collect();
+                    column.typed::<Int64Type>().write_batch(&values, None, None).map_err(to_io)?;
+                }
+                Some(Cell::Bool(_)) => {
+                    let values: Vec<bool> = cells.map(|c| *c == Cell::Bool(true)).collect();
+                    column.typed::<BoolType>().write_batch(&values, None, None).map_err(to_io)?;
+                }
+                _ => {
+                    let values: Vec<ByteArray> = cells.map(|c| ByteArray::from(c.to_string().as_str())).collect();
+                    column.typed::<ByteArrayType>().write_batch(&values, None, None).map_err(to_io)?;
+                }
+            }
+            column.close().map_err(to_io)?;
+            index += 1;
+        }
+        row_group.close().map_err(to_io)?;
+        writer.close().map_err(to_io)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    fn write_parquet<W: Write + Send>(&self, _out: W) -> Result<()> {
+        Err(Error::InvalidQuery("built without Parquet support, rebuild with --features parquet".to_owned()))
+    }
+}
+
+/// Filters shared by every table, see `query --help`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub table: QueryTable,
+    pub symbol: Option<Symbol>,
+    pub from: Option<NaiveDateTime>, // inclusive
+    pub to: Option<NaiveDateTime>,   // exclusive
+    pub interval: Option<KlineInterval>, // kline interval, or the bucket size for aggregations
+    pub aggregations: Vec<Aggregation>,
+    pub limit: Option<i64>,          // rows returned, buckets when aggregating
+}
+
+impl Query {
+    pub fn new(table: QueryTable) -> Self {
+        Query {
+            table,
+            symbol: None,
+            from: None,
+            to: None,
+            interval: None,
+            aggregations: Vec::new(),
+            limit: None,
+        }
+    }
+
+    pub fn run(&self, conn: &PgConnection) -> Result<ResultSet> {
+        let aggregate = !self.aggregations.is_empty() || (self.interval.is_some() && self.table != QueryTable::Klines);
+        if !aggregate {
+            return match self.table {
+                QueryTable::Trades => Ok(ResultSet::from_rows(&self.load_trades(conn)?)),
+                QueryTable::AggregateTrades => Ok(ResultSet::from_rows(&self.load_aggregate_trades(conn)?)),
+                QueryTable::Klines => Ok(ResultSet::from_rows(&self.load_klines(conn)?)),
+                QueryTable::Bars => Ok(ResultSet::from_rows(&self.load_bars(conn)?)),
+                QueryTable::Tickers => Ok(ResultSet::from_rows(&self.load_tickers(conn)?)),
+                QueryTable::BookDepth => Ok(ResultSet::from_rows(&self.load_book_depth(conn)?)),
+                QueryTable::BookSnapshots => Ok(ResultSet::from_rows(&self.load_book_snapshots(conn)?)),
+            };
+        }
+
+        let interval = self
+            .interval
+            .as_ref()
+            .ok_or_else(|| Error::InvalidQuery("aggregations need an interval".to_owned()))?;
+        let aggregations = if self.aggregations.is_empty() {
+            vec![Aggregation::Ohlc, Aggregation::Volume, Aggregation::Count]
+        } else {
+            self.aggregations.clone()
+        };
+        // every row in range goes into the buckets, the limit applies to the buckets
+        let raw = Query { limit: None, ..self.clone() };
+        let samples: Vec<Sample> = match self.table {
+            QueryTable::Trades => raw
+                .load_trades(conn)?
+                .into_iter()
+                .map(|t| Sample { symbol: t.symbol, time: t.trade_time, price: t.price, quantity: t.quantity })
+                .collect(),
+            QueryTable::AggregateTrades => raw
+                .load_aggregate_trades(conn)?
+                .into_iter()
+                .map(|t| Sample { symbol: t.symbol, time: t.trade_time, price: t.price, quantity: t.quantity })
+                .collect(),
+            QueryTable::Tickers => raw
+                .load_tickers(conn)?
+                .into_iter()
+                .map(|t| Sample { symbol: t.symbol, time: t.event_time, price: t.last_price, quantity: t.last_quantity })
+                .collect(),
+            QueryTable::Klines | QueryTable::Bars | QueryTable::BookDepth | QueryTable::BookSnapshots => {
+                return Err(Error::InvalidQuery(format!(
+                    "aggregations need trades, aggregate_trades or tickers, not {:?}",
+                    self.table
+                )))
+            }
+        };
+        let mut result = ResultSet::aggregate(&samples, interval, &aggregations);
+        if let Some(limit) = self.limit {
+            result.rows.truncate(limit as usize);
+        }
+        Ok(result)
+    }
+
+    fn load_trades(&self, conn: &PgConnection) -> Result<Vec<TradeData>> {
+        use crate::schema::trades::dsl;
+
+        let mut query = dsl::trades.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::trade_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::trade_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::trade_time.asc()).load::<TradeData>(conn)?)
+    }
+
+    fn load_aggregate_trades(&self, conn: &PgConnection) -> Result<Vec<AggregateTradeData>> {
+        use crate::schema::aggregate_trades::dsl;
+
+        let mut query = dsl::aggregate_trades.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::trade_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::trade_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::trade_time.asc()).load::<AggregateTradeData>(conn)?)
+    }
+
+    fn load_klines(&self, conn: &PgConnection) -> Result<Vec<KlineData>> {
+        use crate::schema::klines::dsl;
+
+        let mut query = dsl::klines.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(interval) = &self.interval {
+            query = query.filter(dsl::interval.eq(interval.to_string()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::start_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::start_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::start_time.asc()).load::<KlineData>(conn)?)
+    }
+
+    fn load_bars(&self, conn: &PgConnection) -> Result<Vec<BarInsert>> {
+        use crate::schema::bars::dsl;
+
+        let mut query = dsl::bars.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::start_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::start_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order((dsl::start_time.asc(), dsl::first_trade_id.asc())).load::<BarInsert>(conn)?)
+    }
+
+    fn load_tickers(&self, conn: &PgConnection) -> Result<Vec<TickerData>> {
+        use crate::schema::tickers::dsl;
+
+        let mut query = dsl::tickers.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::event_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::event_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::event_time.asc()).load::<TickerData>(conn)?)
+    }
+
+    fn load_book_depth(&self, conn: &PgConnection) -> Result<Vec<BookDepthData>> {
+        use crate::schema::book_depth::dsl;
+
+        let mut query = dsl::book_depth.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::event_time.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::event_time.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::event_time.asc()).load::<BookDepthData>(conn)?)
+    }
+
+    fn load_book_snapshots(&self, conn: &PgConnection) -> Result<Vec<BookSnapshot>> {
+        use crate::schema::book_snapshots::dsl;
+
+        let mut query = dsl::book_snapshots.into_boxed();
+        if let Some(symbol) = &self.symbol {
+            query = query.filter(dsl::symbol.eq(symbol.clone()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(dsl::captured_at.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(dsl::captured_at.lt(to));
+        }
+        if let Some(limit) = self.limit {
+            query = query.limit(limit);
+        }
+        Ok(query.order(dsl::captured_at.asc()).load::<BookSnapshot>(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::models::trades::TEST_TRADE_DATA;
+    use crate::serde_parsers::create_decimal_benchmark;
+    use diesel::connection::Connection;
+
+    fn sample(seconds: i64, price: &str, quantity: &str) -> Sample {
+        Sample {
+            symbol: Symbol::new("ETHBTC"),
+            time: NaiveDateTime::from_timestamp(1_555_444_320 + seconds, 0),
+            price: create_decimal_benchmark(price),
+            quantity: create_decimal_benchmark(quantity),
+        }
+    }
+
+    #[test]
+    fn aggregate_into_minute_buckets() {
+        let samples = vec![sample(0, "0.03", "1"), sample(30, "0.05", "3"), sample(59, "0.02", "1"), sample(60, "0.04", "2")];
+        let result = ResultSet::aggregate(&samples, &KlineInterval::_1m, &[Aggregation::Ohlc, Aggregation::Vwap, Aggregation::Count]);
+        assert_eq!(result.columns, vec!["start_time", "symbol", "open", "high", "low", "close", "vwap", "count"]);
+        assert_eq!(result.rows.len(), 2);
+        let first: Vec<String> = result.rows[0].iter().map(Cell::to_string).collect();
+        // (0.03 + 0.15 + 0.02) / 5
+        assert_eq!(first, vec!["2019-04-16 19:52:00.000", "ETHBTC", "0.03", "0.05", "0.02", "0.02", "0.04000000", "3"]);
+
+        let mut csv = Vec::new();
+        result.write(OutputFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().nth(2), Some("2019-04-16 19:53:00.000,ETHBTC,0.04,0.04,0.04,0.04,0.04000000,1"));
+
+        let mut jsonl = Vec::new();
+        result.write(OutputFormat::JsonLines, &mut jsonl).unwrap();
+        let row: serde_json::Value = serde_json::from_str(String::from_utf8(jsonl).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(row["vwap"], json!("0.04000000"));
+        assert_eq!(row["count"], json!(3));
+    }
+
+    #[test]
+    fn db_query_trades_by_symbol_and_time() {
+        use crate::schema::trades;
+
+        let conn = establish_connection_pg();
+        conn.begin_test_transaction().unwrap();
+        let trade = serde_json::from_str::<TradeData>(TEST_TRADE_DATA).unwrap();
+        let mut later = trade.clone();
+        later.trade_id += 1;
+        later.trade_time += chrono::Duration::minutes(5);
+        diesel::insert_into(trades::table).values(&vec![trade.clone(), later.clone()]).execute(&conn).unwrap();
+
+        let mut query = Query::new(QueryTable::Trades);
+        query.symbol = Some(trade.symbol.clone());
+        query.from = Some(trade.trade_time);
+        query.to = Some(later.trade_time);
+        let result = query.run(&conn).unwrap();
+        assert_eq!(result.rows, vec![trade.to_row()]);
+
+        query.to = None;
+        query.limit = Some(1);
+        assert_eq!(query.run(&conn).unwrap().rows, vec![trade.to_row()]);
+
+        // the limit counts buckets, both trades are still in the first
+        query.interval = Some(KlineInterval::_1h);
+        let result = query.run(&conn).unwrap();
+        assert_eq!(result.columns.last().map(String::as_str), Some("count"));
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0].last(), Some(&Cell::Int(2)));
+
+        query.table = QueryTable::Klines;
+        query.aggregations = vec![Aggregation::Vwap];
+        assert!(query.run(&conn).is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+        use parquet::record::RowAccessor;
+
+        let samples = vec![sample(0, "0.03", "1"), sample(60, "0.04", "2.5")];
+        let result = ResultSet::aggregate(&samples, &KlineInterval::_1m, &[Aggregation::Count, Aggregation::Volume]);
+
+        let path = std::env::temp_dir().join(format!("query-{}.parquet", std::process::id()));
+        result.write(OutputFormat::Parquet, std::fs::File::create(&path).unwrap()).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let rows: Vec<(i64, String, i64, String)> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (
+                    row.get_timestamp_millis(0).unwrap(),
+                    row.get_string(1).unwrap().clone(),
+                    row.get_long(2).unwrap(),
+                    row.get_string(3).unwrap().clone(),
+                )
+            })
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (1_555_444_320_000, "ETHBTC".to_owned(), 1, "1".to_owned()),
+                (1_555_444_380_000, "ETHBTC".to_owned(), 1, "2.5".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDateTime, Timelike};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::Result;
+use crate::models::combined_stream::{parse_combined_message, CombinedMessage};
+use crate::serde_parsers::{deserialize_as_naive_date_time_ms, serialize_as_timestamp_ms};
+
+/////////////////////////////////////////////////////////////////
+/// Raw websocket frames, recorded before they are parsed so
+/// nothing is lost to a parsing bug, and read back for replay.
+/////////////////////////////////////////////////////////////////
+
+pub const DEFAULT_RECORD_DIR: &str = "recordings";
+
+/// One websocket text frame, exactly as received.
+/// Stored one JSON object per line: {"t":1555444333222,"s":"ethbtc@trade","d":"{...}"}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    #[serde(rename = "t")]
+    #[serde(serialize_with = "serialize_as_timestamp_ms")]
+    #[serde(deserialize_with = "deserialize_as_naive_date_time_ms")]
+    pub received_at: NaiveDateTime,
+    #[serde(rename = "s")]
+    pub stream: String, // stream name of the socket, "combined" for `/stream?streams=` sockets
+    #[serde(rename = "d")]
+    pub payload: String,
+}
+
+impl RecordedFrame {
+    /// The market stream the payload was sent on and the payload itself, unwrapped from
+    /// the envelope on combined sockets. Subscription replies on combined sockets carry no event.
+    pub fn market_payload(self) -> serde_json::Result<Option<(String, String)>> {
+        if self.stream != "combined" {
+            return Ok(Some((self.stream, self.payload)));
+        }
+        match parse_combined_message(&self.payload)? {
+            CombinedMessage::Event(envelope) => Ok(Some((envelope.stream, envelope.data.get().to_owned()))),
+            CombinedMessage::Response(_) => Ok(None),
+        }
+    }
+}
+
+/// Appends frames to gzip files rotated every hour: `<dir>/2019-04-16/19.frames.gz`.
+/// Each (re)open appends a new gzip member, so files are never rewritten.
+pub struct FrameRecorder {
+    dir: PathBuf,
+    current: Option<(PathBuf, GzEncoder<File>)>,
+}
+
+impl FrameRecorder {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        FrameRecorder { dir: dir.as_ref().to_path_buf(), current: None }
+    }
+
+    /// File holding frames received at `time`
+    pub fn path_for(&self, time: NaiveDateTime) -> PathBuf {
+        self.dir
+            .join(time.format("%Y-%m-%d").to_string())
+            .join(format!("{:02}.frames.gz", time.hour()))
+    }
+
+    pub fn record(&mut self, frame: &RecordedFrame) -> Result<()> {
+        let path = self.path_for(frame.received_at);
+        if self.current.as_ref().map(|(current, _)| current != &path).unwrap_or(true) {
+            self.rotate(path)?;
+        }
+        if let Some((_, encoder)) = &mut self.current {
+            serde_json::to_writer(&mut *encoder, frame)?;
+            encoder.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Push buffered frames to disk. A crash loses at most what was recorded since the last flush,
+    /// readers stop at the truncated end of the file.
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some((_, encoder)) = &mut self.current {
+            encoder.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current file, writing the gzip trailer
+    pub fn close(&mut self) -> Result<()> {
+        if let Some((_, encoder)) = self.current.take() {
+            encoder.finish()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, path: PathBuf) -> Result<()> {
+        self.close()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        info!("Recording frames to {}", path.display());
+        self.current = Some((path, GzEncoder::new(file, Compression::default())));
+        Ok(())
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Every `.frames.gz` file under `path` in recording order, or `path` itself if it is a file
+pub fn recording_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?.path();
+        if entry.is_dir() {
+            files.extend(recording_files(&entry)?);
+        } else if entry.to_string_lossy().ends_with(".frames.gz") {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Frames from one recording file, read lazily line by line.
+/// A file cut short by a crash ends at its last complete frame.
+pub fn read_frames<P: AsRef<Path>>(path: P) -> Result<FrameReader> {
+    let reader = BufReader::new(MultiGzDecoder::new(File::open(path.as_ref())?));
+    Ok(FrameReader { path: path.as_ref().to_path_buf(), lines: reader.lines(), read: 0, done: false })
+}
+
+/// Iterator over the frames of one recording file, see `read_frames`
+pub struct FrameReader {
+    path: PathBuf,
+    lines: Lines<BufReader<MultiGzDecoder<File>>>,
+    read: usize,
+    done: bool, // stopped at a truncated frame or read error
+}
+
+impl Iterator for FrameReader {
+    type Item = RecordedFrame;
+
+    fn next(&mut self) -> Option<RecordedFrame> {
+        if self.done {
+            return None;
+        }
+        match self.lines.next()? {
+            Ok(line) => match serde_json::from_str::<RecordedFrame>(&line) {
+                Ok(frame) => {
+                    self.read += 1;
+                    Some(frame)
+                }
+                Err(e) => {
+                    warn!("Skipping truncated frame in {}: {}", self.path.display(), e);
+                    self.done = true;
+                    None
+                }
+            },
+            Err(e) => {
+                warn!("Stopped reading {} at {} frames: {}", self.path.display(), self.read, e);
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::trades::TEST_TRADE_DATA;
+    use crate::serde_parsers::create_timestamp_benchmark;
+
+    #[test]
+    fn record_rotate_and_read_back() {
+        let dir = std::env::temp_dir().join(format!("frames-{}", uuid::Uuid::new_v4()));
+        let frame = |ms: i64| RecordedFrame {
+            received_at: create_timestamp_benchmark(ms),
+            stream: "bnbbtc@trade".to_owned(),
+            payload: TEST_TRADE_DATA.to_owned(),
+        };
+        // 2019-04-16 19:52, then 20:00 in the next file
+        let frames = vec![frame(1_555_444_333_222), frame(1_555_444_333_999), frame(1_555_444_800_000)];
+
+        let mut recorder = FrameRecorder::new(&dir);
+        recorder.record(&frames[0]).unwrap();
+        recorder.close().unwrap();
+        // reopened, appended as a second gzip member
+        let mut recorder = FrameRecorder::new(&dir);
+        for f in frames[1..].iter() {
+            recorder.record(f).unwrap();
+        }
+        recorder.flush().unwrap();
+        drop(recorder);
+
+        let files = recording_files(&dir).unwrap();
+        assert_eq!(files, vec![dir.join("2019-04-16/19.frames.gz"), dir.join("2019-04-16/20.frames.gz")]);
+        let read: Vec<RecordedFrame> = files.iter().flat_map(|f| read_frames(f).unwrap()).collect();
+        assert_eq!(read, frames);
+
+        // cut short mid-frame by a crash
+        let mut encoder = GzEncoder::new(OpenOptions::new().append(true).open(&files[1]).unwrap(), Compression::default());
+        encoder.write_all(br#"{"t":1555444800001,"s":"bnb"#).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(read_frames(&files[1]).unwrap().collect::<Vec<_>>(), frames[2..].to_vec());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn frames_into_market_payloads() {
+        let frame = RecordedFrame {
+            received_at: create_timestamp_benchmark(1_555_444_333_222),
+            stream: "bnbbtc@trade".to_owned(),
+            payload: TEST_TRADE_DATA.to_owned(),
+        };
+        assert_eq!(
+            frame.clone().market_payload().unwrap(),
+            Some(("bnbbtc@trade".to_owned(), TEST_TRADE_DATA.to_owned()))
+        );
+
+        let depth = r#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[]}"#;
+        let combined = RecordedFrame {
+            stream: "combined".to_owned(),
+            payload: format!(r#"{{"stream":"bnbbtc@depth5","data":{}}}"#, depth),
+            ..frame.clone()
+        };
+        assert_eq!(combined.market_payload().unwrap(), Some(("bnbbtc@depth5".to_owned(), depth.to_owned())));
+
+        let reply = RecordedFrame { stream: "combined".to_owned(), payload: r#"{"result":null,"id":1}"#.to_owned(), ..frame };
+        assert!(reply.market_payload().unwrap().is_none());
+    }
+}
@@ -42,7 +42,7 @@ pub fn extract_data_regex(html: &str) {
     }).collect::<Vec<_>>();
 
     for row in rows {
-        println!("rows: {:?}", row);
+        debug!("rows: {:?}", row);
     }
 
 }
@@ -0,0 +1,414 @@
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use reqwest::{Method, Url};
+use ring::{digest, hmac};
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+use crate::models::account::{AccountInfo, AccountTrade, CancelOrderResult, NewOrderResult, OrderInfo};
+use crate::models::user_data::ListenKey;
+use crate::symbols::Symbol;
+
+pub const BINANCE_API_URL: &str = "https://api.binance.com";
+pub const DEFAULT_RECV_WINDOW_MS: u64 = 5_000;
+
+/// API key and secret, read from BINANCE_API_KEY and BINANCE_SECRET_KEY
+#[derive(Clone)]
+pub struct ApiCredentials {
+    pub api_key: String,
+    secret_key: String,
+}
+
+impl ApiCredentials {
+    pub fn new(api_key: &str, secret_key: &str) -> Self {
+        ApiCredentials {
+            api_key: api_key.to_owned(),
+            secret_key: secret_key.to_owned(),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        dotenv::dotenv().ok();
+        let api_key = std::env::var("BINANCE_API_KEY").ok()?;
+        let secret_key = std::env::var("BINANCE_SECRET_KEY").ok()?;
+        Some(ApiCredentials::new(&api_key, &secret_key))
+    }
+
+    pub fn sign(&self, query_string: &str) -> String {
+        sign_query(&self.secret_key, query_string)
+    }
+}
+
+/// Never print the secret
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ApiCredentials {{ api_key: {:?}, secret_key: \"***\" }}", self.api_key)
+    }
+}
+
+/// HMAC-SHA256 of the query string, hex encoded, as expected in `signature=`
+pub fn sign_query(secret_key: &str, query_string: &str) -> String {
+    let signing_key = hmac::SigningKey::new(&digest::SHA256, secret_key.as_bytes());
+    let signature = hmac::sign(&signing_key, query_string.as_bytes());
+    HexDigest(signature).to_string()
+}
+
+#[derive(Debug)]
+pub struct HexDigest(pub hmac::Signature);
+
+impl fmt::Display for HexDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sig_as_ref: &[u8] = self.0.as_ref();
+        write!(f, "{}", data_encoding::HEXLOWER.encode(sig_as_ref))
+    }
+}
+
+/// Binance error codes we handle, see
+/// https://github.com/binance-exchange/binance-official-api-docs/blob/master/errors.md
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum ApiErrorCode {
+    Unknown,               // -1000
+    Disconnected,          // -1001
+    TooManyRequests,       // -1003
+    InvalidTimestamp,      // -1021 outside recvWindow, or ahead of the server
+    InvalidSignature,      // -1022
+    IllegalChars,          // -1100
+    MandatoryParamMissing, // -1102
+    InvalidSymbol,         // -1121
+    FilterFailure,         // -1013 price/quantity/notional filter
+    NewOrderRejected,      // -2010 e.g: insufficient balance
+    CancelRejected,        // -2011
+    NoSuchOrder,           // -2013
+    BadApiKeyFormat,       // -2014
+    RejectedApiKey,        // -2015 invalid key, IP or permissions
+    Other(i32),
+}
+
+impl From<i32> for ApiErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            -1000 => ApiErrorCode::Unknown,
+            -1001 => ApiErrorCode::Disconnected,
+            -1003 => ApiErrorCode::TooManyRequests,
+            -1021 => ApiErrorCode::InvalidTimestamp,
+            -1022 => ApiErrorCode::InvalidSignature,
+            -1100 => ApiErrorCode::IllegalChars,
+            -1102 => ApiErrorCode::MandatoryParamMissing,
+            -1121 => ApiErrorCode::InvalidSymbol,
+            -1013 => ApiErrorCode::FilterFailure,
+            -2010 => ApiErrorCode::NewOrderRejected,
+            -2011 => ApiErrorCode::CancelRejected,
+            -2013 => ApiErrorCode::NoSuchOrder,
+            -2014 => ApiErrorCode::BadApiKeyFormat,
+            -2015 => ApiErrorCode::RejectedApiKey,
+            other => ApiErrorCode::Other(other),
+        }
+    }
+}
+
+impl From<ApiErrorCode> for i32 {
+    fn from(code: ApiErrorCode) -> i32 {
+        match code {
+            ApiErrorCode::Unknown => -1000,
+            ApiErrorCode::Disconnected => -1001,
+            ApiErrorCode::TooManyRequests => -1003,
+            ApiErrorCode::InvalidTimestamp => -1021,
+            ApiErrorCode::InvalidSignature => -1022,
+            ApiErrorCode::IllegalChars => -1100,
+            ApiErrorCode::MandatoryParamMissing => -1102,
+            ApiErrorCode::InvalidSymbol => -1121,
+            ApiErrorCode::FilterFailure => -1013,
+            ApiErrorCode::NewOrderRejected => -2010,
+            ApiErrorCode::CancelRejected => -2011,
+            ApiErrorCode::NoSuchOrder => -2013,
+            ApiErrorCode::BadApiKeyFormat => -2014,
+            ApiErrorCode::RejectedApiKey => -2015,
+            ApiErrorCode::Other(code) => code,
+        }
+    }
+}
+
+/// Error body returned with a 4xx/5xx, e.g: {"code": -1021, "msg": "Timestamp for this request is outside of the recvWindow."}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub msg: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:?}): {}", i32::from(self.code), self.code, self.msg)
+    }
+}
+
+/// Look up an order by exchange id, or by the id we assigned
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderRef {
+    OrderId(i64),
+    ClientOrderId(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerTime {
+    server_time: i64,
+}
+
+/// Blocking client for Binance's REST API. Signed requests add `timestamp`
+/// (corrected by the offset from `sync_time`), `recvWindow` and `signature`.
+#[derive(Debug)]
+pub struct BinanceRestClient {
+    pub base_url: String,
+    pub recv_window: u64,
+    credentials: ApiCredentials,
+    time_offset_ms: AtomicI64, // server time - local time
+    client: reqwest::Client,
+}
+
+impl BinanceRestClient {
+    pub fn new(credentials: ApiCredentials) -> Self {
+        BinanceRestClient::with_base_url(credentials, BINANCE_API_URL)
+    }
+
+    pub fn with_base_url(credentials: ApiCredentials, base_url: &str) -> Self {
+        BinanceRestClient {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            recv_window: DEFAULT_RECV_WINDOW_MS,
+            credentials,
+            time_offset_ms: AtomicI64::new(0),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn time_offset_ms(&self) -> i64 {
+        self.time_offset_ms.load(Ordering::SeqCst)
+    }
+
+    /// Local time corrected to the server's clock, in milliseconds
+    pub fn timestamp(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis() + self.time_offset_ms()
+    }
+
+    /// GET `/api/v3/time` and store the clock offset, returns the offset
+    pub fn sync_time(&self) -> Result<i64> {
+        let sent = chrono::Utc::now().timestamp_millis();
+        let server = self.public_get::<ServerTime>("/api/v3/time", &[])?.server_time;
+        let received = chrono::Utc::now().timestamp_millis();
+        let offset = server - (sent + received) / 2;
+        self.time_offset_ms.store(offset, Ordering::SeqCst);
+        Ok(offset)
+    }
+
+    pub fn public_get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T> {
+        let mut url = self.url(path)?;
+        url.query_pairs_mut().extend_pairs(params.iter().map(|(k, v)| (*k, v.as_str())));
+        let res = self.client.get(url).send()?;
+        parse_response(res)
+    }
+
+    pub fn signed_get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T> {
+        self.signed_request(Method::GET, path, params)
+    }
+
+    pub fn signed_post<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T> {
+        self.signed_request(Method::POST, path, params)
+    }
+
+    pub fn signed_delete<T: DeserializeOwned>(&self, path: &str, params: &[(&str, String)]) -> Result<T> {
+        self.signed_request(Method::DELETE, path, params)
+    }
+
+    /// Requests which need the API key header but no signature, e.g: user data stream keys
+    pub fn keyed_request<T: DeserializeOwned>(&self, method: Method, path: &str, params: &[(&str, String)]) -> Result<T> {
+        let mut url = self.url(path)?;
+        url.query_pairs_mut().extend_pairs(params.iter().map(|(k, v)| (*k, v.as_str())));
+        let res = self
+            .client
+            .request(method, url)
+            .header("X-MBX-APIKEY", self.credentials.api_key.as_str())
+            .send()?;
+        parse_response(res)
+    }
+
+    /// Retries once after re-syncing the clock if the timestamp was rejected (-1021)
+    fn signed_request<T: DeserializeOwned>(&self, method: Method, path: &str, params: &[(&str, String)]) -> Result<T> {
+        let mut retried = false;
+        loop {
+            let mut url = self.url(path)?;
+            url.query_pairs_mut()
+                .extend_pairs(params.iter().map(|(k, v)| (*k, v.as_str())))
+                .append_pair("recvWindow", &self.recv_window.to_string())
+                .append_pair("timestamp", &self.timestamp().to_string());
+            let signature = self.credentials.sign(url.query().unwrap_or_default());
+            url.query_pairs_mut().append_pair("signature", &signature);
+
+            let res = self
+                .client
+                .request(method.clone(), url)
+                .header("X-MBX-APIKEY", self.credentials.api_key.as_str())
+                .send()?;
+            match parse_response(res) {
+                Err(Error::Api(ref e)) if e.code == ApiErrorCode::InvalidTimestamp && !retried => {
+                    warn!("Binance rejected timestamp, re-syncing clock: {}", e);
+                    self.sync_time()?;
+                    retried = true;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        Url::parse(&format!("{}{}", self.base_url, path))
+            .map_err(|e| Error::InvalidUrl(format!("{}{}: {}", self.base_url, path, e)))
+    }
+
+    /// GET `/api/v3/account`, balances and commissions
+    pub fn account(&self) -> Result<AccountInfo> {
+        self.signed_get("/api/v3/account", &[])
+    }
+
+    /// GET `/api/v3/openOrders`, for every symbol if None
+    pub fn open_orders(&self, symbol: Option<&Symbol>) -> Result<Vec<OrderInfo>> {
+        let params: Vec<(&str, String)> = symbol.map(|s| ("symbol", s.to_uppercase())).into_iter().collect();
+        self.signed_get("/api/v3/openOrders", &params)
+    }
+
+    /// GET `/api/v3/order`
+    pub fn order_status(&self, symbol: &Symbol, order: &OrderRef) -> Result<OrderInfo> {
+        let params = [("symbol", symbol.to_uppercase()), order_ref_param(order)];
+        self.signed_get("/api/v3/order", &params)
+    }
+
+    /// POST `/api/v3/order`, see `OrderRequest::to_params`
+    pub fn new_order(&self, params: &[(&str, String)]) -> Result<NewOrderResult> {
+        self.signed_post("/api/v3/order", params)
+    }
+
+    /// DELETE `/api/v3/order`
+    pub fn cancel_order(&self, symbol: &Symbol, order: &OrderRef) -> Result<CancelOrderResult> {
+        let params = [("symbol", symbol.to_uppercase()), order_ref_param(order)];
+        self.signed_delete("/api/v3/order", &params)
+    }
+
+    /// POST `/api/v3/userDataStream`, returns the account's active key if it has one
+    pub fn create_listen_key(&self) -> Result<String> {
+        self.keyed_request::<ListenKey>(Method::POST, "/api/v3/userDataStream", &[])
+            .map(|k| k.listen_key)
+    }
+
+    /// PUT `/api/v3/userDataStream`, keys expire 60 minutes after the last keepalive
+    pub fn keepalive_listen_key(&self, listen_key: &str) -> Result<()> {
+        self.keyed_request::<serde_json::Value>(Method::PUT, "/api/v3/userDataStream", &[("listenKey", listen_key.to_owned())])
+            .map(|_| ())
+    }
+
+    /// DELETE `/api/v3/userDataStream`, closes the stream
+    pub fn close_listen_key(&self, listen_key: &str) -> Result<()> {
+        self.keyed_request::<serde_json::Value>(Method::DELETE, "/api/v3/userDataStream", &[("listenKey", listen_key.to_owned())])
+            .map(|_| ())
+    }
+
+    /// GET `/api/v3/myTrades`, trades from `from_id` onwards if given, at most 1000
+    pub fn my_trades(&self, symbol: &Symbol, from_id: Option<i64>, limit: Option<u32>) -> Result<Vec<AccountTrade>> {
+        let mut params = vec![("symbol", symbol.to_uppercase())];
+        if let Some(from_id) = from_id {
+            params.push(("fromId", from_id.to_string()));
+        }
+        if let Some(limit) = limit {
+            params.push(("limit", limit.to_string()));
+        }
+        self.signed_get("/api/v3/myTrades", &params)
+    }
+}
+
+pub fn order_ref_param(order: &OrderRef) -> (&'static str, String) {
+    match order {
+        OrderRef::OrderId(id) => ("orderId", id.to_string()),
+        OrderRef::ClientOrderId(id) => ("origClientOrderId", id.clone()),
+    }
+}
+
+/// JSON body on success, `ApiError` if Binance sent one, otherwise the status and body
+fn parse_response<T: DeserializeOwned>(mut res: reqwest::Response) -> Result<T> {
+    let status = res.status();
+    let body = res.text()?;
+    if status.is_success() {
+        return Ok(serde_json::from_str::<T>(&body)?);
+    }
+    match serde_json::from_str::<ApiError>(&body) {
+        Ok(e) => Err(Error::Api(e)),
+        Err(_) => Err(Error::HttpStatus(status.as_u16(), body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{serve, MockResponse};
+    use crate::models::account::{TEST_ACCOUNT_DATA, TEST_ORDER_DATA};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn signed_requests_are_verifiable_and_resync_time() {
+        let rejected_once = Arc::new(Mutex::new(false));
+        let rejected = rejected_once.clone();
+
+        let url = serve(move |req| {
+            if req.path == "/api/v3/time" {
+                return MockResponse::json(200, r#"{"serverTime": 1555444333222}"#);
+            }
+            // signature is over the query string before `&signature=`
+            let (query, signature) = req.query.split_at(req.query.find("&signature=").unwrap());
+            if req.headers.get("x-mbx-apikey").map(|k| k.as_str()) != Some("key")
+                || &signature["&signature=".len()..] != sign_query("secret", query)
+            {
+                return MockResponse::json(401, r#"{"code": -1022, "msg": "Signature for this request is not valid."}"#);
+            }
+            assert_eq!(req.param("recvWindow"), Some("5000"));
+
+            let mut rejected = rejected.lock().unwrap();
+            match req.path.as_str() {
+                "/api/v3/account" if !*rejected => {
+                    *rejected = true;
+                    MockResponse::json(400, r#"{"code": -1021, "msg": "Timestamp for this request is outside of the recvWindow."}"#)
+                }
+                "/api/v3/account" => MockResponse::json(200, TEST_ACCOUNT_DATA),
+                "/api/v3/order" if req.param("origClientOrderId") == Some("myOrder1") => {
+                    MockResponse::json(200, TEST_ORDER_DATA)
+                }
+                "/api/v3/order" => MockResponse::json(400, r#"{"code": -2013, "msg": "Order does not exist."}"#),
+                _ => MockResponse::json(404, "<html>not found</html>"),
+            }
+        });
+
+        let client = BinanceRestClient::with_base_url(ApiCredentials::new("key", "secret"), &url);
+        let account = client.account().unwrap();
+        assert!(*rejected_once.lock().unwrap());
+        assert_eq!(account.balances.len(), 2);
+        assert!(client.time_offset_ms() < -1_000_000); // server clock is in 2019
+
+        let symbol = Symbol::new("LTCBTC");
+        let order = client.order_status(&symbol, &OrderRef::ClientOrderId("myOrder1".to_owned())).unwrap();
+        assert_eq!(order.order_id, 1);
+
+        match client.order_status(&symbol, &OrderRef::OrderId(2)) {
+            Err(Error::Api(e)) => assert_eq!(e.code, ApiErrorCode::NoSuchOrder),
+            other => panic!("expected -2013, got {:?}", other),
+        }
+        match BinanceRestClient::with_base_url(ApiCredentials::new("key", "wrong"), &url).account() {
+            Err(Error::Api(e)) => assert_eq!(e.code, ApiErrorCode::InvalidSignature),
+            other => panic!("expected -1022, got {:?}", other),
+        }
+        match client.my_trades(&symbol, None, None) {
+            Err(Error::HttpStatus(404, _)) => (),
+            other => panic!("expected 404, got {:?}", other),
+        }
+        match BinanceRestClient::with_base_url(ApiCredentials::new("key", "secret"), "api.binance.com").account() {
+            Err(Error::InvalidUrl(url)) => assert!(url.starts_with("api.binance.com/api/v3/account")),
+            other => panic!("expected an invalid URL, got {:?}", other),
+        }
+    }
+}
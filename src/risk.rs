@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime};
+use num::{Signed, Zero};
+
+use crate::models::orders::{Order, OrderRequest, OrderSide, OrderType};
+use crate::models::tickers::TickerDataInsert;
+use crate::models::user_data::FillInsert;
+use crate::symbols::{Symbol, SymbolRegistry};
+
+/// Limits every order is checked against before it is sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskLimits {
+    pub max_position: HashMap<Symbol, BigDecimal>, // base asset, long or short. Unlisted symbols can't be traded
+    pub max_order_notional: BigDecimal,            // price * quantity, in the quote asset
+    pub max_open_orders: usize,                    // across all symbols
+    pub price_band: BigDecimal,                    // furthest a price may be from the last price, 0.05 = 5%
+    pub daily_loss_limit: BigDecimal,              // realized + unrealized since 00:00 UTC, trips the kill switch
+}
+
+impl Default for RiskLimits {
+    fn default() -> Self {
+        RiskLimits {
+            max_position: HashMap::new(),
+            max_order_notional: BigDecimal::from(1_000),
+            max_open_orders: 10,
+            price_band: "0.05".parse().unwrap(),
+            daily_loss_limit: BigDecimal::from(100),
+        }
+    }
+}
+
+/// Why the risk manager refused an order
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskRejection {
+    Halted(String),                                    // kill switch reason
+    UnknownSymbol(Symbol),
+    NotTrading(Symbol),
+    NoReferencePrice(Symbol),                          // no ticker yet, bands and notional can't be checked
+    TickSize(BigDecimal, BigDecimal),                  // (price, tick size)
+    LotSize(BigDecimal, BigDecimal),                   // (quantity, step size)
+    QuantityRange(BigDecimal, BigDecimal, BigDecimal), // (quantity, min, max)
+    MinNotional(BigDecimal, BigDecimal),               // (notional, min)
+    MaxOrderNotional(BigDecimal, BigDecimal),          // (notional, limit)
+    PriceBand(BigDecimal, BigDecimal),                 // (price, last price)
+    MaxOpenOrders(usize),
+    MaxPosition(BigDecimal, BigDecimal),               // (position if filled, limit)
+    DailyLoss(BigDecimal, BigDecimal),                 // (loss, limit)
+}
+
+impl fmt::Display for RiskRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RiskRejection::Halted(reason) => write!(f, "trading halted: {}", reason),
+            RiskRejection::UnknownSymbol(s) => write!(f, "unknown symbol {}", s),
+            RiskRejection::NotTrading(s) => write!(f, "{} is not trading", s),
+            RiskRejection::NoReferencePrice(s) => write!(f, "no last price for {}", s),
+            RiskRejection::TickSize(price, tick) => write!(f, "price {} is not a multiple of tick size {}", price, tick),
+            RiskRejection::LotSize(qty, step) => write!(f, "quantity {} is not a multiple of step size {}", qty, step),
+            RiskRejection::QuantityRange(qty, min, max) => write!(f, "quantity {} outside [{}, {}]", qty, min, max),
+            RiskRejection::MinNotional(notional, min) => write!(f, "notional {} below minimum {}", notional, min),
+            RiskRejection::MaxOrderNotional(notional, max) => write!(f, "notional {} above limit {}", notional, max),
+            RiskRejection::PriceBand(price, last) => write!(f, "price {} too far from last price {}", price, last),
+            RiskRejection::MaxOpenOrders(max) => write!(f, "already {} open orders", max),
+            RiskRejection::MaxPosition(position, max) => write!(f, "position {} would exceed limit {}", position, max),
+            RiskRejection::DailyLoss(loss, max) => write!(f, "daily loss {} reached limit {}", loss, max),
+        }
+    }
+}
+
+/// Halts all trading when tripped. Clones share the same switch, so any
+/// component holding one can stop every order manager at once.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    reason: Arc<Mutex<Option<String>>>,
+}
+
+impl KillSwitch {
+    pub fn trip(&self, reason: &str) {
+        error!("KILL SWITCH: trading halted: {}", reason);
+        *self.reason.lock().unwrap() = Some(reason.to_owned());
+    }
+
+    pub fn reset(&self) {
+        warn!("KILL SWITCH: trading resumed");
+        *self.reason.lock().unwrap() = None;
+    }
+
+    /// The reason trading was halted, None while trading
+    pub fn halted(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+}
+
+/// Our position in one symbol and what it did today
+#[derive(Debug, Clone, Default)]
+struct Position {
+    quantity: BigDecimal,  // base asset, negative if short
+    day_start: BigDecimal, // quantity at 00:00 UTC
+    day_cash: BigDecimal,  // quote asset received minus spent today, after commission
+}
+
+/// Pre-trade checks: exchange filters, price bands, order size, position and
+/// open order limits, and a daily loss limit. Fed with tickers and our own fills.
+pub struct RiskManager {
+    pub limits: RiskLimits,
+    symbols: SymbolRegistry,
+    kill_switch: KillSwitch,
+    last_prices: HashMap<Symbol, BigDecimal>,
+    positions: HashMap<Symbol, Position>,
+    day: NaiveDate,
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskLimits, symbols: SymbolRegistry, kill_switch: KillSwitch) -> Self {
+        RiskManager {
+            limits,
+            symbols,
+            kill_switch,
+            last_prices: HashMap::new(),
+            positions: HashMap::new(),
+            day: chrono::Utc::now().naive_utc().date(),
+        }
+    }
+
+    pub fn kill_switch(&self) -> &KillSwitch {
+        &self.kill_switch
+    }
+
+    pub fn on_ticker(&mut self, ticker: &TickerDataInsert) {
+        self.set_last_price(&ticker.symbol, ticker.last_price.clone());
+    }
+
+    pub fn set_last_price(&mut self, symbol: &Symbol, price: BigDecimal) {
+        self.last_prices.insert(symbol.clone(), price);
+    }
+
+    /// Start from an existing holding, e.g: the account balance of the base asset
+    pub fn set_position(&mut self, symbol: &Symbol, quantity: BigDecimal) {
+        let position = self.positions.entry(symbol.clone()).or_insert_with(Position::default);
+        position.day_start += &quantity - &position.quantity;
+        position.quantity = quantity;
+    }
+
+    pub fn position(&self, symbol: &Symbol) -> BigDecimal {
+        self.positions.get(symbol).map_or_else(BigDecimal::zero, |p| p.quantity.clone())
+    }
+
+    pub fn on_fill(&mut self, fill: &FillInsert) {
+        self.roll_day(fill.trade_time);
+        let (base, quote) = match self.symbols.get(&fill.symbol) {
+            Some(info) => (info.base_asset.clone(), info.quote_asset.clone()),
+            None => return,
+        };
+        self.last_prices.entry(fill.symbol.clone()).or_insert_with(|| fill.price.clone());
+
+        let position = self.positions.entry(fill.symbol.clone()).or_insert_with(Position::default);
+        match fill.side {
+            OrderSide::Buy => {
+                position.quantity += &fill.quantity;
+                position.day_cash -= &fill.quote_qty;
+            }
+            OrderSide::Sell => {
+                position.quantity -= &fill.quantity;
+                position.day_cash += &fill.quote_qty;
+            }
+        }
+        match &fill.commission_asset {
+            Some(asset) if *asset == base => position.quantity -= &fill.commission,
+            Some(asset) if *asset == quote => position.day_cash -= &fill.commission,
+            _ => (),
+        }
+    }
+
+    /// Today's profit and loss at the last prices. Summed across symbols without
+    /// conversion, so limits assume a single quote asset.
+    pub fn daily_pnl(&self) -> BigDecimal {
+        self.positions
+            .iter()
+            .map(|(symbol, p)| {
+                let traded = &p.quantity - &p.day_start;
+                match self.last_prices.get(symbol) {
+                    Some(price) => &p.day_cash + traded * price,
+                    None => p.day_cash.clone(),
+                }
+            })
+            .fold(BigDecimal::zero(), |sum, pnl| sum + pnl)
+    }
+
+    fn roll_day(&mut self, now: NaiveDateTime) {
+        if now.date() <= self.day {
+            return;
+        }
+        self.day = now.date();
+        for position in self.positions.values_mut() {
+            position.day_start = position.quantity.clone();
+            position.day_cash = BigDecimal::zero();
+        }
+    }
+
+    /// Check an order before it is sent. `open_orders` are the orders already
+    /// working. Rejections are logged; reaching the daily loss limit also trips the kill switch.
+    pub fn check(&mut self, request: &OrderRequest, open_orders: &[&Order]) -> Result<(), RiskRejection> {
+        self.roll_day(chrono::Utc::now().naive_utc());
+        let result = self.evaluate(request, open_orders);
+        if let Err(rejection) = &result {
+            warn!(
+                symbol = request.symbol.as_str();
+                "Risk check rejected {} {} @ {:?}: {}",
+                request.side, request.quantity, request.price, rejection
+            );
+            if let RiskRejection::DailyLoss(..) = rejection {
+                self.kill_switch.trip(&rejection.to_string());
+            }
+        }
+        result
+    }
+
+    fn evaluate(&self, request: &OrderRequest, open_orders: &[&Order]) -> Result<(), RiskRejection> {
+        if let Some(reason) = self.kill_switch.halted() {
+            return Err(RiskRejection::Halted(reason));
+        }
+        let loss = -self.daily_pnl();
+        if loss >= self.limits.daily_loss_limit {
+            return Err(RiskRejection::DailyLoss(loss, self.limits.daily_loss_limit.clone()));
+        }
+
+        let symbol = &request.symbol;
+        let info = match self.symbols.get(symbol) {
+            Some(info) if info.is_trading() => info,
+            Some(_) => return Err(RiskRejection::NotTrading(symbol.clone())),
+            None => return Err(RiskRejection::UnknownSymbol(symbol.clone())),
+        };
+
+        // exchange filters, the same ones Binance answers with -1013
+        let quantity = &request.quantity;
+        if *quantity < info.min_qty || *quantity > info.max_qty {
+            return Err(RiskRejection::QuantityRange(quantity.clone(), info.min_qty.clone(), info.max_qty.clone()));
+        }
+        // LOT_SIZE: (quantity - minQty) % stepSize == 0
+        if !info.step_size.is_zero() && !((quantity - &info.min_qty) % &info.step_size).is_zero() {
+            return Err(RiskRejection::LotSize(quantity.clone(), info.step_size.clone()));
+        }
+        for price in request.price.iter().chain(request.stop_price.iter()) {
+            if !info.tick_size.is_zero() && !(price % &info.tick_size).is_zero() {
+                return Err(RiskRejection::TickSize(price.clone(), info.tick_size.clone()));
+            }
+        }
+
+        let last_price = self
+            .last_prices
+            .get(symbol)
+            .ok_or_else(|| RiskRejection::NoReferencePrice(symbol.clone()))?;
+        let limit_price = match request.order_type {
+            OrderType::Market => None,
+            _ => request.price.as_ref(),
+        };
+        for price in limit_price.into_iter().chain(request.stop_price.as_ref()) {
+            if (price - last_price).abs() > last_price * &self.limits.price_band {
+                return Err(RiskRejection::PriceBand(price.clone(), last_price.clone()));
+            }
+        }
+
+        let notional = limit_price.unwrap_or(last_price) * quantity;
+        if notional < info.min_notional {
+            return Err(RiskRejection::MinNotional(notional, info.min_notional.clone()));
+        }
+        if notional > self.limits.max_order_notional {
+            return Err(RiskRejection::MaxOrderNotional(notional, self.limits.max_order_notional.clone()));
+        }
+
+        if open_orders.len() >= self.limits.max_open_orders {
+            return Err(RiskRejection::MaxOpenOrders(self.limits.max_open_orders));
+        }
+
+        // worst case: every open order on this side fills, then this one
+        let signed = |side: OrderSide, qty: BigDecimal| if side == OrderSide::Buy { qty } else { -qty };
+        let projected = open_orders
+            .iter()
+            .filter(|o| o.request.symbol == *symbol && o.request.side == request.side)
+            .fold(self.position(symbol), |sum, o| sum + signed(o.request.side, o.remaining_qty()))
+            + signed(request.side, quantity.clone());
+        let max_position = self.limits.max_position.get(symbol).cloned().unwrap_or_else(BigDecimal::zero);
+        if projected.abs() > max_position {
+            return Err(RiskRejection::MaxPosition(projected, max_position));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user_data::{parse_user_data_event, UserDataEvent, TEST_EXECUTION_REPORT_DATA};
+    use crate::serde_parsers::create_decimal_benchmark;
+    use crate::symbols::{ExchangeInfo, TEST_EXCHANGE_INFO_DATA};
+
+    fn risk_manager() -> RiskManager {
+        let info = serde_json::from_str::<ExchangeInfo>(TEST_EXCHANGE_INFO_DATA).unwrap();
+        let mut limits = RiskLimits {
+            max_order_notional: BigDecimal::from(1),
+            max_open_orders: 2,
+            daily_loss_limit: create_decimal_benchmark("0.5"),
+            ..RiskLimits::default()
+        };
+        limits.max_position.insert(Symbol::new("ETHBTC"), BigDecimal::from(10));
+        let mut risk = RiskManager::new(limits, SymbolRegistry::from_exchange_info(info), KillSwitch::default());
+        risk.set_last_price(&Symbol::new("ETHBTC"), create_decimal_benchmark("0.03"));
+        risk
+    }
+
+    fn buy(quantity: &str, price: &str) -> OrderRequest {
+        OrderRequest::limit(
+            Symbol::new("ETHBTC"),
+            OrderSide::Buy,
+            create_decimal_benchmark(quantity),
+            create_decimal_benchmark(price),
+        )
+    }
+
+    #[test]
+    fn orders_outside_limits_are_rejected() {
+        let mut risk = risk_manager();
+        assert_eq!(risk.check(&buy("1", "0.0301"), &[]), Ok(()));
+
+        let rejection = |risk: &mut RiskManager, request: OrderRequest| risk.check(&request, &[]).unwrap_err();
+        match rejection(&mut risk, buy("1", "0.03000005")) {
+            RiskRejection::TickSize(..) => (),
+            other => panic!("expected tick size, got {}", other),
+        }
+        match rejection(&mut risk, buy("1.0005", "0.03")) {
+            RiskRejection::LotSize(..) => (),
+            other => panic!("expected lot size, got {}", other),
+        }
+        // steps count from minQty, which needn't be a multiple of the step size
+        let exchange_info = risk.symbols.get(&Symbol::new("ETHBTC")).unwrap().clone();
+        let mut info = exchange_info.clone();
+        info.min_qty = create_decimal_benchmark("0.0015");
+        risk.symbols.insert(info);
+        assert_eq!(risk.check(&buy("1.0005", "0.03"), &[]), Ok(()));
+        match rejection(&mut risk, buy("1", "0.03")) {
+            RiskRejection::LotSize(..) => (),
+            other => panic!("expected lot size, got {}", other),
+        }
+        risk.symbols.insert(exchange_info);
+        match rejection(&mut risk, buy("0.003", "0.03")) {
+            RiskRejection::MinNotional(..) => (),
+            other => panic!("expected min notional, got {}", other),
+        }
+        match rejection(&mut risk, buy("1", "0.04")) {
+            RiskRejection::PriceBand(..) => (),
+            other => panic!("expected price band, got {}", other),
+        }
+        match rejection(&mut risk, buy("40", "0.03")) {
+            RiskRejection::MaxOrderNotional(..) => (),
+            other => panic!("expected max notional, got {}", other),
+        }
+        let mut other_symbol = buy("1", "0.03");
+        other_symbol.symbol = Symbol::new("BNBETH");
+        match rejection(&mut risk, other_symbol) {
+            RiskRejection::NoReferencePrice(..) => (),
+            other => panic!("expected no reference price, got {}", other),
+        }
+
+        // 4 ETH held, 5 more resting: another 2 would make 11
+        risk.set_position(&Symbol::new("ETHBTC"), BigDecimal::from(4));
+        let resting = Order::from(&crate::models::account::OrderInfo {
+            symbol: Symbol::new("ETHBTC"),
+            order_id: 1,
+            client_order_id: "resting".to_owned(),
+            price: create_decimal_benchmark("0.03"),
+            orig_qty: BigDecimal::from(5),
+            executed_qty: BigDecimal::zero(),
+            cumulative_quote_qty: BigDecimal::zero(),
+            status: crate::models::orders::OrderStatus::New,
+            time_in_force: crate::models::orders::TimeInForce::Gtc,
+            order_type: OrderType::Limit,
+            side: OrderSide::Buy,
+            stop_price: BigDecimal::zero(),
+            time: chrono::Utc::now().naive_utc(),
+            update_time: chrono::Utc::now().naive_utc(),
+            is_working: true,
+        });
+        assert_eq!(
+            risk.check(&buy("2", "0.03"), &[&resting]),
+            Err(RiskRejection::MaxPosition(BigDecimal::from(11), BigDecimal::from(10)))
+        );
+        assert_eq!(risk.check(&buy("1", "0.03"), &[&resting]), Ok(()));
+        assert_eq!(risk.check(&buy("1", "0.03"), &[&resting, &resting]), Err(RiskRejection::MaxOpenOrders(2)));
+    }
+
+    #[test]
+    fn daily_loss_trips_the_kill_switch() {
+        let mut risk = risk_manager();
+        let report = match parse_user_data_event(TEST_EXECUTION_REPORT_DATA).unwrap() {
+            UserDataEvent::ExecutionReport(report) => report,
+            other => panic!("expected executionReport, got {:?}", other),
+        };
+        let mut fill = report.to_fill().unwrap();
+        fill.trade_time = chrono::Utc::now().naive_utc();
+        fill.quantity = BigDecimal::from(10);
+        fill.quote_qty = BigDecimal::from(1);
+        fill.commission = BigDecimal::zero();
+
+        // bought 10 ETH at 0.1 BTC, marked at 0.03: down 0.7 BTC
+        risk.on_fill(&fill);
+        assert_eq!(risk.position(&fill.symbol), BigDecimal::from(10));
+        assert_eq!(risk.daily_pnl(), create_decimal_benchmark("-0.7"));
+        match risk.check(&buy("0.1", "0.03"), &[]) {
+            Err(RiskRejection::DailyLoss(..)) => (),
+            other => panic!("expected daily loss, got {:?}", other),
+        }
+        assert!(risk.kill_switch().halted().unwrap().contains("daily loss"));
+
+        // halted until reset, even once back above the limit
+        risk.set_last_price(&fill.symbol, create_decimal_benchmark("0.1"));
+        match risk.check(&buy("0.1", "0.1"), &[]) {
+            Err(RiskRejection::Halted(..)) => (),
+            other => panic!("expected halted, got {:?}", other),
+        }
+        risk.kill_switch().reset();
+        let sell = OrderRequest::limit(fill.symbol.clone(), OrderSide::Sell, BigDecimal::from(1), create_decimal_benchmark("0.1"));
+        assert_eq!(risk.check(&sell, &[]), Ok(()));
+    }
+}
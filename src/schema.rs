@@ -1,31 +1,90 @@
 table! {
-    aggregate_trades (trade_id) {
-        trade_id -> Int4,
+    aggregate_trades (symbol, trade_id) {
+        trade_id -> Int8,
         event -> Text,
         event_time -> Timestamp,
         symbol -> Text,
-        price -> Float4,
-        quantity -> Float4,
-        first_trade_id -> Int4,
-        last_trade_id -> Int4,
+        price -> Numeric,
+        quantity -> Numeric,
+        first_trade_id -> Int8,
+        last_trade_id -> Int8,
         trade_time -> Timestamp,
         buyer_mkt_maker -> Bool,
     }
 }
 
+table! {
+    bars (symbol, spec, first_trade_id) {
+        symbol -> Text,
+        spec -> Text,
+        first_trade_id -> Int8,
+        last_trade_id -> Int8,
+        start_time -> Timestamp,
+        close_time -> Timestamp,
+        open -> Numeric,
+        close -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        volume -> Numeric,
+        num_of_trades -> Int4,
+        quote_asset_vol -> Numeric,
+        taker_buy_base_vol -> Numeric,
+        taker_buy_quote_vol -> Numeric,
+    }
+}
+
 table! {
     book_depth (id) {
         id -> Int4,
         event -> Text,
         event_time -> Timestamp,
         symbol -> Text,
-        update_first -> Int4,
-        update_final -> Int4,
+        update_first -> Int8,
+        update_final -> Int8,
+        bids -> Array<Jsonb>,
+        asks -> Array<Jsonb>,
+    }
+}
+
+table! {
+    book_snapshots (symbol, last_update_id, captured_at) {
+        symbol -> Text,
+        last_update_id -> Int8,
+        captured_at -> Timestamp,
+        levels -> Int4,
         bids -> Array<Jsonb>,
         asks -> Array<Jsonb>,
     }
 }
 
+table! {
+    dead_letters (id) {
+        id -> Int4,
+        received_at -> Timestamp,
+        stream -> Text,
+        payload -> Text,
+        error -> Text,
+    }
+}
+
+table! {
+    fills (id) {
+        id -> Int4,
+        symbol -> Text,
+        trade_id -> Int8,
+        order_id -> Int8,
+        client_order_id -> Text,
+        side -> Text,
+        price -> Numeric,
+        quantity -> Numeric,
+        quote_qty -> Numeric,
+        commission -> Numeric,
+        commission_asset -> Nullable<Text>,
+        is_maker -> Bool,
+        trade_time -> Timestamp,
+    }
+}
+
 table! {
     klines (id) {
         id -> Int4,
@@ -35,18 +94,18 @@ table! {
         close_time -> Timestamp,
         symbol -> Text,
         interval -> Text,
-        first_trade_id -> Int4,
-        last_trade_id -> Int4,
-        open -> Float4,
-        close -> Float4,
-        high -> Float4,
-        low -> Float4,
-        volume -> Float4,
+        first_trade_id -> Int8,
+        last_trade_id -> Int8,
+        open -> Numeric,
+        close -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        volume -> Numeric,
         num_of_trades -> Int4,
         is_kline_closed -> Bool,
-        quote_asset_vol -> Float4,
-        taker_buy_base_vol -> Float4,
-        taker_buy_quote_vol -> Float4,
+        quote_asset_vol -> Numeric,
+        taker_buy_base_vol -> Numeric,
+        taker_buy_quote_vol -> Numeric,
     }
 }
 
@@ -56,12 +115,32 @@ table! {
         event -> Text,
         event_time -> Timestamp,
         symbol -> Text,
-        open -> Float4,
-        close -> Float4,
-        high -> Float4,
-        low -> Float4,
-        base_asset_vol -> Float4,
-        quote_asset_vol -> Float4,
+        open -> Numeric,
+        close -> Numeric,
+        high -> Numeric,
+        low -> Numeric,
+        base_asset_vol -> Numeric,
+        quote_asset_vol -> Numeric,
+    }
+}
+
+table! {
+    orders (id) {
+        id -> Int4,
+        client_order_id -> Text,
+        order_id -> Nullable<Int8>,
+        symbol -> Text,
+        side -> Text,
+        order_type -> Text,
+        time_in_force -> Nullable<Text>,
+        price -> Nullable<Numeric>,
+        stop_price -> Nullable<Numeric>,
+        orig_qty -> Numeric,
+        executed_qty -> Numeric,
+        cumulative_quote_qty -> Numeric,
+        status -> Text,
+        reason -> Nullable<Text>,
+        event_time -> Timestamp,
     }
 }
 
@@ -74,56 +153,77 @@ table! {
     }
 }
 
+table! {
+    symbols (symbol) {
+        symbol -> Text,
+        status -> Text,
+        base_asset -> Text,
+        quote_asset -> Text,
+        tick_size -> Numeric,
+        step_size -> Numeric,
+        min_qty -> Numeric,
+        max_qty -> Numeric,
+        min_notional -> Numeric,
+        updated_at -> Timestamp,
+    }
+}
+
 table! {
     tickers (id) {
         id -> Int4,
         event -> Text,
         event_time -> Timestamp,
         symbol -> Text,
-        price_change -> Float4,
-        price_change_pct -> Float4,
-        weight_avg_price -> Float4,
-        first_trade_before_24hr_window -> Float4,
-        last_price -> Float4,
-        last_quantity -> Float4,
-        best_bid_price -> Float4,
-        best_bid_quantity -> Float4,
-        best_ask_price -> Float4,
-        best_ask_quantity -> Float4,
-        open_price -> Float4,
-        high_price -> Float4,
-        low_price -> Float4,
-        base_asset_vol -> Float4,
-        quote_asset_vol -> Float4,
+        price_change -> Numeric,
+        price_change_pct -> Numeric,
+        weight_avg_price -> Numeric,
+        first_trade_before_24hr_window -> Numeric,
+        last_price -> Numeric,
+        last_quantity -> Numeric,
+        best_bid_price -> Numeric,
+        best_bid_quantity -> Numeric,
+        best_ask_price -> Numeric,
+        best_ask_quantity -> Numeric,
+        open_price -> Numeric,
+        high_price -> Numeric,
+        low_price -> Numeric,
+        base_asset_vol -> Numeric,
+        quote_asset_vol -> Numeric,
         open_time -> Timestamp,
         close_time -> Timestamp,
-        first_trade_id -> Int4,
-        last_trade_id -> Int4,
+        first_trade_id -> Int8,
+        last_trade_id -> Int8,
         total_num_trades -> Int4,
     }
 }
 
 table! {
-    trades (trade_id) {
-        trade_id -> Int4,
+    trades (symbol, trade_id) {
+        trade_id -> Int8,
         event -> Text,
         event_time -> Timestamp,
         symbol -> Text,
-        price -> Float4,
-        quantity -> Float4,
+        price -> Numeric,
+        quantity -> Numeric,
         trade_time -> Timestamp,
-        buyer_order_id -> Int4,
-        seller_order_id -> Int4,
+        buyer_order_id -> Int8,
+        seller_order_id -> Int8,
         buyer_mkt_maker -> Bool,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
     aggregate_trades,
+    bars,
     book_depth,
+    book_snapshots,
+    dead_letters,
+    fills,
     klines,
     mini_tickers,
+    orders,
     posts,
+    symbols,
     tickers,
     trades,
 );
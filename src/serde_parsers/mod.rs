@@ -3,6 +3,8 @@ use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Unexpected, Visitor
 use serde_derive::*;
 use std::fmt;
 
+use bigdecimal::BigDecimal;
+
 ///////////////////////////////////////////////////////////////////////////
 /// Deserializers
 ///////////////////////////////////////////////////////////////////////////
@@ -26,7 +28,7 @@ where
             E: de::Error,
         {
             // convert to f64
-            Ok(serde_json::from_str(v).unwrap())
+            v.parse::<f64>().map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
         }
     }
     // use our visitor to deserialize
@@ -52,7 +54,9 @@ where
             E: de::Error,
         {
             // convert to f64
-            Ok(Some(serde_json::from_str(v).unwrap()))
+            v.parse::<f64>()
+                .map(Some)
+                .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
         }
 
         fn visit_unit<E>(self) -> Result<Self::Value, E>
@@ -85,13 +89,55 @@ where
             E: de::Error,
         {
             // convert to f32
-            Ok(serde_json::from_str(v).unwrap())
+            v.parse::<f32>().map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
         }
     }
     // use our visitor to deserialize
     deserializer.deserialize_any(F32Visitor)
 }
 
+/// Prices and quantities arrive as decimal strings, e.g: "0.00002345".
+/// Parsing them straight into a `BigDecimal` keeps every digit, going through f32/f64 would not.
+pub fn deserialize_as_decimal<'de, D>(deserializer: D) -> Result<BigDecimal, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    // define a visitor that deserializes String or integers to BigDecimal
+    struct DecimalVisitor;
+
+    impl<'de> de::Visitor<'de> for DecimalVisitor {
+        type Value = BigDecimal;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a string containing decimal data")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            v.parse::<BigDecimal>()
+                .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(BigDecimal::from(v))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(BigDecimal::from(v))
+        }
+    }
+    // use our visitor to deserialize
+    deserializer.deserialize_any(DecimalVisitor)
+}
+
 pub fn deserialize_as_naive_date_time_ms<'de, D>(
     deserializer: D,
 ) -> Result<chrono::NaiveDateTime, D::Error>
@@ -112,64 +158,69 @@ where
         where
             E: de::Error,
         {
-            Ok(create_timestamp_ms(timestamp))
+            create_timestamp_ms(timestamp).map_err(E::custom)
         }
 
         fn visit_u64<E>(self, timestamp: u64) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            Ok(create_timestamp_ms(timestamp))
+            create_timestamp_ms(timestamp as i64).map_err(E::custom)
         }
 
         fn visit_f64<E>(self, timestamp: f64) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            Ok(create_timestamp_ms(timestamp))
+            create_timestamp_ms(timestamp as i64).map_err(E::custom)
         }
 
         fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
         where
             E: de::Error,
         {
-            let timestamp: i64 = s.parse::<i64>().unwrap();
-            Ok(create_timestamp_ms(timestamp))
+            let timestamp: i64 = s
+                .parse::<i64>()
+                .map_err(|_| E::invalid_value(Unexpected::Str(s), &self))?;
+            create_timestamp_ms(timestamp).map_err(E::custom)
         }
     }
     // use our visitor to deserialize
     deserializer.deserialize_any(NaiveDateTimeVisitor)
 }
 
-use num::cast::{FromPrimitive, ToPrimitive};
-use std::ops::{AddAssign, Div};
-
-fn create_timestamp_ms<T>(_timestamp: T) -> chrono::NaiveDateTime
+/// The inverse of `deserialize_as_naive_date_time_ms`, milliseconds since the epoch
+pub fn serialize_as_timestamp_ms<S>(time: &chrono::NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
 where
-    T: Div + ToPrimitive + AddAssign + Default,
+    S: serde::Serializer,
 {
-    // convert to NaiveDateTime
-    let timestamp: i64 = _timestamp.to_i64().unwrap();
+    serializer.serialize_i64(time.timestamp_millis())
+}
+
+use crate::error;
+
+fn create_timestamp_ms(timestamp: i64) -> error::Result<chrono::NaiveDateTime> {
     // Timestamps should much much larger than 10 digits
     match timestamp {
-        0...1_000_000_000_000 => {
+        std::i64::MIN...1_000_000_000_000 => {
             // Timestamp of 1_000_000_000 is 14th July 2017 2:40:00 AM
             // Binance Launched on 14th July 2017.
-            panic!("Timestamp too small: {}.\n Timestamp format may be in seconds instead of milliseconds", timestamp)
+            Err(error::Error::InvalidTimestamp(timestamp))
         }
         1_000_000_000_000...1_500_000_000_000 => {
             // Timestamp of 1_500_000_000 is 14th July 2017 2:40:00 AM
             // Binance Launched on 14th July 2017.
             warn!("Timestamp {} appears before Binance launched on the 14-Jul-2017.", timestamp);
             let ms = (timestamp % 1000) * 1_000_000;
-            chrono::NaiveDateTime::from_timestamp(timestamp / 1_000, ms as u32)
+            Ok(chrono::NaiveDateTime::from_timestamp(timestamp / 1_000, ms as u32))
         }
         _ => {
             // Timestamp are in milliseconds
             let ms = (timestamp % 1000) * 1_000_000;
             // get remainder in milliseconds, convert to nanoseconds
             // as from_timestamp takes nanoseconds in the 2nd argument
-            chrono::NaiveDateTime::from_timestamp(timestamp / 1_000, ms as u32)
+            chrono::NaiveDateTime::from_timestamp_opt(timestamp / 1_000, ms as u32)
+                .ok_or(error::Error::InvalidTimestamp(timestamp))
             // first argument is seconds, second argument is in nanoseconds
         }
     }
@@ -181,6 +232,10 @@ pub fn create_timestamp_benchmark(sec: i64) -> chrono::NaiveDateTime {
     t_benchmark
 }
 
+pub fn create_decimal_benchmark(s: &str) -> BigDecimal {
+    s.parse::<BigDecimal>().unwrap()
+}
+
 
 
 ///////////////// Tests ///////////////
@@ -191,6 +246,8 @@ mod tests {
 
     use crate::serde_parsers::create_timestamp_benchmark;
     use crate::serde_parsers::deserialize_as_naive_date_time_ms;
+    use crate::serde_parsers::{create_decimal_benchmark, deserialize_as_decimal};
+    use bigdecimal::BigDecimal;
 
     #[derive(Deserialize)]
     struct Mock_Json_Timestamp {
@@ -222,6 +279,42 @@ mod tests {
         assert_eq!(t1.json_time, t_benchmark)
     }
 
+    #[test]
+    fn bad_timestamps_are_errors() {
+        let seconds = r#"{ "json_time": 1555444333 }"#;
+        let err = serde_json::from_str::<Mock_Json_Timestamp>(seconds).err().unwrap();
+        assert!(err.to_string().contains("timestamp too small: 1555444333"));
+
+        let negative = r#"{ "json_time": -1 }"#;
+        assert!(serde_json::from_str::<Mock_Json_Timestamp>(negative).is_err());
+        let not_a_number = r#"{ "json_time": "yesterday" }"#;
+        assert!(serde_json::from_str::<Mock_Json_Timestamp>(not_a_number).is_err());
+    }
+
+    #[derive(Deserialize)]
+    struct Mock_Json_Price {
+        #[serde(deserialize_with = "deserialize_as_decimal")]
+        price: BigDecimal,
+    }
+
+    #[test]
+    fn try_decimal_keeps_satoshis() {
+        let p: Mock_Json_Price = serde_json::from_str(r#"{ "price": "0.03456781" }"#).unwrap();
+        assert_eq!(p.price, create_decimal_benchmark("0.03456781"));
+        assert_eq!(p.price.to_string(), "0.03456781");
+
+        // 10,000 one satoshi fills sum to exactly 0.0001, f32 drifts
+        let satoshi: Mock_Json_Price = serde_json::from_str(r#"{ "price": "0.00000001" }"#).unwrap();
+        let total: BigDecimal = (0..10_000).map(|_| satoshi.price.clone()).sum();
+        assert_eq!(total, create_decimal_benchmark("0.0001"));
+        let total_f32: f32 = (0..10_000).map(|_| 0.000_000_01f32).sum();
+        assert!(total_f32 != 0.0001);
+
+        let qty: Mock_Json_Price = serde_json::from_str(r#"{ "price": 100 }"#).unwrap();
+        assert_eq!(qty.price, BigDecimal::from(100));
+        assert!(serde_json::from_str::<Mock_Json_Price>(r#"{ "price": "abc" }"#).is_err());
+    }
+
     proptest! {
         #[test]
         fn try_random_timestamps(n in 1_000_000_000_000..2_000_000_000_000 as i64) {
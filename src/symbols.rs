@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::deserialize::FromSql;
+use diesel::pg::upsert::excluded;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::error::{Error, Result};
+use crate::schema::symbols;
+use crate::serde_parsers::deserialize_as_decimal;
+
+pub const BINANCE_EXCHANGE_INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+pub const BINANCE_TICKER_PRICE_URL: &str = "https://api.binance.com/api/v3/ticker/price";
+
+/// Trading pair, e.g: ETHBTC. Stored upper case as Binance's REST API expects,
+/// displayed lower case as used in websocket stream names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub struct Symbol(String);
+
+impl Symbol {
+    /// Any alphanumeric name is accepted, so new listings never fail to parse.
+    /// See `SymbolRegistry` to check it is actually listed.
+    pub fn new(symbol: &str) -> Self {
+        Symbol(symbol.to_uppercase())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn to_uppercase(&self) -> String {
+        self.0.clone()
+    }
+
+    pub fn to_lowercase(&self) -> String {
+        self.0.to_lowercase()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_lowercase())
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Symbol> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Error::UnknownSymbol(s.to_string()));
+        }
+        Ok(Symbol::new(s))
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Symbol, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse::<Symbol>().map_err(de::Error::custom)
+    }
+}
+
+/// Traits for PostgreSQL serialization for Symbol
+impl ToSql<Text, Pg> for Symbol {
+    fn to_sql<W: std::io::Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
+        ToSql::<Text, Pg>::to_sql(&self.0, out)
+    }
+}
+
+impl FromSql<Text, Pg> for Symbol {
+    fn from_sql(maybe_bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        let symbol = <String as FromSql<Text, Pg>>::from_sql(maybe_bytes)?;
+        Ok(symbol.parse::<Symbol>()?)
+    }
+}
+
+/// Latest price from `/api/v3/ticker/price`
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct SymbolPrice {
+    pub symbol: Symbol,
+    #[serde(deserialize_with = "deserialize_as_decimal")]
+    pub price: BigDecimal,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+/// Symbol metadata from `/api/v3/exchangeInfo`
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ExchangeInfo {
+    pub symbols: Vec<ExchangeSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeSymbol {
+    pub symbol: Symbol,
+    pub status: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub filters: Vec<SymbolFilter>,
+}
+
+/// Trading rules for a symbol, only the ones we use are parsed
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "filterType")]
+#[allow(non_camel_case_types)]
+pub enum SymbolFilter {
+    PRICE_FILTER {
+        #[serde(rename = "tickSize", deserialize_with = "deserialize_as_decimal")]
+        tick_size: BigDecimal,
+    },
+    LOT_SIZE {
+        #[serde(rename = "minQty", deserialize_with = "deserialize_as_decimal")]
+        min_qty: BigDecimal,
+        #[serde(rename = "maxQty", deserialize_with = "deserialize_as_decimal")]
+        max_qty: BigDecimal,
+        #[serde(rename = "stepSize", deserialize_with = "deserialize_as_decimal")]
+        step_size: BigDecimal,
+    },
+    MIN_NOTIONAL {
+        #[serde(rename = "minNotional", deserialize_with = "deserialize_as_decimal")]
+        min_notional: BigDecimal,
+    },
+    NOTIONAL {
+        #[serde(rename = "minNotional", deserialize_with = "deserialize_as_decimal")]
+        min_notional: BigDecimal,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Queryable, Insertable)]
+#[table_name = "symbols"]
+pub struct SymbolInfo {
+    pub symbol: Symbol,            // Symbol, e.g: ETHBTC
+    pub status: String,            // TRADING, BREAK, HALT, ...
+    pub base_asset: String,        // ETH in ETHBTC
+    pub quote_asset: String,       // BTC in ETHBTC
+    pub tick_size: BigDecimal,     // Price increment
+    pub step_size: BigDecimal,     // Quantity increment (lot size)
+    pub min_qty: BigDecimal,       // Smallest order quantity
+    pub max_qty: BigDecimal,       // Largest order quantity
+    pub min_notional: BigDecimal,  // Smallest price * quantity
+    pub updated_at: NaiveDateTime, // Last refreshed from exchangeInfo
+}
+
+impl SymbolInfo {
+    pub fn is_trading(&self) -> bool {
+        self.status == "TRADING"
+    }
+}
+
+impl From<ExchangeSymbol> for SymbolInfo {
+    fn from(s: ExchangeSymbol) -> Self {
+        let mut info = SymbolInfo {
+            symbol: s.symbol,
+            status: s.status,
+            base_asset: s.base_asset,
+            quote_asset: s.quote_asset,
+            tick_size: BigDecimal::from(0),
+            step_size: BigDecimal::from(0),
+            min_qty: BigDecimal::from(0),
+            max_qty: BigDecimal::from(0),
+            min_notional: BigDecimal::from(0),
+            updated_at: chrono::Utc::now().naive_utc(),
+        };
+        for filter in s.filters {
+            match filter {
+                SymbolFilter::PRICE_FILTER { tick_size } => info.tick_size = tick_size,
+                SymbolFilter::LOT_SIZE { min_qty, max_qty, step_size } => {
+                    info.min_qty = min_qty;
+                    info.max_qty = max_qty;
+                    info.step_size = step_size;
+                }
+                SymbolFilter::MIN_NOTIONAL { min_notional } | SymbolFilter::NOTIONAL { min_notional } => {
+                    info.min_notional = min_notional
+                }
+                SymbolFilter::Other => (),
+            }
+        }
+        info
+    }
+}
+
+/// Every symbol listed on the exchange, refreshed from exchangeInfo and persisted to `symbols`
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    symbols: HashMap<Symbol, SymbolInfo>,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> Self {
+        SymbolRegistry::default()
+    }
+
+    pub fn from_exchange_info(exchange_info: ExchangeInfo) -> Self {
+        let mut registry = SymbolRegistry::new();
+        for symbol in exchange_info.symbols {
+            registry.insert(SymbolInfo::from(symbol));
+        }
+        registry
+    }
+
+    /// GET `/api/v3/exchangeInfo`
+    pub fn fetch() -> Result<Self> {
+        let exchange_info = reqwest::get(BINANCE_EXCHANGE_INFO_URL)?.json::<ExchangeInfo>()?;
+        Ok(SymbolRegistry::from_exchange_info(exchange_info))
+    }
+
+    /// Last saved symbols, for when the REST API is unreachable
+    pub fn load(conn: &PgConnection) -> QueryResult<Self> {
+        let mut registry = SymbolRegistry::new();
+        for info in symbols::table.load::<SymbolInfo>(conn)? {
+            registry.insert(info);
+        }
+        Ok(registry)
+    }
+
+    /// Upsert every symbol into the `symbols` table
+    pub fn save(&self, conn: &PgConnection) -> QueryResult<usize> {
+        use crate::schema::symbols::dsl::*;
+
+        let rows: Vec<&SymbolInfo> = self.symbols.values().collect();
+        diesel::insert_into(symbols)
+            .values(rows)
+            .on_conflict(symbol)
+            .do_update()
+            .set((
+                status.eq(excluded(status)),
+                base_asset.eq(excluded(base_asset)),
+                quote_asset.eq(excluded(quote_asset)),
+                tick_size.eq(excluded(tick_size)),
+                step_size.eq(excluded(step_size)),
+                min_qty.eq(excluded(min_qty)),
+                max_qty.eq(excluded(max_qty)),
+                min_notional.eq(excluded(min_notional)),
+                updated_at.eq(excluded(updated_at)),
+            ))
+            .execute(conn)
+    }
+
+    pub fn insert(&mut self, info: SymbolInfo) {
+        self.symbols.insert(info.symbol.clone(), info);
+    }
+
+    pub fn get(&self, symbol: &Symbol) -> Option<&SymbolInfo> {
+        self.symbols.get(symbol)
+    }
+
+    pub fn contains(&self, symbol: &Symbol) -> bool {
+        self.symbols.contains_key(symbol)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SymbolInfo> {
+        self.symbols.values()
+    }
+
+    /// Parse a symbol name, rejecting anything that isn't listed
+    pub fn lookup(&self, name: &str) -> Result<&SymbolInfo> {
+        let symbol = name.parse::<Symbol>()?;
+        self.symbols
+            .get(&symbol)
+            .ok_or_else(|| Error::UnknownSymbol(name.to_string()))
+    }
+
+    /// Currently trading symbols quoted in `asset`, e.g: "ETH" -> [BNBETH, LINKETH, ...]
+    pub fn quoted_in(&self, asset: &str) -> Vec<&SymbolInfo> {
+        let mut infos: Vec<&SymbolInfo> = self
+            .symbols
+            .values()
+            .filter(|info| info.is_trading() && info.quote_asset.eq_ignore_ascii_case(asset))
+            .collect();
+        infos.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        infos
+    }
+
+    /// Currently trading symbols with `asset` as the base, e.g: "ETH" -> [ETHBTC, ETHUSDT, ...]
+    pub fn with_base(&self, asset: &str) -> Vec<&SymbolInfo> {
+        let mut infos: Vec<&SymbolInfo> = self
+            .symbols
+            .values()
+            .filter(|info| info.is_trading() && info.base_asset.eq_ignore_ascii_case(asset))
+            .collect();
+        infos.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        infos
+    }
+}
+
+pub static TEST_EXCHANGE_INFO_DATA: &str = r#"
+{
+  "timezone": "UTC",
+  "serverTime": 1565246363776,
+  "rateLimits": [],
+  "exchangeFilters": [],
+  "symbols": [
+    {
+      "symbol": "ETHBTC",
+      "status": "TRADING",
+      "baseAsset": "ETH",
+      "baseAssetPrecision": 8,
+      "quoteAsset": "BTC",
+      "quotePrecision": 8,
+      "orderTypes": ["LIMIT", "MARKET"],
+      "icebergAllowed": true,
+      "filters": [
+        { "filterType": "PRICE_FILTER", "minPrice": "0.00000100", "maxPrice": "100000.00000000", "tickSize": "0.00000100" },
+        { "filterType": "LOT_SIZE", "minQty": "0.00100000", "maxQty": "100000.00000000", "stepSize": "0.00100000" },
+        { "filterType": "MIN_NOTIONAL", "minNotional": "0.00010000", "applyToMarket": true, "avgPriceMins": 5 },
+        { "filterType": "ICEBERG_PARTS", "limit": 10 }
+      ]
+    },
+    {
+      "symbol": "BNBETH",
+      "status": "TRADING",
+      "baseAsset": "BNB",
+      "quoteAsset": "ETH",
+      "filters": [
+        { "filterType": "PRICE_FILTER", "minPrice": "0.00000100", "maxPrice": "100000.00000000", "tickSize": "0.00000100" },
+        { "filterType": "LOT_SIZE", "minQty": "0.01000000", "maxQty": "90000000.00000000", "stepSize": "0.01000000" },
+        { "filterType": "NOTIONAL", "minNotional": "0.00500000" }
+      ]
+    },
+    {
+      "symbol": "NEWETH",
+      "status": "BREAK",
+      "baseAsset": "NEW",
+      "quoteAsset": "ETH",
+      "filters": []
+    }
+  ]
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::establish_connection_pg;
+    use crate::serde_parsers::create_decimal_benchmark;
+
+    #[test]
+    fn try_symbol_registry_from_exchange_info() {
+        let exchange_info = serde_json::from_str::<ExchangeInfo>(TEST_EXCHANGE_INFO_DATA).unwrap();
+        let registry = SymbolRegistry::from_exchange_info(exchange_info);
+        assert_eq!(registry.len(), 3);
+
+        let ethbtc = registry.lookup("ethbtc").unwrap();
+        assert_eq!(ethbtc.base_asset, "ETH");
+        assert_eq!(ethbtc.quote_asset, "BTC");
+        assert_eq!(ethbtc.tick_size, create_decimal_benchmark("0.000001"));
+        assert_eq!(ethbtc.step_size, create_decimal_benchmark("0.001"));
+        assert_eq!(ethbtc.min_notional, create_decimal_benchmark("0.0001"));
+        assert_eq!(registry.lookup("BNBETH").unwrap().min_notional, create_decimal_benchmark("0.005"));
+
+        // unlisted symbols parse fine, but aren't in the registry
+        assert_eq!("XYZBTC".parse::<Symbol>().unwrap().to_string(), "xyzbtc");
+        assert!(registry.lookup("XYZBTC").is_err());
+        assert!("ETH/BTC".parse::<Symbol>().is_err());
+
+        // NEWETH isn't trading yet
+        let eth_quoted: Vec<String> = registry.quoted_in("ETH").iter().map(|s| s.symbol.to_uppercase()).collect();
+        assert_eq!(eth_quoted, vec!["BNBETH"]);
+        assert_eq!(registry.with_base("eth").len(), 1);
+    }
+
+    #[test]
+    fn db_symbols_postgres_write() {
+        let exchange_info = serde_json::from_str::<ExchangeInfo>(TEST_EXCHANGE_INFO_DATA).unwrap();
+        let mut registry = SymbolRegistry::from_exchange_info(exchange_info);
+
+        let conn: PgConnection = establish_connection_pg();
+        conn.test_transaction::<_, diesel::result::Error, _>(|| {
+            assert_eq!(registry.save(&conn)?, 3);
+
+            // saving again updates in place
+            let mut ethbtc = registry.get(&Symbol::new("ETHBTC")).unwrap().clone();
+            ethbtc.status = "HALT".to_string();
+            registry.insert(ethbtc);
+            assert_eq!(registry.save(&conn)?, 3);
+
+            let loaded = SymbolRegistry::load(&conn)?;
+            assert_eq!(loaded.len(), 3);
+            assert!(!loaded.get(&Symbol::new("ETHBTC")).unwrap().is_trading());
+            Ok(())
+        });
+    }
+}